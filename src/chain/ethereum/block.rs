@@ -34,6 +34,8 @@ pub struct AscEthereumBlock {
     pub difficulty: AscPtr<AscBigInt>,
     pub total_difficulty: AscPtr<AscBigInt>,
     pub size: AscPtr<AscBigInt>,
+    /// Read by the mapping as `block.baseFeePerGas`, filled from `EthereumBlockData::base_fee_per_gas`:
+    /// only the field's position matters to the guest, its name follows graph-node's
     pub base_fee_per_block: AscPtr<AscBigInt>,
 }
 
@@ -76,6 +78,7 @@ pub struct EthereumBlockData {
     pub difficulty: U256,
     pub total_difficulty: U256,
     pub size: Option<U256>,
+    /// Written to `AscEthereumBlock::base_fee_per_block`, `None` before London
     pub base_fee_per_gas: Option<U256>,
 }
 
@@ -154,3 +157,32 @@ impl FromAscObj<AscEthereumBlock> for EthereumBlockData {
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::runtime::asc::base::tests::MockHeap;
+
+    #[test]
+    fn test_base_fee_per_gas() {
+        let mut heap = MockHeap::new(0);
+        let base_fee = U256::from(7_000_000_000u64);
+        let block = EthereumBlockData {
+            base_fee_per_gas: Some(base_fee),
+            ..Default::default()
+        };
+
+        let asc_block: AscEthereumBlock = block.to_asc_obj(&mut heap).unwrap();
+        assert!(!asc_block.base_fee_per_block.is_null());
+        let value: BigInt = asc_get(&heap, asc_block.base_fee_per_block, 0).unwrap();
+        assert_eq!(value, BigInt::from_unsigned_u256(&base_fee));
+
+        let block = EthereumBlockData::from_asc_obj(asc_block, &heap, 0).unwrap();
+        assert_eq!(block.base_fee_per_gas, Some(base_fee));
+
+        // Pre-London blocks have no base fee
+        let block = EthereumBlockData::default();
+        let asc_block: AscEthereumBlock = block.to_asc_obj(&mut heap).unwrap();
+        assert!(asc_block.base_fee_per_block.is_null());
+    }
+}
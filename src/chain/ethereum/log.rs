@@ -193,10 +193,13 @@ impl ToAscObj<AscEthereumLog> for Log {
                 .as_ref()
                 .map(|log_type| asc_new(heap, &log_type))
                 .unwrap_or(Ok(AscPtr::null()))?,
-            removed: self
-                .removed
-                .map(|removed| asc_new(heap, &AscWrapped { inner: removed }))
-                .unwrap_or(Ok(AscPtr::null()))?,
+            // Mappings read `log.removed` as non-nullable, so a missing flag means not removed
+            removed: asc_new(
+                heap,
+                &AscWrapped {
+                    inner: self.removed.unwrap_or(false),
+                },
+            )?,
         })
     }
 }
@@ -256,3 +259,41 @@ impl FromAscObj<AscLogArray> for Vec<Log> {
             .collect::<Result<Vec<Log>, _>>()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::runtime::asc::base::tests::MockHeap;
+    use web3::types::H160;
+
+    #[test]
+    fn test_log_removed_defaults_to_false() {
+        let mut heap = MockHeap::new(0);
+        let log = Log {
+            address: H160::zero(),
+            topics: vec![H256::zero()],
+            data: web3::types::Bytes(vec![1, 2, 3]),
+            block_hash: None,
+            block_number: None,
+            transaction_hash: None,
+            transaction_index: None,
+            log_index: None,
+            transaction_log_index: None,
+            log_type: None,
+            removed: None,
+        };
+
+        let asc_log: AscEthereumLog = log.to_asc_obj(&mut heap).unwrap();
+        assert!(!asc_log.removed.is_null());
+        let removed: bool = asc_get(&heap, asc_log.removed, 0).unwrap();
+        assert!(!removed);
+
+        let log = Log {
+            removed: Some(true),
+            ..log
+        };
+        let asc_log: AscEthereumLog = log.to_asc_obj(&mut heap).unwrap();
+        let removed: bool = asc_get(&heap, asc_log.removed, 0).unwrap();
+        assert!(removed);
+    }
+}
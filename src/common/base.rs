@@ -100,16 +100,42 @@ pub struct BlockPtr {
     pub parent_hash: String,
 }
 
+/// Position of a source's stream at a committed block, for sources resuming from
+/// their own cursor rather than a block number (eg firehose)
+#[derive(Debug, Eq, PartialEq, Clone, Deserialize, Serialize)]
+pub struct Checkpoint {
+    pub block_ptr: BlockPtr,
+    pub cursor: String,
+}
+
+/// Block range reverted by the last reprocess, kept so that restarting
+/// with the same range configured doesn't revert it again
+#[derive(Debug, Eq, PartialEq, Clone, Deserialize, Serialize)]
+pub struct ReprocessedRange {
+    pub from_block: u64,
+    pub to_block: u64,
+}
+
 pub type EntityType = String;
 pub type EntityID = String;
 pub type FieldName = String;
+
+/// Id of an entity, distinct from its type's name so the two are not swapped by accident
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct EntityKey(pub EntityID);
+
+/// Name of an entity type as declared in the graphql schema
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct EntityTypeName(pub EntityType);
 pub type RawEntity = HashMap<FieldName, Value>;
 
-#[derive(Clone, Default, Debug)]
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
 pub struct FieldKind {
     pub kind: StoreValueKind,
     pub relation: Option<(EntityType, FieldName)>,
     pub list_inner_kind: Option<StoreValueKind>,
+    /// Declared without `!` in the graphql schema
+    pub is_nullable: bool,
 }
 
 pub type Schema = BTreeMap<FieldName, FieldKind>;
@@ -57,6 +57,37 @@ impl Display for BlockPtr {
     }
 }
 
+macro_rules! impl_string_newtype {
+    ($newtype:ident) => {
+        impl From<&str> for $newtype {
+            fn from(value: &str) -> Self {
+                Self(value.to_owned())
+            }
+        }
+
+        impl From<String> for $newtype {
+            fn from(value: String) -> Self {
+                Self(value)
+            }
+        }
+
+        impl From<$newtype> for String {
+            fn from(value: $newtype) -> Self {
+                value.0
+            }
+        }
+
+        impl Display for $newtype {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    };
+}
+
+impl_string_newtype!(EntityKey);
+impl_string_newtype!(EntityTypeName);
+
 impl FromIterator<(String, serde_json::Value)> for ABIs {
     fn from_iter<I: IntoIterator<Item = (String, serde_json::Value)>>(iter: I) -> Self {
         Self(iter.into_iter().collect::<HashMap<_, _>>())
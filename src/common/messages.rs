@@ -1,6 +1,8 @@
 use super::base::BlockPtr;
 use super::base::EntityID;
+use super::base::EntityKey;
 use super::base::EntityType;
+use super::base::EntityTypeName;
 use super::base::FieldName;
 use super::base::RawEntity;
 use crate::chain::ethereum::block::EthereumBlockData;
@@ -54,27 +56,70 @@ impl FilteredDataMessage {
             },
         }
     }
+
+    pub fn get_block_timestamp(&self) -> u64 {
+        match self {
+            FilteredDataMessage::Ethereum { block, .. } => block.timestamp.as_u64(),
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum StoreOperationMessage {
     Create((EntityType, RawEntity)),
     Load((EntityType, EntityID)),
     Update((EntityType, EntityID, RawEntity)),
+    /// Apply only the given fields on top of the current entity, loading it from the database if needed
+    Merge((EntityType, EntityID, RawEntity)),
     Delete((EntityType, EntityID)),
     LoadRelated((EntityType, EntityID, FieldName)),
     LoadInBlock((EntityType, EntityID)),
+    LoadWithFreshness((EntityType, EntityID)),
 }
 
 impl StoreOperationMessage {
+    pub fn load(entity_type: EntityTypeName, entity_id: EntityKey) -> Self {
+        Self::Load((entity_type.into(), entity_id.into()))
+    }
+
+    pub fn update(entity_type: EntityTypeName, entity_id: EntityKey, data: RawEntity) -> Self {
+        Self::Update((entity_type.into(), entity_id.into(), data))
+    }
+
+    pub fn merge(entity_type: EntityTypeName, entity_id: EntityKey, data: RawEntity) -> Self {
+        Self::Merge((entity_type.into(), entity_id.into(), data))
+    }
+
+    pub fn delete(entity_type: EntityTypeName, entity_id: EntityKey) -> Self {
+        Self::Delete((entity_type.into(), entity_id.into()))
+    }
+
+    pub fn load_related(
+        entity_type: EntityTypeName,
+        entity_id: EntityKey,
+        field_name: FieldName,
+    ) -> Self {
+        Self::LoadRelated((entity_type.into(), entity_id.into(), field_name))
+    }
+
+    pub fn load_in_block(entity_type: EntityTypeName, entity_id: EntityKey) -> Self {
+        Self::LoadInBlock((entity_type.into(), entity_id.into()))
+    }
+
+    pub fn load_with_freshness(entity_type: EntityTypeName, entity_id: EntityKey) -> Self {
+        Self::LoadWithFreshness((entity_type.into(), entity_id.into()))
+    }
+
     pub fn operation_type(&self) -> String {
         match self {
             Self::Create(_) => "CREATE".to_owned(),
             Self::Load(_) => "LOAD".to_owned(),
             Self::Update(_) => "UPDATE".to_owned(),
+            Self::Merge(_) => "MERGE".to_owned(),
             Self::Delete(_) => "DELETE".to_owned(),
             Self::LoadRelated(_) => "LOAD_RELATED".to_owned(),
             Self::LoadInBlock(_) => "LOAD_IN_BLOCK".to_owned(),
+            Self::LoadWithFreshness(_) => "LOAD_WITH_FRESHNESS".to_owned(),
         }
     }
 
@@ -83,9 +128,11 @@ impl StoreOperationMessage {
             Self::Create((entity, _)) => entity.to_owned(),
             Self::Load((entity, _)) => entity.to_owned(),
             Self::Update((entity, ..)) => entity.to_owned(),
+            Self::Merge((entity, ..)) => entity.to_owned(),
             Self::Delete((entity, _)) => entity.to_owned(),
             Self::LoadRelated((entity, ..)) => entity.to_owned(),
             Self::LoadInBlock((entity, _)) => entity.to_owned(),
+            Self::LoadWithFreshness((entity, _)) => entity.to_owned(),
         }
     }
 }
@@ -94,8 +141,68 @@ impl StoreOperationMessage {
 pub enum StoreRequestResult {
     Create(String),
     Load(Option<RawEntity>),
-    Delete,
+    /// Number of entities deleted
+    Delete(usize),
     Update,
     LoadRelated(Vec<RawEntity>),
     LoadInBlock(Option<RawEntity>),
+    /// The loaded entity & whether it was created in the current block (not yet committed)
+    LoadWithFreshness(Option<(RawEntity, bool)>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entity_id_conversions() {
+        let entity_id = EntityKey::from("0xabc");
+        assert_eq!(entity_id, EntityKey::from("0xabc".to_string()));
+        assert_eq!(entity_id.to_string(), "0xabc");
+        assert_eq!(String::from(entity_id), "0xabc");
+
+        let entity_type = EntityTypeName::from("Token");
+        assert_eq!(String::from(entity_type), "Token");
+    }
+
+    #[test]
+    fn test_store_request_builders() {
+        let request = StoreOperationMessage::load("Token".into(), "token_1".into());
+        assert!(matches!(
+            request,
+            StoreOperationMessage::Load((entity_type, entity_id))
+                if entity_type == "Token" && entity_id == "token_1"
+        ));
+
+        let request = StoreOperationMessage::update(
+            EntityTypeName::from("User"),
+            EntityKey::from("user_1"),
+            RawEntity::new(),
+        );
+        assert_eq!(request.operation_type(), "UPDATE");
+        assert_eq!(request.entity_type(), "User");
+
+        let request = StoreOperationMessage::load_related(
+            "User".into(),
+            "user_1".into(),
+            "tokens".to_string(),
+        );
+        assert!(matches!(
+            request,
+            StoreOperationMessage::LoadRelated((entity_type, entity_id, field))
+                if entity_type == "User" && entity_id == "user_1" && field == "tokens"
+        ));
+
+        let request =
+            StoreOperationMessage::merge("User".into(), "user_1".into(), RawEntity::new());
+        assert_eq!(request.operation_type(), "MERGE");
+        assert_eq!(request.entity_type(), "User");
+
+        let request = StoreOperationMessage::delete("Token".into(), "token_1".into());
+        assert_eq!(request.operation_type(), "DELETE");
+        let request = StoreOperationMessage::load_in_block("Token".into(), "token_1".into());
+        assert_eq!(request.operation_type(), "LOAD_IN_BLOCK");
+        let request = StoreOperationMessage::load_with_freshness("Token".into(), "token_1".into());
+        assert_eq!(request.operation_type(), "LOAD_WITH_FRESHNESS");
+    }
 }
@@ -1,17 +1,41 @@
 use super::base::EntityType;
 use super::base::FieldKind;
 use super::base::FieldName;
+use super::base::RawEntity;
 use super::base::Schema;
 use crate::error;
+use crate::errors::SchemaError;
 use crate::runtime::asc::native_types::store::StoreValueKind;
+use crate::runtime::asc::native_types::store::Value;
+use crate::warn;
 use apollo_parser::cst::CstNode;
 use apollo_parser::cst::Definition;
+use apollo_parser::cst::ObjectTypeDefinition;
 use apollo_parser::cst::Type;
 use apollo_parser::Parser;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
 
-#[derive(Clone, Default, Debug)]
-pub struct Schemas(HashMap<EntityType, Schema>);
+const DEFAULT_PRIMARY_KEY: &str = "id";
+
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Schemas {
+    entities: HashMap<EntityType, Schema>,
+    /// Entities whose primary key is not named `id`
+    primary_keys: HashMap<EntityType, FieldName>,
+    /// Entities declared with `@entity(immutable: true)`, written once & never updated
+    immutable_entities: HashSet<EntityType>,
+    /// Fields flagged `@index`, backed by a secondary index so entities can be looked up by them
+    indexed_fields: HashMap<EntityType, HashSet<FieldName>>,
+    /// Fields declared with `@derivedFrom`, never stored but resolved from the entities referencing them
+    derived_fields: HashMap<EntityType, HashSet<FieldName>>,
+    /// Fields flagged `@encrypted`, stored encrypted at rest, backends not supporting it refuse them
+    encrypted_fields: HashMap<EntityType, HashSet<FieldName>>,
+}
 
 impl Schemas {
     pub fn new_from_graphql_schema(schema: &str) -> Self {
@@ -27,7 +51,7 @@ impl Schemas {
                     .expect("Name of Object Definition invalid")
                     .text()
                     .to_string();
-                schemas.0.insert(entity_type, Schema::new());
+                schemas.entities.insert(entity_type, Schema::new());
             }
         });
         for def in doc.definitions() {
@@ -37,7 +61,11 @@ impl Schemas {
                     .unwrap_or_else(|| panic!("Name of Object Definition invalid"))
                     .text()
                     .to_string();
+                if Self::is_immutable_entity(&object) {
+                    schemas.set_immutable(&entity_type);
+                }
                 let mut schema = Schema::new();
+                let mut primary_key = None;
                 for field in object.fields_definition().unwrap().field_definitions() {
                     let ty = field
                         .ty()
@@ -46,11 +74,32 @@ impl Schemas {
                         .name()
                         .unwrap_or_else(|| panic!("Name of field {:?} error", field))
                         .text();
+                    if Self::is_id_field(&ty) && (primary_key.is_none() || field_name == "id") {
+                        primary_key = Some(field_name.to_string());
+                    }
+                    let is_derived = field.directives().is_some_and(|dirs| {
+                        dirs.directives()
+                            .any(|dir| dir.name().is_some_and(|name| name.text() == "derivedFrom"))
+                    });
                     let mut field_kind = Self::parse_entity_field(ty);
-                    if let Some(dir) = field.directives() {
-                        let first = dir.directives().next();
-                        if let Some(first) = first {
-                            let arg = first.arguments().unwrap().arguments().next().unwrap();
+                    // Derived fields are never written, so they cannot be required
+                    field_kind.is_nullable |= is_derived;
+                    for directive in field.directives().iter().flat_map(|dir| dir.directives()) {
+                        if directive.name().is_some_and(|name| name.text() == "index") {
+                            schemas.set_indexed(&entity_type, field_name);
+                            continue;
+                        }
+                        if directive
+                            .name()
+                            .is_some_and(|name| name.text() == "encrypted")
+                        {
+                            schemas.set_encrypted(&entity_type, field_name);
+                            continue;
+                        }
+                        let arg = directive
+                            .arguments()
+                            .and_then(|args| args.arguments().next());
+                        if let Some(arg) = arg {
                             let name = arg.name().unwrap().text();
                             if field_kind.relation.is_some() && name == "field" {
                                 field_kind.relation = Some((
@@ -60,13 +109,38 @@ impl Schemas {
                             }
                         }
                     }
+                    if is_derived {
+                        schemas
+                            .derived_fields
+                            .entry(entity_type.clone())
+                            .or_default()
+                            .insert(field_name.to_string());
+                    }
                     schema.insert(field_name.to_string(), field_kind);
                 }
-                schemas.0.remove(&entity_type);
+                schemas.entities.remove(&entity_type);
+                if let Some(primary_key) = primary_key {
+                    schemas.set_primary_key(&entity_type, &primary_key);
+                }
                 schemas.add_schema(&entity_type, schema)
             }
         }
 
+        // Derived fields are resolved by looking the referencing entities up by the field
+        // they point at, so that field is indexed whether flagged `@index` or not
+        let referencing_fields = schemas
+            .derived_fields
+            .iter()
+            .flat_map(|(entity_type, fields)| {
+                fields
+                    .iter()
+                    .filter_map(|field| schemas.get_derived_field(entity_type, field))
+            })
+            .collect::<Vec<_>>();
+        for (relation_table, relation_field) in referencing_fields {
+            schemas.set_indexed(&relation_table, &relation_field);
+        }
+
         schemas
     }
 
@@ -78,6 +152,7 @@ impl Schemas {
                     kind: StoreValueKind::Bool,
                     relation: None,
                     list_inner_kind: None,
+                    is_nullable: true,
                 },
             );
         }
@@ -88,10 +163,47 @@ impl Schemas {
                     kind: StoreValueKind::Int8,
                     relation: None,
                     list_inner_kind: None,
+                    is_nullable: true,
+                },
+            );
+        }
+        self.entities.insert(entity_name.to_owned(), schema);
+    }
+
+    /// Add the indexed `__block_timestamp__` column to every entity schema
+    pub fn add_block_timestamp_field(&mut self) {
+        for schema in self.entities.values_mut() {
+            schema.insert(
+                "__block_timestamp__".to_string(),
+                FieldKind {
+                    kind: StoreValueKind::Int8,
+                    relation: None,
+                    list_inner_kind: None,
+                    is_nullable: true,
+                },
+            );
+        }
+    }
+
+    /// Add the `__checksum__` column used to detect corrupted entities to every entity schema
+    pub fn add_checksum_field(&mut self) {
+        for schema in self.entities.values_mut() {
+            schema.insert(
+                "__checksum__".to_string(),
+                FieldKind {
+                    kind: StoreValueKind::Int8,
+                    relation: None,
+                    list_inner_kind: None,
+                    is_nullable: true,
                 },
             );
         }
-        self.0.insert(entity_name.to_owned(), schema);
+    }
+
+    pub fn has_block_timestamp_field(&self, entity_type: &str) -> bool {
+        self.entities
+            .get(entity_type)
+            .is_some_and(|schema| schema.contains_key("__block_timestamp__"))
     }
 
     pub fn get_relation_field(
@@ -99,7 +211,7 @@ impl Schemas {
         entity_name: &str,
         field_name: &str,
     ) -> Option<(EntityType, FieldName)> {
-        let entity = self.0.get(entity_name);
+        let entity = self.entities.get(entity_name);
         entity?;
         let field = entity.unwrap().get(field_name);
 
@@ -112,17 +224,273 @@ impl Schemas {
         Some(relation)
     }
 
+    /// Kind of every field of `entity_type` as declared by the schema
+    pub fn get_field_kinds(&self, entity_type: &str) -> HashMap<FieldName, StoreValueKind> {
+        self.entities
+            .get(entity_type)
+            .map(|schema| {
+                schema
+                    .iter()
+                    .map(|(field, field_kind)| (field.to_owned(), field_kind.kind))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Fields of `entity_type` declared as `Int`
+    pub fn get_int_fields(&self, entity_type: &str) -> Vec<FieldName> {
+        self.entities
+            .get(entity_type)
+            .map(|schema| {
+                schema
+                    .iter()
+                    .filter(|(_, field_kind)| field_kind.kind == StoreValueKind::Int)
+                    .map(|(field, _)| field.to_owned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Entity type & field referencing `entity_name` if `field_name` is declared with `@derivedFrom`
+    pub fn get_derived_field(
+        &self,
+        entity_name: &str,
+        field_name: &str,
+    ) -> Option<(EntityType, FieldName)> {
+        let is_derived = self
+            .derived_fields
+            .get(entity_name)
+            .is_some_and(|fields| fields.contains(field_name));
+        match is_derived {
+            true => self.get_relation_field(entity_name, field_name),
+            false => None,
+        }
+    }
+
+    /// Rank of every entity type so that types come after the ones their relation
+    /// fields reference. Types within a relation cycle are ranked by name
+    pub fn write_order(&self) -> HashMap<EntityType, usize> {
+        let mut dependencies = self
+            .entities
+            .iter()
+            .map(|(entity_type, schema)| {
+                let referenced = schema
+                    .iter()
+                    .filter(|(field, _)| self.get_derived_field(entity_type, field).is_none())
+                    .filter_map(|(_, field_kind)| field_kind.relation.as_ref())
+                    .map(|(related, _)| related.to_owned())
+                    .filter(|related| related != entity_type && self.entities.contains_key(related))
+                    .collect::<HashSet<_>>();
+                (entity_type.to_owned(), referenced)
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut order = HashMap::new();
+        while !dependencies.is_empty() {
+            let mut ready = dependencies
+                .iter()
+                .filter(|(_, referenced)| referenced.is_empty())
+                .map(|(entity_type, _)| entity_type.to_owned())
+                .collect::<Vec<_>>();
+
+            if ready.is_empty() {
+                let entity_type = dependencies.keys().min().cloned().unwrap();
+                warn!(Schemas, "relation cycle, write order is not guaranteed"; entity_type => entity_type);
+                ready.push(entity_type);
+            }
+
+            ready.sort();
+            for entity_type in ready {
+                dependencies.remove(&entity_type);
+                dependencies.values_mut().for_each(|referenced| {
+                    referenced.remove(&entity_type);
+                });
+                order.insert(entity_type, order.len());
+            }
+        }
+        order
+    }
+
+    /// Name of the field identifying an entity, `id` unless the schema's `ID` field is named otherwise
+    pub fn get_primary_key(&self, entity_type: &str) -> &str {
+        self.primary_keys
+            .get(entity_type)
+            .map(|field| field.as_str())
+            .unwrap_or(DEFAULT_PRIMARY_KEY)
+    }
+
+    pub fn set_primary_key(&mut self, entity_type: &str, field_name: &str) {
+        if field_name == DEFAULT_PRIMARY_KEY {
+            self.primary_keys.remove(entity_type);
+        } else {
+            self.primary_keys
+                .insert(entity_type.to_owned(), field_name.to_owned());
+        }
+    }
+
+    pub fn is_immutable(&self, entity_type: &str) -> bool {
+        self.immutable_entities.contains(entity_type)
+    }
+
+    pub fn set_immutable(&mut self, entity_type: &str) {
+        self.immutable_entities.insert(entity_type.to_owned());
+    }
+
+    pub fn is_indexed(&self, entity_type: &str, field_name: &str) -> bool {
+        self.indexed_fields
+            .get(entity_type)
+            .is_some_and(|fields| fields.contains(field_name))
+    }
+
+    pub fn set_indexed(&mut self, entity_type: &str, field_name: &str) {
+        self.indexed_fields
+            .entry(entity_type.to_owned())
+            .or_default()
+            .insert(field_name.to_owned());
+    }
+
+    pub fn get_indexed_fields(&self, entity_type: &str) -> Vec<FieldName> {
+        let mut fields = self
+            .indexed_fields
+            .get(entity_type)
+            .map(|fields| fields.iter().cloned().collect::<Vec<_>>())
+            .unwrap_or_default();
+        fields.sort();
+        fields
+    }
+
+    #[cfg_attr(not(feature = "scylla"), allow(dead_code))]
+    pub fn is_encrypted(&self, entity_type: &str, field_name: &str) -> bool {
+        self.encrypted_fields
+            .get(entity_type)
+            .is_some_and(|fields| fields.contains(field_name))
+    }
+
+    pub fn set_encrypted(&mut self, entity_type: &str, field_name: &str) {
+        self.encrypted_fields
+            .entry(entity_type.to_owned())
+            .or_default()
+            .insert(field_name.to_owned());
+    }
+
+    pub fn has_encrypted_fields(&self) -> bool {
+        self.encrypted_fields
+            .values()
+            .any(|fields| !fields.is_empty())
+    }
+
+    /// Persist the resolved schema, so later runs can tell whether it changed
+    pub fn save_catalog(&self, path: &Path) -> Result<(), SchemaError> {
+        let catalog_error = |e: String| SchemaError::Catalog(path.display().to_string(), e);
+        let content =
+            serde_json::to_string_pretty(self).map_err(|e| catalog_error(e.to_string()))?;
+        std::fs::write(path, content).map_err(|e| catalog_error(e.to_string()))
+    }
+
+    /// The schema saved by `save_catalog`, `None` if nothing was saved yet
+    pub fn load_catalog(path: &Path) -> Result<Option<Self>, SchemaError> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let catalog_error = |e: String| SchemaError::Catalog(path.display().to_string(), e);
+        let content = std::fs::read_to_string(path).map_err(|e| catalog_error(e.to_string()))?;
+        let schemas = serde_json::from_str(&content).map_err(|e| catalog_error(e.to_string()))?;
+        Ok(Some(schemas))
+    }
+
+    /// Entity types added, removed or changed in any way from `other`, sorted by name
+    pub fn drifted_entities(&self, other: &Schemas) -> Vec<EntityType> {
+        let entity_types = self
+            .entities
+            .keys()
+            .chain(other.entities.keys())
+            .collect::<BTreeSet<_>>();
+        entity_types
+            .into_iter()
+            .filter(|entity_type| !self.same_entity(other, entity_type))
+            .cloned()
+            .collect()
+    }
+
+    fn same_entity(&self, other: &Schemas, entity_type: &str) -> bool {
+        self.entities.get(entity_type) == other.entities.get(entity_type)
+            && self.primary_keys.get(entity_type) == other.primary_keys.get(entity_type)
+            && self.immutable_entities.contains(entity_type)
+                == other.immutable_entities.contains(entity_type)
+            && self.indexed_fields.get(entity_type) == other.indexed_fields.get(entity_type)
+            && self.derived_fields.get(entity_type) == other.derived_fields.get(entity_type)
+            && self.encrypted_fields.get(entity_type) == other.encrypted_fields.get(entity_type)
+    }
+
+    /// Check that all required fields are present, no unknown field exists
+    /// and every value matches the kind of its field
+    pub fn validate_entity(&self, entity_type: &str, data: &RawEntity) -> Result<(), SchemaError> {
+        let schema = self
+            .entities
+            .get(entity_type)
+            .ok_or_else(|| SchemaError::UnknownEntity(entity_type.to_owned()))?;
+
+        if let Some((field, _)) = schema.iter().find(|(field, field_kind)| {
+            !field_kind.is_nullable && matches!(data.get(*field), None | Some(Value::Null))
+        }) {
+            return Err(SchemaError::MissingField(
+                entity_type.to_owned(),
+                field.to_owned(),
+            ));
+        }
+
+        for (field_name, value) in data {
+            let field_kind = schema.get(field_name).ok_or_else(|| {
+                SchemaError::UnknownField(entity_type.to_owned(), field_name.to_owned())
+            })?;
+
+            if !Self::value_matches_kind(value, field_kind) {
+                return Err(SchemaError::InvalidFieldType {
+                    entity_type: entity_type.to_owned(),
+                    field: field_name.to_owned(),
+                    expected: field_kind.kind,
+                    found: StoreValueKind::get_kind(value),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn value_matches_kind(value: &Value, field_kind: &FieldKind) -> bool {
+        match (value, field_kind.kind) {
+            (Value::Null, _) => field_kind.is_nullable,
+            // Relations hold the related entity's id, which may be either a string or bytes
+            (Value::String(_) | Value::Bytes(_), _) if field_kind.relation.is_some() => {
+                field_kind.kind != StoreValueKind::Array
+            }
+            (Value::List(values), StoreValueKind::Array) => {
+                let inner = FieldKind {
+                    kind: field_kind.list_inner_kind.unwrap_or_default(),
+                    relation: field_kind.relation.clone(),
+                    list_inner_kind: None,
+                    is_nullable: true,
+                };
+                values
+                    .iter()
+                    .all(|value| Self::value_matches_kind(value, &inner))
+            }
+            (Value::Int8(_), StoreValueKind::Timestamp) => true,
+            (value, kind) => StoreValueKind::get_kind(value) == kind,
+        }
+    }
+
     pub fn get_entity_names(&self) -> Vec<String> {
-        self.0.keys().cloned().collect()
+        self.entities.keys().cloned().collect()
     }
 
     pub fn get_schema(&self, entity_type: &str) -> Schema {
-        self.0.get(entity_type).unwrap().clone()
+        self.entities.get(entity_type).unwrap().clone()
     }
 
     pub fn get_field(&self, entity_type: &str, field_name: &str) -> FieldKind {
         let entity_schema = self
-            .0
+            .entities
             .get(entity_type)
             .cloned()
             .unwrap_or_else(|| panic!("No entity named = {entity_type}"));
@@ -142,6 +510,35 @@ impl Schemas {
         field_kind
     }
 
+    fn is_immutable_entity(object: &ObjectTypeDefinition) -> bool {
+        object
+            .directives()
+            .into_iter()
+            .flat_map(|directives| directives.directives())
+            .filter(|directive| directive.name().is_some_and(|name| name.text() == "entity"))
+            .flat_map(|directive| directive.arguments())
+            .flat_map(|arguments| arguments.arguments())
+            .any(|argument| {
+                argument
+                    .name()
+                    .is_some_and(|name| name.text() == "immutable")
+                    && argument
+                        .value()
+                        .is_some_and(|value| value.source_string().trim() == "true")
+            })
+    }
+
+    fn is_id_field(field_type: &Type) -> bool {
+        let named_type = match field_type {
+            Type::NamedType(name_type) => Some(name_type.clone()),
+            Type::NonNullType(value) => value.named_type(),
+            Type::ListType(_) => None,
+        };
+        named_type
+            .and_then(|name_type| name_type.name())
+            .is_some_and(|name| name.text() == "ID")
+    }
+
     fn parse_entity_field(field_type: Type) -> FieldKind {
         match field_type {
             Type::NamedType(name_type) => {
@@ -160,6 +557,7 @@ impl Schemas {
                     "Boolean" => StoreValueKind::Bool,
                     "Int" => StoreValueKind::Int,
                     "Int8" => StoreValueKind::Int8,
+                    "Timestamp" => StoreValueKind::Timestamp,
                     unknown_type => {
                         relation = Some((unknown_type.to_string(), "id".to_string()));
                         StoreValueKind::String
@@ -169,6 +567,7 @@ impl Schemas {
                     kind,
                     relation,
                     list_inner_kind: None,
+                    is_nullable: true,
                 }
             }
             Type::ListType(list) => {
@@ -179,17 +578,21 @@ impl Schemas {
                     kind: StoreValueKind::Array,
                     relation: value.relation,
                     list_inner_kind: Some(value.kind),
+                    is_nullable: true,
                 }
             }
             Type::NonNullType(value) => {
-                if let Some(list) = value.list_type() {
-                    return Schemas::parse_entity_field(Type::ListType(list));
-                }
-
-                if let Some(name_type) = value.named_type() {
-                    return Schemas::parse_entity_field(Type::NamedType(name_type));
+                let field_kind = if let Some(list) = value.list_type() {
+                    Schemas::parse_entity_field(Type::ListType(list))
+                } else if let Some(name_type) = value.named_type() {
+                    Schemas::parse_entity_field(Type::NamedType(name_type))
+                } else {
+                    unimplemented!()
+                };
+                FieldKind {
+                    is_nullable: false,
+                    ..field_kind
                 }
-                unimplemented!()
             }
         }
     }
@@ -209,6 +612,251 @@ mod test {
 
         let schemas = Schemas::new_from_graphql_schema(&gql);
         let entity_type = "Pool";
-        let _token = schemas.0.get(entity_type).unwrap();
+        let _token = schemas.entities.get(entity_type).unwrap();
+    }
+
+    #[test]
+    fn test_schema_primary_key() {
+        env_logger::try_init().unwrap_or_default();
+        let gql = r#"
+            type Token @entity {
+                id: ID!
+                owner: ID!
+            }
+            type Snapshot @entity(immutable: true) {
+                pk: ID!
+                value: BigInt!
+            }
+        "#;
+        let schemas = Schemas::new_from_graphql_schema(gql);
+        assert_eq!(schemas.get_primary_key("Token"), "id");
+        assert_eq!(schemas.get_primary_key("Snapshot"), "pk");
+        assert_eq!(schemas.get_primary_key("Unknown"), "id");
+        assert!(schemas.is_immutable("Snapshot"));
+        assert!(!schemas.is_immutable("Token"));
+    }
+
+    #[test]
+    fn test_schema_indexed_fields() {
+        env_logger::try_init().unwrap_or_default();
+        let gql = r#"
+            type Position @entity {
+                id: ID!
+                owner: String! @index
+                pool: Pool!
+            }
+            type Pool @entity {
+                id: ID!
+                positions: [Position!]! @derivedFrom(field: "pool")
+            }
+        "#;
+        let schemas = Schemas::new_from_graphql_schema(gql);
+        assert!(schemas.is_indexed("Position", "owner"));
+        // Referenced by `Pool.positions`
+        assert!(schemas.is_indexed("Position", "pool"));
+        assert_eq!(
+            schemas.get_indexed_fields("Position"),
+            vec!["owner", "pool"]
+        );
+        assert!(schemas.get_indexed_fields("Pool").is_empty());
+        assert!(!schemas.has_encrypted_fields());
+        assert_eq!(
+            schemas.get_relation_field("Pool", "positions"),
+            Some(("Position".to_string(), "pool".to_string()))
+        );
+        assert_eq!(
+            schemas.get_derived_field("Pool", "positions"),
+            Some(("Position".to_string(), "pool".to_string()))
+        );
+        assert_eq!(schemas.get_derived_field("Position", "pool"), None);
+    }
+
+    #[test]
+    fn test_schema_encrypted_fields() {
+        env_logger::try_init().unwrap_or_default();
+        let gql = r#"
+            type Account @entity {
+                id: ID!
+                email: String @encrypted
+                balance: BigInt!
+            }
+        "#;
+        let schemas = Schemas::new_from_graphql_schema(gql);
+        assert!(schemas.has_encrypted_fields());
+        assert!(schemas.is_encrypted("Account", "email"));
+        assert!(!schemas.is_encrypted("Account", "balance"));
+        assert!(!schemas.is_indexed("Account", "email"));
+    }
+
+    fn token_schemas() -> Schemas {
+        let gql = r#"
+            type Token @entity {
+                id: ID!
+                symbol: String!
+                decimals: Int
+                holders: [Account!]!
+                transfers: [Transfer!]! @derivedFrom(field: "token")
+            }
+            type Account @entity {
+                id: ID!
+            }
+            type Transfer @entity {
+                id: ID!
+                token: Token!
+            }
+        "#;
+        Schemas::new_from_graphql_schema(gql)
+    }
+
+    fn token() -> RawEntity {
+        RawEntity::from([
+            ("id".to_string(), Value::String("token_0".to_string())),
+            ("symbol".to_string(), Value::String("TKN".to_string())),
+            ("decimals".to_string(), Value::Null),
+            (
+                "holders".to_string(),
+                Value::List(vec![Value::String("account_0".to_string())]),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_validate_valid_entity() {
+        env_logger::try_init().unwrap_or_default();
+        let schemas = token_schemas();
+        assert!(schemas.validate_entity("Token", &token()).is_ok());
+
+        let mut data = token();
+        data.insert("decimals".to_string(), Value::Int(18));
+        assert!(schemas.validate_entity("Token", &data).is_ok());
+    }
+
+    #[test]
+    fn test_validate_missing_required_field() {
+        env_logger::try_init().unwrap_or_default();
+        let schemas = token_schemas();
+        let mut data = token();
+        data.remove("symbol");
+
+        assert!(matches!(
+            schemas.validate_entity("Token", &data),
+            Err(SchemaError::MissingField(entity_type, field))
+                if entity_type == "Token" && field == "symbol"
+        ));
+
+        data.insert("symbol".to_string(), Value::Null);
+        assert!(matches!(
+            schemas.validate_entity("Token", &data),
+            Err(SchemaError::MissingField(..))
+        ));
+
+        let mut data = token();
+        data.insert("name".to_string(), Value::String("Token".to_string()));
+        assert!(matches!(
+            schemas.validate_entity("Token", &data),
+            Err(SchemaError::UnknownField(_, field)) if field == "name"
+        ));
+    }
+
+    #[test]
+    fn test_nullable_fields() {
+        env_logger::try_init().unwrap_or_default();
+        let schemas = token_schemas();
+        assert!(!schemas.get_field("Token", "symbol").is_nullable);
+        assert!(!schemas.get_field("Token", "holders").is_nullable);
+        assert!(schemas.get_field("Token", "decimals").is_nullable);
+        assert!(schemas.get_field("Token", "transfers").is_nullable);
+
+        let mut data = token();
+        data.remove("decimals");
+        assert!(schemas.validate_entity("Token", &data).is_ok());
+
+        data.remove("holders");
+        assert!(matches!(
+            schemas.validate_entity("Token", &data),
+            Err(SchemaError::MissingField(_, field)) if field == "holders"
+        ));
+    }
+
+    #[test]
+    fn test_validate_wrong_type() {
+        env_logger::try_init().unwrap_or_default();
+        let schemas = token_schemas();
+        let mut data = token();
+        data.insert("decimals".to_string(), Value::String("18".to_string()));
+
+        assert!(matches!(
+            schemas.validate_entity("Token", &data),
+            Err(SchemaError::InvalidFieldType {
+                expected: StoreValueKind::Int,
+                found: StoreValueKind::String,
+                ..
+            })
+        ));
+
+        let mut data = token();
+        data.insert("holders".to_string(), Value::List(vec![Value::Int(1)]));
+        assert!(matches!(
+            schemas.validate_entity("Token", &data),
+            Err(SchemaError::InvalidFieldType { field, .. }) if field == "holders"
+        ));
+    }
+
+    #[test]
+    fn test_schema_write_order() {
+        env_logger::try_init().unwrap_or_default();
+        let gql = r#"
+        type Swap @entity { id: ID! pool: Pool! token: Token! }
+        type Pool @entity { id: ID! token0: Token! swaps: [Swap!]! @derivedFrom(field: "pool") }
+        type Token @entity { id: ID! parent: Token }
+        type A @entity { id: ID! b: B! }
+        type B @entity { id: ID! a: A! }
+        "#;
+        let schemas = Schemas::new_from_graphql_schema(gql);
+        let order = schemas.write_order();
+
+        // Derived & self-referencing fields are no dependency
+        assert!(order["Token"] < order["Pool"]);
+        assert!(order["Pool"] < order["Swap"]);
+        // Cycles fall back to the name order
+        assert!(order["A"] < order["B"]);
+        assert_eq!(order.len(), 5);
+    }
+
+    #[test]
+    fn test_schema_catalog() {
+        env_logger::try_init().unwrap_or_default();
+        let gql = r#"
+        type Pool @entity { id: ID! token0: Token! owner: Bytes! @index fee: BigInt }
+        type Token @entity(immutable: true) { id: ID! pools: [Pool!]! @derivedFrom(field: "token0") }
+        "#;
+        let schemas = Schemas::new_from_graphql_schema(gql);
+        let path = std::env::temp_dir().join(format!("schema-catalog-{}.json", std::process::id()));
+        assert!(Schemas::load_catalog(&path).unwrap().is_none());
+
+        schemas.save_catalog(&path).unwrap();
+        let loaded = Schemas::load_catalog(&path).unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        for entity_type in ["Pool", "Token"] {
+            let fields = schemas.get_schema(entity_type);
+            for (field, field_kind) in fields.iter() {
+                assert_eq!(loaded.get_field(entity_type, field), field_kind.clone());
+            }
+        }
+        assert_eq!(loaded, schemas);
+        assert!(loaded.drifted_entities(&schemas).is_empty());
+
+        let changed = Schemas::new_from_graphql_schema(
+            r#"
+        type Pool @entity { id: ID! token0: Token! owner: Bytes! fee: BigInt }
+        type Token @entity(immutable: true) { id: ID! pools: [Pool!]! @derivedFrom(field: "token0") }
+        type Swap @entity { id: ID! }
+        "#,
+        );
+        assert_eq!(
+            changed.drifted_entities(&loaded),
+            vec!["Pool".to_string(), "Swap".to_string()]
+        );
     }
 }
@@ -1,16 +1,17 @@
+use super::super::decode_block;
 use super::proto::ethereum::Block as PbBlock;
 use super::proto::ethereum::Transaction as PbTransaction;
 use super::DeltaBlockTrait;
 use crate::chain::ethereum::block::EthereumBlockData;
 use crate::chain::ethereum::transaction::EthereumTransactionData;
 use crate::common::BlockDataMessage;
+use crate::config::ContentType;
 use crate::errors::SourceError;
 use deltalake::arrow::array::Array;
 use deltalake::arrow::array::BinaryArray;
 use deltalake::arrow::record_batch::RecordBatch;
 use ethabi::Bytes;
 use hex::FromHex;
-use prost::Message;
 use rayon::prelude::IntoParallelIterator;
 use rayon::prelude::ParallelIterator;
 use std::str::FromStr;
@@ -125,8 +126,8 @@ impl TryFrom<RecordBatch> for DeltaEthereumBlocks {
 
         let blocks = block_data
             .into_iter()
-            .map(|b| PbBlock::decode(&mut b.unwrap()).unwrap())
-            .collect::<Vec<PbBlock>>();
+            .map(|b| decode_block(b.unwrap(), &ContentType::Protobuf))
+            .collect::<Result<Vec<PbBlock>, _>>()?;
 
         Ok(Self(blocks))
     }
@@ -135,6 +135,7 @@ impl DeltaClient {
                 return Ok(());
             }
 
+            valve.acquire_inflight(blocks.len()).await;
             valve.set_downloaded(blocks.last().map(|b| b.get_block_ptr().number).unwrap());
             sender.send(blocks).await?;
             start_block += self.query_step;
@@ -0,0 +1,234 @@
+// Messages of `sf.ethereum.type.v2`, the blocks served by Firehose-Ethereum,
+// only the fields the runtime maps
+use crate::chain::ethereum::block::EthereumBlockData;
+use crate::chain::ethereum::transaction::EthereumTransactionData;
+use crate::common::BlockDataMessage;
+use web3::types::Bytes as Web3Bytes;
+use web3::types::Index;
+use web3::types::Log as Web3Log;
+use web3::types::H160;
+use web3::types::H256;
+use web3::types::U128;
+use web3::types::U256;
+use web3::types::U64;
+
+pub const BLOCK_TYPE_URL: &str = "type.googleapis.com/sf.ethereum.type.v2.Block";
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Block {
+    #[prost(bytes = "vec", tag = "2")]
+    pub hash: ::prost::alloc::vec::Vec<u8>,
+    #[prost(uint64, tag = "3")]
+    pub number: u64,
+    #[prost(uint64, tag = "4")]
+    pub size: u64,
+    #[prost(message, optional, tag = "5")]
+    pub header: ::core::option::Option<BlockHeader>,
+    #[prost(message, repeated, tag = "10")]
+    pub transaction_traces: ::prost::alloc::vec::Vec<TransactionTrace>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BlockHeader {
+    #[prost(bytes = "vec", tag = "1")]
+    pub parent_hash: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub uncle_hash: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub coinbase: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub state_root: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub transactions_root: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "6")]
+    pub receipt_root: ::prost::alloc::vec::Vec<u8>,
+    #[prost(message, optional, tag = "8")]
+    pub difficulty: ::core::option::Option<BigInt>,
+    #[prost(uint64, tag = "9")]
+    pub number: u64,
+    #[prost(uint64, tag = "10")]
+    pub gas_limit: u64,
+    #[prost(uint64, tag = "11")]
+    pub gas_used: u64,
+    #[prost(message, optional, tag = "12")]
+    pub timestamp: ::core::option::Option<Timestamp>,
+    #[prost(bytes = "vec", tag = "16")]
+    pub hash: ::prost::alloc::vec::Vec<u8>,
+    #[prost(message, optional, tag = "17")]
+    pub total_difficulty: ::core::option::Option<BigInt>,
+    /// Base fee per unit of gas, unset before London
+    #[prost(message, optional, tag = "18")]
+    pub base_fee_per_gas: ::core::option::Option<BigInt>,
+}
+/// `google.protobuf.Timestamp`
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Timestamp {
+    #[prost(int64, tag = "1")]
+    pub seconds: i64,
+    #[prost(int32, tag = "2")]
+    pub nanos: i32,
+}
+/// Big-endian unsigned integer
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BigInt {
+    #[prost(bytes = "vec", tag = "1")]
+    pub bytes: ::prost::alloc::vec::Vec<u8>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TransactionTrace {
+    /// Empty when the transaction creates a contract
+    #[prost(bytes = "vec", tag = "1")]
+    pub to: ::prost::alloc::vec::Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub nonce: u64,
+    #[prost(message, optional, tag = "3")]
+    pub gas_price: ::core::option::Option<BigInt>,
+    #[prost(uint64, tag = "4")]
+    pub gas_limit: u64,
+    #[prost(message, optional, tag = "5")]
+    pub value: ::core::option::Option<BigInt>,
+    #[prost(bytes = "vec", tag = "6")]
+    pub input: ::prost::alloc::vec::Vec<u8>,
+    #[prost(uint32, tag = "20")]
+    pub index: u32,
+    #[prost(bytes = "vec", tag = "21")]
+    pub hash: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "22")]
+    pub from: ::prost::alloc::vec::Vec<u8>,
+    #[prost(enumeration = "TransactionTraceStatus", tag = "30")]
+    pub status: i32,
+    #[prost(message, optional, tag = "31")]
+    pub receipt: ::core::option::Option<TransactionReceipt>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TransactionReceipt {
+    #[prost(message, repeated, tag = "4")]
+    pub logs: ::prost::alloc::vec::Vec<Log>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Log {
+    #[prost(bytes = "vec", tag = "1")]
+    pub address: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    pub topics: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+    /// Index of the log within its transaction
+    #[prost(uint32, tag = "4")]
+    pub index: u32,
+    /// Index of the log within the block
+    #[prost(uint32, tag = "6")]
+    pub block_index: u32,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum TransactionTraceStatus {
+    Unknown = 0,
+    Succeeded = 1,
+    Failed = 2,
+    Reverted = 3,
+}
+
+fn big_int(value: &Option<BigInt>) -> U256 {
+    value
+        .as_ref()
+        .map(|value| U256::from_big_endian(&value.bytes))
+        .unwrap_or_default()
+}
+
+impl From<&Block> for EthereumBlockData {
+    fn from(b: &Block) -> Self {
+        let header = b.header.clone().unwrap_or_default();
+        EthereumBlockData {
+            hash: H256::from_slice(&b.hash),
+            parent_hash: H256::from_slice(&header.parent_hash),
+            uncles_hash: H256::from_slice(&header.uncle_hash),
+            author: H160::from_slice(&header.coinbase),
+            state_root: H256::from_slice(&header.state_root),
+            transactions_root: H256::from_slice(&header.transactions_root),
+            receipts_root: H256::from_slice(&header.receipt_root),
+            number: U64::from(b.number),
+            gas_used: U256::from(header.gas_used),
+            gas_limit: U256::from(header.gas_limit),
+            timestamp: U256::from(header.timestamp.map(|t| t.seconds).unwrap_or_default()),
+            difficulty: big_int(&header.difficulty),
+            total_difficulty: big_int(&header.total_difficulty),
+            size: Some(U256::from(b.size)),
+            base_fee_per_gas: header
+                .base_fee_per_gas
+                .as_ref()
+                .map(|fee| U256::from_big_endian(&fee.bytes)),
+        }
+    }
+}
+
+impl From<&TransactionTrace> for EthereumTransactionData {
+    fn from(tx: &TransactionTrace) -> Self {
+        EthereumTransactionData {
+            hash: H256::from_slice(&tx.hash),
+            index: U128::from(tx.index),
+            from: H160::from_slice(&tx.from),
+            to: match tx.to.is_empty() {
+                true => None,
+                false => Some(H160::from_slice(&tx.to)),
+            },
+            value: big_int(&tx.value),
+            gas_limit: U256::from(tx.gas_limit),
+            gas_price: big_int(&tx.gas_price),
+            input: tx.input.clone(),
+            nonce: U256::from(tx.nonce),
+        }
+    }
+}
+
+/// Logs of the succeeded transactions, those of the failed ones were reverted
+impl From<&Block> for Vec<Web3Log> {
+    fn from(b: &Block) -> Self {
+        let mut result = vec![];
+
+        for tx in b.transaction_traces.iter() {
+            if tx.status != TransactionTraceStatus::Succeeded as i32 {
+                continue;
+            }
+            let logs = tx.receipt.iter().flat_map(|receipt| receipt.logs.iter());
+            for log in logs {
+                let log_data = Web3Log {
+                    address: H160::from_slice(&log.address),
+                    topics: log.topics.iter().map(|t| H256::from_slice(t)).collect(),
+                    data: Web3Bytes::from(log.data.clone()),
+                    block_hash: Some(H256::from_slice(&b.hash)),
+                    block_number: Some(U64::from(b.number)),
+                    transaction_hash: Some(H256::from_slice(&tx.hash)),
+                    transaction_index: Some(Index::from(tx.index)),
+                    log_index: Some(U256::from(log.block_index)),
+                    transaction_log_index: Some(U256::from(log.index)),
+                    log_type: None,
+                    removed: Some(false),
+                };
+                result.push(log_data);
+            }
+        }
+
+        result
+    }
+}
+
+impl From<Block> for BlockDataMessage {
+    fn from(block: Block) -> Self {
+        BlockDataMessage::Ethereum {
+            block: EthereumBlockData::from(&block),
+            transactions: block
+                .transaction_traces
+                .iter()
+                .map(EthereumTransactionData::from)
+                .collect(),
+            logs: Vec::<Web3Log>::from(&block),
+        }
+    }
+}
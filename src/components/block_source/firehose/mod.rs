@@ -0,0 +1,356 @@
+pub mod ethereum;
+pub mod proto;
+
+use super::metrics::BlockSourceMetrics;
+use crate::common::BlockDataMessage;
+use crate::components::Valve;
+use crate::config::FirehoseConfig;
+use crate::errors::SourceError;
+use crate::info;
+use crate::warn;
+use kanal::AsyncSender;
+use prometheus::Registry;
+use prost::Message;
+use proto::ForkStep;
+use proto::Request;
+use proto::Response;
+use std::time::Duration;
+use tokio_retry::strategy::ExponentialBackoff;
+use tonic::codec::ProstCodec;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::transport::ClientTlsConfig;
+use tonic::transport::Endpoint;
+
+const STREAM_BLOCKS_PATH: &str = "/sf.firehose.v2.Stream/Blocks";
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// Stream `sf.ethereum.type.v2.Block`s from a Firehose-Ethereum endpoint. The cursor
+/// of the last block sent is kept so the stream resumes right after it when reconnecting
+pub struct FirehoseClient {
+    endpoint: Endpoint,
+    start_block: u64,
+    start_cursor: Option<String>,
+    metrics: BlockSourceMetrics,
+}
+
+impl FirehoseClient {
+    /// A `resume_cursor` of the last committed block takes over the configured start-cursor
+    pub fn new(
+        cfg: FirehoseConfig,
+        start_block: u64,
+        resume_cursor: Option<String>,
+        registry: &Registry,
+    ) -> Result<Self, SourceError> {
+        let mut endpoint = Endpoint::from_shared(cfg.endpoint.clone())
+            .map_err(|e| SourceError::FirehoseConnectFail(format!("{}: {e}", cfg.endpoint)))?;
+
+        if cfg.endpoint.starts_with("https://") {
+            endpoint = endpoint
+                .tls_config(ClientTlsConfig::new())
+                .map_err(|e| SourceError::FirehoseConnectFail(e.to_string()))?;
+        }
+
+        let start_cursor = resume_cursor.or(cfg.start_cursor);
+        info!(
+            FirehoseClient,
+            "Setup done";
+            endpoint => cfg.endpoint,
+            start_block => start_block,
+            start_cursor => format!("{:?}", start_cursor)
+        );
+
+        Ok(Self {
+            endpoint,
+            start_block,
+            start_cursor,
+            metrics: BlockSourceMetrics::new(registry),
+        })
+    }
+
+    async fn open_stream(
+        &self,
+        cursor: &Option<String>,
+    ) -> Result<tonic::Streaming<Response>, SourceError> {
+        let channel = self
+            .endpoint
+            .connect()
+            .await
+            .map_err(|e| SourceError::FirehoseConnectFail(e.to_string()))?;
+        let mut client = tonic::client::Grpc::new(channel);
+        client
+            .ready()
+            .await
+            .map_err(|e| SourceError::FirehoseConnectFail(e.to_string()))?;
+
+        let request = Request {
+            start_block_num: self.start_block as i64,
+            cursor: cursor.clone().unwrap_or_default(),
+            ..Default::default()
+        };
+        let response = client
+            .server_streaming(
+                tonic::Request::new(request),
+                PathAndQuery::from_static(STREAM_BLOCKS_PATH),
+                ProstCodec::default(),
+            )
+            .await?;
+        Ok(response.into_inner())
+    }
+
+    /// The block carried by a response, `None` for steps the runtime does not act upon:
+    /// undone blocks are superseded by the fork-blocks following them
+    fn decode_block(response: &Response) -> Result<Option<BlockDataMessage>, SourceError> {
+        match ForkStep::try_from(response.step) {
+            Ok(ForkStep::StepNew) => (),
+            _ => return Ok(None),
+        }
+
+        match &response.block {
+            Some(block) if block.type_url == ethereum::BLOCK_TYPE_URL => {
+                let block = ethereum::Block::decode(block.value.as_slice())
+                    .map_err(|e| SourceError::DecodeError(block.value.len(), e.to_string()))?;
+                Ok(Some(BlockDataMessage::from(block)))
+            }
+            Some(block) => Err(SourceError::UnsupportedContentType(block.type_url.clone())),
+            None => Ok(None),
+        }
+    }
+
+    fn reconnect_backoff() -> impl Iterator<Item = Duration> {
+        ExponentialBackoff::from_millis(2)
+            .factor(100)
+            .max_delay(MAX_RECONNECT_DELAY)
+    }
+
+    pub async fn get_block_stream(
+        &self,
+        sender: AsyncSender<Vec<BlockDataMessage>>,
+        valve: Valve,
+    ) -> Result<(), SourceError> {
+        info!(BlockSource, "start streaming block-data from firehose 🔥");
+        let mut cursor = self.start_cursor.clone();
+        let mut last_downloaded = 0;
+        let mut backoff = Self::reconnect_backoff();
+
+        loop {
+            let mut stream = match self.open_stream(&cursor).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    let delay = backoff.next().unwrap_or(MAX_RECONNECT_DELAY);
+                    warn!(
+                        BlockSource,
+                        "connecting to firehose failed, retrying";
+                        error => e,
+                        retry_in => format!("{:?}", delay)
+                    );
+                    tokio::time::sleep(delay).await;
+                    continue;
+                }
+            };
+
+            loop {
+                let response = match stream.message().await {
+                    Ok(Some(response)) => response,
+                    Ok(None) => {
+                        warn!(BlockSource, "firehose stream closed by the server");
+                        break;
+                    }
+                    Err(status) => {
+                        warn!(BlockSource, "firehose stream interrupted"; error => status);
+                        break;
+                    }
+                };
+                backoff = Self::reconnect_backoff();
+
+                if let Some(block) = Self::decode_block(&response)? {
+                    let block_number = block.get_block_ptr().number;
+                    self.metrics.block_source_total_blocks.inc();
+                    valve.acquire_inflight(1).await;
+                    // Fork-blocks may go back below what was already downloaded
+                    if block_number > last_downloaded {
+                        last_downloaded = block_number;
+                        valve.set_downloaded(block_number);
+                    }
+                    valve.set_cursor(block_number, response.cursor.clone());
+                    sender.send(vec![block]).await?;
+                    valve.temporarily_close().await;
+                }
+
+                cursor = Some(response.cursor);
+            }
+
+            let delay = backoff.next().unwrap_or(MAX_RECONNECT_DELAY);
+            info!(
+                BlockSource,
+                "reconnecting to firehose";
+                cursor => format!("{:?}", cursor),
+                retry_in => format!("{:?}", delay)
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::ValveConfig;
+    use ethereum::BigInt;
+    use ethereum::Block as FhBlock;
+    use ethereum::BlockHeader;
+    use ethereum::Log;
+    use ethereum::Timestamp;
+    use ethereum::TransactionReceipt;
+    use ethereum::TransactionTrace;
+    use ethereum::TransactionTraceStatus;
+    use prometheus::default_registry;
+    use proto::Any;
+
+    fn transaction(index: u32, status: TransactionTraceStatus) -> TransactionTrace {
+        TransactionTrace {
+            to: vec![0xaa; 20],
+            value: Some(BigInt {
+                bytes: vec![0x01, 0x00],
+            }),
+            index,
+            hash: vec![index as u8; 32],
+            from: vec![0xbb; 20],
+            status: status as i32,
+            receipt: Some(TransactionReceipt {
+                logs: vec![Log {
+                    address: vec![0xcc; 20],
+                    topics: vec![vec![0xdd; 32]],
+                    data: vec![0x01],
+                    index: 0,
+                    block_index: index,
+                }],
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn response(number: u64, step: ForkStep) -> Response {
+        let block = FhBlock {
+            hash: vec![number as u8; 32],
+            number,
+            size: 100,
+            header: Some(BlockHeader {
+                parent_hash: vec![number.saturating_sub(1) as u8; 32],
+                uncle_hash: vec![0; 32],
+                coinbase: vec![0; 20],
+                state_root: vec![0; 32],
+                transactions_root: vec![0; 32],
+                receipt_root: vec![0; 32],
+                number,
+                timestamp: Some(Timestamp {
+                    seconds: 1_600_000_000 + number as i64,
+                    nanos: 0,
+                }),
+                ..Default::default()
+            }),
+            transaction_traces: vec![
+                transaction(0, TransactionTraceStatus::Succeeded),
+                transaction(1, TransactionTraceStatus::Reverted),
+            ],
+        };
+        Response {
+            block: Some(Any {
+                type_url: ethereum::BLOCK_TYPE_URL.to_string(),
+                value: block.encode_to_vec(),
+            }),
+            step: step as i32,
+            cursor: format!("cursor-{number}"),
+        }
+    }
+
+    #[test]
+    fn test_decode_block() {
+        env_logger::try_init().unwrap_or_default();
+        let block = FirehoseClient::decode_block(&response(10, ForkStep::StepNew))
+            .unwrap()
+            .unwrap();
+        assert_eq!(block.get_block_ptr().number, 10);
+        let BlockDataMessage::Ethereum {
+            block,
+            transactions,
+            logs,
+        } = block;
+        assert_eq!(block.timestamp, 1_600_000_010.into());
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].value, 256.into());
+        // Logs of the reverted transaction are dropped
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].transaction_hash, Some(transactions[0].hash));
+
+        // Undone & finalized blocks are only followed through the cursor
+        for step in [ForkStep::StepUndo, ForkStep::StepFinal] {
+            assert!(FirehoseClient::decode_block(&response(10, step))
+                .unwrap()
+                .is_none());
+        }
+
+        let mut corrupted = response(10, ForkStep::StepNew);
+        corrupted.block.as_mut().unwrap().value = vec![0xff; 4];
+        assert!(matches!(
+            FirehoseClient::decode_block(&corrupted),
+            Err(SourceError::DecodeError(4, _))
+        ));
+
+        let mut other_chain = response(10, ForkStep::StepNew);
+        other_chain.block.as_mut().unwrap().type_url =
+            "type.googleapis.com/sf.near.type.v1.Block".to_string();
+        assert!(matches!(
+            FirehoseClient::decode_block(&other_chain),
+            Err(SourceError::UnsupportedContentType(_))
+        ));
+    }
+
+    #[test]
+    fn test_reconnect_backoff() {
+        let delays = FirehoseClient::reconnect_backoff()
+            .take(12)
+            .collect::<Vec<_>>();
+        assert_eq!(delays[0], Duration::from_millis(200));
+        assert_eq!(delays[1], Duration::from_millis(400));
+        assert_eq!(delays.last(), Some(&MAX_RECONNECT_DELAY));
+    }
+
+    #[tokio::test]
+    async fn test_stream_keeps_reconnecting() {
+        env_logger::try_init().unwrap_or_default();
+        let cfg = FirehoseConfig {
+            endpoint: "http://127.0.0.1:1".to_string(),
+            start_cursor: Some("cursor-5".to_string()),
+        };
+        let client = FirehoseClient::new(cfg, 0, None, default_registry()).unwrap();
+        let valve = Valve::new(
+            &ValveConfig {
+                allowed_lag: 1000,
+                wait_time: 0,
+                max_inflight_blocks: None,
+            },
+            default_registry(),
+        );
+        let (sender, _recv) = kanal::bounded_async(1);
+
+        // Nothing listens on the endpoint, the client keeps retrying instead of failing
+        let streaming = tokio::time::timeout(
+            Duration::from_millis(500),
+            client.get_block_stream(sender, valve),
+        )
+        .await;
+        assert!(streaming.is_err());
+    }
+
+    #[test]
+    fn test_invalid_endpoint() {
+        let cfg = FirehoseConfig {
+            endpoint: "not a uri".to_string(),
+            start_cursor: None,
+        };
+        assert!(matches!(
+            FirehoseClient::new(cfg, 0, None, default_registry()),
+            Err(SourceError::FirehoseConnectFail(_))
+        ));
+    }
+}
@@ -0,0 +1,48 @@
+// Messages of `sf.firehose.v2.Stream`, only the ones the client needs
+
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Any {
+    #[prost(string, tag = "1")]
+    pub type_url: ::prost::alloc::string::String,
+    #[prost(bytes = "vec", tag = "2")]
+    pub value: ::prost::alloc::vec::Vec<u8>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Request {
+    /// Ignored when a cursor is given
+    #[prost(int64, tag = "1")]
+    pub start_block_num: i64,
+    /// Resume the stream right after the block this cursor was received with
+    #[prost(string, tag = "2")]
+    pub cursor: ::prost::alloc::string::String,
+    /// 0 streams forever
+    #[prost(uint64, tag = "3")]
+    pub stop_block_num: u64,
+    #[prost(bool, tag = "4")]
+    pub final_blocks_only: bool,
+    #[prost(message, repeated, tag = "10")]
+    pub transforms: ::prost::alloc::vec::Vec<Any>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Response {
+    #[prost(message, optional, tag = "1")]
+    pub block: ::core::option::Option<Any>,
+    #[prost(enumeration = "ForkStep", tag = "6")]
+    pub step: i32,
+    #[prost(string, tag = "10")]
+    pub cursor: ::prost::alloc::string::String,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ForkStep {
+    StepUnset = 0,
+    /// Block is new head block of the chain
+    StepNew = 1,
+    /// Block is now forked and should be undone, it's not the head anymore
+    StepUndo = 2,
+    /// Block is now irreversible
+    StepFinal = 3,
+}
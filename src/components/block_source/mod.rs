@@ -1,19 +1,112 @@
 mod delta;
+mod firehose;
 mod metrics;
+mod readdir;
 
 use super::Valve;
 use crate::common::BlockDataMessage;
+use crate::common::BlockPtr;
 use crate::common::Chain;
 use crate::config::Config;
+use crate::config::ContentType;
 use crate::config::SourceTypes;
 use crate::errors::SourceError;
+use crate::info;
+use delta::proto::ethereum::Block as PbBlock;
 use delta::DeltaClient;
 use delta::DeltaEthereumBlocks;
+use firehose::FirehoseClient;
+use futures_util::future::try_join_all;
+use futures_util::future::LocalBoxFuture;
+use futures_util::FutureExt;
+use kanal::AsyncReceiver;
 use kanal::AsyncSender;
 use prometheus::Registry;
+use prost::Message;
+use readdir::ReadDir;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use web3::types::Log;
 
 enum Source {
     Delta(DeltaClient),
+    ReadDir(ReadDir),
+    Firehose(FirehoseClient),
+    /// Drain every source in turn, eg an archive then a live source at the chain's tip
+    Chained(Vec<ChainedSource>),
+    /// Run every source at once, their blocks merged in order of block number
+    Merged(Vec<(Source, Route)>),
+    /// Emit a predetermined sequence of blocks, one block per batch
+    #[cfg(test)]
+    InMemory(Vec<BlockDataMessage>),
+}
+
+/// Build a source from the block it starts at, and the cursor to resume from if any
+type SourceBuilder =
+    Box<dyn FnOnce(u64, Option<String>) -> LocalBoxFuture<'static, Result<Source, SourceError>>>;
+
+enum ChainedSource {
+    Ready(Source),
+    /// Built once the previous sources end, to start at the block after the last one they sent
+    Deferred {
+        start_block: u64,
+        resume_cursor: Option<String>,
+        build: SourceBuilder,
+    },
+}
+
+/// Logs a source of `Source::Merged` is in charge of, by contract address
+enum Route {
+    /// The default source: every log but those of the datasources having their own source
+    Excluding(HashSet<String>),
+    /// The source of a datasource: only the logs of its contract
+    Only(HashSet<String>),
+}
+
+impl Route {
+    fn delivers(&self, log: &Log) -> bool {
+        let address = format!("{:?}", log.address).to_lowercase();
+        match self {
+            Route::Excluding(addresses) => !addresses.contains(&address),
+            Route::Only(addresses) => addresses.contains(&address),
+        }
+    }
+
+    /// Leave out the logs another source is in charge of. The source of a datasource
+    /// only keeps the transactions of its logs
+    fn apply(&self, block: BlockDataMessage) -> BlockDataMessage {
+        match block {
+            BlockDataMessage::Ethereum {
+                block,
+                transactions,
+                logs,
+            } => {
+                let logs = logs
+                    .into_iter()
+                    .filter(|log| self.delivers(log))
+                    .collect::<Vec<_>>();
+                let transactions = match self {
+                    Route::Excluding(_) => transactions,
+                    Route::Only(_) => {
+                        let log_transactions = logs
+                            .iter()
+                            .filter_map(|log| log.transaction_hash)
+                            .collect::<HashSet<_>>();
+                        transactions
+                            .into_iter()
+                            .filter(|tx| log_transactions.contains(&tx.hash))
+                            .collect()
+                    }
+                };
+                BlockDataMessage::Ethereum {
+                    block,
+                    transactions,
+                    logs,
+                }
+            }
+        }
+    }
 }
 
 pub struct BlockSource {
@@ -22,38 +115,741 @@ pub struct BlockSource {
 }
 
 impl BlockSource {
+    /// `resume_cursor` is the cursor of the last committed block, for sources resuming from one.
+    /// `datasource_addresses` routes the logs of the datasources having their own source to it
     pub async fn new(
         config: &Config,
+        datasource_addresses: &HashMap<String, String>,
         start_block: u64,
+        resume_cursor: Option<String>,
         registry: &Registry,
     ) -> Result<Self, SourceError> {
-        let source = match &config.source {
-            SourceTypes::Delta(delta_cfg) => {
-                Source::Delta(DeltaClient::new(delta_cfg.to_owned(), start_block, registry).await?)
+        let mut source =
+            Self::new_source(&config.source, start_block, resume_cursor, registry).await?;
+
+        if let Some(datasource_sources) = &config.datasource_sources {
+            let mut names = datasource_sources.keys().collect::<Vec<_>>();
+            names.sort();
+            let own_addresses = names
+                .iter()
+                .filter_map(|name| datasource_addresses.get(*name).cloned())
+                .collect();
+            let mut sources = vec![(source, Route::Excluding(own_addresses))];
+            for name in names {
+                info!(BlockSource, "datasource has its own source"; datasource => name);
+                let cfg = &datasource_sources[name];
+                let route = Route::Only(
+                    datasource_addresses
+                        .get(name)
+                        .cloned()
+                        .into_iter()
+                        .collect(),
+                );
+                sources.push((
+                    Self::new_source(cfg, start_block, None, registry).await?,
+                    route,
+                ));
             }
-        };
+            source = Source::Merged(sources);
+        }
+
         Ok(Self {
             source,
             chain: config.chain.clone(),
         })
     }
 
+    /// Boxed, as a chained source builds its inner sources recursively
+    fn new_source<'a>(
+        cfg: &'a SourceTypes,
+        start_block: u64,
+        resume_cursor: Option<String>,
+        registry: &'a Registry,
+    ) -> LocalBoxFuture<'a, Result<Source, SourceError>> {
+        async move {
+            let source = match cfg {
+                SourceTypes::Delta(delta_cfg) => Source::Delta(
+                    DeltaClient::new(delta_cfg.to_owned(), start_block, registry).await?,
+                ),
+                SourceTypes::ReadDir(readdir_cfg) => {
+                    Source::ReadDir(ReadDir::new(readdir_cfg.to_owned(), start_block, registry)?)
+                }
+                SourceTypes::Firehose(firehose_cfg) => Source::Firehose(FirehoseClient::new(
+                    firehose_cfg.to_owned(),
+                    start_block,
+                    resume_cursor,
+                    registry,
+                )?),
+                SourceTypes::Chained(cfgs) => {
+                    let mut sources = vec![];
+                    for (idx, cfg) in cfgs.iter().enumerate() {
+                        if idx == 0 {
+                            let source =
+                                Self::new_source(cfg, start_block, resume_cursor.clone(), registry);
+                            sources.push(ChainedSource::Ready(source.await?));
+                            continue;
+                        }
+                        let cfg = cfg.clone();
+                        let registry = registry.clone();
+                        sources.push(ChainedSource::Deferred {
+                            start_block,
+                            resume_cursor: resume_cursor.clone(),
+                            build: Box::new(move |start_block, resume_cursor| {
+                                async move {
+                                    Self::new_source(&cfg, start_block, resume_cursor, &registry)
+                                        .await
+                                }
+                                .boxed_local()
+                            }),
+                        });
+                    }
+                    Source::Chained(sources)
+                }
+            };
+            Ok(source)
+        }
+        .boxed_local()
+    }
+
+    #[cfg(test)]
+    pub fn in_memory(chain: Chain, blocks: Vec<BlockDataMessage>) -> Self {
+        Self {
+            source: Source::InMemory(blocks),
+            chain,
+        }
+    }
+
     pub async fn run(
         self,
         sender: AsyncSender<Vec<BlockDataMessage>>,
         valve: Valve,
     ) -> Result<(), SourceError> {
-        match self.source {
+        Self::run_source(self.source, self.chain, sender, valve).await
+    }
+
+    /// Boxed, as a chained source runs its inner sources recursively
+    fn run_source(
+        source: Source,
+        chain: Chain,
+        sender: AsyncSender<Vec<BlockDataMessage>>,
+        valve: Valve,
+    ) -> LocalBoxFuture<'static, Result<(), SourceError>> {
+        Self::run_single(source, chain, sender, valve).boxed_local()
+    }
+
+    async fn run_single(
+        source: Source,
+        chain: Chain,
+        sender: AsyncSender<Vec<BlockDataMessage>>,
+        valve: Valve,
+    ) -> Result<(), SourceError> {
+        match source {
             Source::Delta(source) => {
-                let query_blocks = match self.chain {
+                let query_blocks = match chain {
                     Chain::Ethereum => {
                         source.get_block_stream::<DeltaEthereumBlocks>(sender, valve)
                     }
                 };
                 query_blocks.await?
             }
+            Source::ReadDir(source) => {
+                let read_blocks = match chain {
+                    Chain::Ethereum => source.get_block_stream(sender, valve),
+                };
+                read_blocks.await?
+            }
+            Source::Firehose(source) => {
+                let stream_blocks = match chain {
+                    Chain::Ethereum => source.get_block_stream(sender, valve),
+                };
+                stream_blocks.await?
+            }
+            Source::Chained(sources) => {
+                let mut last_block = None;
+                for source in sources {
+                    let source = match source {
+                        ChainedSource::Ready(source) => source,
+                        ChainedSource::Deferred {
+                            start_block,
+                            resume_cursor,
+                            build,
+                        } => match &last_block {
+                            // The resume cursor is only that of the start block until the handoff
+                            Some(last) => build(last.number + 1, None).await?,
+                            None => build(start_block, resume_cursor).await?,
+                        },
+                    };
+                    let (inner_sender, inner_recv) = kanal::bounded_async(1);
+                    let run_inner =
+                        Self::run_source(source, chain.clone(), inner_sender, valve.clone());
+                    let forward = forward_chained(inner_recv, &sender, &valve, &mut last_block);
+                    let (run_result, forward_result) = tokio::join!(run_inner, forward);
+                    forward_result?;
+                    run_result?;
+                }
+            }
+            Source::Merged(sources) => {
+                let mut runs = vec![];
+                let mut recvs = vec![];
+                for (source, route) in sources {
+                    let (inner_sender, inner_recv) = kanal::bounded_async(1);
+                    runs.push(Self::run_source(
+                        source,
+                        chain.clone(),
+                        inner_sender,
+                        valve.clone(),
+                    ));
+                    recvs.push((inner_recv, route));
+                }
+                // A failing source stops all the others
+                let forward = forward_merged(recvs, &sender, &valve);
+                tokio::try_join!(try_join_all(runs), forward)?;
+            }
+            #[cfg(test)]
+            Source::InMemory(blocks) => {
+                for block in blocks {
+                    valve.acquire_inflight(1).await;
+                    valve.set_downloaded(block.get_block_ptr().number);
+                    sender.send(vec![block]).await?;
+                }
+            }
         };
 
         Ok(())
     }
 }
+
+/// Decode a block payload of any source, one that does not parse is reported with its length
+fn decode_block(data: &[u8], content_type: &ContentType) -> Result<PbBlock, SourceError> {
+    let block = match content_type {
+        ContentType::Json => serde_json::from_slice::<PbBlock>(data).map_err(|e| e.to_string()),
+        ContentType::Protobuf => PbBlock::decode(data).map_err(|e| e.to_string()),
+    };
+    block.map_err(|e| SourceError::DecodeError(data.len(), e))
+}
+
+/// Pass the blocks of one of the chained sources on. Until the handoff, blocks already sent
+/// by the previous sources are skipped, and the first new one must follow the last block sent
+async fn forward_chained(
+    recv: AsyncReceiver<Vec<BlockDataMessage>>,
+    sender: &AsyncSender<Vec<BlockDataMessage>>,
+    valve: &Valve,
+    last_block: &mut Option<BlockPtr>,
+) -> Result<(), SourceError> {
+    let mut handed_off = last_block.is_none();
+
+    while let Ok(blocks) = recv.recv().await {
+        let count_blocks = blocks.len();
+        let mut forwarded = vec![];
+
+        for block in blocks {
+            if !handed_off {
+                let block_ptr = block.get_block_ptr();
+                // Set as soon as the first source sends anything
+                let last = last_block.as_ref().unwrap();
+                if block_ptr.number <= last.number {
+                    continue;
+                }
+                if !last.is_parent(&block_ptr) {
+                    return Err(SourceError::ChainedSourceBroken(
+                        last.number,
+                        block_ptr.number,
+                    ));
+                }
+                handed_off = true;
+            }
+            forwarded.push(block);
+        }
+
+        valve.release_inflight(count_blocks - forwarded.len());
+        if let Some(block) = forwarded.last() {
+            *last_block = Some(block.get_block_ptr());
+            sender.send(forwarded).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Pass the blocks of several sources on in order of their numbers: a block is only sent once
+/// every source still running has one waiting. Blocks of the same number are sent as one,
+/// each source contributing the logs of its route
+async fn forward_merged(
+    recvs: Vec<(AsyncReceiver<Vec<BlockDataMessage>>, Route)>,
+    sender: &AsyncSender<Vec<BlockDataMessage>>,
+    valve: &Valve,
+) -> Result<(), SourceError> {
+    let mut pending = recvs.iter().map(|_| VecDeque::new()).collect::<Vec<_>>();
+    let mut running = vec![true; recvs.len()];
+
+    loop {
+        for (idx, (recv, route)) in recvs.iter().enumerate() {
+            while running[idx] && pending[idx].is_empty() {
+                match recv.recv().await {
+                    Ok(blocks) => pending[idx].extend(blocks.into_iter().map(|b| route.apply(b))),
+                    Err(_) => running[idx] = false,
+                }
+            }
+        }
+
+        let next_number = pending
+            .iter()
+            .filter_map(|blocks| blocks.front())
+            .map(|block| block.get_block_ptr().number)
+            .min();
+        let next_number = match next_number {
+            Some(number) => number,
+            None => return Ok(()),
+        };
+
+        let mut merged: Option<BlockDataMessage> = None;
+        for blocks in pending.iter_mut() {
+            if blocks
+                .front()
+                .is_some_and(|block| block.get_block_ptr().number == next_number)
+            {
+                let block = blocks.pop_front().unwrap();
+                merged = match merged {
+                    Some(merged) => {
+                        // Each source took an inflight slot for its own copy
+                        valve.release_inflight(1);
+                        Some(merge_blocks(merged, block)?)
+                    }
+                    None => Some(block),
+                };
+            }
+        }
+
+        sender.send(vec![merged.unwrap()]).await?;
+    }
+}
+
+/// Add the transactions & logs of another source's copy of the same block, leaving out
+/// those already there, in the order they have in the block. Copies of different blocks,
+/// eg a source on another fork, are rejected
+fn merge_blocks(
+    into: BlockDataMessage,
+    other: BlockDataMessage,
+) -> Result<BlockDataMessage, SourceError> {
+    let block_ptr = into.get_block_ptr();
+    if block_ptr != other.get_block_ptr() {
+        return Err(SourceError::MergedSourceMismatch(block_ptr.number));
+    }
+
+    match (into, other) {
+        (
+            BlockDataMessage::Ethereum {
+                block,
+                mut transactions,
+                mut logs,
+            },
+            BlockDataMessage::Ethereum {
+                transactions: other_transactions,
+                logs: other_logs,
+                ..
+            },
+        ) => {
+            let known_transactions = transactions
+                .iter()
+                .map(|tx| tx.hash)
+                .collect::<HashSet<_>>();
+            transactions.extend(
+                other_transactions
+                    .into_iter()
+                    .filter(|tx| !known_transactions.contains(&tx.hash)),
+            );
+
+            let known_logs = logs
+                .iter()
+                .map(|log| (log.transaction_hash, log.log_index))
+                .collect::<HashSet<_>>();
+            logs.extend(
+                other_logs
+                    .into_iter()
+                    .filter(|log| !known_logs.contains(&(log.transaction_hash, log.log_index))),
+            );
+
+            transactions.sort_by_key(|tx| tx.index);
+            logs.sort_by_key(|log| (log.transaction_index, log.log_index));
+
+            Ok(BlockDataMessage::Ethereum {
+                block,
+                transactions,
+                logs,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chain::ethereum::block::EthereumBlockData;
+    use crate::chain::ethereum::transaction::EthereumTransactionData;
+    use crate::common::ABIs;
+    use crate::common::Datasource;
+    use crate::common::FilteredDataMessage;
+    use crate::common::RawEntity;
+    use crate::common::StoreOperationMessage;
+    use crate::common::StoreRequestResult;
+    use crate::components::DataFilter;
+    use crate::components::ManifestAgent;
+    use crate::config::ValveConfig;
+    use crate::database::DatabaseAgent;
+    use crate::runtime::asc::native_types::store::Value;
+    use prometheus::default_registry;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use web3::types::H256;
+    use web3::types::U64;
+
+    const TOKEN: &str = "0xdac17f958d2ee523a2206206994597c13d831ec7";
+    const OTHER_TOKEN: &str = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+
+    fn erc20_datasource() -> Datasource {
+        serde_yaml::from_str(&format!(
+            r#"
+kind: ethereum/contract
+name: Token
+network: mainnet
+source:
+  address: "{TOKEN}"
+  abi: ERC20
+  startBlock: 1
+mapping:
+  kind: ethereum/events
+  apiVersion: 0.0.5
+  entities: []
+  abis: []
+  eventHandlers:
+    - event: Transfer(indexed address,indexed address,uint256)
+      handler: handleTransfer
+  file: ./mapping.ts
+"#
+        ))
+        .unwrap()
+    }
+
+    fn erc20_abi() -> serde_json::Value {
+        serde_json::json!([
+            {"anonymous":false,"inputs":[{"indexed":true,"name":"from","type":"address"},{"indexed":true,"name":"to","type":"address"},{"indexed":false,"name":"value","type":"uint256"}],"name":"Transfer","type":"event"}
+        ])
+    }
+
+    fn transfer_block(number: u64, from: u64, to: u64, value: u64) -> BlockDataMessage {
+        let log: Log = serde_json::from_value(serde_json::json!({
+            "address": TOKEN,
+            "data": format!("0x{value:064x}"),
+            "logIndex": "0x0",
+            "topics": [
+                "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef",
+                format!("0x{from:064x}"),
+                format!("0x{to:064x}"),
+            ],
+            "transactionIndex": "0x0"
+        }))
+        .unwrap();
+
+        BlockDataMessage::Ethereum {
+            block: EthereumBlockData {
+                number: U64::from(number),
+                ..Default::default()
+            },
+            transactions: vec![EthereumTransactionData::default()],
+            logs: vec![log],
+        }
+    }
+
+    /// Move the logs of `block` to another contract, at `log_index` within the block
+    fn with_log(mut block: BlockDataMessage, address: &str, log_index: u64) -> BlockDataMessage {
+        let BlockDataMessage::Ethereum { logs, .. } = &mut block;
+        for log in logs.iter_mut() {
+            log.address = address.parse().unwrap();
+            log.log_index = Some(web3::types::U256::from(log_index));
+        }
+        block
+    }
+
+    fn load_balance(db: &DatabaseAgent, account: &str) -> Option<Value> {
+        let request = StoreOperationMessage::Load(("Balance".to_string(), account.to_string()));
+        match db.wasm_send_store_request(request).unwrap() {
+            StoreRequestResult::Load(entity) => entity.and_then(|e| e.get("amount").cloned()),
+            _ => unreachable!(),
+        }
+    }
+
+    fn update_balance(db: &DatabaseAgent, account: String, delta: i64) {
+        let amount = match load_balance(db, &account) {
+            Some(Value::Int8(amount)) => amount,
+            _ => 0,
+        };
+        let mut data = RawEntity::new();
+        data.insert("id".to_string(), Value::String(account.clone()));
+        data.insert("amount".to_string(), Value::Int8(amount + delta));
+        let request = StoreOperationMessage::Update(("Balance".to_string(), account, data));
+        db.wasm_send_store_request(request).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_in_memory_pipeline() {
+        env_logger::try_init().unwrap_or_default();
+        let registry = default_registry();
+        let valve = Valve::new(
+            &ValveConfig {
+                allowed_lag: 1000,
+                wait_time: 0,
+                max_inflight_blocks: None,
+            },
+            registry,
+        );
+
+        let source = BlockSource::in_memory(
+            Chain::Ethereum,
+            vec![transfer_block(1, 1, 2, 10), transfer_block(2, 2, 3, 3)],
+        );
+        let (sender, recv) = kanal::bounded_async(10);
+        source.run(sender, valve).await.unwrap();
+
+        let manifest =
+            ManifestAgent::with_abis(ABIs::from_iter([("ERC20".to_string(), erc20_abi())]));
+        let filter = DataFilter::new(Chain::Ethereum, vec![erc20_datasource()], &manifest).unwrap();
+        let db = DatabaseAgent::empty(registry);
+
+        let mut processed = vec![];
+        while let Ok(blocks) = recv.recv().await {
+            for message in filter.filter_multi(blocks).unwrap() {
+                let block_ptr = message.get_block_ptr();
+                let FilteredDataMessage::Ethereum { events, .. } = message;
+
+                // Stand-in for the wasm handler: move `value` between balances
+                for event in events {
+                    assert_eq!(event.handler, "handleTransfer");
+                    let params = event.event.params;
+                    let from = format!("{:?}", params[0].value.clone().into_address().unwrap());
+                    let to = format!("{:?}", params[1].value.clone().into_address().unwrap());
+                    let value = params[2].value.clone().into_uint().unwrap().as_u64() as i64;
+                    update_balance(&db, from, -value);
+                    update_balance(&db, to, value);
+                }
+
+                db.commit_data(block_ptr.clone()).await.unwrap();
+                processed.push(block_ptr.number);
+            }
+        }
+
+        assert_eq!(processed, vec![1, 2]);
+        let account = |n: u64| format!("0x{n:040x}");
+        assert_eq!(load_balance(&db, &account(1)), Some(Value::Int8(-10)));
+        assert_eq!(load_balance(&db, &account(2)), Some(Value::Int8(7)));
+        assert_eq!(load_balance(&db, &account(3)), Some(Value::Int8(3)));
+    }
+
+    fn linked_block(number: u64) -> BlockDataMessage {
+        BlockDataMessage::Ethereum {
+            block: EthereumBlockData {
+                number: U64::from(number),
+                hash: H256::from_low_u64_be(number),
+                parent_hash: H256::from_low_u64_be(number - 1),
+                ..Default::default()
+            },
+            transactions: vec![],
+            logs: vec![],
+        }
+    }
+
+    async fn run_chained(sources: Vec<ChainedSource>) -> (Result<(), SourceError>, Vec<u64>) {
+        let valve = Valve::new(
+            &ValveConfig {
+                allowed_lag: 1000,
+                wait_time: 0,
+                max_inflight_blocks: None,
+            },
+            default_registry(),
+        );
+        let source = BlockSource {
+            source: Source::Chained(sources),
+            chain: Chain::Ethereum,
+        };
+        let (sender, recv) = kanal::bounded_async(100);
+        let result = source.run(sender, valve).await;
+
+        let mut received = vec![];
+        while let Ok(blocks) = recv.recv().await {
+            received.extend(blocks.iter().map(|b| b.get_block_ptr().number));
+        }
+        (result, received)
+    }
+
+    fn ready(sources: Vec<Source>) -> Vec<ChainedSource> {
+        sources.into_iter().map(ChainedSource::Ready).collect()
+    }
+
+    #[tokio::test]
+    async fn test_chained_source() {
+        env_logger::try_init().unwrap_or_default();
+        let blocks = |range: std::ops::RangeInclusive<u64>| range.map(linked_block).collect();
+
+        // The live stream starts behind the archive's last block, the overlap is skipped
+        let archive = Source::InMemory(blocks(1..=5));
+        let live = Source::InMemory(blocks(4..=8));
+        let (result, received) = run_chained(ready(vec![archive, live])).await;
+        assert!(result.is_ok());
+        assert_eq!(received, (1..=8).collect::<Vec<_>>());
+
+        // Blocks missing at the handoff
+        let archive = Source::InMemory(blocks(1..=3));
+        let live = Source::InMemory(blocks(5..=8));
+        let (result, received) = run_chained(ready(vec![archive, live])).await;
+        assert!(matches!(
+            result,
+            Err(SourceError::ChainedSourceBroken(3, 5))
+        ));
+        assert_eq!(received, vec![1, 2, 3]);
+
+        // The live stream is on another fork than the archive
+        let archive = Source::InMemory(blocks(1..=3));
+        let mut forked = linked_block(4);
+        let BlockDataMessage::Ethereum { block, .. } = &mut forked;
+        block.parent_hash = H256::repeat_byte(0xff);
+        let live = Source::InMemory(vec![forked]);
+        let (result, _) = run_chained(ready(vec![archive, live])).await;
+        assert!(matches!(
+            result,
+            Err(SourceError::ChainedSourceBroken(3, 4))
+        ));
+
+        // A source built from the config starts at the handoff block,
+        // or at the start block with the resume cursor if nothing was sent before it
+        let started_at = Rc::new(RefCell::new(vec![]));
+        let deferred = |last: u64| {
+            let started_at = started_at.clone();
+            ChainedSource::Deferred {
+                start_block: 1,
+                resume_cursor: Some("cursor".to_string()),
+                build: Box::new(move |start_block, resume_cursor| {
+                    started_at.borrow_mut().push((start_block, resume_cursor));
+                    async move {
+                        let blocks = (start_block..=last).map(linked_block).collect();
+                        Ok::<_, SourceError>(Source::InMemory(blocks))
+                    }
+                    .boxed_local()
+                }),
+            }
+        };
+        let archive = ChainedSource::Ready(Source::InMemory(blocks(1..=5)));
+        let (result, received) = run_chained(vec![archive, deferred(8), deferred(10)]).await;
+        assert!(result.is_ok());
+        assert_eq!(received, (1..=10).collect::<Vec<_>>());
+        let empty = ChainedSource::Ready(Source::InMemory(vec![]));
+        let (result, received) = run_chained(vec![empty, deferred(3)]).await;
+        assert!(result.is_ok());
+        assert_eq!(received, vec![1, 2, 3]);
+        assert_eq!(
+            *started_at.borrow(),
+            vec![(6, None), (9, None), (1, Some("cursor".to_string()))]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_merged_source() {
+        env_logger::try_init().unwrap_or_default();
+        let valve = Valve::new(
+            &ValveConfig {
+                allowed_lag: 1000,
+                wait_time: 0,
+                max_inflight_blocks: None,
+            },
+            default_registry(),
+        );
+
+        // Two datasources on their own sources, both having events in block 6.
+        // The default source also has a copy of the other token's log, left to its own source
+        let other = |number| with_log(transfer_block(number, 3, 4, 1), OTHER_TOKEN, 1);
+        let token_source = Source::InMemory(vec![
+            transfer_block(1, 1, 2, 10),
+            transfer_block(3, 1, 2, 10),
+            with_log(transfer_block(6, 1, 2, 10), TOKEN, 2),
+            with_log(transfer_block(7, 3, 4, 5), OTHER_TOKEN, 1),
+        ]);
+        let other_source = Source::InMemory(vec![other(2), other(4), other(5), other(6), other(7)]);
+        let routes = (
+            Route::Excluding(HashSet::from([OTHER_TOKEN.to_string()])),
+            Route::Only(HashSet::from([OTHER_TOKEN.to_string()])),
+        );
+        let source = BlockSource {
+            source: Source::Merged(vec![(token_source, routes.0), (other_source, routes.1)]),
+            chain: Chain::Ethereum,
+        };
+        let (sender, recv) = kanal::bounded_async(100);
+        source.run(sender, valve).await.unwrap();
+
+        let mut received = vec![];
+        while let Ok(blocks) = recv.recv().await {
+            for block in blocks {
+                let BlockDataMessage::Ethereum { logs, .. } = &block;
+                let values = logs.iter().map(|log| log.data.0[31]).collect::<Vec<_>>();
+                received.push((block.get_block_ptr().number, values));
+            }
+        }
+        // Logs of the same block are in the order of their index, whichever source sent them
+        assert_eq!(
+            received,
+            vec![
+                (1, vec![10]),
+                (2, vec![1]),
+                (3, vec![10]),
+                (4, vec![1]),
+                (5, vec![1]),
+                (6, vec![1, 10]),
+                (7, vec![1])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decode_block() {
+        let block = PbBlock {
+            chain_id: 1,
+            block_number: 10,
+            ..Default::default()
+        };
+        let encoded = block.encode_to_vec();
+        assert_eq!(
+            decode_block(&encoded, &ContentType::Protobuf).unwrap(),
+            block
+        );
+        let json = serde_json::to_vec(&block).unwrap();
+        assert_eq!(decode_block(&json, &ContentType::Json).unwrap(), block);
+
+        // Corrupted bytes, or a payload of the other content-type
+        assert!(matches!(
+            decode_block(&[0xff; 4], &ContentType::Protobuf),
+            Err(SourceError::DecodeError(4, _))
+        ));
+        assert!(matches!(
+            decode_block(&encoded, &ContentType::Json),
+            Err(SourceError::DecodeError(len, _)) if len == encoded.len()
+        ));
+    }
+
+    #[test]
+    fn test_merge_blocks() {
+        let merged =
+            merge_blocks(transfer_block(6, 1, 2, 10), transfer_block(6, 1, 2, 10)).unwrap();
+        let BlockDataMessage::Ethereum {
+            transactions, logs, ..
+        } = merged;
+        // The same transaction & log from both sources are kept once
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(logs.len(), 1);
+
+        // A copy of another block of the same number is not merged in
+        let mut forked = transfer_block(6, 1, 2, 10);
+        let BlockDataMessage::Ethereum { block, .. } = &mut forked;
+        block.hash = H256::from_low_u64_be(1);
+        assert!(matches!(
+            merge_blocks(transfer_block(6, 1, 2, 10), forked),
+            Err(SourceError::MergedSourceMismatch(6))
+        ));
+    }
+}
@@ -0,0 +1,343 @@
+use super::decode_block;
+use super::delta::proto::ethereum::Block as PbBlock;
+use super::metrics::BlockSourceMetrics;
+use crate::common::BlockDataMessage;
+use crate::components::Valve;
+use crate::config::ContentType;
+use crate::config::ReadDirConfig;
+use crate::config::SourceExhaustedPolicy;
+use crate::errors::SourceError;
+use crate::info;
+use crate::warn;
+use kanal::AsyncSender;
+use prometheus::Registry;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Read blocks from a local directory where every file holds a single block,
+/// files are processed in the order of their names
+pub struct ReadDir {
+    files: Vec<PathBuf>,
+    content_type: Option<ContentType>,
+    on_exhausted: SourceExhaustedPolicy,
+    start_block: u64,
+    metrics: BlockSourceMetrics,
+}
+
+impl ReadDir {
+    const BATCH_SIZE: usize = 100;
+
+    pub fn new(
+        cfg: ReadDirConfig,
+        start_block: u64,
+        registry: &Registry,
+    ) -> Result<Self, SourceError> {
+        let entries = fs::read_dir(&cfg.source_dir)
+            .map_err(|e| SourceError::ReadFileFail(format!("{}: {e}", cfg.source_dir)))?;
+
+        let mut files = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect::<Vec<_>>();
+        files.sort();
+
+        info!(
+            ReadDir,
+            "Setup done";
+            source_dir => cfg.source_dir,
+            content_type => format!("{:?}", cfg.content_type),
+            file_count => files.len(),
+            start_block => start_block
+        );
+
+        Ok(Self {
+            files,
+            content_type: cfg.content_type,
+            on_exhausted: cfg.on_exhausted.unwrap_or_default(),
+            start_block,
+            metrics: BlockSourceMetrics::new(registry),
+        })
+    }
+
+    fn detect_content_type(path: &Path) -> Result<ContentType, SourceError> {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default();
+
+        match extension {
+            "json" => Ok(ContentType::Json),
+            "pb" => Ok(ContentType::Protobuf),
+            other => Err(SourceError::UnsupportedContentType(format!(
+                "`.{other}` ({})",
+                path.display()
+            ))),
+        }
+    }
+
+    fn read_block(&self, path: &Path) -> Result<PbBlock, SourceError> {
+        let content_type = match &self.content_type {
+            Some(content_type) => content_type.clone(),
+            None => Self::detect_content_type(path)?,
+        };
+
+        let data = fs::read(path)
+            .map_err(|e| SourceError::ReadFileFail(format!("{}: {e}", path.display())))?;
+
+        decode_block(&data, &content_type)
+    }
+
+    pub async fn get_block_stream(
+        &self,
+        sender: AsyncSender<Vec<BlockDataMessage>>,
+        valve: Valve,
+    ) -> Result<(), SourceError> {
+        info!(BlockSource, "start reading block-data from directory 📂");
+        // The last file holds the last block there is to read
+        if let Some(path) = self.files.last() {
+            valve.set_source_head(self.read_block(path)?.block_number);
+        }
+
+        for paths in self.files.chunks(Self::BATCH_SIZE) {
+            let start_time = self.metrics.block_source_serialized_duration.start_timer();
+
+            let mut blocks = vec![];
+            for path in paths {
+                let block = self.read_block(path)?;
+                if block.block_number >= self.start_block {
+                    blocks.push(BlockDataMessage::from(block));
+                }
+            }
+
+            blocks.sort_by_key(|b| b.get_block_ptr().number);
+            start_time.stop_and_record();
+
+            if blocks.is_empty() {
+                continue;
+            }
+
+            self.metrics
+                .block_source_total_blocks
+                .inc_by(blocks.len() as u64);
+
+            valve.acquire_inflight(blocks.len()).await;
+            valve.set_downloaded(blocks.last().map(|b| b.get_block_ptr().number).unwrap());
+            sender.send(blocks).await?;
+            valve.temporarily_close().await;
+        }
+
+        warn!(BlockSource, "No more block to read..."; on_exhausted => format!("{:?}", self.on_exhausted));
+
+        if self.on_exhausted == SourceExhaustedPolicy::Wait {
+            // The sender is held as long as this future is pending,
+            // so the stream stays open & the runtime stays alive
+            std::future::pending::<()>().await;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::delta::proto::ethereum::Header as PbHeader;
+    use super::*;
+    use crate::common::BlockPtr;
+    use crate::config::ValveConfig;
+    use crate::database::DatabaseAgent;
+    use prometheus::default_registry;
+    use prost::Message;
+    use std::env;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    fn mock_pb_block(number: u64) -> PbBlock {
+        PbBlock {
+            chain_id: 1,
+            block_hash: format!("0x{:064x}", number),
+            parent_hash: format!("0x{:064x}", number.saturating_sub(1)),
+            block_number: number,
+            header: Some(PbHeader {
+                author: format!("0x{:040x}", 0),
+                state_root: format!("0x{:064x}", 0),
+                transactions_root: format!("0x{:064x}", 0),
+                receipts_root: format!("0x{:064x}", 0),
+                gas_used: "0".to_string(),
+                gas_limit: "0".to_string(),
+                timestamp: (1_600_000_000 + number).to_string(),
+                difficulty: "0".to_string(),
+                total_difficulty: "0".to_string(),
+                ..Default::default()
+            }),
+            transactions: vec![],
+            logs: vec![],
+        }
+    }
+
+    fn setup_dir(name: &str) -> PathBuf {
+        let mut dir = env::temp_dir();
+        dir.push(name);
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn open_valve() -> Valve {
+        let cfg = ValveConfig {
+            allowed_lag: 1000,
+            wait_time: 0,
+            max_inflight_blocks: None,
+        };
+        Valve::new(&cfg, default_registry())
+    }
+
+    async fn read_all(source: ReadDir) -> Vec<u64> {
+        let (sender, recv) = kanal::bounded_async(10);
+        let valve = open_valve();
+        source.get_block_stream(sender, valve).await.unwrap();
+
+        let mut numbers = vec![];
+        while let Ok(Some(blocks)) = recv.try_recv() {
+            numbers.extend(blocks.iter().map(|b| b.get_block_ptr().number));
+        }
+        numbers
+    }
+
+    #[tokio::test]
+    async fn test_readdir_dispatch_by_extension() {
+        env_logger::try_init().unwrap_or_default();
+        let dir = setup_dir("dfr_readdir_mixed");
+
+        let block_1 = serde_json::to_vec(&mock_pb_block(1)).unwrap();
+        fs::write(dir.join("0001.json"), block_1).unwrap();
+        fs::write(dir.join("0002.pb"), mock_pb_block(2).encode_to_vec()).unwrap();
+
+        let cfg = ReadDirConfig {
+            source_dir: dir.to_str().unwrap().to_owned(),
+            content_type: None,
+            on_exhausted: None,
+        };
+        let source = ReadDir::new(cfg, 0, default_registry()).unwrap();
+        assert_eq!(read_all(source).await, vec![1, 2]);
+
+        fs::write(dir.join("0003.msgpack"), vec![0u8]).unwrap();
+        let cfg = ReadDirConfig {
+            source_dir: dir.to_str().unwrap().to_owned(),
+            content_type: None,
+            on_exhausted: None,
+        };
+        let source = ReadDir::new(cfg, 0, default_registry()).unwrap();
+        let (sender, _recv) = kanal::bounded_async(10);
+        let valve = open_valve();
+        let result = source.get_block_stream(sender, valve).await;
+        assert!(matches!(
+            result,
+            Err(SourceError::UnsupportedContentType(_))
+        ));
+    }
+
+    #[test]
+    fn test_detect_content_type() {
+        for (file, expected) in [
+            ("0001.json", Some(ContentType::Json)),
+            ("0001.pb", Some(ContentType::Protobuf)),
+            ("0001.msgpack", None),
+            ("0001.block", None),
+            ("0001", None),
+        ] {
+            let result = ReadDir::detect_content_type(Path::new(file));
+            match expected {
+                Some(content_type) => assert_eq!(result.unwrap(), content_type, "{file}"),
+                None => assert!(
+                    matches!(result, Err(SourceError::UnsupportedContentType(_))),
+                    "{file}"
+                ),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_readdir_forced_content_type() {
+        env_logger::try_init().unwrap_or_default();
+        let dir = setup_dir("dfr_readdir_forced");
+
+        for number in 5..8 {
+            fs::write(
+                dir.join(format!("{number}.block")),
+                mock_pb_block(number).encode_to_vec(),
+            )
+            .unwrap();
+        }
+
+        let cfg = ReadDirConfig {
+            source_dir: dir.to_str().unwrap().to_owned(),
+            content_type: Some(ContentType::Protobuf),
+            on_exhausted: None,
+        };
+        let source = ReadDir::new(cfg, 6, default_registry()).unwrap();
+        assert_eq!(read_all(source).await, vec![6, 7]);
+    }
+
+    #[tokio::test]
+    async fn test_readdir_exhausted_policy() {
+        env_logger::try_init().unwrap_or_default();
+        let dir = setup_dir("dfr_readdir_exhausted");
+
+        for number in 1..4 {
+            fs::write(
+                dir.join(format!("{number}.pb")),
+                mock_pb_block(number).encode_to_vec(),
+            )
+            .unwrap();
+        }
+
+        // Exit: the stream is closed once every block has been delivered,
+        // letting the consumer drain & flush the final batch before shutting down
+        let cfg = ReadDirConfig {
+            source_dir: dir.to_str().unwrap().to_owned(),
+            content_type: None,
+            on_exhausted: Some(SourceExhaustedPolicy::Exit),
+        };
+        let source = ReadDir::new(cfg, 0, default_registry()).unwrap();
+        let (sender, recv) = kanal::bounded_async(10);
+        source.get_block_stream(sender, open_valve()).await.unwrap();
+
+        // The blocks are held back by `commit_depth`, only the final flush writes them
+        let db = DatabaseAgent::empty(default_registry());
+        db.set_commit_depth(10);
+        let written = Arc::new(Mutex::new(vec![]));
+        let hook_written = written.clone();
+        db.set_commit_hook(Box::new(move |block_ptr| {
+            hook_written.lock().unwrap().push(block_ptr.number);
+        }));
+
+        let mut received = vec![];
+        let mut last_processed: Option<BlockPtr> = None;
+        while let Ok(blocks) = recv.recv().await {
+            received.extend(blocks.iter().map(|b| b.get_block_ptr().number));
+            let last_block = blocks.last().unwrap().get_block_ptr();
+            db.commit_data(last_block.clone()).await.unwrap();
+            last_processed = Some(last_block);
+        }
+        assert_eq!(received, vec![1, 2, 3]);
+        assert!(written.lock().unwrap().is_empty());
+
+        db.commit_final(last_processed, false).await.unwrap();
+        assert_eq!(*written.lock().unwrap(), vec![3]);
+
+        // Wait: the stream stays open after the last block
+        let cfg = ReadDirConfig {
+            source_dir: dir.to_str().unwrap().to_owned(),
+            content_type: None,
+            on_exhausted: Some(SourceExhaustedPolicy::Wait),
+        };
+        let source = ReadDir::new(cfg, 0, default_registry()).unwrap();
+        let (sender, recv) = kanal::bounded_async(10);
+        let stream = source.get_block_stream(sender, open_valve());
+        let result = tokio::time::timeout(std::time::Duration::from_millis(200), stream).await;
+        assert!(result.is_err());
+        assert_eq!(recv.recv().await.unwrap().len(), 3);
+    }
+}
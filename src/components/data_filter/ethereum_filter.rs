@@ -1,22 +1,37 @@
 use super::utils::get_handler_for_log;
 use super::utils::parse_event;
+use super::utils::parse_topic0_event;
 use super::DataFilterTrait;
 use crate::chain::ethereum::block::EthereumBlockData;
 use crate::chain::ethereum::transaction::EthereumTransactionData;
-use crate::common::ABIs;
 use crate::common::BlockDataMessage;
 use crate::common::Datasource;
 use crate::common::EthereumFilteredEvent;
 use crate::common::FilteredDataMessage;
+use crate::components::manifest::ParsedAbi;
+use crate::components::ManifestAgent;
 use crate::debug;
 use crate::errors::FilterError;
-use ethabi::Contract;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
 use web3::types::Log;
+use web3::types::H256;
 
 #[derive(Debug, Clone)]
 struct DatasourceWithContract {
     ds: Datasource,
-    contract: Contract,
+    abi: Arc<ParsedAbi>,
+    /// Topic0 of the only events dispatched, all of them if unset
+    allowed_topics: Option<Vec<H256>>,
+}
+
+impl DatasourceWithContract {
+    fn is_allowed(&self, topic0: &H256) -> bool {
+        self.allowed_topics
+            .as_ref()
+            .map_or(true, |topics| topics.contains(topic0))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -25,18 +40,44 @@ pub struct EthereumFilter {
 }
 
 impl EthereumFilter {
-    pub fn new(datasources: Vec<Datasource>, abis: ABIs) -> Self {
+    pub fn new(datasources: Vec<Datasource>, manifest: &ManifestAgent) -> Self {
         let ds = datasources
             .into_iter()
             .map(|ds| {
-                let abi_name = ds.source.abi.clone();
-                let contract = abis.get_contract(&abi_name).unwrap();
-                DatasourceWithContract { ds, contract }
+                let abi = manifest.get_abi(&ds.source.abi).unwrap();
+                DatasourceWithContract {
+                    ds,
+                    abi,
+                    allowed_topics: None,
+                }
             })
             .collect::<Vec<_>>();
         Self { ds }
     }
 
+    pub fn set_event_allowlist(
+        &mut self,
+        allowlist: &HashMap<String, Vec<String>>,
+    ) -> Result<(), FilterError> {
+        for source in self.ds.iter_mut() {
+            let events = match allowlist.get(&source.ds.name) {
+                Some(events) => events,
+                None => continue,
+            };
+            let topics = events
+                .iter()
+                .map(|event| {
+                    if !event.starts_with("0x") {
+                        return Ok(parse_topic0_event(event));
+                    }
+                    H256::from_str(event).map_err(|_| FilterError::InvalidTopic(event.to_owned()))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            source.allowed_topics = Some(topics);
+        }
+        Ok(())
+    }
+
     fn filter_events(
         &self,
         block_header: EthereumBlockData,
@@ -56,7 +97,11 @@ impl EthereumFilter {
                         .unwrap_or(false)
                 });
 
-                if let Some(DatasourceWithContract { ds, contract }) = source {
+                if let Some(source) = source {
+                    if !source.is_allowed(&log.topics[0]) {
+                        return None;
+                    }
+                    let DatasourceWithContract { ds, abi, .. } = source;
                     let event_handler = get_handler_for_log(ds, &log.topics[0]);
 
                     event_handler.as_ref()?;
@@ -69,7 +114,7 @@ impl EthereumFilter {
                         .cloned()
                         .expect("No Tx found for log");
 
-                    let event = parse_event(contract, log, block_header.to_owned(), tx)
+                    let event = parse_event(abi, log, block_header.to_owned(), tx)
                         .map(|e| EthereumFilteredEvent {
                             event: e,
                             handler: event_handler.handler,
@@ -87,30 +132,26 @@ impl EthereumFilter {
                     self.ds
                         .iter()
                         .filter(|ds| ds.ds.source.address.is_none())
+                        .filter(|ds| ds.is_allowed(&log.topics[0]))
                         .find_map(|ds| {
-                            parse_event(
-                                &ds.contract,
-                                log.clone(),
-                                block_header.to_owned(),
-                                tx.clone(),
-                            )
-                            .and_then(|e| {
-                                let handler = get_handler_for_log(&ds.ds, &log.topics[0]);
-                                if let Some(event_handler) = handler {
-                                    return Some(EthereumFilteredEvent {
-                                        event: e,
-                                        handler: event_handler.handler,
-                                        datasource: ds.ds.name.clone(),
-                                    });
-                                }
-                                debug!(DataFilter,
-                                    "No handler found for log";
-                                    log => format!("{:?}", log),
-                                    datasource => ds.ds.name.clone(),
-                                    block => format!("{:?}", block_header)
-                                );
-                                None
-                            })
+                            parse_event(&ds.abi, log.clone(), block_header.to_owned(), tx.clone())
+                                .and_then(|e| {
+                                    let handler = get_handler_for_log(&ds.ds, &log.topics[0]);
+                                    if let Some(event_handler) = handler {
+                                        return Some(EthereumFilteredEvent {
+                                            event: e,
+                                            handler: event_handler.handler,
+                                            datasource: ds.ds.name.clone(),
+                                        });
+                                    }
+                                    debug!(DataFilter,
+                                        "No handler found for log";
+                                        log => format!("{:?}", log),
+                                        datasource => ds.ds.name.clone(),
+                                        block => format!("{:?}", block_header)
+                                    );
+                                    None
+                                })
                         })
                 }
             })
@@ -145,9 +186,14 @@ impl DataFilterTrait for EthereumFilter {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::common::ABIs;
     use crate::components::ManifestAgent;
 
-    fn erc20_contract() -> Contract {
+    fn erc20_contract() -> ethabi::Contract {
+        serde_json::from_value(erc20_abi()).unwrap()
+    }
+
+    fn erc20_abi() -> serde_json::Value {
         let erc20_abi = r#"
 [{"constant":true,"inputs":[],"name":"name","outputs":[{"name":"","type":"string"}],"payable":false,"stateMutability":"view","type":"function"},{"constant":false,"inputs":[{"name":"_spender","type":"address"},{"name":"_value","type":"uint256"}],"name":"approve","outputs":[{"name":"","type":"bool"}],"payable":false,"stateMutability":"nonpayable","type":"function"},{"constant":true,"inputs":[],"name":"totalSupply","outputs":[{"name":"","type":"uint256"}],"payable":false,"stateMutability":"view","type":"function"},{"constant":false,"inputs":[{"name":"_from","type":"address"},{"name":"_to","type":"address"},{"name":"_value","type":"uint256"}],"name":"transferFrom","outputs":[{"name":"","type":"bool"}],"payable":false,"stateMutability":"nonpayable","type":"function"},{"constant":true,"inputs":[],"name":"decimals","outputs":[{"name":"","type":"uint256"}],"payable":false,"stateMutability":"view","type":"function"},{"constant":true,"inputs":[{"name":"_owner","type":"address"}],"name":"balanceOf","outputs":[{"name":"balance","type":"uint256"}],"payable":false,"stateMutability":"view","type":"function"},{"constant":true,"inputs":[],"name":"symbol","outputs":[{"name":"","type":"string"}],"payable":false,"stateMutability":"view","type":"function"},{"constant":false,"inputs":[{"name":"_to","type":"address"},{"name":"_value","type":"uint256"}],"name":"transfer","outputs":[{"name":"","type":"bool"}],"payable":false,"stateMutability":"nonpayable","type":"function"},{"constant":true,"inputs":[{"name":"_owner","type":"address"},{"name":"_spender","type":"address"}],"name":"allowance","outputs":[{"name":"","type":"uint256"}],"payable":false,"stateMutability":"view","type":"function"},{"payable":true,"stateMutability":"payable","type":"fallback"},{"anonymous":false,"inputs":[{"indexed":true,"name":"owner","type":"address"},{"indexed":true,"name":"spender","type":"address"},{"indexed":false,"name":"value","type":"uint256"}],"name":"Approval","type":"event"},{"anonymous":false,"inputs":[{"indexed":true,"name":"from","type":"address"},{"indexed":true,"name":"to","type":"address"},{"indexed":false,"name":"value","type":"uint256"}],"name":"Transfer","type":"event"}]
 "#;
@@ -236,13 +282,13 @@ mod test {
 ]
 "#;
         let logs: Vec<Log> = serde_json::from_str(logs).unwrap();
-        let contract = erc20_contract();
-        let test_manifest = ManifestAgent::new("fs://Users/vutran/Desktop/build")
+        let abi = ParsedAbi::from(erc20_contract());
+        let test_manifest = ManifestAgent::new("fs://Users/vutran/Desktop/build", None)
             .await
             .unwrap();
         let datasources_1: Vec<Datasource> = test_manifest.datasources().into();
 
-        let mut filter = EthereumFilter::new(datasources_1.clone(), ABIs::default());
+        let mut filter = EthereumFilter::new(datasources_1.clone(), &test_manifest);
         let header = EthereumBlockData::default();
         let txs = vec![EthereumTransactionData::default()];
 
@@ -250,7 +296,7 @@ mod test {
         let events = logs
             .clone()
             .into_iter()
-            .filter_map(|log| parse_event(&contract, log, header.clone(), txs[0].clone()))
+            .filter_map(|log| parse_event(&abi, log, header.clone(), txs[0].clone()))
             .collect::<Vec<_>>();
 
         assert_eq!(events.len(), 4);
@@ -299,4 +345,72 @@ mod test {
             2670201350
         );
     }
+
+    #[test]
+    fn test_event_allowlist() {
+        env_logger::try_init().unwrap_or_default();
+        let token = "0x8e870d67f660d95d5be530380d0ec0bd388289e1";
+        let ds: Datasource = serde_yaml::from_str(&format!(
+            r#"
+kind: ethereum/contract
+name: Token
+network: mainnet
+source:
+  address: "{token}"
+  abi: ERC20
+  startBlock: 1
+mapping:
+  kind: ethereum/events
+  apiVersion: 0.0.5
+  entities: []
+  abis: []
+  eventHandlers:
+    - event: Transfer(indexed address,indexed address,uint256)
+      handler: handleTransfer
+    - event: Approval(indexed address,indexed address,uint256)
+      handler: handleApproval
+  file: ./mapping.ts
+"#
+        ))
+        .unwrap();
+        let manifest =
+            ManifestAgent::with_abis(ABIs::from_iter([("ERC20".to_string(), erc20_abi())]));
+        let log = |topic0: &str| -> Log {
+            serde_json::from_value(serde_json::json!({
+                "address": token,
+                "data": format!("0x{:064x}", 10),
+                "logIndex": "0x0",
+                "topics": [topic0, format!("0x{:064x}", 1), format!("0x{:064x}", 2)],
+                "transactionIndex": "0x0"
+            }))
+            .unwrap()
+        };
+        let logs = vec![
+            log("0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"),
+            log("0x8c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b2291e5b200ac8c7c3b925"),
+        ];
+        let header = EthereumBlockData::default();
+        let txs = vec![EthereumTransactionData::default()];
+
+        let mut filter = EthereumFilter::new(vec![ds], &manifest);
+        let events = filter
+            .filter_events(header.clone(), txs.clone(), logs.clone())
+            .unwrap();
+        assert_eq!(events.len(), 2);
+
+        let allowlist = HashMap::from([(
+            "Token".to_string(),
+            vec!["Transfer(indexed address,indexed address,uint256)".to_string()],
+        )]);
+        filter.set_event_allowlist(&allowlist).unwrap();
+        let events = filter.filter_events(header, txs, logs).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].handler, "handleTransfer");
+
+        let allowlist = HashMap::from([("Token".to_string(), vec!["0xinvalid".to_string()])]);
+        assert!(matches!(
+            filter.set_event_allowlist(&allowlist),
+            Err(FilterError::InvalidTopic(_))
+        ));
+    }
 }
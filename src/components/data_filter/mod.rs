@@ -1,15 +1,16 @@
 mod ethereum_filter;
 mod utils;
 
-use crate::common::ABIs;
 use crate::common::BlockDataMessage;
 use crate::common::Chain;
 use crate::common::Datasource;
 use crate::common::FilteredDataMessage;
+use crate::components::ManifestAgent;
 use crate::errors::FilterError;
 use ethereum_filter::EthereumFilter;
 use rayon::prelude::IntoParallelIterator;
 use rayon::prelude::ParallelIterator;
+use std::collections::HashMap;
 
 pub trait DataFilterTrait: Sized {
     fn handle_serialize_message(
@@ -39,13 +40,23 @@ impl DataFilter {
     pub fn new(
         chain: Chain,
         datasources: Vec<Datasource>,
-        abis: ABIs,
+        manifest: &ManifestAgent,
     ) -> Result<Self, FilterError> {
         let filter = match chain {
-            Chain::Ethereum => DataFilter::Ethereum(EthereumFilter::new(datasources, abis)),
+            Chain::Ethereum => DataFilter::Ethereum(EthereumFilter::new(datasources, manifest)),
         };
         Ok(filter)
     }
+
+    /// Drop the events of these datasources, by name, that are not in their allowlist
+    pub fn set_event_allowlist(
+        &mut self,
+        allowlist: &HashMap<String, Vec<String>>,
+    ) -> Result<(), FilterError> {
+        match self {
+            DataFilter::Ethereum(filter) => filter.set_event_allowlist(allowlist),
+        }
+    }
 }
 
 impl DataFilterTrait for DataFilter {
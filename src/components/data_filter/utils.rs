@@ -3,13 +3,13 @@ use crate::chain::ethereum::event::EthereumEventData;
 use crate::chain::ethereum::transaction::EthereumTransactionData;
 use crate::common::Datasource;
 use crate::common::EventHandler;
-use ethabi::Contract;
+use crate::components::manifest::ParsedAbi;
 use tiny_keccak::Hasher;
 use web3::types::Log;
 use web3::types::H256;
 
 pub fn parse_event(
-    contract: &Contract,
+    abi: &ParsedAbi,
     log: Log,
     block_header: EthereumBlockData,
     transaction: EthereumTransactionData,
@@ -18,13 +18,7 @@ pub fn parse_event(
         return None;
     }
 
-    let event = contract
-        .events()
-        .find(|event| event.signature() == log.topics[0]);
-
-    event?;
-
-    let event = event.unwrap();
+    let event = abi.decoder(&log.topics[0])?;
 
     event
         .parse_log(ethabi::RawLog {
@@ -56,7 +50,7 @@ pub fn get_handler_for_log(source: &Datasource, topic0: &H256) -> Option<EventHa
         })
 }
 
-fn parse_topic0_event(handler: &str) -> H256 {
+pub fn parse_topic0_event(handler: &str) -> H256 {
     let mut result = [0u8; 32];
     let data = handler.replace("indexed", "").replace(' ', "").into_bytes();
     let mut sponge = tiny_keccak::Keccak::v256();
@@ -1,10 +1,16 @@
 use crate::common::BlockPtr;
+use crate::config::ReorgThresholdPolicy;
 use crate::critical;
+use crate::database::DatabaseAgent;
 use crate::error;
+use crate::errors::MainError;
+use crate::errors::RPCError;
 use crate::info;
 use crate::warn;
 use std::collections::VecDeque;
 
+pub const DEFAULT_MAX_REORG_THRESHOLD: u16 = 1000;
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum BlockInspectionResult {
     OkToProceed,
@@ -13,13 +19,76 @@ pub enum BlockInspectionResult {
     MaybeReorg,
     ForkBlock,
     UnrecognizedBlock,
+    /// A fork-block reverting more blocks than `max_reorg_depth`, nothing was reverted
+    ReorgTooDeep,
+}
+
+/// Walk the stored head back until the source agrees with it, reverting the blocks
+/// the source no longer has, as the chain may have reorged while the runtime was down
+pub async fn recover_diverged_head<F>(
+    inspector: &mut Inspector,
+    db: &DatabaseAgent,
+    mut get_source_block: F,
+) -> Result<(), MainError>
+where
+    F: FnMut(u64) -> Result<BlockPtr, RPCError>,
+{
+    while let Some(head) = inspector.get_head() {
+        let source_block = get_source_block(head.number)?;
+        match inspector.verify_head(&source_block) {
+            BlockInspectionResult::OkToProceed => break,
+            BlockInspectionResult::ForkBlock => {
+                db.revert_from_block(head.number).await?;
+            }
+            _ => return Err(MainError::HeadDiverged),
+        }
+    }
+    Ok(())
+}
+
+/// Catch misconfigured thresholds, as that many recent block pointers are kept in memory
+pub fn check_reorg_threshold(
+    reorg_threshold: u16,
+    max_reorg_threshold: u16,
+    policy: &ReorgThresholdPolicy,
+) -> Result<(), MainError> {
+    if reorg_threshold <= max_reorg_threshold {
+        return Ok(());
+    }
+
+    match policy {
+        ReorgThresholdPolicy::Abort => {
+            error!(
+                Inspector,
+                "reorg-threshold exceeds the allowed maximum";
+                reorg_threshold => reorg_threshold,
+                max_reorg_threshold => max_reorg_threshold
+            );
+            Err(MainError::ReorgThresholdTooLarge(
+                reorg_threshold,
+                max_reorg_threshold,
+            ))
+        }
+        ReorgThresholdPolicy::Warn => {
+            warn!(
+                Inspector,
+                "reorg-threshold exceeds the allowed maximum, check your config";
+                reorg_threshold => reorg_threshold,
+                max_reorg_threshold => max_reorg_threshold
+            );
+            Ok(())
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct Inspector {
     recent_block_ptrs: VecDeque<BlockPtr>,
     ds_min_start_block: u64,
+    /// Start-block of every datasource, in the manifest's order
+    ds_start_blocks: Vec<u64>,
     reorg_threshold: u16,
+    max_reorg_depth: Option<u64>,
 }
 
 impl Inspector {
@@ -33,10 +102,31 @@ impl Inspector {
         Self {
             recent_block_ptrs: VecDeque::from(recent_block_ptrs),
             ds_min_start_block,
+            ds_start_blocks: vec![],
             reorg_threshold,
+            max_reorg_depth: None,
         }
     }
 
+    pub fn set_max_reorg_depth(&mut self, max_reorg_depth: Option<u64>) {
+        self.max_reorg_depth = max_reorg_depth;
+    }
+
+    pub fn set_source_start_blocks(&mut self, start_blocks: Vec<u64>) {
+        self.ds_start_blocks = start_blocks;
+    }
+
+    /// Indexes of the datasources whose start-block is reached at `block_number`,
+    /// later ones have nothing to handle yet
+    pub fn get_active_sources(&self, block_number: u64) -> Vec<usize> {
+        self.ds_start_blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, start_block)| **start_block <= block_number)
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
     pub fn get_expected_block_number(&self) -> u64 {
         let last_processed_block = self.recent_block_ptrs.front().cloned();
 
@@ -51,6 +141,71 @@ impl Inspector {
         self.ds_min_start_block
     }
 
+    pub fn get_head(&self) -> Option<BlockPtr> {
+        self.recent_block_ptrs.front().cloned()
+    }
+
+    /// Whether `maybe_ancestor` is reached from `descendant` by following the parent hashes
+    /// of the recent block pointers. Blocks out of the tracked window are never ancestors
+    pub fn is_ancestor(&self, maybe_ancestor: &BlockPtr, descendant: &BlockPtr) -> bool {
+        if maybe_ancestor.number >= descendant.number {
+            return false;
+        }
+
+        let position = match self.recent_block_ptrs.iter().position(|b| b == descendant) {
+            Some(position) => position,
+            None => return false,
+        };
+
+        let mut child = descendant;
+        for block in self.recent_block_ptrs.iter().skip(position + 1) {
+            if !block.is_parent(child) {
+                return false;
+            }
+            if block == maybe_ancestor {
+                return true;
+            }
+            child = block;
+        }
+
+        false
+    }
+
+    /// Compare the stored head with the block the source has at the same number.
+    /// A diverged head is dropped and `ForkBlock` returned, so the caller can revert
+    /// from it and verify the new head again. `UnrecognizedBlock` means no stored
+    /// block pointer is left to resume from, `UnexpectedBlock` that the source block
+    /// is not at the head's number
+    pub fn verify_head(&mut self, source_block: &BlockPtr) -> BlockInspectionResult {
+        let head = match self.recent_block_ptrs.front() {
+            Some(head) if head.number == source_block.number => head.clone(),
+            _ => return BlockInspectionResult::UnexpectedBlock,
+        };
+
+        if head.hash == source_block.hash {
+            return BlockInspectionResult::OkToProceed;
+        }
+
+        warn!(
+            Inspector,
+            "stored head diverged from the source while the runtime was down";
+            stored_head => head,
+            source_block => source_block
+        );
+        self.recent_block_ptrs.pop_front();
+
+        if self.recent_block_ptrs.is_empty() {
+            critical!(
+                Inspector,
+                "every stored block pointer diverged from the source, reorg is deeper than reorg-threshold";
+                source_block => source_block
+            );
+            return BlockInspectionResult::UnrecognizedBlock;
+        }
+
+        BlockInspectionResult::ForkBlock
+    }
+
     pub fn check_block(&mut self, new_block_ptr: BlockPtr) -> BlockInspectionResult {
         match self.recent_block_ptrs.front() {
             None => {
@@ -122,6 +277,17 @@ Please check your setup - as it can be either:
                     }
 
                     if block.is_parent(&new_block_ptr) {
+                        let depth = last_processed.number - block.number;
+                        if self.max_reorg_depth.is_some_and(|max| depth > max) {
+                            critical!(
+                                Inspector,
+                                "Reorg deeper than max-reorg-depth, halting instead of reverting!";
+                                fork_block => new_block_ptr,
+                                reverted_blocks => depth,
+                                max_reorg_depth => self.max_reorg_depth.unwrap()
+                            );
+                            return BlockInspectionResult::ReorgTooDeep;
+                        }
                         info!(
                             Inspector,
                             "Reorg happened and a proper fork-block received";
@@ -144,6 +310,11 @@ Please check your setup - as it can be either:
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::common::RawEntity;
+    use crate::common::StoreOperationMessage;
+    use crate::common::StoreRequestResult;
+    use crate::runtime::asc::native_types::store::Value;
+    use prometheus::default_registry;
     use rstest::rstest;
 
     #[rstest]
@@ -301,4 +472,216 @@ mod tests {
         );
         assert_eq!(pc.recent_block_ptrs.back().unwrap().number, 11);
     }
+
+    #[test]
+    fn test_max_reorg_depth() {
+        env_logger::try_init().unwrap_or_default();
+        let block_ptr = |n: u64, fork: &str| BlockPtr {
+            number: n,
+            hash: format!("n={fork}{n}"),
+            parent_hash: format!("n={}", n - 1),
+        };
+        let mut pc = Inspector::new(vec![], 1, 10);
+        pc.set_max_reorg_depth(Some(3));
+        for n in 1..20 {
+            assert_eq!(
+                pc.check_block(block_ptr(n, "")),
+                BlockInspectionResult::OkToProceed
+            );
+        }
+
+        // Reverting blocks 16..=19 is one too many
+        assert_eq!(
+            pc.check_block(block_ptr(16, "fork")),
+            BlockInspectionResult::ReorgTooDeep
+        );
+        assert_eq!(pc.get_head(), Some(block_ptr(19, "")));
+
+        // Reverting blocks 17..=19 is still recovered from
+        assert_eq!(
+            pc.check_block(block_ptr(17, "fork")),
+            BlockInspectionResult::ForkBlock
+        );
+        assert_eq!(pc.get_head(), Some(block_ptr(17, "fork")));
+    }
+
+    #[test]
+    fn test_is_ancestor() {
+        let block_ptr = |n: u64| BlockPtr {
+            number: n,
+            hash: format!("n={n}"),
+            parent_hash: format!("n={}", n - 1),
+        };
+        let mut pc = Inspector::new(vec![], 1, 10);
+        for n in 1..20 {
+            assert_eq!(
+                pc.check_block(block_ptr(n)),
+                BlockInspectionResult::OkToProceed
+            );
+        }
+
+        // Blocks 10..=19 are tracked
+        assert!(pc.is_ancestor(&block_ptr(18), &block_ptr(19)));
+        assert!(pc.is_ancestor(&block_ptr(10), &block_ptr(19)));
+        assert!(pc.is_ancestor(&block_ptr(12), &block_ptr(15)));
+        assert!(!pc.is_ancestor(&block_ptr(15), &block_ptr(12)));
+        assert!(!pc.is_ancestor(&block_ptr(15), &block_ptr(15)));
+
+        // Out of the window
+        assert!(!pc.is_ancestor(&block_ptr(9), &block_ptr(19)));
+        assert!(!pc.is_ancestor(&block_ptr(15), &block_ptr(20)));
+
+        // A fork-block of the same number is not an ancestor
+        let fork = BlockPtr {
+            number: 12,
+            hash: "n=fork12".to_string(),
+            parent_hash: "n=11".to_string(),
+        };
+        assert!(!pc.is_ancestor(&fork, &block_ptr(19)));
+    }
+
+    #[test]
+    fn test_verify_head() {
+        env_logger::try_init().unwrap_or_default();
+        let stored = (1..=5)
+            .map(|n| BlockPtr {
+                number: n,
+                hash: format!("n={n}"),
+                parent_hash: format!("n={}", n - 1),
+            })
+            .collect::<Vec<_>>();
+        // Blocks 4 & 5 were reorged out while the runtime was down
+        let source_block = |n: u64| BlockPtr {
+            number: n,
+            hash: if n >= 4 {
+                format!("n=fork{n}")
+            } else {
+                format!("n={n}")
+            },
+            parent_hash: "".to_string(),
+        };
+
+        let mut pc = Inspector::new(stored.clone(), 0, 10);
+        let mut reverted_from = vec![];
+
+        while let Some(head) = pc.get_head() {
+            match pc.verify_head(&source_block(head.number)) {
+                BlockInspectionResult::OkToProceed => break,
+                BlockInspectionResult::ForkBlock => reverted_from.push(head.number),
+                result => panic!("unexpected result: {:?}", result),
+            }
+        }
+
+        assert_eq!(reverted_from, vec![5, 4]);
+        assert_eq!(pc.get_expected_block_number(), 4);
+        assert_eq!(
+            pc.verify_head(&source_block(4)),
+            BlockInspectionResult::UnexpectedBlock
+        );
+
+        // Nothing left to resume from when every stored block diverged
+        let mut pc = Inspector::new(stored[3..].to_vec(), 0, 10);
+        assert_eq!(
+            pc.verify_head(&source_block(5)),
+            BlockInspectionResult::ForkBlock
+        );
+        assert_eq!(
+            pc.verify_head(&source_block(4)),
+            BlockInspectionResult::UnrecognizedBlock
+        );
+    }
+
+    #[test]
+    fn test_active_sources() {
+        env_logger::try_init().unwrap_or_default();
+        let mut pc = Inspector::new(vec![], 0, 10);
+        assert!(pc.get_active_sources(50).is_empty());
+
+        pc.set_source_start_blocks(vec![0, 100]);
+        assert_eq!(pc.get_expected_block_number(), 0);
+        assert_eq!(pc.get_active_sources(0), vec![0]);
+        assert_eq!(pc.get_active_sources(50), vec![0]);
+        assert_eq!(pc.get_active_sources(100), vec![0, 1]);
+        assert_eq!(pc.get_active_sources(150), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_check_reorg_threshold() {
+        env_logger::try_init().unwrap_or_default();
+        assert!(check_reorg_threshold(200, 200, &ReorgThresholdPolicy::Abort).is_ok());
+        assert!(check_reorg_threshold(5000, 1000, &ReorgThresholdPolicy::Warn).is_ok());
+        assert!(matches!(
+            check_reorg_threshold(5000, 1000, &ReorgThresholdPolicy::Abort),
+            Err(MainError::ReorgThresholdTooLarge(5000, 1000))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_recover_diverged_head() {
+        env_logger::try_init().unwrap_or_default();
+        let stored = (1..=5)
+            .map(|n| BlockPtr {
+                number: n,
+                hash: format!("n={n}"),
+                parent_hash: format!("n={}", n - 1),
+            })
+            .collect::<Vec<_>>();
+        // The source forked from block `fork` on while the runtime was down
+        let source = |fork: u64| {
+            move |n: u64| {
+                Ok::<_, RPCError>(BlockPtr {
+                    number: n,
+                    hash: match n >= fork {
+                        true => format!("n=fork{n}"),
+                        false => format!("n={n}"),
+                    },
+                    parent_hash: "".to_string(),
+                })
+            }
+        };
+        let db = DatabaseAgent::empty(default_registry());
+        let write_token = || {
+            let mut data = RawEntity::new();
+            data.insert("id".to_string(), Value::String("token".to_string()));
+            let request = StoreOperationMessage::Create(("Token".to_string(), data));
+            db.wasm_send_store_request(request).unwrap();
+        };
+        let token_exists = || {
+            let request = StoreOperationMessage::load("Token".to_string(), "token".into());
+            matches!(
+                db.wasm_send_store_request(request).unwrap(),
+                StoreRequestResult::Load(Some(_))
+            )
+        };
+
+        // Nothing is reverted while the source agrees with the stored head
+        write_token();
+        let mut pc = Inspector::new(stored.clone(), 0, 10);
+        recover_diverged_head(&mut pc, &db, source(6))
+            .await
+            .unwrap();
+        assert_eq!(pc.get_expected_block_number(), 6);
+        assert!(token_exists());
+
+        // Diverged blocks are reverted, resuming after the last block the source agrees with
+        let mut pc = Inspector::new(stored.clone(), 0, 10);
+        recover_diverged_head(&mut pc, &db, source(4))
+            .await
+            .unwrap();
+        assert_eq!(pc.get_head().map(|head| head.number), Some(3));
+        assert_eq!(pc.get_expected_block_number(), 4);
+        assert!(!token_exists());
+
+        let mut pc = Inspector::new(stored.clone(), 0, 10);
+        assert!(matches!(
+            recover_diverged_head(&mut pc, &db, source(0)).await,
+            Err(MainError::HeadDiverged)
+        ));
+
+        let mut pc = Inspector::new(stored, 0, 10);
+        assert!(matches!(
+            recover_diverged_head(&mut pc, &db, |n| Err(RPCError::GetBlockFail(n))).await,
+            Err(MainError::Rpc(RPCError::GetBlockFail(5)))
+        ));
+    }
 }
@@ -0,0 +1,178 @@
+use ethabi::Contract;
+use ethabi::Event;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::sync::Arc;
+use web3::types::H256;
+
+const DEFAULT_ABI_CACHE_CAPACITY: usize = 64;
+
+/// A parsed ABI together with its event decoders, indexed by topic0
+#[derive(Debug)]
+pub struct ParsedAbi {
+    contract: Contract,
+    decoders: HashMap<H256, Event>,
+}
+
+impl ParsedAbi {
+    pub fn contract(&self) -> &Contract {
+        &self.contract
+    }
+
+    pub fn decoder(&self, topic0: &H256) -> Option<&Event> {
+        self.decoders.get(topic0)
+    }
+}
+
+impl From<Contract> for ParsedAbi {
+    fn from(contract: Contract) -> Self {
+        let decoders = contract
+            .events()
+            .filter(|event| !event.anonymous)
+            .map(|event| (event.signature(), event.clone()))
+            .collect();
+        Self { contract, decoders }
+    }
+}
+
+type AbiKey = (String, u64);
+
+/// Bounded LRU of parsed ABIs, keyed by ABI name and a hash of its json content
+#[derive(Debug)]
+pub struct AbiCache {
+    capacity: usize,
+    entries: HashMap<AbiKey, Arc<ParsedAbi>>,
+    recency: VecDeque<AbiKey>,
+    parse_count: usize,
+}
+
+impl Default for AbiCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_ABI_CACHE_CAPACITY)
+    }
+}
+
+impl AbiCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            recency: VecDeque::new(),
+            parse_count: 0,
+        }
+    }
+
+    pub fn get_or_parse(&mut self, name: &str, abi: &serde_json::Value) -> Option<Arc<ParsedAbi>> {
+        let key = (name.to_owned(), Self::hash_abi(abi));
+
+        if let Some(parsed) = self.entries.get(&key).cloned() {
+            self.touch(&key);
+            return Some(parsed);
+        }
+
+        let contract: Contract = serde_json::from_value(abi.clone()).ok()?;
+        self.parse_count += 1;
+        let parsed = Arc::new(ParsedAbi::from(contract));
+
+        if self.entries.len() >= self.capacity {
+            if let Some(evicted) = self.recency.pop_front() {
+                self.entries.remove(&evicted);
+            }
+        }
+
+        self.entries.insert(key.clone(), parsed.clone());
+        self.recency.push_back(key);
+        Some(parsed)
+    }
+
+    pub fn parse_count(&self) -> usize {
+        self.parse_count
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn touch(&mut self, key: &AbiKey) {
+        if let Some(position) = self.recency.iter().position(|k| k == key) {
+            let key = self.recency.remove(position).unwrap();
+            self.recency.push_back(key);
+        }
+    }
+
+    fn hash_abi(abi: &serde_json::Value) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        abi.to_string().hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn erc20_abi() -> serde_json::Value {
+        serde_json::json!([
+            {"anonymous":false,"inputs":[{"indexed":true,"name":"from","type":"address"},{"indexed":true,"name":"to","type":"address"},{"indexed":false,"name":"value","type":"uint256"}],"name":"Transfer","type":"event"},
+            {"anonymous":false,"inputs":[{"indexed":true,"name":"owner","type":"address"},{"indexed":true,"name":"spender","type":"address"},{"indexed":false,"name":"value","type":"uint256"}],"name":"Approval","type":"event"}
+        ])
+    }
+
+    #[test]
+    fn test_abi_cache_reuses_parsed_contract() {
+        env_logger::try_init().unwrap_or_default();
+        let mut cache = AbiCache::default();
+        let abi = erc20_abi();
+
+        let first = cache.get_or_parse("ERC20", &abi).unwrap();
+        let second = cache.get_or_parse("ERC20", &abi).unwrap();
+        assert_eq!(cache.parse_count(), 1);
+        assert!(Arc::ptr_eq(&first, &second));
+
+        // Same name with different content is parsed separately
+        let other = serde_json::json!([erc20_abi()[0].clone()]);
+        cache.get_or_parse("ERC20", &other).unwrap();
+        assert_eq!(cache.parse_count(), 2);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_abi_cache_evicts_least_recently_used() {
+        env_logger::try_init().unwrap_or_default();
+        let mut cache = AbiCache::new(2);
+        let abi = erc20_abi();
+
+        cache.get_or_parse("A", &abi).unwrap();
+        cache.get_or_parse("B", &abi).unwrap();
+        cache.get_or_parse("A", &abi).unwrap();
+        cache.get_or_parse("C", &abi).unwrap();
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.parse_count(), 3);
+
+        // A was recently used, so B is the one that got evicted
+        cache.get_or_parse("A", &abi).unwrap();
+        assert_eq!(cache.parse_count(), 3);
+        cache.get_or_parse("B", &abi).unwrap();
+        assert_eq!(cache.parse_count(), 4);
+    }
+
+    #[test]
+    fn test_event_decoder_keyed_by_topic0() {
+        env_logger::try_init().unwrap_or_default();
+        let mut cache = AbiCache::default();
+        let parsed = cache.get_or_parse("ERC20", &erc20_abi()).unwrap();
+        let transfer_topic0 =
+            H256::from_str("0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef")
+                .unwrap();
+
+        let first = parsed.decoder(&transfer_topic0).unwrap();
+        let second = parsed.decoder(&transfer_topic0).unwrap();
+        assert_eq!(first.name, "Transfer");
+        assert!(std::ptr::eq(first, second));
+        assert!(parsed.decoder(&H256::zero()).is_none());
+    }
+}
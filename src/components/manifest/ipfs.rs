@@ -0,0 +1,268 @@
+use super::ManifestBundle;
+use crate::common::*;
+use crate::config::IpfsConfig;
+use crate::errors::ManifestLoaderError;
+use crate::info;
+use crate::warn;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const DEFAULT_IPFS_TIMEOUT: u64 = 30;
+const DEFAULT_IPFS_CACHE_DIR: &str = ".ipfs-cache";
+
+/// Loads a subgraph deployed to IPFS, where every file the manifest references
+/// is a `{ /: /ipfs/<cid> }` link. Content is addressed by CID, so fetched files
+/// are cached on disk as-is
+pub struct IpfsLoader {
+    client: reqwest::Client,
+    gateway: String,
+    cache_dir: PathBuf,
+}
+
+impl IpfsLoader {
+    pub fn new(config: &IpfsConfig) -> Result<Self, ManifestLoaderError> {
+        let timeout = config.timeout.unwrap_or(DEFAULT_IPFS_TIMEOUT);
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(timeout))
+            .build()
+            .map_err(|e| ManifestLoaderError::IpfsClient(e.to_string()))?;
+
+        Ok(Self {
+            client,
+            gateway: config.gateway.trim_end_matches('/').to_owned(),
+            cache_dir: PathBuf::from(
+                config
+                    .cache_dir
+                    .clone()
+                    .unwrap_or(DEFAULT_IPFS_CACHE_DIR.to_owned()),
+            ),
+        })
+    }
+
+    pub async fn try_subgraph_cid(&self, cid: &str) -> Result<ManifestBundle, ManifestLoaderError> {
+        info!(Manifest, "fetching subgraph from ipfs"; cid => cid, gateway => self.gateway);
+        let raw_yaml = self.fetch(cid).await?;
+        let mut yaml: serde_yaml::Value = serde_yaml::from_slice(&raw_yaml)
+            .map_err(|_| ManifestLoaderError::InvalidSubgraphYAML(cid.to_owned()))?;
+        resolve_links(&mut yaml);
+
+        let schema_cid = yaml["schema"]["file"]
+            .as_str()
+            .map(link_to_cid)
+            .ok_or(ManifestLoaderError::InvalidSubgraphYAML(cid.to_owned()))?;
+        let subgraph_yaml: SubgraphYaml = serde_yaml::from_value(yaml)
+            .map_err(|_| ManifestLoaderError::InvalidSubgraphYAML(cid.to_owned()))?;
+
+        let mut abis = ABIs::default();
+        for (name, file) in subgraph_yaml.abis() {
+            let raw_abi = self.fetch(link_to_cid(&file)).await?;
+            let abi = serde_json::from_slice(&raw_abi)
+                .map_err(|_| ManifestLoaderError::InvalidABI(name.clone()))?;
+            abis.insert(name, abi);
+        }
+
+        let mut wasms = WASMs::default();
+        for (datasource_name, file) in subgraph_yaml.wasms() {
+            let wasm_bytes = self.fetch(link_to_cid(&file)).await?;
+            wasms.insert(datasource_name, wasm_bytes);
+        }
+
+        let raw_schema = self.fetch(schema_cid).await?;
+        let schema =
+            String::from_utf8(raw_schema).map_err(|_| ManifestLoaderError::SchemaParsingError)?;
+        let schema = Schemas::new_from_graphql_schema(&schema);
+
+        Ok(ManifestBundle::new(subgraph_yaml, abis, wasms, schema))
+    }
+
+    async fn fetch(&self, cid: &str) -> Result<Vec<u8>, ManifestLoaderError> {
+        // Path-style CIDs (eg `<cid>/file.wasm`) are flattened into a single file name
+        let cached_file = self.cache_dir.join(cid.replace('/', "_"));
+
+        if let Ok(content) = fs::read(&cached_file) {
+            return Ok(content);
+        }
+
+        let url = format!("{}/ipfs/{}", self.gateway, cid);
+        let to_error = |e: reqwest::Error| {
+            warn!(Manifest, "fetching from ipfs gateway failed"; cid => cid, error => e);
+            if e.is_timeout() {
+                ManifestLoaderError::IpfsTimeout(cid.to_owned())
+            } else {
+                ManifestLoaderError::UnresolvableCid(cid.to_owned())
+            }
+        };
+
+        let content = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(to_error)?
+            .bytes()
+            .await
+            .map_err(to_error)?
+            .to_vec();
+
+        if let Err(e) =
+            fs::create_dir_all(&self.cache_dir).and_then(|_| fs::write(&cached_file, &content))
+        {
+            warn!(Manifest, "caching ipfs file failed"; cid => cid, error => e);
+        }
+
+        Ok(content)
+    }
+}
+
+/// `/ipfs/<cid>` or a bare `<cid>` into `<cid>`
+fn link_to_cid(link: &str) -> &str {
+    link.trim_start_matches("/ipfs/")
+}
+
+/// Replace every `{ /: <link> }` with the link itself, so the manifest reads
+/// like a local one whose file paths are links
+fn resolve_links(value: &mut serde_yaml::Value) {
+    match value {
+        serde_yaml::Value::Mapping(mapping) => {
+            if mapping.len() == 1 {
+                if let Some(link) = mapping.get("/").cloned() {
+                    *value = link;
+                    return;
+                }
+            }
+            mapping.values_mut().for_each(resolve_links);
+        }
+        serde_yaml::Value::Sequence(items) => items.iter_mut().for_each(resolve_links),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    const MANIFEST_CID: &str = "QmManifest";
+
+    fn manifest_yaml() -> &'static str {
+        r#"
+specVersion: 0.0.5
+schema:
+  file:
+    /: /ipfs/QmSchema
+dataSources:
+  - kind: ethereum/contract
+    name: Token
+    network: mainnet
+    source:
+      address: "0x1f9840a85d5af5bf1d1762f925bdaddc4201f984"
+      abi: ERC20
+      startBlock: 10861674
+    mapping:
+      kind: ethereum/events
+      apiVersion: 0.0.7
+      language: wasm/assemblyscript
+      entities:
+        - Transfer
+      abis:
+        - name: ERC20
+          file:
+            /: /ipfs/QmAbi
+      eventHandlers:
+        - event: Transfer(indexed address,indexed address,uint256)
+          handler: handleTransfer
+      file:
+        /: /ipfs/QmWasm
+"#
+    }
+
+    fn setup_cache(name: &str) -> PathBuf {
+        let mut dir = env::temp_dir();
+        dir.push(name);
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn loader(gateway: &str, cache_dir: &PathBuf, timeout: u64) -> IpfsLoader {
+        IpfsLoader::new(&IpfsConfig {
+            gateway: gateway.to_owned(),
+            timeout: Some(timeout),
+            cache_dir: Some(cache_dir.to_str().unwrap().to_owned()),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_resolve_links() {
+        let mut yaml: serde_yaml::Value = serde_yaml::from_str(manifest_yaml()).unwrap();
+        resolve_links(&mut yaml);
+
+        assert_eq!(yaml["schema"]["file"].as_str(), Some("/ipfs/QmSchema"));
+        assert_eq!(
+            yaml["dataSources"][0]["mapping"]["file"].as_str(),
+            Some("/ipfs/QmWasm")
+        );
+        assert_eq!(link_to_cid("/ipfs/QmWasm"), "QmWasm");
+        assert_eq!(link_to_cid("QmWasm"), "QmWasm");
+    }
+
+    #[tokio::test]
+    async fn test_ipfs_loader_from_cache() {
+        env_logger::try_init().unwrap_or_default();
+        let cache_dir = setup_cache("ipfs-loader-cache");
+        fs::write(cache_dir.join(MANIFEST_CID), manifest_yaml()).unwrap();
+        fs::write(cache_dir.join("QmAbi"), "[]").unwrap();
+        fs::write(cache_dir.join("QmWasm"), [0, 97, 115, 109]).unwrap();
+        fs::write(
+            cache_dir.join("QmSchema"),
+            "type Transfer @entity { id: ID! }",
+        )
+        .unwrap();
+
+        // Everything is cached, so the gateway is never reached
+        let m = loader("http://127.0.0.1:1", &cache_dir, 1)
+            .try_subgraph_cid(MANIFEST_CID)
+            .await
+            .unwrap();
+
+        assert_eq!(m.subgraph_yaml.dataSources.len(), 1);
+        assert_eq!(m.abis.get("ERC20"), Some(serde_json::json!([])));
+        assert_eq!(m.wasms.get("Token"), Some(vec![0, 97, 115, 109]));
+        assert_eq!(m.schema.get_entity_names(), vec!["Transfer".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_ipfs_loader_errors() {
+        env_logger::try_init().unwrap_or_default();
+        let cache_dir = setup_cache("ipfs-loader-errors");
+
+        let unresolvable = loader("http://127.0.0.1:1", &cache_dir, 1)
+            .try_subgraph_cid(MANIFEST_CID)
+            .await;
+        assert!(matches!(
+            unresolvable,
+            Err(ManifestLoaderError::UnresolvableCid(cid)) if cid == MANIFEST_CID
+        ));
+
+        // A gateway accepting connections but never answering
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let gateway = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut connections = vec![];
+            while let Ok((stream, _)) = listener.accept().await {
+                connections.push(stream);
+            }
+        });
+
+        let timed_out = loader(&gateway, &cache_dir, 1)
+            .try_subgraph_cid(MANIFEST_CID)
+            .await;
+        assert!(matches!(
+            timed_out,
+            Err(ManifestLoaderError::IpfsTimeout(cid)) if cid == MANIFEST_CID
+        ));
+    }
+}
@@ -3,7 +3,6 @@ use super::Schemas;
 use crate::common::*;
 use crate::errors::ManifestLoaderError;
 use crate::info;
-use std::collections::HashMap;
 use std::fs;
 use std::fs::read_to_string;
 use std::io::BufReader;
@@ -27,23 +26,7 @@ impl LocalFileLoader {
         let abis = LocalFileLoader::load_abis(subgraph_dir, &subgraph_yaml)?;
         let wasms = LocalFileLoader::load_wasm(subgraph_dir, &subgraph_yaml)?;
         let schema = LocalFileLoader::load_schema(subgraph_dir)?;
-        let datasources = DatasourceBundles::from((&subgraph_yaml.dataSources, &abis, &wasms));
-        let templates = DatasourceBundles::from((
-            subgraph_yaml.templates.clone().unwrap_or(vec![]).as_ref(),
-            &abis,
-            &wasms,
-        ));
-
-        let manifest = ManifestBundle {
-            subgraph_yaml,
-            abis,
-            wasms,
-            schema,
-            datasources,
-            templates,
-            block_ptr: BlockPtr::default(),
-            templates_address_filter: HashMap::default(),
-        };
+        let manifest = ManifestBundle::new(subgraph_yaml, abis, wasms, schema);
 
         Ok(manifest)
     }
@@ -130,7 +113,7 @@ mod test {
     #[tokio::test]
     async fn test_get_template() {
         env_logger::try_init().unwrap_or_default();
-        let m = ManifestAgent::new("../subgraph-testing/packages/uniswap-v3/build")
+        let m = ManifestAgent::new("../subgraph-testing/packages/uniswap-v3/build", None)
             .await
             .unwrap();
         assert_eq!(m.abis().len(), 6);
@@ -138,4 +121,41 @@ mod test {
         assert_eq!(m.datasources().len(), 2);
         assert_eq!(m.datasource_and_templates().len(), 3);
     }
+
+    #[tokio::test]
+    async fn test_create_datasource() {
+        env_logger::try_init().unwrap_or_default();
+        let m = ManifestAgent::new("../subgraph-testing/packages/uniswap-v3/build", None)
+            .await
+            .unwrap();
+        m.set_block_ptr(&BlockPtr {
+            number: 12_370_000,
+            ..Default::default()
+        });
+        let address = "0x8AD599C3A0FF1DE082011EFDDC58F1908EB6E6D8";
+
+        assert!(m.should_process_address("Pool", "0xabc"));
+        m.create_datasource("Pool", vec![address.to_string()])
+            .unwrap();
+        assert!(m.should_process_address("Pool", &address.to_lowercase()));
+        assert!(!m.should_process_address("Pool", "0xabc"));
+        assert!(m
+            .create_datasource("Unknown", vec![address.to_string()])
+            .is_err());
+        assert!(m.create_datasource("Pool", vec![]).is_err());
+
+        let created = m.take_created_datasources();
+        assert_eq!(created.len(), 1);
+        assert_eq!(created[0].source.address, Some(address.to_lowercase()));
+        assert_eq!(created[0].source.startBlock, Some(12_370_000));
+        assert!(m.take_created_datasources().is_empty());
+
+        // Created datasources are restored after a restart
+        let restarted = ManifestAgent::new("../subgraph-testing/packages/uniswap-v3/build", None)
+            .await
+            .unwrap();
+        restarted.restore_datasources(created);
+        assert!(restarted.should_process_address("Pool", &address.to_lowercase()));
+        assert!(!restarted.should_process_address("Pool", "0xabc"));
+    }
 }
@@ -1,14 +1,22 @@
+mod abi_cache;
+mod ipfs;
 mod local;
 
+pub use abi_cache::AbiCache;
+pub use abi_cache::ParsedAbi;
+
 use crate::common::Schemas;
 use crate::common::*;
+use crate::config::IpfsConfig;
 use crate::error;
 use crate::errors::ManifestLoaderError;
+use ipfs::IpfsLoader;
 use local::LocalFileLoader;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::rc::Rc;
+use std::sync::Arc;
 
 #[derive(Debug, Default)]
 pub struct ManifestBundle {
@@ -20,6 +28,33 @@ pub struct ManifestBundle {
     datasources: DatasourceBundles,
     block_ptr: BlockPtr,
     templates_address_filter: HashMap<String, HashSet<String>>,
+    /// Datasources created from templates that are not persisted yet
+    created_datasources: Vec<Datasource>,
+    abi_cache: AbiCache,
+}
+
+impl ManifestBundle {
+    fn new(subgraph_yaml: SubgraphYaml, abis: ABIs, wasms: WASMs, schema: Schemas) -> Self {
+        let datasources = DatasourceBundles::from((&subgraph_yaml.dataSources, &abis, &wasms));
+        let templates = DatasourceBundles::from((
+            subgraph_yaml.templates.clone().unwrap_or(vec![]).as_ref(),
+            &abis,
+            &wasms,
+        ));
+
+        Self {
+            subgraph_yaml,
+            abis,
+            wasms,
+            schema,
+            datasources,
+            templates,
+            block_ptr: BlockPtr::default(),
+            templates_address_filter: HashMap::default(),
+            created_datasources: vec![],
+            abi_cache: AbiCache::default(),
+        }
+    }
 }
 
 #[derive(Clone, Default)]
@@ -28,21 +63,50 @@ pub struct ManifestAgent(Rc<RefCell<ManifestBundle>>);
 unsafe impl Send for ManifestAgent {}
 
 impl ManifestAgent {
-    pub async fn new(subgraph_path: &str) -> Result<Self, ManifestLoaderError> {
-        let manifest = LocalFileLoader::try_subgraph_dir(subgraph_path)?;
+    pub async fn new(
+        subgraph_path: &str,
+        ipfs: Option<&IpfsConfig>,
+    ) -> Result<Self, ManifestLoaderError> {
+        let manifest = match subgraph_path.strip_prefix("ipfs://") {
+            Some(cid) => {
+                let ipfs = ipfs.ok_or(ManifestLoaderError::IpfsNotConfigured)?;
+                IpfsLoader::new(ipfs)?.try_subgraph_cid(cid).await?
+            }
+            None => LocalFileLoader::try_subgraph_dir(subgraph_path)?,
+        };
         Ok(Self(Rc::new(RefCell::new(manifest))))
     }
 
+    #[cfg(test)]
+    pub fn with_abis(abis: ABIs) -> Self {
+        let manifest = ManifestBundle {
+            abis,
+            ..Default::default()
+        };
+        Self(Rc::new(RefCell::new(manifest)))
+    }
+
     pub fn set_block_ptr(&self, block_ptr: &BlockPtr) {
         let mut manifest = self.0.borrow_mut();
         manifest.block_ptr = block_ptr.clone();
     }
 
+    pub fn block_ptr(&self) -> BlockPtr {
+        let manifest = self.0.borrow();
+        manifest.block_ptr.clone()
+    }
+
     pub fn abis(&self) -> ABIs {
         let manifest = self.0.borrow();
         manifest.abis.clone()
     }
 
+    pub fn get_abi(&self, name: &str) -> Option<Arc<ParsedAbi>> {
+        let manifest = &mut *self.0.borrow_mut();
+        let abi = manifest.abis.0.get(name)?;
+        manifest.abi_cache.get_or_parse(name, abi)
+    }
+
     pub fn schemas(&self) -> Schemas {
         let manifest = self.0.borrow();
         manifest.schema.clone()
@@ -85,6 +149,32 @@ impl ManifestAgent {
         manifest.datasources.len()
     }
 
+    pub fn datasource_names(&self) -> Vec<String> {
+        let manifest = self.0.borrow();
+        manifest.datasources.ds.iter().map(|ds| ds.name()).collect()
+    }
+
+    /// Contract address of every datasource declaring one, by datasource name
+    pub fn datasource_addresses(&self) -> HashMap<String, String> {
+        let manifest = self.0.borrow();
+        manifest
+            .datasources
+            .ds
+            .iter()
+            .filter_map(|ds| ds.address().map(|address| (ds.name(), address)))
+            .collect()
+    }
+
+    pub fn source_start_blocks(&self) -> Vec<u64> {
+        let manifest = self.0.borrow();
+        manifest
+            .datasources
+            .ds
+            .iter()
+            .map(|ds| ds.start_block())
+            .collect()
+    }
+
     pub fn min_start_block(&self) -> u64 {
         let manifest = self.0.borrow();
         manifest.subgraph_yaml.min_start_block()
@@ -104,31 +194,57 @@ impl ManifestAgent {
         params: Vec<String>,
     ) -> Result<(), ManifestLoaderError> {
         let mut manifest = self.0.borrow_mut();
-        let address = params.first().cloned().map(|s| s.to_lowercase());
-
-        if address.is_none() {
-            error!(
-                Manifest,
-                "invalid datasource create, address must not be None"
-            );
-            return Err(ManifestLoaderError::CreateDatasourceFail);
-        }
+        let address = match params.first() {
+            Some(address) => address.to_lowercase(),
+            None => {
+                error!(
+                    Manifest,
+                    "invalid datasource create, address must not be None"
+                );
+                return Err(ManifestLoaderError::CreateDatasourceFail);
+            }
+        };
 
-        if !manifest.templates_address_filter.contains_key(name) {
-            manifest
-                .templates_address_filter
-                .insert(name.to_string(), HashSet::new());
-        }
+        let mut datasource = match manifest.templates.ds.iter().find(|ds| ds.name() == name) {
+            Some(template) => template.ds.clone(),
+            None => {
+                error!(Manifest, "invalid datasource create, no such template"; name => name);
+                return Err(ManifestLoaderError::InvalidDataSource(name.to_string()));
+            }
+        };
+        datasource.source.address = Some(address.clone());
+        datasource.source.startBlock = Some(manifest.block_ptr.number);
 
         manifest
             .templates_address_filter
-            .get_mut(name)
-            .unwrap()
-            .insert(address.clone().unwrap());
+            .entry(name.to_string())
+            .or_default()
+            .insert(address);
+        manifest.created_datasources.push(datasource);
 
         Ok(())
     }
 
+    /// Re-register datasources created from templates before a restart
+    pub fn restore_datasources(&self, datasources: Vec<Datasource>) {
+        let mut manifest = self.0.borrow_mut();
+        for ds in datasources {
+            if let Some(address) = ds.source.address {
+                manifest
+                    .templates_address_filter
+                    .entry(ds.name)
+                    .or_default()
+                    .insert(address.to_lowercase());
+            }
+        }
+    }
+
+    /// Datasources created since the last call, to be persisted
+    pub fn take_created_datasources(&self) -> Vec<Datasource> {
+        let mut manifest = self.0.borrow_mut();
+        std::mem::take(&mut manifest.created_datasources)
+    }
+
     pub fn should_process_address(&self, name: &str, address: &str) -> bool {
         let manifest = self.0.borrow();
         let template = manifest.templates_address_filter.get(name);
@@ -2,13 +2,18 @@ mod block_source;
 mod data_filter;
 mod inspector;
 mod manifest;
+mod reorder_buffer;
 mod subgraph;
 mod valve;
 
 pub use block_source::BlockSource;
 pub use data_filter::DataFilter;
+pub use inspector::check_reorg_threshold;
+pub use inspector::recover_diverged_head;
 pub use inspector::BlockInspectionResult;
 pub use inspector::Inspector;
+pub use inspector::DEFAULT_MAX_REORG_THRESHOLD;
 pub use manifest::ManifestAgent;
+pub use reorder_buffer::ReorderBuffer;
 pub use subgraph::Subgraph;
 pub use valve::Valve;
@@ -0,0 +1,120 @@
+use crate::debug;
+use crate::warn;
+use std::collections::BTreeMap;
+
+/// Holds blocks that arrive ahead of the expected one (eg from partitioned sources)
+/// until their predecessors show up, so the inspector always sees them in order.
+/// Blocks at or below the last released number are passed through untouched,
+/// letting the inspector decide whether they are duplicates or fork-blocks.
+pub struct ReorderBuffer<T> {
+    window: usize,
+    expected_block: u64,
+    pending: BTreeMap<u64, T>,
+}
+
+impl<T> ReorderBuffer<T> {
+    pub fn new(window: usize, expected_block: u64) -> Self {
+        Self {
+            window: window.max(1),
+            expected_block,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the blocks that are now ready to be processed, in order
+    pub fn push(&mut self, block_number: u64, block: T) -> Vec<T> {
+        if block_number < self.expected_block {
+            return vec![block];
+        }
+
+        if block_number > self.expected_block {
+            debug!(
+                ReorderBuffer,
+                "holding block that arrived ahead of its predecessor";
+                block_number => block_number,
+                expected_block => self.expected_block
+            );
+        }
+
+        self.pending.insert(block_number, block);
+        let mut ready = vec![];
+
+        while let Some(block) = self.pending.remove(&self.expected_block) {
+            ready.push(block);
+            self.expected_block += 1;
+        }
+
+        if self.pending.len() > self.window {
+            // Predecessor never arrived within the window, release everything
+            // and let the inspector report the gap
+            warn!(
+                ReorderBuffer,
+                "reorder window exceeded, releasing held blocks";
+                expected_block => self.expected_block,
+                held_blocks => self.pending.len()
+            );
+            let held = std::mem::take(&mut self.pending);
+            if let Some(last) = held.keys().last() {
+                self.expected_block = last + 1;
+            }
+            ready.extend(held.into_values());
+        }
+
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::BlockPtr;
+    use crate::components::BlockInspectionResult;
+    use crate::components::Inspector;
+
+    fn block(number: u64) -> BlockPtr {
+        BlockPtr {
+            number,
+            hash: format!("n={number}"),
+            parent_hash: format!("n={}", number - 1),
+        }
+    }
+
+    #[test]
+    fn test_reorder_buffer() {
+        env_logger::try_init().unwrap_or_default();
+        let mut inspector = Inspector::new(vec![], 1, 10);
+        let mut buffer = ReorderBuffer::new(4, inspector.get_expected_block_number());
+        let mut processed = vec![];
+
+        for received in [1, 3, 2] {
+            for ptr in buffer.push(received, block(received)) {
+                assert_eq!(
+                    inspector.check_block(ptr.clone()),
+                    BlockInspectionResult::OkToProceed
+                );
+                processed.push(ptr.number);
+            }
+        }
+
+        assert_eq!(processed, vec![1, 2, 3]);
+        assert_eq!(buffer.pending.len(), 0);
+
+        // Blocks already released are passed through as-is
+        assert_eq!(buffer.push(2, block(2)), vec![block(2)]);
+    }
+
+    #[test]
+    fn test_reorder_buffer_window_exceeded() {
+        env_logger::try_init().unwrap_or_default();
+        let mut buffer = ReorderBuffer::new(2, 1);
+
+        assert!(buffer.push(3, 3).is_empty());
+        assert!(buffer.push(4, 4).is_empty());
+        assert_eq!(buffer.pending.len(), 2);
+
+        // Block 2 is still missing once the window is full
+        assert_eq!(buffer.push(5, 5), vec![3, 4, 5]);
+        assert_eq!(buffer.pending.len(), 0);
+        assert_eq!(buffer.push(6, 6), vec![6]);
+    }
+}
@@ -2,7 +2,9 @@ use crate::common::Datasource;
 use crate::common::DatasourceBundle;
 use crate::common::HandlerTypes;
 use crate::components::ManifestAgent;
+use crate::config::MissingHandlerPolicy;
 use crate::database::DatabaseAgent;
+use crate::error;
 use crate::errors::SubgraphError;
 use crate::rpc_client::RpcAgent;
 use crate::runtime::asc::base::asc_new;
@@ -10,6 +12,7 @@ use crate::runtime::asc::base::AscIndexId;
 use crate::runtime::asc::base::AscType;
 use crate::runtime::asc::base::ToAscObj;
 use crate::runtime::wasm_host::AscHost;
+use crate::warn;
 use std::collections::HashMap;
 use wasmer::Exports;
 use wasmer::Function;
@@ -42,49 +45,109 @@ pub struct DatasourceWasmInstance {
     pub name: String,
     // NOTE: Add more chain-based handler here....
     pub ethereum_handlers: EthereumHandlers,
+    /// Handlers declared in the manifest but not exported by the wasm module
+    pub missing_handlers: Vec<String>,
     host: AscHost,
 }
 
-impl TryFrom<(&AscHost, &Datasource)> for EthereumHandlers {
-    type Error = SubgraphError;
-    fn try_from((host, ds): (&AscHost, &Datasource)) -> Result<Self, SubgraphError> {
+impl EthereumHandlers {
+    /// Bind declared handlers to the wasm exports,
+    /// also returning the names of handlers that cannot be found
+    pub fn new(exports: &Exports, ds: &Datasource) -> (Self, Vec<String>) {
         let mut eth_event_handlers = HashMap::new();
         let mut eth_block_handlers = HashMap::new();
+        let mut missing_handlers = vec![];
 
         for event_handler in ds.mapping.eventHandlers.clone().unwrap_or_default().iter() {
             // FIXME: assuming handlers are ethereum-event handler, must fix later
-            let handler = Handler::new(&host.instance.exports, &event_handler.handler)?;
-            eth_event_handlers.insert(event_handler.handler.to_owned(), handler);
+            match Handler::new(exports, &event_handler.handler) {
+                Ok(handler) => {
+                    eth_event_handlers.insert(event_handler.handler.to_owned(), handler);
+                }
+                Err(_) => missing_handlers.push(event_handler.handler.to_owned()),
+            }
         }
 
         for block_handler in ds.mapping.blockHandlers.clone().unwrap_or_default().iter() {
             // FIXME: assuming handlers are ethereum-block handler, must fix later
-            let handler = Handler::new(&host.instance.exports, &block_handler.handler)?;
-            eth_block_handlers.insert(block_handler.handler.to_owned(), handler);
+            match Handler::new(exports, &block_handler.handler) {
+                Ok(handler) => {
+                    eth_block_handlers.insert(block_handler.handler.to_owned(), handler);
+                }
+                Err(_) => missing_handlers.push(block_handler.handler.to_owned()),
+            }
         }
 
-        Ok(EthereumHandlers {
+        let handlers = EthereumHandlers {
             block: eth_block_handlers,
             events: eth_event_handlers,
-        })
+        };
+        (handlers, missing_handlers)
     }
 }
 
-impl TryFrom<(DatasourceBundle, DatabaseAgent, RpcAgent, ManifestAgent)>
-    for DatasourceWasmInstance
+/// Apply the missing-handler policy to the list of `datasource.handler` names
+pub fn check_missing_handlers(
+    policy: &MissingHandlerPolicy,
+    missing_handlers: Vec<String>,
+) -> Result<(), SubgraphError> {
+    if missing_handlers.is_empty() {
+        return Ok(());
+    }
+
+    let missing_handlers = missing_handlers.join(", ");
+
+    match policy {
+        MissingHandlerPolicy::Abort => {
+            error!(
+                DatasourceWasmInstance,
+                "handlers declared in manifest are missing from wasm exports";
+                missing_handlers => missing_handlers
+            );
+            Err(SubgraphError::MissingHandlers(missing_handlers))
+        }
+        MissingHandlerPolicy::Warn => {
+            warn!(
+                DatasourceWasmInstance,
+                "handlers declared in manifest are missing from wasm exports, their triggers will be skipped";
+                missing_handlers => missing_handlers
+            );
+            Ok(())
+        }
+    }
+}
+
+impl
+    TryFrom<(
+        DatasourceBundle,
+        DatabaseAgent,
+        RpcAgent,
+        ManifestAgent,
+        u32,
+        Option<u64>,
+    )> for DatasourceWasmInstance
 {
     type Error = SubgraphError;
     fn try_from(
-        value: (DatasourceBundle, DatabaseAgent, RpcAgent, ManifestAgent),
+        value: (
+            DatasourceBundle,
+            DatabaseAgent,
+            RpcAgent,
+            ManifestAgent,
+            u32,
+            Option<u64>,
+        ),
     ) -> Result<Self, Self::Error> {
         let host = AscHost::try_from(value.clone())
             .map_err(|e| SubgraphError::CreateSourceFail(e.to_string()))?;
-        let ethereum_handlers = EthereumHandlers::try_from((&host, &value.0.ds))?;
+        let (ethereum_handlers, missing_handlers) =
+            EthereumHandlers::new(&host.instance.exports, &value.0.ds);
         let name = value.0.name();
         Ok(Self {
             host,
             name,
             ethereum_handlers,
+            missing_handlers,
         })
     }
 }
@@ -98,6 +161,16 @@ impl DatasourceWasmInstance {
         handler_name: &str,
         data: impl ToAscObj<T>,
     ) -> Result<(), SubgraphError> {
+        if self.missing_handlers.iter().any(|h| h == handler_name) {
+            warn!(
+                DatasourceWasmInstance,
+                "handler missing from wasm exports, skip";
+                datasource => self.name,
+                handler => handler_name
+            );
+            return Ok(());
+        }
+
         let handler = match handler_type {
             HandlerTypes::EthereumBlock => self.ethereum_handlers.block.get(handler_name),
             HandlerTypes::EthereumEvent => self.ethereum_handlers.events.get(handler_name),
@@ -105,10 +178,30 @@ impl DatasourceWasmInstance {
         .ok_or(SubgraphError::InvalidHandlerName(handler_name.to_owned()))?;
 
         let asc_data = asc_new(&mut self.host, &data)?;
-        handler.inner.call(
+        self.host.gas.reset();
+        let result = handler.inner.call(
             &mut self.host.store,
             &[Value::I32(asc_data.wasm_ptr() as i32)],
-        )?;
+        );
+
+        if let Err(error) = result {
+            return match self.host.gas.limit() {
+                Some(limit) if self.host.gas.exceeded() => {
+                    error!(
+                        DatasourceWasmInstance,
+                        "handler aborted, gas limit exceeded";
+                        datasource => self.name,
+                        handler => handler_name,
+                        gas_limit => limit
+                    );
+                    Err(SubgraphError::GasLimitExceeded(
+                        handler_name.to_owned(),
+                        limit,
+                    ))
+                }
+                _ => Err(error.into()),
+            };
+        }
 
         Ok(())
     }
@@ -117,3 +210,120 @@ impl DatasourceWasmInstance {
         (self.host.current_ptr() as f32) > Self::MAXIMUM_HEAP_SIZE
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::runtime::asc::base::DEFAULT_MAX_ASC_LENGTH;
+    use crate::runtime::asc::native_types::string::AscString;
+    use wasmer::imports;
+    use wasmer::Instance;
+    use wasmer::Module;
+    use wasmer::Store;
+
+    fn mock_datasource() -> Datasource {
+        serde_yaml::from_str(
+            r#"
+kind: ethereum/contract
+name: Pool
+network: mainnet
+source:
+  address: "0x0000000000000000000000000000000000000001"
+  abi: Pool
+  startBlock: 1
+mapping:
+  kind: ethereum/events
+  apiVersion: 0.0.5
+  entities: []
+  abis: []
+  eventHandlers:
+    - event: Swap(indexed address,uint256)
+      handler: handleSwap
+    - event: Mint(indexed address,uint256)
+      handler: handleMint
+  blockHandlers:
+    - handler: handleBlock
+  file: ./mapping.ts
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_missing_handlers() {
+        env_logger::try_init().unwrap_or_default();
+        let mut store = Store::default();
+        let module = Module::new(&store, r#"(module (func (export "handleSwap")))"#).unwrap();
+        let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+
+        let (handlers, missing) = EthereumHandlers::new(&instance.exports, &mock_datasource());
+        assert!(handlers.events.contains_key("handleSwap"));
+        assert!(handlers.block.is_empty());
+        assert_eq!(missing, vec!["handleMint", "handleBlock"]);
+
+        let missing = missing
+            .into_iter()
+            .map(|h| format!("Pool.{h}"))
+            .collect::<Vec<_>>();
+
+        let result = check_missing_handlers(&MissingHandlerPolicy::Abort, missing.clone());
+        assert!(
+            matches!(result, Err(SubgraphError::MissingHandlers(list)) if list == "Pool.handleMint, Pool.handleBlock")
+        );
+
+        assert!(check_missing_handlers(&MissingHandlerPolicy::Warn, missing).is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_invoke_gas_limit_exceeded() {
+        env_logger::try_init().unwrap_or_default();
+        let registry = prometheus::default_registry();
+        let mut ds = mock_datasource();
+        ds.mapping.apiVersion = semver::Version::new(0, 0, 4);
+        // `handleSwap` calls store.get until aborted, `handleMint` traps on its own
+        let wat = r#"
+            (module
+                (import "index" "store.get" (func $get (param i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (global $next (mut i32) (i32.const 1024))
+                (func (export "memory.allocate") (param $size i32) (result i32)
+                    (global.get $next)
+                    (global.set $next (i32.add (global.get $next) (local.get $size))))
+                (func (export "handleSwap") (param $event i32)
+                    (loop $calls
+                        (drop (call $get (local.get $event) (local.get $event)))
+                        (br $calls)))
+                (func (export "handleMint") (param i32) unreachable)
+                (func (export "handleBlock") (param i32)))
+        "#;
+        let bundle = DatasourceBundle {
+            ds,
+            abi: serde_json::json!([]),
+            wasm: wat.as_bytes().to_vec(),
+        };
+        let mut instance = DatasourceWasmInstance::try_from((
+            bundle,
+            DatabaseAgent::empty(registry),
+            RpcAgent::new_mock(registry),
+            ManifestAgent::default(),
+            DEFAULT_MAX_ASC_LENGTH,
+            Some(0),
+        ))
+        .unwrap();
+
+        let result =
+            instance.invoke::<AscString>(HandlerTypes::EthereumEvent, "handleSwap", "swap");
+        assert!(matches!(
+            result,
+            Err(SubgraphError::GasLimitExceeded(handler, 0)) if handler == "handleSwap"
+        ));
+
+        // Other traps are not taken for the gas running out, the next handler gets a full tank
+        let result =
+            instance.invoke::<AscString>(HandlerTypes::EthereumEvent, "handleMint", "mint");
+        assert!(matches!(result, Err(e) if !matches!(e, SubgraphError::GasLimitExceeded(..))));
+        let result =
+            instance.invoke::<AscString>(HandlerTypes::EthereumBlock, "handleBlock", "block");
+        assert!(result.is_ok());
+    }
+}
@@ -11,6 +11,7 @@ pub struct SubgraphMetrics {
     pub eth_trigger_counter: IntCounter,
     pub block_process_duration: Histogram,
     pub current_block_number: IntGauge,
+    pub block_processed_counter: IntCounter,
     pub datasource_creation_counter: IntCounter,
     pub datasource_creation_duration: Histogram,
 }
@@ -42,6 +43,12 @@ impl SubgraphMetrics {
             .register(Box::new(current_block_number.clone()))
             .unwrap_or_default();
 
+        let block_processed_counter =
+            IntCounter::new("block_processed_counter", "count processed blocks").unwrap();
+        registry
+            .register(Box::new(block_processed_counter.clone()))
+            .unwrap_or_default();
+
         let datasource_creation_counter = IntCounter::new(
             "datasource_creation_counter",
             "count number of datasource re-creations",
@@ -65,6 +72,7 @@ impl SubgraphMetrics {
             eth_event_process_duration,
             eth_trigger_counter,
             current_block_number,
+            block_processed_counter,
             datasource_creation_counter,
             datasource_creation_duration,
         }
@@ -6,14 +6,20 @@ use crate::chain::ethereum::block::EthereumBlockData;
 use crate::common::EthereumFilteredEvent;
 use crate::common::FilteredDataMessage;
 use crate::common::HandlerTypes;
+use crate::common::StoreOperationMessage;
+use crate::config::Config;
+use crate::config::MissingHandlerPolicy;
 use crate::database::DatabaseAgent;
 use crate::errors::SubgraphError;
 use crate::info;
 use crate::rpc_client::RpcAgent;
+use crate::runtime::asc::base::DEFAULT_MAX_ASC_LENGTH;
+use datasource_wasm_instance::check_missing_handlers;
 use datasource_wasm_instance::DatasourceWasmInstance;
 use metrics::SubgraphMetrics;
 use prometheus::Registry;
 use std::collections::HashMap;
+use std::collections::HashSet;
 
 pub struct Subgraph {
     sources: HashMap<(String, Option<String>), DatasourceWasmInstance>,
@@ -21,10 +27,16 @@ pub struct Subgraph {
     rpc: RpcAgent,
     db: DatabaseAgent,
     manifest: ManifestAgent,
+    missing_handler_policy: MissingHandlerPolicy,
+    max_asc_length: u32,
+    handler_gas_limit: Option<u64>,
+    /// Datasources whose start-block is not reached yet, templates are never in there
+    inactive_sources: HashSet<String>,
 }
 
 impl Subgraph {
     pub fn new(
+        config: &Config,
         db: &DatabaseAgent,
         rpc: &RpcAgent,
         manifest: &ManifestAgent,
@@ -36,17 +48,33 @@ impl Subgraph {
             rpc: rpc.clone(),
             db: db.clone(),
             manifest: manifest.clone(),
+            missing_handler_policy: config.missing_handler_policy.clone().unwrap_or_default(),
+            max_asc_length: config.max_asc_length.unwrap_or(DEFAULT_MAX_ASC_LENGTH),
+            handler_gas_limit: config.handler_gas_limit,
+            inactive_sources: HashSet::new(),
         }
     }
 
+    /// Only handle the data of these datasources, by index of the manifest's datasources
+    pub fn set_active_sources(&mut self, active_sources: &[usize]) {
+        self.inactive_sources = self
+            .manifest
+            .datasource_names()
+            .into_iter()
+            .enumerate()
+            .filter(|(idx, _)| !active_sources.contains(idx))
+            .map(|(_, name)| name)
+            .collect();
+    }
+
     pub fn should_process(&self, data: &FilteredDataMessage) -> bool {
         match data {
             FilteredDataMessage::Ethereum { events, .. } => {
                 return !events.is_empty()
-                    || self
-                        .sources
-                        .values()
-                        .any(|ds| !ds.ethereum_handlers.block.is_empty());
+                    || self.sources.values().any(|ds| {
+                        !ds.ethereum_handlers.block.is_empty()
+                            && !self.inactive_sources.contains(&ds.name)
+                    });
             }
         }
     }
@@ -72,10 +100,24 @@ impl Subgraph {
                         self.db.clone(),
                         self.rpc.clone(),
                         self.manifest.clone(),
+                        self.max_asc_length,
+                        self.handler_gas_limit,
                     ))?,
                 );
             }
             self.metrics.datasource_creation_counter.inc();
+
+            let missing_handlers = self
+                .sources
+                .values()
+                .flat_map(|source| {
+                    source
+                        .missing_handlers
+                        .iter()
+                        .map(|handler| format!("{}.{handler}", source.name))
+                })
+                .collect::<Vec<_>>();
+            check_missing_handlers(&self.missing_handler_policy, missing_handlers)?;
         }
 
         Ok(())
@@ -89,6 +131,10 @@ impl Subgraph {
         let mut block_handlers = HashMap::new();
 
         for ((source_name, _), source_instance) in self.sources.iter() {
+            if self.inactive_sources.contains(source_name) {
+                continue;
+            }
+
             let source_block_handlers = source_instance
                 .ethereum_handlers
                 .block
@@ -117,6 +163,10 @@ impl Subgraph {
             let handler_name = event.handler.clone();
             let event_address = format!("{:?}", event.event.address).to_lowercase();
 
+            if self.inactive_sources.contains(&ds_name) {
+                continue;
+            }
+
             if let Some(source) = self
                 .sources
                 .get_mut(&(ds_name.clone(), Some(event_address)))
@@ -163,6 +213,24 @@ impl Subgraph {
         Ok(())
     }
 
+    /// Run all handlers for a single block against the current state,
+    /// returning the store write-operations without applying them
+    pub fn replay_block(
+        &mut self,
+        msg: FilteredDataMessage,
+    ) -> Result<Vec<StoreOperationMessage>, SubgraphError> {
+        info!(Subgraph, "replaying block"; block => msg.get_block_ptr());
+        self.db.start_replay();
+        let result = match msg {
+            FilteredDataMessage::Ethereum { events, block } => {
+                self.handle_ethereum_data(events, block)
+            }
+        };
+        let operations = self.db.finish_replay();
+        result?;
+        Ok(operations)
+    }
+
     pub fn process(&mut self, msg: FilteredDataMessage) -> Result<(), SubgraphError> {
         let block_ptr = msg.get_block_ptr();
 
@@ -177,6 +245,7 @@ impl Subgraph {
             }
         };
         timer.stop_and_record();
+        self.metrics.block_processed_counter.inc();
 
         Ok(())
     }
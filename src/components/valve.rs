@@ -3,12 +3,16 @@ use crate::info;
 use prometheus::IntGauge;
 use prometheus::Registry;
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::rc::Rc;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::Semaphore;
 
 struct ValveMetrics {
     block_downloaded_counter: IntGauge,
     block_finished_counter: IntGauge,
+    source_lag: IntGauge,
 }
 
 impl ValveMetrics {
@@ -25,9 +29,19 @@ impl ValveMetrics {
             .register(Box::new(block_finished_counter.clone()))
             .unwrap_or_default();
 
+        let source_lag = IntGauge::new(
+            "source_lag",
+            "number of blocks the source is ahead of the processed head",
+        )
+        .unwrap();
+        registry
+            .register(Box::new(source_lag.clone()))
+            .unwrap_or_default();
+
         Self {
             block_finished_counter,
             block_downloaded_counter,
+            source_lag,
         }
     }
 }
@@ -35,8 +49,13 @@ impl ValveMetrics {
 pub struct InnerValve {
     finished: u64,
     downloaded: u64,
+    /// Latest block the source has available, as reported by sources knowing it
+    source_head: u64,
     cfg: ValveConfig,
     metrics: ValveMetrics,
+    inflight: Option<Arc<Semaphore>>,
+    /// Cursors of the blocks sent by sources that have one, by block number
+    cursors: BTreeMap<u64, String>,
 }
 
 #[derive(Clone)]
@@ -47,12 +66,30 @@ impl Valve {
         let this = InnerValve {
             finished: 0,
             downloaded: 0,
+            source_head: 0,
             cfg: cfg.to_owned(),
             metrics: ValveMetrics::new(registry),
+            inflight: cfg
+                .max_inflight_blocks
+                .map(|max| Arc::new(Semaphore::new(max))),
+            cursors: BTreeMap::new(),
         };
         Valve(Rc::new(RefCell::new(this)))
     }
 
+    /// Sources not reporting their head are taken to be at the last block they sent
+    fn update_source_lag(this: &InnerValve) {
+        let head = this.source_head.max(this.downloaded);
+        let lag = head.saturating_sub(this.finished);
+        this.metrics.source_lag.set(lag as i64);
+    }
+
+    pub fn set_source_head(&self, block_number: u64) {
+        let mut this = self.0.borrow_mut();
+        this.source_head = this.source_head.max(block_number);
+        Self::update_source_lag(&this);
+    }
+
     pub async fn temporarily_close(&self) {
         loop {
             let this = self.0.borrow();
@@ -69,6 +106,28 @@ impl Valve {
         }
     }
 
+    /// Wait until `count` more blocks can be in flight,
+    /// a batch larger than the limit takes every permit instead of waiting forever
+    pub async fn acquire_inflight(&self, count: usize) {
+        let this = self.0.borrow();
+        let (semaphore, max) = match (&this.inflight, this.cfg.max_inflight_blocks) {
+            (Some(semaphore), Some(max)) => (semaphore.clone(), max),
+            _ => return,
+        };
+        drop(this);
+
+        let permits = count.min(max) as u32;
+        semaphore.acquire_many(permits).await.unwrap().forget();
+    }
+
+    /// Give back the permits of `count` blocks once they are committed
+    pub fn release_inflight(&self, count: usize) {
+        let this = self.0.borrow();
+        if let (Some(semaphore), Some(max)) = (&this.inflight, this.cfg.max_inflight_blocks) {
+            semaphore.add_permits(count.min(max));
+        }
+    }
+
     pub fn set_finished(&self, finished_block: u64) {
         if finished_block % 1000 == 0 {
             info!(Valve, format!("finished block #{finished_block}"));
@@ -79,6 +138,18 @@ impl Valve {
         this.metrics
             .block_finished_counter
             .set(finished_block as i64);
+        Self::update_source_lag(&this);
+    }
+
+    pub fn set_cursor(&self, block_number: u64, cursor: String) {
+        self.0.borrow_mut().cursors.insert(block_number, cursor);
+    }
+
+    /// The cursor of a processed block, those of older blocks are dropped along
+    pub fn take_cursor(&self, block_number: u64) -> Option<String> {
+        let mut this = self.0.borrow_mut();
+        let newer = this.cursors.split_off(&(block_number + 1));
+        std::mem::replace(&mut this.cursors, newer).remove(&block_number)
     }
 
     pub fn set_downloaded(&self, block_number: u64) {
@@ -88,5 +159,84 @@ impl Valve {
         this.metrics
             .block_downloaded_counter
             .set(block_number as i64);
+        Self::update_source_lag(&this);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_source_lag() {
+        let cfg = ValveConfig {
+            allowed_lag: 1000,
+            wait_time: 0,
+            max_inflight_blocks: None,
+        };
+        let valve = Valve::new(&cfg, &Registry::new());
+
+        // The head is ahead of the blocks downloaded so far
+        valve.set_source_head(100);
+        valve.set_downloaded(95);
+        valve.set_finished(90);
+        assert_eq!(valve.0.borrow().metrics.source_lag.get(), 10);
+
+        valve.set_finished(95);
+        assert_eq!(valve.0.borrow().metrics.source_lag.get(), 5);
+
+        // Without a newer head reported, the last block downloaded stands for it
+        valve.set_downloaded(120);
+        valve.set_finished(110);
+        assert_eq!(valve.0.borrow().metrics.source_lag.get(), 10);
+    }
+
+    #[test]
+    fn test_cursors() {
+        let cfg = ValveConfig {
+            allowed_lag: 1000,
+            wait_time: 0,
+            max_inflight_blocks: None,
+        };
+        let valve = Valve::new(&cfg, &Registry::new());
+        for number in 1..=5 {
+            valve.set_cursor(number, format!("cursor-{number}"));
+        }
+
+        assert_eq!(valve.take_cursor(3), Some("cursor-3".to_string()));
+        assert_eq!(valve.take_cursor(2), None);
+        assert_eq!(valve.take_cursor(5), Some("cursor-5".to_string()));
+        assert!(valve.0.borrow().cursors.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_max_inflight_blocks() {
+        let cfg = ValveConfig {
+            allowed_lag: 1000,
+            wait_time: 0,
+            max_inflight_blocks: Some(2),
+        };
+        let valve = Valve::new(&cfg, &Registry::new());
+        let wait = Duration::from_millis(100);
+
+        valve.acquire_inflight(1).await;
+        valve.acquire_inflight(1).await;
+
+        // Two blocks are still in flight: the source is throttled
+        let throttled = tokio::time::timeout(wait, valve.acquire_inflight(1)).await;
+        assert!(throttled.is_err());
+
+        // One block got committed: the source can send one more
+        valve.release_inflight(1);
+        let resumed = tokio::time::timeout(wait, valve.acquire_inflight(1)).await;
+        assert!(resumed.is_ok());
+
+        // A batch larger than the limit waits for every in-flight block to commit
+        valve.release_inflight(1);
+        let throttled = tokio::time::timeout(wait, valve.acquire_inflight(5)).await;
+        assert!(throttled.is_err());
+        valve.release_inflight(1);
+        let resumed = tokio::time::timeout(wait, valve.acquire_inflight(5)).await;
+        assert!(resumed.is_ok());
     }
 }
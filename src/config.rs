@@ -4,6 +4,7 @@ use figment::providers::Format;
 use figment::providers::Toml;
 use figment::Figment;
 use serde::Deserialize;
+use std::collections::HashMap;
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct DeltaConfig {
@@ -12,19 +13,245 @@ pub struct DeltaConfig {
     pub version: Option<u64>,
 }
 
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentType {
+    Json,
+    Protobuf,
+}
+
+/// What a finite source does once it has no more block to read
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceExhaustedPolicy {
+    /// Close the block stream so the runtime flushes the last batch & exits
+    #[default]
+    Exit,
+    /// Keep the block stream open and the process alive
+    Wait,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ReadDirConfig {
+    pub source_dir: String,
+    /// Decode every file with this content-type,
+    /// otherwise the content-type is inferred from each file's extension
+    pub content_type: Option<ContentType>,
+    pub on_exhausted: Option<SourceExhaustedPolicy>,
+}
+
+/// gRPC endpoint serving `sf.firehose.v2.Stream/Blocks`
+#[derive(Clone, Debug, Deserialize)]
+pub struct FirehoseConfig {
+    pub endpoint: String,
+    /// Resume from this cursor instead of the next block to process
+    pub start_cursor: Option<String>,
+}
+
 #[derive(Deserialize, Clone, Debug)]
 #[serde(rename_all = "lowercase")]
 pub enum SourceTypes {
     Delta(DeltaConfig),
+    ReadDir(ReadDirConfig),
+    Firehose(FirehoseConfig),
+    /// Sources drained one after another, every one but the last must end,
+    /// eg a `readdir` archive with `on_exhausted = "exit"` followed by a live source
+    Chained(Vec<SourceTypes>),
+}
+
+#[cfg(feature = "scylla")]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScyllaConsistency {
+    One,
+    LocalOne,
+    Quorum,
+    LocalQuorum,
+    All,
+}
+
+#[cfg(feature = "scylla")]
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CompactionWindowUnit {
+    Minutes,
+    Hours,
+    Days,
+}
+
+/// Compaction strategy of the entity-tables, eg `{ class = "time_window", window_unit = "days", window_size = 1 }`
+#[cfg(feature = "scylla")]
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "class", rename_all = "snake_case")]
+pub enum ScyllaCompaction {
+    SizeTiered,
+    Leveled,
+    /// Suits the append-only snapshots, defaults to 1-day windows
+    TimeWindow {
+        window_unit: Option<CompactionWindowUnit>,
+        window_size: Option<u32>,
+    },
+}
+
+#[cfg(feature = "scylla")]
+#[derive(Clone, Debug, Deserialize)]
+pub struct ScyllaConfig {
+    pub uri: String,
+    pub keyspace: String,
+    /// Group batched inserts so that every batch only touches a single entity-table
+    pub batch_per_entity_type: Option<bool>,
+    /// Timeout in milliseconds applied to every statement (reads, writes & batches)
+    pub db_query_timeout: Option<u64>,
+    /// Prefix every table with the subgraph name, so multiple subgraphs can share one keyspace
+    pub prefix_tables: Option<bool>,
+    /// Contact point for reads, if set (or if `read_consistency` is set) reads
+    /// go through a separate session, otherwise reads & writes share one session
+    pub read_uri: Option<String>,
+    pub read_consistency: Option<ScyllaConsistency>,
+    pub write_consistency: Option<ScyllaConsistency>,
+    /// Create the keyspace & tables at startup (default), when disabled the schema is
+    /// expected to be provisioned externally and the tables are only checked to exist
+    pub bootstrap_schema: Option<bool>,
+    /// Hex-encoded 32-byte AES-256-GCM key, required if the schema flags any field `@encrypted`
+    pub encryption_key: Option<String>,
+    /// Applied when the entity-tables are created, Scylla's default (size-tiered) if not set
+    pub compaction: Option<ScyllaCompaction>,
+    /// Prefer the nodes of this datacenter for reads & writes (DC-aware round-robin),
+    /// for multi-DC clusters. Every node is used alike if not set
+    pub local_datacenter: Option<String>,
+    /// Max number of statements per batched insert, 100 if not set
+    pub batch_size: Option<usize>,
+    /// Max number of batched inserts sent at once when committing a block,
+    /// also bounds the concurrent queries of a chunked entity load. 16 if not set
+    pub max_inflight_batches: Option<usize>,
+    /// Max number of ids per `IN` query when loading entities, 100 if not set
+    pub load_chunk_size: Option<usize>,
+    /// Allow `load_entities_by_time_range`, disabled by default. The block-timestamp column
+    /// then gets a secondary index, adding a write to every snapshot, and each query filters
+    /// the whole entity-table across all nodes (`ALLOW FILTERING`): its cost grows with the table
+    pub time_range_queries: Option<bool>,
 }
 
 #[derive(Deserialize, Clone, Debug)]
 #[serde(rename_all = "lowercase")]
 pub enum DatabaseConfig {
     #[cfg(feature = "scylla")]
-    Scylla { uri: String, keyspace: String },
+    Scylla(ScyllaConfig),
     #[cfg(feature = "mongo")]
     Mongo { uri: String, database: String },
+    #[cfg(feature = "postgres")]
+    Postgres { uri: String },
+    /// Write to every backend, eg to dual-write when migrating between stores.
+    /// Failed writes to backends other than the first one (the primary) are only logged
+    Multi {
+        backends: Vec<DatabaseConfig>,
+        /// Positions of the backends to read from, each one consulted in turn when the previous
+        /// one misses or fails (eg old data living only in the old store). Only the primary if not set
+        read_order: Option<Vec<usize>>,
+    },
+}
+
+/// What to do when a handler declared in the manifest is not exported by the datasource's wasm
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MissingHandlerPolicy {
+    /// Fail at startup, listing every missing handler
+    #[default]
+    Abort,
+    /// Log a warning and skip the events dispatched to missing handlers
+    Warn,
+}
+
+/// What to do when `reorg_threshold` exceeds `max_reorg_threshold`
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReorgThresholdPolicy {
+    /// Log a warning and keep the configured threshold
+    #[default]
+    Warn,
+    /// Refuse to start
+    Abort,
+}
+
+/// What to do when an entity is created more than once within the same block
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateCreatePolicy {
+    /// The last write wins
+    #[default]
+    Overwrite,
+    /// Reject duplicate creates of immutable entities, mutable ones are still overwritten
+    Strict,
+}
+
+/// How entities written by handlers are checked against the graphql schema
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SchemaValidationPolicy {
+    /// No validation
+    #[default]
+    Off,
+    /// Log a warning for every invalid entity, but still store it
+    Warn,
+    /// Reject invalid entities, failing the handler
+    Strict,
+}
+
+/// Case applied to entity ids on every read & write, so mixed-case addresses resolve the same entity
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntityIdCase {
+    Lower,
+    Upper,
+}
+
+/// What an optional side channel (eg the change feed) does when its consumer falls behind
+#[derive(Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Wait for the consumer, stalling indexing
+    #[default]
+    Block,
+    /// Drop the oldest pending message to make room
+    DropOldest,
+    /// Stop sending to the channel for the rest of the run
+    DisableOnOverflow,
+}
+
+/// Gateway used to fetch subgraphs deployed to IPFS, when `subgraph_dir` is `ipfs://<cid>`
+#[derive(Deserialize, Clone, Debug)]
+pub struct IpfsConfig {
+    pub gateway: String,
+    /// Seconds before a gateway request is given up, defaults to 30
+    pub timeout: Option<u64>,
+    /// Where fetched files are cached by CID, defaults to `.ipfs-cache`
+    pub cache_dir: Option<String>,
+}
+
+/// Committed entity changes are appended to `output` as json lines,
+/// or logged at debug level when no output file is set
+#[derive(Deserialize, Clone, Debug)]
+pub struct ChangeFeedConfig {
+    pub capacity: usize,
+    pub policy: Option<OverflowPolicy>,
+    pub output: Option<String>,
+}
+
+/// Relation list of an entity, eg `Token.holders` of token `0x...`
+#[derive(Deserialize, Clone, Debug)]
+pub struct RelationConfig {
+    pub entity_type: String,
+    pub entity_id: String,
+    pub field_name: String,
+}
+
+/// Revert to `from_block` then re-index from the source in place, the runtime keeps indexing past `to_block`.
+/// Everything from `from_block` is reverted, so the blocks after the range are indexed again too.
+/// The range is stored once reverted, restarting with it still configured doesn't revert it again
+#[derive(Deserialize, Clone, Debug)]
+pub struct ReprocessConfig {
+    pub from_block: u64,
+    pub to_block: u64,
 }
 
 #[derive(Deserialize, Default, Clone, Debug)]
@@ -32,20 +259,98 @@ pub enum DatabaseConfig {
 pub struct ValveConfig {
     pub allowed_lag: u64,
     pub wait_time: u64,
+    /// Max number of blocks sent by the source but not yet committed,
+    /// bounds the pipeline's peak memory during bursts
+    pub max_inflight_blocks: Option<usize>,
 }
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct Config {
     pub chain: Chain,
     pub source: SourceTypes,
+    /// Sources of some datasources by name, eg a contract indexed from another provider.
+    /// Their blocks are merged by number with those of `source`, used by the other datasources
+    pub datasource_sources: Option<HashMap<String, SourceTypes>>,
+    /// Only dispatch these events of some datasources by name, given as event signatures
+    /// like in the manifest (eg `Transfer(indexed address,indexed address,uint256)`) or topic0
+    pub event_allowlist: Option<HashMap<String, Vec<String>>>,
     pub subgraph_name: String,
+    /// Local build dir of the subgraph, or `ipfs://<cid>` of its deployed manifest
     pub subgraph_dir: String,
+    pub ipfs: Option<IpfsConfig>,
+    /// File keeping the schema resolved on the last run,
+    /// entity types changed since are reported at startup
+    pub schema_catalog: Option<String>,
     pub database: DatabaseConfig,
     pub reorg_threshold: u16,
+    /// Sanity cap on `reorg_threshold` to catch misconfiguration, as that many recent
+    /// block pointers are kept in memory. Defaults to 1000
+    pub max_reorg_threshold: Option<u16>,
+    pub reorg_threshold_policy: Option<ReorgThresholdPolicy>,
+    /// Halt instead of reverting when a fork-block would revert more than this many blocks,
+    /// such a reorg needs looking into. Only reorgs within `reorg_threshold` are detected
+    pub max_reorg_depth: Option<u64>,
+    /// On startup, check the stored head block against the one the rpc reports at
+    /// the same number, and revert the blocks the source no longer agrees with
+    pub verify_head: Option<bool>,
+    /// Only write blocks at least this many blocks behind the head to the database,
+    /// newer ones are held in memory and lost on restart (then indexed again). Disabled by default
+    pub commit_depth: Option<u64>,
+    /// Remove every snapshot of a deleted entity once its deletion is `reorg_threshold` blocks old,
+    /// reclaiming storage. Deletes within the reorg window stay soft so they can still be reverted
+    pub hard_delete: Option<bool>,
     pub metric_port: Option<u16>,
     pub rpc_endpoint: String,
     pub valve: ValveConfig,
     pub block_data_retention: Option<u64>,
+    /// Up to (and including) this block, handlers still run but entities are only
+    /// kept in memory, then the final state is written once as a single snapshot
+    /// at this block. Time-travel queries for any block before it are not possible,
+    /// and a restart before reaching it replays the whole backfill from the start-block.
+    /// All touched entities stay in memory until the cutover, so keep the range reasonable.
+    pub fast_forward_to_block: Option<u64>,
+    /// If set, only these entity types are stored, `store.set` for others is dropped
+    pub entities_allowlist: Option<Vec<String>>,
+    /// Entity types whose `store.set` is dropped
+    pub entities_denylist: Option<Vec<String>>,
+    /// Store the timestamp of the block being processed with every entity snapshot,
+    /// in an indexed `__block_timestamp__` column to allow time-range queries
+    pub block_timestamp_column: Option<bool>,
+    /// Store a checksum with every entity snapshot and verify it when loading
+    /// from database, costs an extra column & hashing on every write
+    pub entity_checksum: Option<bool>,
+    /// Normalize the ids of these entity types, eg `{ Token = "lower" }`
+    pub entity_id_case: Option<HashMap<String, EntityIdCase>>,
+    pub missing_handler_policy: Option<MissingHandlerPolicy>,
+    pub schema_validation: Option<SchemaValidationPolicy>,
+    pub duplicate_create: Option<DuplicateCreatePolicy>,
+    /// Hold blocks arriving ahead of the expected one (eg from partitioned sources)
+    /// until their predecessors arrive, up to this many blocks. Disabled by default
+    pub reorder_window: Option<usize>,
+    /// Re-run all handlers of this block against the current state then exit,
+    /// reporting the resulting store operations without committing anything
+    pub replay_block: Option<u64>,
+    pub reprocess: Option<ReprocessConfig>,
+    /// Largest string/array (in bytes) the runtime will read out of wasm memory at once,
+    /// guards against mappings forcing huge allocations, defaults to 256MB
+    pub max_asc_length: Option<u32>,
+    /// Most host operations (store, bignumber & conversion calls) a single handler may do,
+    /// the handler is aborted past it, eg a mapping stuck in a runaway loop. Unlimited by default
+    pub handler_gas_limit: Option<u64>,
+    /// Most entities a single `store.loadRelated` returns, the rest of the relation
+    /// list is dropped with a warning. Unlimited by default
+    pub max_related_entities: Option<usize>,
+    /// Log every entity of this relation list page by page then exit,
+    /// eg to check a relation that `max_related_entities` truncates
+    pub inspect_relation: Option<RelationConfig>,
+    /// Warn about blocks writing more entities than this, eg a mapping stuck in a runaway loop
+    pub max_entities_per_block: Option<usize>,
+    /// Number of entity types written concurrently when committing a block, defaults to 4
+    pub migration_concurrency: Option<usize>,
+    /// Write & report a block's entities so that referenced entity types come first,
+    /// following the schema's relations. Entity types are then written one at a time
+    pub ordered_writes: Option<bool>,
+    pub change_feed: Option<ChangeFeedConfig>,
 }
 
 impl Config {
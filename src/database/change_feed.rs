@@ -0,0 +1,178 @@
+use crate::common::EntityID;
+use crate::common::EntityType;
+use crate::common::RawEntity;
+use crate::config::ChangeFeedConfig;
+use crate::config::OverflowPolicy;
+use crate::debug;
+use crate::errors::DatabaseError;
+use crate::warn;
+use kanal::AsyncReceiver;
+use kanal::AsyncSender;
+use serde::Serialize;
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeOp {
+    Create,
+    Update,
+    Delete,
+}
+
+/// Final state of an entity written in a committed block
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntityChange {
+    pub block: u64,
+    pub entity_type: EntityType,
+    pub id: EntityID,
+    pub op: ChangeOp,
+    pub entity: RawEntity,
+}
+
+/// Bounded channel of committed entity changes, for downstream systems to consume
+pub struct ChangeFeed {
+    sender: AsyncSender<EntityChange>,
+    /// Kept to evict the oldest change when the feed is full
+    receiver: AsyncReceiver<EntityChange>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    /// Set once the feed overflowed under `DisableOnOverflow`
+    disabled: bool,
+}
+
+impl ChangeFeed {
+    pub fn new(config: &ChangeFeedConfig) -> Self {
+        let capacity = config.capacity.max(1);
+        let (sender, receiver) = kanal::bounded_async(capacity);
+        Self {
+            sender,
+            receiver,
+            capacity,
+            policy: config.policy.clone().unwrap_or_default(),
+            disabled: false,
+        }
+    }
+
+    pub fn subscribe(&self) -> AsyncReceiver<EntityChange> {
+        self.receiver.clone()
+    }
+
+    pub async fn send(&mut self, change: EntityChange) -> Result<(), DatabaseError> {
+        if self.disabled {
+            return Ok(());
+        }
+
+        if self.sender.len() >= self.capacity {
+            match self.policy {
+                OverflowPolicy::Block => (),
+                OverflowPolicy::DropOldest => {
+                    if let Ok(Some(dropped)) = self.receiver.try_recv() {
+                        warn!(
+                            ChangeFeed,
+                            "change feed is full, dropping oldest change";
+                            block => dropped.block,
+                            entity_type => dropped.entity_type,
+                            id => dropped.id
+                        );
+                    }
+                }
+                OverflowPolicy::DisableOnOverflow => {
+                    warn!(
+                        ChangeFeed,
+                        "change feed is full, disabling it";
+                        block => change.block,
+                        capacity => self.capacity
+                    );
+                    self.disabled = true;
+                    return Ok(());
+                }
+            }
+        }
+
+        self.sender.send(change).await?;
+        Ok(())
+    }
+}
+
+/// Hand every change to the feed's sink: the `output` file as json lines if set, the debug log otherwise
+pub async fn forward_changes(
+    changes: AsyncReceiver<EntityChange>,
+    output: Option<String>,
+) -> std::io::Result<()> {
+    let mut file = match output {
+        Some(path) => Some(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .await?,
+        ),
+        None => None,
+    };
+
+    while let Ok(change) = changes.recv().await {
+        let line = serde_json::to_string(&change).unwrap_or_default();
+        match file.as_mut() {
+            Some(file) => {
+                file.write_all(format!("{line}\n").as_bytes()).await?;
+                file.flush().await?;
+            }
+            None => {
+                debug!(ChangeFeed, "entity changed"; change => line);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::runtime::asc::native_types::store::Value;
+
+    #[tokio::test]
+    async fn test_forward_changes_to_file() {
+        let path = std::env::temp_dir().join(format!("change-feed-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut feed = ChangeFeed::new(&ChangeFeedConfig {
+            capacity: 10,
+            policy: None,
+            output: None,
+        });
+
+        for block in 1..=2 {
+            let mut entity = RawEntity::new();
+            entity.insert("id".to_string(), Value::String("token".to_string()));
+            feed.send(EntityChange {
+                block,
+                entity_type: "Token".to_string(),
+                id: "token".to_string(),
+                op: ChangeOp::Create,
+                entity,
+            })
+            .await
+            .unwrap();
+        }
+        let output = Some(path.to_string_lossy().to_string());
+        let sink = tokio::spawn(forward_changes(feed.subscribe(), output));
+
+        let mut lines = String::new();
+        for _ in 0..100 {
+            lines = std::fs::read_to_string(&path).unwrap_or_default();
+            if lines.lines().count() == 2 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        sink.abort();
+
+        let blocks = lines
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["block"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(blocks, [1, 2]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
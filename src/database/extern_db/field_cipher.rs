@@ -0,0 +1,92 @@
+use crate::errors::DatabaseError;
+use crate::runtime::asc::native_types::store::Value;
+use aes_gcm::aead::Aead;
+use aes_gcm::aead::AeadCore;
+use aes_gcm::aead::KeyInit;
+use aes_gcm::aead::OsRng;
+use aes_gcm::Aes256Gcm;
+use aes_gcm::Nonce;
+
+const NONCE_SIZE: usize = 12;
+
+/// Encrypts values of fields flagged `@encrypted` with AES-256-GCM.
+/// A stored value is the random nonce followed by the ciphertext of the json-encoded value
+pub struct FieldCipher {
+    cipher: Aes256Gcm,
+}
+
+impl FieldCipher {
+    /// `key` is 32 bytes, hex-encoded
+    pub fn new(key: &str) -> Result<Self, DatabaseError> {
+        let key = hex::decode(key.trim_start_matches("0x"))
+            .map_err(|_| DatabaseError::InvalidEncryptionKey)?;
+        let cipher =
+            Aes256Gcm::new_from_slice(&key).map_err(|_| DatabaseError::InvalidEncryptionKey)?;
+        Ok(Self { cipher })
+    }
+
+    pub fn encrypt(&self, field_name: &str, value: &Value) -> Result<Vec<u8>, DatabaseError> {
+        let plaintext = serde_json::to_vec(value)
+            .map_err(|_| DatabaseError::InvalidValue(field_name.to_owned()))?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| DatabaseError::InvalidValue(field_name.to_owned()))?;
+        Ok([nonce.as_slice(), &ciphertext].concat())
+    }
+
+    pub fn decrypt(&self, field_name: &str, data: &[u8]) -> Result<Value, DatabaseError> {
+        if data.len() < NONCE_SIZE {
+            return Err(DatabaseError::DecryptionFailed(field_name.to_owned()));
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_SIZE);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| DatabaseError::DecryptionFailed(field_name.to_owned()))?;
+        serde_json::from_slice(&plaintext)
+            .map_err(|_| DatabaseError::DecryptionFailed(field_name.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    #[test]
+    fn test_field_cipher_round_trip() {
+        env_logger::try_init().unwrap_or_default();
+        let cipher = FieldCipher::new(KEY).unwrap();
+        let value = Value::String("alice@example.com".to_string());
+
+        let first = cipher.encrypt("email", &value).unwrap();
+        let second = cipher.encrypt("email", &value).unwrap();
+        assert_ne!(first, second, "every value gets its own nonce");
+        assert!(!first
+            .windows("alice".len())
+            .any(|window| window == "alice".as_bytes()));
+        assert_eq!(cipher.decrypt("email", &first).unwrap(), value);
+
+        let other = FieldCipher::new(&KEY.replace("00", "ff")).unwrap();
+        assert!(matches!(
+            other.decrypt("email", &first),
+            Err(DatabaseError::DecryptionFailed(field)) if field == "email"
+        ));
+    }
+
+    #[test]
+    fn test_field_cipher_invalid_key() {
+        env_logger::try_init().unwrap_or_default();
+        assert!(matches!(
+            FieldCipher::new("not-hex"),
+            Err(DatabaseError::InvalidEncryptionKey)
+        ));
+        assert!(matches!(
+            FieldCipher::new(&KEY[..32]),
+            Err(DatabaseError::InvalidEncryptionKey)
+        ));
+    }
+}
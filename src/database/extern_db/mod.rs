@@ -1,4 +1,6 @@
 #[cfg(feature = "scylla")]
+mod field_cipher;
+#[cfg(feature = "scylla")]
 mod scylladb;
 #[cfg(feature = "scylla")]
 use scylladb::*;
@@ -8,15 +10,32 @@ mod mongo;
 #[cfg(feature = "mongo")]
 use mongo::*;
 
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "postgres")]
+use postgres::*;
+
+mod multi;
+use multi::MultiDB;
+
 use crate::common::BlockPtr;
+use crate::common::Checkpoint;
 use crate::common::Datasource;
 use crate::common::EntityID;
 use crate::common::EntityType;
 use crate::common::RawEntity;
+use crate::common::ReprocessedRange;
 use crate::common::Schemas;
 use crate::config::DatabaseConfig;
 use crate::errors::DatabaseError;
+use crate::runtime::asc::native_types::store::Value;
 use async_trait::async_trait;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::io::BufRead;
+use std::io::Read;
+use std::io::Write;
 
 #[derive(Default)]
 pub enum ExternDB {
@@ -24,27 +43,107 @@ pub enum ExternDB {
     Scylla(Scylladb),
     #[cfg(feature = "mongo")]
     Mongo(MongoDB),
+    #[cfg(feature = "postgres")]
+    Postgres(Postgres),
+    /// Writes go to every backend, reads to the configured ones in turn
+    Multi(MultiDB),
     #[default]
     None,
 }
 
 impl ExternDB {
-    pub async fn new(config: &DatabaseConfig, schemas: Schemas) -> Result<Self, DatabaseError> {
+    pub async fn new(
+        config: &DatabaseConfig,
+        schemas: Schemas,
+        subgraph_name: &str,
+    ) -> Result<Self, DatabaseError> {
+        match config {
+            DatabaseConfig::Multi {
+                backends,
+                read_order,
+            } => {
+                let mut dbs = vec![];
+                for config in backends {
+                    dbs.push(Self::new_single(config, schemas.clone(), subgraph_name).await?);
+                }
+                let read_order = read_order.clone().unwrap_or(vec![0]);
+                Ok(ExternDB::Multi(MultiDB::new(dbs, read_order)?))
+            }
+            config => Self::new_single(config, schemas, subgraph_name).await,
+        }
+    }
+
+    #[cfg_attr(not(feature = "scylla"), allow(unused_variables))]
+    async fn new_single(
+        config: &DatabaseConfig,
+        schemas: Schemas,
+        subgraph_name: &str,
+    ) -> Result<Self, DatabaseError> {
         let db = match config {
             #[cfg(feature = "scylla")]
-            DatabaseConfig::Scylla { uri, keyspace } => {
-                ExternDB::Scylla(Scylladb::new(uri, keyspace, schemas).await?)
+            DatabaseConfig::Scylla(cfg) => {
+                ExternDB::Scylla(Scylladb::new(cfg, schemas, subgraph_name).await?)
             }
             #[cfg(feature = "mongo")]
             DatabaseConfig::Mongo { uri, database } => {
                 ExternDB::Mongo(MongoDB::new(uri, database, schemas).await?)
             }
+            #[cfg(feature = "postgres")]
+            DatabaseConfig::Postgres { uri } => {
+                ExternDB::Postgres(Postgres::new(uri, schemas).await?)
+            }
+            DatabaseConfig::Multi { .. } => {
+                return Err(DatabaseError::InvalidMultiBackend(
+                    "backends cannot be nested".to_string(),
+                ))
+            }
         };
 
         Ok(db)
     }
 }
 
+/// A single line of an exported NDJSON entity snapshot
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotRecord {
+    pub entity_type: EntityType,
+    pub entity: RawEntity,
+}
+
+impl SnapshotRecord {
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), DatabaseError> {
+        serde_json::to_writer(&mut *writer, self)
+            .map_err(|e| DatabaseError::Plain(e.to_string()))?;
+        writer
+            .write_all(b"\n")
+            .map_err(|e| DatabaseError::Plain(e.to_string()))
+    }
+
+    pub fn read_lines<R: BufRead>(
+        reader: R,
+    ) -> impl Iterator<Item = Result<SnapshotRecord, DatabaseError>> {
+        reader
+            .lines()
+            .filter(|line| !line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+            .map(|line| {
+                let line = line.map_err(|e| DatabaseError::Plain(e.to_string()))?;
+                serde_json::from_str(&line).map_err(|e| DatabaseError::Plain(e.to_string()))
+            })
+    }
+
+    /// The entity is restored at the block of its exported snapshot
+    pub fn block_ptr(&self) -> BlockPtr {
+        let number = match self.entity.get("__block_ptr__") {
+            Some(Value::Int8(number)) => *number as u64,
+            _ => 0,
+        };
+        BlockPtr {
+            number,
+            ..Default::default()
+        }
+    }
+}
+
 #[async_trait]
 pub trait ExternDBTrait: Sized {
     async fn create_entity_tables(&self) -> Result<(), DatabaseError>;
@@ -53,6 +152,10 @@ pub trait ExternDBTrait: Sized {
 
     async fn create_datasource_table(&self) -> Result<(), DatabaseError>;
 
+    async fn create_checkpoint_table(&self) -> Result<(), DatabaseError>;
+
+    async fn create_reprocess_table(&self) -> Result<(), DatabaseError>;
+
     async fn load_entity(
         &self,
         entity_type: &str,
@@ -65,6 +168,47 @@ pub trait ExternDBTrait: Sized {
         ids: Vec<String>,
     ) -> Result<Vec<RawEntity>, DatabaseError>;
 
+    /// Load every stored snapshot of an entity, including soft-deleted ones,
+    /// ordered by block number ascending
+    async fn load_entity_history(
+        &self,
+        entity_type: &str,
+        entity_id: &str,
+    ) -> Result<Vec<(u64, RawEntity)>, DatabaseError>;
+
+    /// Load every snapshot flagging an entity as deleted, with the block it was written at
+    async fn load_deleted_entities(
+        &self,
+        entity_type: &str,
+    ) -> Result<Vec<(u64, RawEntity)>, DatabaseError>;
+
+    /// Load non-deleted snapshots whose `__block_timestamp__` is within `[from_timestamp, to_timestamp]`,
+    /// only available if entities are stored with block-timestamp
+    async fn load_entities_by_time_range(
+        &self,
+        entity_type: &str,
+        from_timestamp: u64,
+        to_timestamp: u64,
+    ) -> Result<Vec<RawEntity>, DatabaseError>;
+
+    /// Load the latest non-deleted snapshot of every entity whose `field` currently equals `value`,
+    /// the field must be flagged `@index` in the schema
+    async fn load_entities_by_field(
+        &self,
+        entity_type: &str,
+        field: &str,
+        value: Value,
+    ) -> Result<Vec<RawEntity>, DatabaseError>;
+
+    /// Ids of the entities whose `field` equals `value` in any of their snapshots,
+    /// a superset of those currently matching. The field must be flagged `@index` in the schema
+    async fn load_entity_ids_by_field(
+        &self,
+        entity_type: &str,
+        field: &str,
+        value: Value,
+    ) -> Result<Vec<EntityID>, DatabaseError>;
+
     async fn create_entity(
         &self,
         block_ptr: BlockPtr,
@@ -81,6 +225,23 @@ pub trait ExternDBTrait: Sized {
 
     async fn get_earliest_block_ptr(&self) -> Result<Option<BlockPtr>, DatabaseError>;
 
+    /// Only the latest checkpoint is kept
+    async fn save_checkpoint(&self, checkpoint: Checkpoint) -> Result<(), DatabaseError>;
+
+    async fn load_checkpoint(&self) -> Result<Option<Checkpoint>, DatabaseError>;
+
+    /// Only the latest reprocessed range is kept
+    async fn save_reprocessed_range(&self, range: ReprocessedRange) -> Result<(), DatabaseError>;
+
+    async fn load_reprocessed_range(&self) -> Result<Option<ReprocessedRange>, DatabaseError>;
+
+    /// Block numbers within `[from_block, to_block]` without a stored block pointer, ascending
+    async fn find_block_ptr_gaps(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<u64>, DatabaseError>;
+
     async fn save_datasources(&self, datasources: Vec<Datasource>) -> Result<(), DatabaseError>;
 
     async fn load_datasources(&self) -> Result<Option<Vec<Datasource>>, DatabaseError>;
@@ -91,7 +252,8 @@ pub trait ExternDBTrait: Sized {
         values: Vec<(EntityType, RawEntity)>,
     ) -> Result<(), DatabaseError>;
 
-    async fn revert_from_block(&self, from_block: u64) -> Result<(), DatabaseError>;
+    /// Remove every snapshot from `from_block` on, returning the number of entities reverted
+    async fn revert_from_block(&self, from_block: u64) -> Result<usize, DatabaseError>;
 
     async fn remove_snapshots(
         &self,
@@ -100,6 +262,17 @@ pub trait ExternDBTrait: Sized {
     ) -> Result<usize, DatabaseError>;
 
     async fn clean_data_history(&self, to_block: u64) -> Result<u64, DatabaseError>;
+
+    /// Stream the latest non-deleted snapshot of every entity into `writer` as NDJSON
+    /// of `SnapshotRecord`, returning the number of exported entities
+    async fn export_snapshot<W: Write + Send>(
+        &self,
+        writer: &mut W,
+    ) -> Result<usize, DatabaseError>;
+
+    /// Insert every `SnapshotRecord` read line-by-line from `reader`,
+    /// returning the number of imported entities
+    async fn import_snapshot<R: BufRead + Send>(&self, reader: R) -> Result<usize, DatabaseError>;
 }
 
 #[async_trait]
@@ -110,6 +283,9 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.create_entity_tables().await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.create_entity_tables().await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.create_entity_tables().await,
+            ExternDB::Multi(multi) => multi.write_all(|db| db.create_entity_tables()).await,
             ExternDB::None => Ok(()),
         }
     }
@@ -120,6 +296,9 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.create_block_ptr_table().await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.create_block_ptr_table().await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.create_block_ptr_table().await,
+            ExternDB::Multi(multi) => multi.write_all(|db| db.create_block_ptr_table()).await,
             ExternDB::None => Ok(()),
         }
     }
@@ -130,6 +309,35 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.create_datasource_table().await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.create_datasource_table().await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.create_datasource_table().await,
+            ExternDB::Multi(multi) => multi.write_all(|db| db.create_datasource_table()).await,
+            ExternDB::None => Ok(()),
+        }
+    }
+
+    async fn create_checkpoint_table(&self) -> Result<(), DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => db.create_checkpoint_table().await,
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => db.create_checkpoint_table().await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.create_checkpoint_table().await,
+            ExternDB::Multi(multi) => multi.write_all(|db| db.create_checkpoint_table()).await,
+            ExternDB::None => Ok(()),
+        }
+    }
+
+    async fn create_reprocess_table(&self) -> Result<(), DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => db.create_reprocess_table().await,
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => db.create_reprocess_table().await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.create_reprocess_table().await,
+            ExternDB::Multi(multi) => multi.write_all(|db| db.create_reprocess_table()).await,
             ExternDB::None => Ok(()),
         }
     }
@@ -144,6 +352,13 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.load_entity(entity_type, entity_id).await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.load_entity(entity_type, entity_id).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.load_entity(entity_type, entity_id).await,
+            ExternDB::Multi(multi) => {
+                multi
+                    .read(|db| db.load_entity(entity_type, entity_id))
+                    .await
+            }
             ExternDB::None => Ok(None),
         }
     }
@@ -159,6 +374,13 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.create_entity(block_ptr, entity_type, data).await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.create_entity(block_ptr, entity_type, data).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.create_entity(block_ptr, entity_type, data).await,
+            ExternDB::Multi(multi) => {
+                multi
+                    .write_all(|db| db.create_entity(block_ptr.clone(), entity_type, data.clone()))
+                    .await
+            }
             ExternDB::None => Ok(()),
         }
     }
@@ -173,17 +395,27 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.batch_insert_entities(block_ptr, values).await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.batch_insert_entities(block_ptr, values).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.batch_insert_entities(block_ptr, values).await,
+            ExternDB::Multi(multi) => {
+                multi
+                    .write_all(|db| db.batch_insert_entities(block_ptr.clone(), values.clone()))
+                    .await
+            }
             ExternDB::None => Ok(()),
         }
     }
 
-    async fn revert_from_block(&self, from_block: u64) -> Result<(), DatabaseError> {
+    async fn revert_from_block(&self, from_block: u64) -> Result<usize, DatabaseError> {
         match self {
             #[cfg(feature = "scylla")]
             ExternDB::Scylla(db) => db.revert_from_block(from_block).await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.revert_from_block(from_block).await,
-            ExternDB::None => Ok(()),
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.revert_from_block(from_block).await,
+            ExternDB::Multi(multi) => multi.write_all(|db| db.revert_from_block(from_block)).await,
+            ExternDB::None => Ok(0),
         }
     }
 
@@ -193,6 +425,13 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.save_block_ptr(block_ptr).await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.save_block_ptr(block_ptr).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.save_block_ptr(block_ptr).await,
+            ExternDB::Multi(multi) => {
+                multi
+                    .write_all(|db| db.save_block_ptr(block_ptr.clone()))
+                    .await
+            }
             ExternDB::None => Ok(()),
         }
     }
@@ -207,6 +446,123 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.load_entities(entity_type, ids).await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.load_entities(entity_type, ids).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.load_entities(entity_type, ids).await,
+            ExternDB::Multi(multi) => multi.load_entities(entity_type, ids).await,
+            ExternDB::None => Ok(vec![]),
+        }
+    }
+
+    async fn load_entity_history(
+        &self,
+        entity_type: &str,
+        entity_id: &str,
+    ) -> Result<Vec<(u64, RawEntity)>, DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => db.load_entity_history(entity_type, entity_id).await,
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => db.load_entity_history(entity_type, entity_id).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.load_entity_history(entity_type, entity_id).await,
+            ExternDB::Multi(multi) => {
+                multi
+                    .read(|db| db.load_entity_history(entity_type, entity_id))
+                    .await
+            }
+            ExternDB::None => Ok(vec![]),
+        }
+    }
+
+    async fn load_deleted_entities(
+        &self,
+        entity_type: &str,
+    ) -> Result<Vec<(u64, RawEntity)>, DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => db.load_deleted_entities(entity_type).await,
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => db.load_deleted_entities(entity_type).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.load_deleted_entities(entity_type).await,
+            ExternDB::Multi(multi) => multi.read(|db| db.load_deleted_entities(entity_type)).await,
+            ExternDB::None => Ok(vec![]),
+        }
+    }
+
+    async fn load_entities_by_time_range(
+        &self,
+        entity_type: &str,
+        from_timestamp: u64,
+        to_timestamp: u64,
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => {
+                db.load_entities_by_time_range(entity_type, from_timestamp, to_timestamp)
+                    .await
+            }
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => {
+                db.load_entities_by_time_range(entity_type, from_timestamp, to_timestamp)
+                    .await
+            }
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => {
+                db.load_entities_by_time_range(entity_type, from_timestamp, to_timestamp)
+                    .await
+            }
+            ExternDB::Multi(multi) => {
+                multi
+                    .read(|db| {
+                        db.load_entities_by_time_range(entity_type, from_timestamp, to_timestamp)
+                    })
+                    .await
+            }
+            ExternDB::None => Ok(vec![]),
+        }
+    }
+
+    async fn load_entities_by_field(
+        &self,
+        entity_type: &str,
+        field: &str,
+        value: Value,
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => db.load_entities_by_field(entity_type, field, value).await,
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => db.load_entities_by_field(entity_type, field, value).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.load_entities_by_field(entity_type, field, value).await,
+            ExternDB::Multi(multi) => {
+                multi
+                    .read(|db| db.load_entities_by_field(entity_type, field, value.clone()))
+                    .await
+            }
+            ExternDB::None => Ok(vec![]),
+        }
+    }
+
+    async fn load_entity_ids_by_field(
+        &self,
+        entity_type: &str,
+        field: &str,
+        value: Value,
+    ) -> Result<Vec<EntityID>, DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => db.load_entity_ids_by_field(entity_type, field, value).await,
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => db.load_entity_ids_by_field(entity_type, field, value).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.load_entity_ids_by_field(entity_type, field, value).await,
+            ExternDB::Multi(multi) => {
+                multi
+                    .read(|db| db.load_entity_ids_by_field(entity_type, field, value.clone()))
+                    .await
+            }
             ExternDB::None => Ok(vec![]),
         }
     }
@@ -220,6 +576,13 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.load_recent_block_ptrs(number_of_blocks).await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.load_recent_block_ptrs(number_of_blocks).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.load_recent_block_ptrs(number_of_blocks).await,
+            ExternDB::Multi(multi) => {
+                multi
+                    .read_or_fail(|db| db.load_recent_block_ptrs(number_of_blocks))
+                    .await
+            }
             ExternDB::None => Ok(vec![]),
         }
     }
@@ -230,16 +593,109 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.get_earliest_block_ptr().await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.get_earliest_block_ptr().await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.get_earliest_block_ptr().await,
+            ExternDB::Multi(multi) => multi.read_or_fail(|db| db.get_earliest_block_ptr()).await,
+            ExternDB::None => Ok(None),
+        }
+    }
+
+    async fn save_checkpoint(&self, checkpoint: Checkpoint) -> Result<(), DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => db.save_checkpoint(checkpoint).await,
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => db.save_checkpoint(checkpoint).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.save_checkpoint(checkpoint).await,
+            ExternDB::Multi(multi) => {
+                multi
+                    .write_all(|db| db.save_checkpoint(checkpoint.clone()))
+                    .await
+            }
+            ExternDB::None => Ok(()),
+        }
+    }
+
+    async fn load_checkpoint(&self) -> Result<Option<Checkpoint>, DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => db.load_checkpoint().await,
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => db.load_checkpoint().await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.load_checkpoint().await,
+            ExternDB::Multi(multi) => multi.read_or_fail(|db| db.load_checkpoint()).await,
             ExternDB::None => Ok(None),
         }
     }
 
+    async fn save_reprocessed_range(&self, range: ReprocessedRange) -> Result<(), DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => db.save_reprocessed_range(range).await,
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => db.save_reprocessed_range(range).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.save_reprocessed_range(range).await,
+            ExternDB::Multi(multi) => {
+                multi
+                    .write_all(|db| db.save_reprocessed_range(range.clone()))
+                    .await
+            }
+            ExternDB::None => Ok(()),
+        }
+    }
+
+    async fn load_reprocessed_range(&self) -> Result<Option<ReprocessedRange>, DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => db.load_reprocessed_range().await,
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => db.load_reprocessed_range().await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.load_reprocessed_range().await,
+            ExternDB::Multi(multi) => multi.read_or_fail(|db| db.load_reprocessed_range()).await,
+            ExternDB::None => Ok(None),
+        }
+    }
+
+    async fn find_block_ptr_gaps(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<u64>, DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => db.find_block_ptr_gaps(from_block, to_block).await,
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => db.find_block_ptr_gaps(from_block, to_block).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.find_block_ptr_gaps(from_block, to_block).await,
+            // Secondaries may lag behind, only the primary's gaps are reported
+            ExternDB::Multi(multi) => {
+                multi
+                    .primary()
+                    .find_block_ptr_gaps(from_block, to_block)
+                    .await
+            }
+            ExternDB::None => Ok(vec![]),
+        }
+    }
+
     async fn save_datasources(&self, datasources: Vec<Datasource>) -> Result<(), DatabaseError> {
         match self {
             #[cfg(feature = "scylla")]
             ExternDB::Scylla(db) => db.save_datasources(datasources).await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.save_datasources(datasources).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.save_datasources(datasources).await,
+            ExternDB::Multi(multi) => {
+                multi
+                    .write_all(|db| db.save_datasources(datasources.clone()))
+                    .await
+            }
             ExternDB::None => Ok(()),
         }
     }
@@ -250,6 +706,9 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.load_datasources().await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.load_datasources().await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.load_datasources().await,
+            ExternDB::Multi(multi) => multi.read_or_fail(|db| db.load_datasources()).await,
             ExternDB::None => Ok(None),
         }
     }
@@ -264,6 +723,13 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.remove_snapshots(entities, to_block).await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.remove_snapshots(entities, to_block).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.remove_snapshots(entities, to_block).await,
+            ExternDB::Multi(multi) => {
+                multi
+                    .write_all(|db| db.remove_snapshots(entities.clone(), to_block))
+                    .await
+            }
             ExternDB::None => Ok(0),
         }
     }
@@ -274,7 +740,77 @@ impl ExternDBTrait for ExternDB {
             ExternDB::Scylla(db) => db.clean_data_history(to_block).await,
             #[cfg(feature = "mongo")]
             ExternDB::Mongo(db) => db.clean_data_history(to_block).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.clean_data_history(to_block).await,
+            ExternDB::Multi(multi) => multi.write_all(|db| db.clean_data_history(to_block)).await,
             ExternDB::None => Ok(1),
         }
     }
+
+    async fn export_snapshot<W: Write + Send>(
+        &self,
+        writer: &mut W,
+    ) -> Result<usize, DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => db.export_snapshot(writer).await,
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => db.export_snapshot(writer).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.export_snapshot(writer).await,
+            ExternDB::Multi(multi) => multi.primary().export_snapshot(writer).await,
+            ExternDB::None => Ok(0),
+        }
+    }
+
+    async fn import_snapshot<R: BufRead + Send>(&self, reader: R) -> Result<usize, DatabaseError> {
+        match self {
+            #[cfg(feature = "scylla")]
+            ExternDB::Scylla(db) => db.import_snapshot(reader).await,
+            #[cfg(feature = "mongo")]
+            ExternDB::Mongo(db) => db.import_snapshot(reader).await,
+            #[cfg(feature = "postgres")]
+            ExternDB::Postgres(db) => db.import_snapshot(reader).await,
+            ExternDB::Multi(multi) => {
+                // Every backend imports the same records, so the reader is drained once
+                let mut buffer = vec![];
+                let mut reader = reader;
+                reader
+                    .read_to_end(&mut buffer)
+                    .map_err(|e| DatabaseError::Plain(e.to_string()))?;
+                multi
+                    .write_all(|db| db.import_snapshot(buffer.as_slice()))
+                    .await
+            }
+            ExternDB::None => Ok(0),
+        }
+    }
+}
+
+/// Block numbers of `[from_block, to_block]` missing from `stored`
+fn missing_block_numbers(
+    from_block: u64,
+    to_block: u64,
+    stored: impl IntoIterator<Item = u64>,
+) -> Vec<u64> {
+    let stored = stored.into_iter().collect::<HashSet<_>>();
+    (from_block..=to_block)
+        .filter(|number| !stored.contains(number))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_block_numbers() {
+        assert_eq!(missing_block_numbers(1, 5, [1, 2, 4, 5]), vec![3]);
+        assert_eq!(
+            missing_block_numbers(1, 5, [5, 4, 3, 2, 1]),
+            Vec::<u64>::new()
+        );
+        assert_eq!(missing_block_numbers(1, 5, [2, 9]), vec![1, 3, 4, 5]);
+        assert_eq!(missing_block_numbers(5, 1, []), Vec::<u64>::new());
+    }
 }
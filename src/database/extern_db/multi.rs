@@ -0,0 +1,369 @@
+use super::ExternDB;
+use super::ExternDBTrait;
+use crate::common::RawEntity;
+use crate::error;
+use crate::errors::DatabaseError;
+use crate::warn;
+use futures_util::future::join_all;
+use futures_util::future::try_join_all;
+use std::future::Future;
+
+/// Several backends used as one: writes go to all of them,
+/// reads to the backends of `read_order` until one has the data
+pub struct MultiDB {
+    backends: Vec<ExternDB>,
+    read_order: Vec<usize>,
+}
+
+impl MultiDB {
+    pub fn new(backends: Vec<ExternDB>, read_order: Vec<usize>) -> Result<Self, DatabaseError> {
+        if backends.is_empty() {
+            return Err(DatabaseError::InvalidMultiBackend(
+                "at least one backend is required".to_string(),
+            ));
+        }
+        if read_order.is_empty() || read_order.iter().any(|idx| *idx >= backends.len()) {
+            return Err(DatabaseError::InvalidMultiBackend(format!(
+                "read order {read_order:?} must list backends among the {} configured",
+                backends.len()
+            )));
+        }
+        Ok(Self {
+            backends,
+            read_order,
+        })
+    }
+
+    pub fn primary(&self) -> &ExternDB {
+        &self.backends[0]
+    }
+
+    /// A secondary backend failing is reported without failing the write
+    pub async fn write_all<'a, T, F, Fut>(&'a self, write: F) -> Result<T, DatabaseError>
+    where
+        F: Fn(&'a ExternDB) -> Fut,
+        Fut: Future<Output = Result<T, DatabaseError>>,
+    {
+        let (result, failures) = fan_out(&self.backends, write).await;
+        for (backend, e) in failures {
+            error!(ExternDB, "write to secondary backend failed"; backend => backend, error => e);
+        }
+        result
+    }
+
+    pub async fn read<'a, T, F, Fut>(&'a self, read: F) -> Result<T, DatabaseError>
+    where
+        T: ReadOutcome,
+        F: Fn(&'a ExternDB) -> Fut,
+        Fut: Future<Output = Result<T, DatabaseError>>,
+    {
+        read_in_order(&self.backends, &self.read_order, read).await
+    }
+
+    /// Reads of the chain progress (block pointers, checkpoint...) must not be answered by
+    /// a lagging backend standing in for a failing one, so a failure is returned as is
+    pub async fn read_or_fail<'a, T, F, Fut>(&'a self, read: F) -> Result<T, DatabaseError>
+    where
+        T: ReadOutcome,
+        F: Fn(&'a ExternDB) -> Fut,
+        Fut: Future<Output = Result<T, DatabaseError>>,
+    {
+        read_in_order_or_fail(&self.backends, &self.read_order, read).await
+    }
+
+    /// Every id is looked up on its own, so that ids missing from a backend
+    /// are found in the next one while the others are served by the first
+    pub async fn load_entities(
+        &self,
+        entity_type: &str,
+        ids: Vec<String>,
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
+        load_each_in_order(&self.backends, &self.read_order, &ids, |db, entity_id| {
+            db.load_entity(entity_type, entity_id)
+        })
+        .await
+    }
+}
+
+/// Whether a read found nothing, so the next backend should be consulted
+pub trait ReadOutcome {
+    fn is_miss(&self) -> bool;
+}
+
+impl<T> ReadOutcome for Option<T> {
+    fn is_miss(&self) -> bool {
+        self.is_none()
+    }
+}
+
+impl<T> ReadOutcome for Vec<T> {
+    fn is_miss(&self) -> bool {
+        self.is_empty()
+    }
+}
+
+/// Run a write against every backend at once, returning the result of the first one (the primary)
+/// along with the failures of the others by their position
+async fn fan_out<'a, B, T, F, Fut>(
+    backends: &'a [B],
+    write: F,
+) -> (Result<T, DatabaseError>, Vec<(usize, DatabaseError)>)
+where
+    F: Fn(&'a B) -> Fut,
+    Fut: Future<Output = Result<T, DatabaseError>>,
+{
+    let mut results = join_all(backends.iter().map(write)).await.into_iter();
+    let primary = results
+        .next()
+        .unwrap_or(Err(DatabaseError::InvalidMultiBackend(
+            "at least one backend is required".to_string(),
+        )));
+    let failures = results
+        .enumerate()
+        .filter_map(|(idx, result)| result.err().map(|e| (idx + 1, e)))
+        .collect();
+    (primary, failures)
+}
+
+/// Consult the backends one after another until one has the data. If none has,
+/// the first failure is returned so a failing backend is not mistaken for a miss
+async fn read_in_order<'a, B, T, F, Fut>(
+    backends: &'a [B],
+    order: &[usize],
+    read: F,
+) -> Result<T, DatabaseError>
+where
+    T: ReadOutcome,
+    F: Fn(&'a B) -> Fut,
+    Fut: Future<Output = Result<T, DatabaseError>>,
+{
+    let mut miss = None;
+    let mut failure = None;
+
+    for idx in order {
+        match read(&backends[*idx]).await {
+            Ok(value) if !value.is_miss() => return Ok(value),
+            Ok(value) => miss = miss.or(Some(value)),
+            Err(e) => {
+                warn!(ExternDB, "read from backend failed, falling back"; backend => idx, error => e);
+                failure = failure.or(Some(e));
+            }
+        }
+    }
+
+    match (failure, miss) {
+        (Some(e), _) => Err(e),
+        (None, Some(value)) => Ok(value),
+        (None, None) => Err(DatabaseError::InvalidMultiBackend(
+            "no backend to read from".to_string(),
+        )),
+    }
+}
+
+/// Look every id up through `read_in_order`, leaving out the ones no backend has
+async fn load_each_in_order<'a, B, F, Fut>(
+    backends: &'a [B],
+    order: &[usize],
+    ids: &'a [String],
+    load: F,
+) -> Result<Vec<RawEntity>, DatabaseError>
+where
+    F: Fn(&'a B, &'a str) -> Fut,
+    Fut: Future<Output = Result<Option<RawEntity>, DatabaseError>>,
+{
+    let entities = try_join_all(
+        ids.iter()
+            .map(|entity_id| read_in_order(backends, order, |db| load(db, entity_id))),
+    )
+    .await?;
+    Ok(entities.into_iter().flatten().collect())
+}
+
+/// Consult the backends one after another until one has the data, stopping at the first failure
+async fn read_in_order_or_fail<'a, B, T, F, Fut>(
+    backends: &'a [B],
+    order: &[usize],
+    read: F,
+) -> Result<T, DatabaseError>
+where
+    T: ReadOutcome,
+    F: Fn(&'a B) -> Fut,
+    Fut: Future<Output = Result<T, DatabaseError>>,
+{
+    let mut miss = None;
+
+    for idx in order {
+        match read(&backends[*idx]).await {
+            Ok(value) if !value.is_miss() => return Ok(value),
+            Ok(value) => miss = miss.or(Some(value)),
+            Err(e) => {
+                error!(ExternDB, "read from backend failed"; backend => idx, error => e);
+                return Err(e);
+            }
+        }
+    }
+
+    miss.ok_or(DatabaseError::InvalidMultiBackend(
+        "no backend to read from".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::asc::native_types::store::Value;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct MockBackend {
+        entities: RefCell<HashMap<String, RawEntity>>,
+        failing: bool,
+    }
+
+    impl MockBackend {
+        async fn insert(&self, id: &str, data: RawEntity) -> Result<(), DatabaseError> {
+            if self.failing {
+                return Err(DatabaseError::Plain("backend unavailable".to_string()));
+            }
+            self.entities.borrow_mut().insert(id.to_string(), data);
+            Ok(())
+        }
+
+        async fn load(&self, id: &str) -> Result<Option<RawEntity>, DatabaseError> {
+            if self.failing {
+                return Err(DatabaseError::Plain("backend unavailable".to_string()));
+            }
+            Ok(self.entities.borrow().get(id).cloned())
+        }
+    }
+
+    fn token(name: &str) -> RawEntity {
+        RawEntity::from([("name".to_string(), Value::String(name.to_string()))])
+    }
+
+    #[tokio::test]
+    async fn test_fan_out() {
+        let backends = vec![MockBackend::default(), MockBackend::default()];
+        let (result, failures) = fan_out(&backends, |db| db.insert("token", token("TKN"))).await;
+        assert!(result.is_ok());
+        assert!(failures.is_empty());
+        for backend in backends.iter() {
+            assert_eq!(backend.load("token").await.unwrap(), Some(token("TKN")));
+        }
+
+        // A failing secondary is reported, the primary still gets the write
+        let backends = vec![
+            MockBackend::default(),
+            MockBackend {
+                failing: true,
+                ..Default::default()
+            },
+        ];
+        let (result, failures) = fan_out(&backends, |db| db.insert("token", token("TKN"))).await;
+        assert!(result.is_ok());
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, 1);
+        assert_eq!(backends[0].load("token").await.unwrap(), Some(token("TKN")));
+
+        // A failing primary fails the write
+        let backends = vec![
+            MockBackend {
+                failing: true,
+                ..Default::default()
+            },
+            MockBackend::default(),
+        ];
+        let (result, failures) = fan_out(&backends, |db| db.insert("token", token("TKN"))).await;
+        assert!(result.is_err());
+        assert!(failures.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_read_fallback() {
+        let backends = vec![MockBackend::default(), MockBackend::default()];
+        backends[0].insert("new", token("NEW")).await.unwrap();
+        backends[1].insert("old", token("OLD")).await.unwrap();
+        backends[1].insert("new", token("stale")).await.unwrap();
+
+        // Only the primary is read from by default
+        let result = read_in_order(&backends, &[0], |db| db.load("old")).await;
+        assert_eq!(result.unwrap(), None);
+
+        // The entity only living in the secondary is found through the fallback
+        let result = read_in_order(&backends, &[0, 1], |db| db.load("old")).await;
+        assert_eq!(result.unwrap(), Some(token("OLD")));
+        let result = read_in_order(&backends, &[0, 1], |db| db.load("new")).await;
+        assert_eq!(result.unwrap(), Some(token("NEW")));
+        let result = read_in_order(&backends, &[1, 0], |db| db.load("new")).await;
+        assert_eq!(result.unwrap(), Some(token("stale")));
+        let result = read_in_order(&backends, &[0, 1], |db| db.load("missing")).await;
+        assert_eq!(result.unwrap(), None);
+
+        // A failing backend is skipped, but not taken for a miss
+        let backends = vec![
+            MockBackend {
+                failing: true,
+                ..Default::default()
+            },
+            MockBackend::default(),
+        ];
+        backends[1].insert("old", token("OLD")).await.unwrap();
+        let result = read_in_order(&backends, &[0, 1], |db| db.load("old")).await;
+        assert_eq!(result.unwrap(), Some(token("OLD")));
+        let result = read_in_order(&backends, &[0, 1], |db| db.load("missing")).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_load_each() {
+        let backends = vec![MockBackend::default(), MockBackend::default()];
+        backends[0].insert("new", token("NEW")).await.unwrap();
+        backends[1].insert("old", token("OLD")).await.unwrap();
+        backends[1].insert("new", token("stale")).await.unwrap();
+        let ids = ["new", "old", "missing"].map(String::from);
+
+        // A partial hit on the primary does not hide the ids only the secondary has
+        let result = load_each_in_order(&backends, &[0, 1], &ids, |db, id| db.load(id)).await;
+        assert_eq!(result.unwrap(), vec![token("NEW"), token("OLD")]);
+        let result = load_each_in_order(&backends, &[0], &ids, |db, id| db.load(id)).await;
+        assert_eq!(result.unwrap(), vec![token("NEW")]);
+    }
+
+    #[tokio::test]
+    async fn test_read_or_fail() {
+        let backends = vec![
+            MockBackend {
+                failing: true,
+                ..Default::default()
+            },
+            MockBackend::default(),
+        ];
+        backends[1].insert("old", token("OLD")).await.unwrap();
+
+        // The lagging secondary is not read in place of the failing primary
+        let result = read_in_order_or_fail(&backends, &[0, 1], |db| db.load("old")).await;
+        assert!(result.is_err());
+
+        // A miss still falls through to the next backend
+        let backends = vec![MockBackend::default(), MockBackend::default()];
+        backends[1].insert("old", token("OLD")).await.unwrap();
+        let result = read_in_order_or_fail(&backends, &[0, 1], |db| db.load("old")).await;
+        assert_eq!(result.unwrap(), Some(token("OLD")));
+        let result = read_in_order_or_fail(&backends, &[0, 1], |db| db.load("missing")).await;
+        assert_eq!(result.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_multi_db() {
+        assert!(MultiDB::new(vec![], vec![0]).is_err());
+        assert!(MultiDB::new(vec![ExternDB::None], vec![]).is_err());
+        assert!(MultiDB::new(vec![ExternDB::None], vec![0, 1]).is_err());
+
+        let multi = MultiDB::new(vec![ExternDB::None, ExternDB::None], vec![1, 0]).unwrap();
+        assert!(matches!(multi.primary(), ExternDB::None));
+        let db = ExternDB::Multi(multi);
+        assert!(db.load_entity("Token", "token").await.unwrap().is_none());
+        assert_eq!(db.clean_data_history(10).await.unwrap(), 1);
+    }
+}
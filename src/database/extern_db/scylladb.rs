@@ -1,10 +1,19 @@
+use super::field_cipher::FieldCipher;
 use super::ExternDBTrait;
+use super::SnapshotRecord;
 use crate::common::BlockPtr;
+use crate::common::Checkpoint;
+use crate::common::Datasource;
 use crate::common::EntityID;
 use crate::common::EntityType;
 use crate::common::FieldKind;
 use crate::common::RawEntity;
+use crate::common::ReprocessedRange;
 use crate::common::Schemas;
+use crate::config::CompactionWindowUnit;
+use crate::config::ScyllaCompaction;
+use crate::config::ScyllaConfig;
+use crate::config::ScyllaConsistency;
 use crate::debug;
 use crate::error;
 use crate::errors::DatabaseError;
@@ -14,17 +23,35 @@ use crate::runtime::asc::native_types::store::StoreValueKind;
 use crate::runtime::asc::native_types::store::Value;
 use crate::runtime::bignumber::bigdecimal::BigDecimal;
 use crate::runtime::bignumber::bigint::BigInt;
+use crate::warn;
 use async_trait::async_trait;
-use futures_util::future::try_join_all;
+use futures_util::StreamExt;
 use scylla::_macro_internal::CqlValue;
 use scylla::batch::Batch;
+use scylla::frame::response::result::ColumnSpec;
+use scylla::frame::response::result::Row;
+use scylla::frame::value::BatchValues;
+use scylla::frame::value::ValueList;
+use scylla::load_balancing::DefaultPolicy;
+use scylla::statement::Consistency;
+use scylla::transport::errors::DbError;
+use scylla::transport::errors::QueryError;
 use scylla::transport::session::Session;
+use scylla::ExecutionProfile;
 use scylla::QueryResult;
 use scylla::SessionBuilder;
+use std::collections::BTreeMap;
 use std::collections::HashSet;
 use std::fmt::Display;
+use std::future::Future;
+use std::io::BufRead;
+use std::io::Write;
 use std::str::FromStr;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::sync::RwLock;
+use std::time::Duration;
 use tokio_retry::strategy::ExponentialBackoff;
 use tokio_retry::Retry;
 
@@ -34,7 +61,7 @@ impl From<Value> for CqlValue {
             Value::String(str) => CqlValue::Text(str),
             Value::Int(int) => CqlValue::Int(int),
             Value::Int8(int8) => CqlValue::BigInt(int8),
-            Value::BigDecimal(decimal) => CqlValue::Text(decimal.to_string()),
+            Value::BigDecimal(decimal) => CqlValue::Text(decimal.to_canonical_string()),
             Value::Bool(bool) => CqlValue::Boolean(bool),
             Value::List(list) => CqlValue::List(list.into_iter().map(CqlValue::from).collect()),
             Value::Bytes(bytes) => CqlValue::Blob(bytes.as_slice().to_vec()),
@@ -44,6 +71,18 @@ impl From<Value> for CqlValue {
     }
 }
 
+impl From<ScyllaConsistency> for Consistency {
+    fn from(consistency: ScyllaConsistency) -> Self {
+        match consistency {
+            ScyllaConsistency::One => Consistency::One,
+            ScyllaConsistency::LocalOne => Consistency::LocalOne,
+            ScyllaConsistency::Quorum => Consistency::Quorum,
+            ScyllaConsistency::LocalQuorum => Consistency::LocalQuorum,
+            ScyllaConsistency::All => Consistency::All,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub enum BlockPtrFilter {
     // Gt(u64),
@@ -61,31 +100,352 @@ impl Display for BlockPtrFilter {
     }
 }
 
+/// Settings of a session, applied through its default execution profile
+#[derive(Clone, Debug, Default)]
+struct SessionOptions {
+    timeout: Option<u64>,
+    consistency: Option<ScyllaConsistency>,
+    /// Datacenter whose nodes are preferred for every statement
+    local_datacenter: Option<String>,
+}
+
+impl SessionOptions {
+    fn execution_profile(&self) -> ExecutionProfile {
+        let mut profile = ExecutionProfile::builder();
+        // Without a timeout configured the driver's default one is kept
+        if let Some(timeout) = self.timeout {
+            profile = profile.request_timeout(Some(Duration::from_millis(timeout)));
+        }
+        if let Some(consistency) = self.consistency.clone() {
+            profile = profile.consistency(consistency.into());
+        }
+        if let Some(datacenter) = &self.local_datacenter {
+            let policy = DefaultPolicy::builder()
+                .prefer_datacenter(datacenter.to_owned())
+                .token_aware(true)
+                .build();
+            profile = profile.load_balancing_policy(policy);
+        }
+        profile.build()
+    }
+}
+
+/// A session that can be rebuilt when its connections die, eg after the node restarts
+struct ScyllaSession {
+    session: RwLock<Arc<Session>>,
+    uri: String,
+    options: SessionOptions,
+    reconnects: AtomicUsize,
+}
+
+impl ScyllaSession {
+    async fn connect(uri: &str, options: SessionOptions) -> Result<Self, DatabaseError> {
+        let session = Scylladb::connect(uri, &options).await?;
+        Ok(Self {
+            session: RwLock::new(Arc::new(session)),
+            uri: uri.to_owned(),
+            options,
+            reconnects: AtomicUsize::new(0),
+        })
+    }
+
+    fn get(&self) -> Arc<Session> {
+        self.session.read().unwrap().clone()
+    }
+
+    async fn reconnect(&self) -> Result<(), DatabaseError> {
+        let session = Scylladb::connect(&self.uri, &self.options).await?;
+        *self.session.write().unwrap() = Arc::new(session);
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+        info!(ExternDB, "session rebuilt"; uri => self.uri);
+        Ok(())
+    }
+
+    fn is_connection_error(error: &QueryError) -> bool {
+        matches!(
+            error,
+            QueryError::IoError(_)
+                | QueryError::UnableToAllocStreamId
+                | QueryError::TooManyOrphanedStreamIds(_)
+        )
+    }
+
+    /// Run `operation`, rebuilding the session & retrying once if it failed on a dead connection
+    async fn with_reconnect<T, F, Fut>(&self, operation: F) -> Result<T, QueryError>
+    where
+        F: Fn(Arc<Session>) -> Fut,
+        Fut: Future<Output = Result<T, QueryError>>,
+    {
+        match operation(self.get()).await {
+            Err(error) if Self::is_connection_error(&error) => {
+                warn!(ExternDB, "connection error, rebuilding session"; error => format!("{:?}", error));
+                if let Err(reconnect_error) = self.reconnect().await {
+                    error!(ExternDB, "session rebuild failed"; error => format!("{:?}", reconnect_error));
+                    return Err(error);
+                }
+                operation(self.get()).await
+            }
+            result => result,
+        }
+    }
+}
+
 pub struct Scylladb {
-    session: Arc<Session>,
+    /// Session used for DDL and every write statement
+    session: Arc<ScyllaSession>,
+    /// Session used for reads, same as `session` unless configured separately
+    read_session: Arc<ScyllaSession>,
     keyspace: String,
+    /// Prepended to every table name, so multiple subgraphs can share a keyspace
+    table_prefix: String,
     schemas: Schemas,
+    batch_per_entity_type: bool,
+    batch_size: usize,
+    max_inflight_batches: usize,
+    load_chunk_size: usize,
+    /// Set when `encryption_key` is configured, used for fields flagged `@encrypted`
+    cipher: Option<FieldCipher>,
+    compaction: Option<ScyllaCompaction>,
+    time_range_queries: bool,
 }
 
 impl Scylladb {
-    pub async fn new(uri: &str, keyspace: &str, schemas: Schemas) -> Result<Self, DatabaseError> {
+    const DEFAULT_BATCH_SIZE: usize = 100;
+    const DEFAULT_MAX_INFLIGHT_BATCHES: usize = 16;
+    const DEFAULT_LOAD_CHUNK_SIZE: usize = 100;
+
+    pub async fn new(
+        cfg: &ScyllaConfig,
+        schemas: Schemas,
+        subgraph_name: &str,
+    ) -> Result<Self, DatabaseError> {
         info!(ExternDB, "Init db connection");
-        let session: Session = SessionBuilder::new().known_node(uri).build().await?;
+        let options = SessionOptions {
+            timeout: cfg.db_query_timeout,
+            consistency: cfg.write_consistency.clone(),
+            local_datacenter: cfg.local_datacenter.clone(),
+        };
+        let session = Arc::new(ScyllaSession::connect(&cfg.uri, options.clone()).await?);
+        let read_session = match (&cfg.read_uri, &cfg.read_consistency) {
+            (None, None) => session.clone(),
+            (read_uri, read_consistency) => {
+                info!(ExternDB, "Init separate read-session");
+                let uri = read_uri.as_ref().unwrap_or(&cfg.uri);
+                let options = SessionOptions {
+                    consistency: read_consistency.clone(),
+                    ..options
+                };
+                Arc::new(ScyllaSession::connect(uri, options).await?)
+            }
+        };
+        let cipher = match &cfg.encryption_key {
+            Some(key) => Some(FieldCipher::new(key)?),
+            None if schemas.has_encrypted_fields() => {
+                return Err(DatabaseError::MissingEncryptionKey)
+            }
+            None => None,
+        };
         let entities = schemas.get_entity_names();
         let this = Self {
-            session: Arc::new(session),
-            keyspace: keyspace.to_owned(),
+            session,
+            read_session,
+            keyspace: cfg.keyspace.to_owned(),
+            table_prefix: match cfg.prefix_tables {
+                Some(true) => Scylladb::table_prefix(subgraph_name),
+                _ => String::new(),
+            },
             schemas,
+            batch_per_entity_type: cfg.batch_per_entity_type.unwrap_or(false),
+            batch_size: cfg.batch_size.unwrap_or(Self::DEFAULT_BATCH_SIZE).max(1),
+            max_inflight_batches: cfg
+                .max_inflight_batches
+                .unwrap_or(Self::DEFAULT_MAX_INFLIGHT_BATCHES)
+                .max(1),
+            load_chunk_size: cfg
+                .load_chunk_size
+                .unwrap_or(Self::DEFAULT_LOAD_CHUNK_SIZE)
+                .max(1),
+            cipher,
+            compaction: cfg.compaction.clone(),
+            time_range_queries: cfg.time_range_queries.unwrap_or(false),
         };
+        if !cfg.bootstrap_schema.unwrap_or(true) {
+            this.verify_tables().await?;
+            info!(ExternDB, "Tables verified OK"; entities => format!("{:?}", entities));
+            return Ok(this);
+        }
         this.create_keyspace().await?;
-        info!(ExternDB, "Namespace created OK"; namespace => keyspace);
+        info!(ExternDB, "Namespace created OK"; namespace => cfg.keyspace);
         this.create_entity_tables().await?;
         info!(ExternDB, "Entities table created OK"; entities => format!("{:?}", entities));
         this.create_block_ptr_table().await?;
         info!(ExternDB, "Block_Ptr table created OK");
+        this.create_datasource_table().await?;
+        info!(ExternDB, "Datasource table created OK");
+        this.create_checkpoint_table().await?;
+        info!(ExternDB, "Checkpoint table created OK");
+        this.create_reprocess_table().await?;
+        info!(ExternDB, "Reprocess table created OK");
         Ok(this)
     }
 
+    /// Derive a valid table-name prefix from the subgraph name, eg `uniswap/v3-pools` => `uniswap_v3_pools_`
+    fn table_prefix(subgraph_name: &str) -> String {
+        let name = subgraph_name
+            .chars()
+            .map(|c| match c.is_ascii_alphanumeric() {
+                true => c.to_ascii_lowercase(),
+                false => '_',
+            })
+            .collect::<String>();
+        format!("{name}_")
+    }
+
+    fn entity_table(&self, entity_type: &str) -> String {
+        format!(
+            r#"{}."{}{}""#,
+            self.keyspace, self.table_prefix, entity_type
+        )
+    }
+
+    /// Turn the driver's error for a missing table (eg schema drift or a typo'd entity type)
+    /// into a typed one naming the entity type
+    fn table_not_found(entity_type: &str, error: DatabaseError) -> DatabaseError {
+        match &error {
+            DatabaseError::ScyllaQuery(QueryError::DbError(DbError::Invalid, msg))
+                if msg.contains("unconfigured table") || msg.contains("does not exist") =>
+            {
+                DatabaseError::TableNotFound {
+                    entity_type: entity_type.to_owned(),
+                }
+            }
+            _ => error,
+        }
+    }
+
+    fn primary_key(&self, entity_type: &str) -> String {
+        format!(r#""{}""#, self.schemas.get_primary_key(entity_type))
+    }
+
+    fn block_ptr_table(&self) -> String {
+        format!("{}.{}block_ptr", self.keyspace, self.table_prefix)
+    }
+
+    fn datasource_table(&self) -> String {
+        format!("{}.{}datasources", self.keyspace, self.table_prefix)
+    }
+
+    fn checkpoint_table(&self) -> String {
+        format!("{}.{}checkpoint", self.keyspace, self.table_prefix)
+    }
+
+    fn reprocess_table(&self) -> String {
+        format!("{}.{}reprocess", self.keyspace, self.table_prefix)
+    }
+
+    async fn connect(uri: &str, options: &SessionOptions) -> Result<Session, DatabaseError> {
+        let session = SessionBuilder::new()
+            .known_node(uri)
+            .default_execution_profile_handle(options.execution_profile().into_handle())
+            .build()
+            .await?;
+        Ok(session)
+    }
+
+    /// Run a statement with the write-session
+    async fn query(
+        &self,
+        query: String,
+        values: impl ValueList + Clone,
+    ) -> Result<QueryResult, DatabaseError> {
+        Scylladb::query_with(&self.session, query, values).await
+    }
+
+    /// Run a statement with the read-session
+    async fn read(
+        &self,
+        query: String,
+        values: impl ValueList + Clone,
+    ) -> Result<QueryResult, DatabaseError> {
+        Scylladb::query_with(&self.read_session, query, values).await
+    }
+
+    async fn query_with(
+        session: &ScyllaSession,
+        query: String,
+        values: impl ValueList + Clone,
+    ) -> Result<QueryResult, DatabaseError> {
+        session
+            .with_reconnect(|session| {
+                let query = query.clone();
+                let values = values.clone();
+                async move { session.query(query.as_str(), values).await }
+            })
+            .await
+            .map_err(|error| match error {
+                QueryError::RequestTimeout(msg) => {
+                    DatabaseError::Timeout(format!("{msg}, query = {}", query.trim()))
+                }
+                error => DatabaseError::from(error),
+            })
+    }
+
+    /// Run a batch of statements with the write-session
+    async fn batch(
+        &self,
+        batch: &Batch,
+        values: impl BatchValues + Clone,
+    ) -> Result<(), DatabaseError> {
+        self.session
+            .with_reconnect(|session| {
+                let values = values.clone();
+                async move {
+                    let st_batch = session.prepare_batch(batch).await?;
+                    session.batch(&st_batch, values).await
+                }
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Check that every table the runtime uses exists, without running any DDL
+    async fn verify_tables(&self) -> Result<(), DatabaseError> {
+        // Unquoted names are stored lower-cased, entity tables are quoted
+        let query = "SELECT table_name FROM system_schema.tables WHERE keyspace_name = ?";
+        let existing: HashSet<String> = self
+            .query(query.to_string(), (self.keyspace.to_lowercase(),))
+            .await?
+            .rows()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|row| row.columns.into_iter().next().flatten())
+            .filter_map(|value| value.into_string())
+            .collect();
+
+        let mut expected: Vec<String> = self
+            .schemas
+            .get_entity_names()
+            .into_iter()
+            .map(|entity_type| format!("{}{}", self.table_prefix, entity_type))
+            .collect();
+        expected.sort();
+        for table in ["block_ptr", "datasources", "checkpoint", "reprocess"] {
+            expected.push(format!("{}{}", self.table_prefix, table));
+        }
+
+        let tables: Vec<String> = expected
+            .into_iter()
+            .filter(|table| !existing.contains(table))
+            .collect();
+        if !tables.is_empty() {
+            return Err(DatabaseError::MissingTables {
+                keyspace: self.keyspace.clone(),
+                tables,
+            });
+        }
+        Ok(())
+    }
+
     async fn create_keyspace(&self) -> Result<(), DatabaseError> {
         let q = format!(
             r#"
@@ -93,14 +453,49 @@ impl Scylladb {
             "#,
             self.keyspace
         );
-        self.session.query(q, []).await?;
+        self.query(q, []).await?;
         Ok(())
     }
 
+    /// `WITH` clause of the entity-tables, snapshots are read newest first
+    fn entity_table_options(compaction: Option<&ScyllaCompaction>) -> String {
+        let mut options = vec![
+            "compression = {'sstable_compression': 'LZ4Compressor'}".to_string(),
+            "CLUSTERING ORDER BY (__block_ptr__ DESC)".to_string(),
+        ];
+        let compaction = match compaction {
+            None => None,
+            Some(ScyllaCompaction::SizeTiered) => {
+                Some("{'class': 'SizeTieredCompactionStrategy'}".to_string())
+            }
+            Some(ScyllaCompaction::Leveled) => {
+                Some("{'class': 'LeveledCompactionStrategy'}".to_string())
+            }
+            Some(ScyllaCompaction::TimeWindow {
+                window_unit,
+                window_size,
+            }) => {
+                let window_unit = match window_unit {
+                    Some(CompactionWindowUnit::Minutes) => "MINUTES",
+                    Some(CompactionWindowUnit::Hours) => "HOURS",
+                    Some(CompactionWindowUnit::Days) | None => "DAYS",
+                };
+                Some(format!(
+                    "{{'class': 'TimeWindowCompactionStrategy', 'compaction_window_unit': '{window_unit}', 'compaction_window_size': {}}}",
+                    window_size.unwrap_or(1)
+                ))
+            }
+        };
+        if let Some(compaction) = compaction {
+            options.push(format!("compaction = {compaction}"));
+        }
+        options.join(" AND ")
+    }
+
     fn store_kind_to_db_type(field_kind: FieldKind) -> String {
         match field_kind.kind {
             StoreValueKind::Int => "int",
-            StoreValueKind::Int8 => "bigint",
+            StoreValueKind::Int8 | StoreValueKind::Timestamp => "bigint",
             StoreValueKind::String => "text",
             StoreValueKind::Bool => "boolean",
             StoreValueKind::BigDecimal => "text",
@@ -111,6 +506,7 @@ impl Scylladb {
                     kind: field_kind.list_inner_kind.unwrap(),
                     relation: None,
                     list_inner_kind: None,
+                    is_nullable: false,
                 });
                 return format!("list<{}>", inner_type);
             }
@@ -119,45 +515,109 @@ impl Scylladb {
         .to_string()
     }
 
-    fn cql_value_to_store_value(field_kind: FieldKind, value: Option<CqlValue>) -> Value {
-        match field_kind.kind {
-            StoreValueKind::Int => Value::Int(value.unwrap().as_int().unwrap()),
-            StoreValueKind::Int8 => Value::Int8(value.unwrap().as_bigint().unwrap()),
-            StoreValueKind::String => Value::String(value.unwrap().as_text().unwrap().to_owned()),
-            StoreValueKind::Bool => Value::Bool(value.unwrap().as_boolean().unwrap()),
-            StoreValueKind::BigDecimal => {
-                Value::BigDecimal(BigDecimal::from_str(value.unwrap().as_text().unwrap()).unwrap())
-            }
-            StoreValueKind::BigInt => {
-                Value::BigInt(BigInt::from_str(value.unwrap().as_text().unwrap()).unwrap())
-            }
-            StoreValueKind::Bytes => {
-                let bytes_value = value.unwrap();
-                let bytes = bytes_value.as_blob().unwrap();
-                Value::Bytes(Bytes::from(bytes.as_slice()))
+    /// A stored value that does not match its field's kind is reported instead of
+    /// panicking, so a single corrupt row does not take the runtime down
+    fn cql_value_to_store_value(
+        field_name: &str,
+        field_kind: FieldKind,
+        value: Option<CqlValue>,
+    ) -> Result<Value, DatabaseError> {
+        if value.is_none() && field_kind.is_nullable && field_kind.kind != StoreValueKind::Array {
+            return Ok(Value::Null);
+        }
+
+        let converted = match field_kind.kind {
+            StoreValueKind::Int => value.as_ref().and_then(|v| v.as_int()).map(Value::Int),
+            StoreValueKind::Int8 | StoreValueKind::Timestamp => {
+                value.as_ref().and_then(|v| v.as_bigint()).map(Value::Int8)
             }
+            StoreValueKind::String => value
+                .as_ref()
+                .and_then(|v| v.as_text())
+                .map(|text| Value::String(text.to_owned())),
+            StoreValueKind::Bool => value.as_ref().and_then(|v| v.as_boolean()).map(Value::Bool),
+            StoreValueKind::BigDecimal => value
+                .as_ref()
+                .and_then(|v| v.as_text())
+                .and_then(|text| BigDecimal::from_str(text).ok())
+                .map(Value::BigDecimal),
+            StoreValueKind::BigInt => value
+                .as_ref()
+                .and_then(|v| v.as_text())
+                .and_then(|text| BigInt::from_str(text).ok())
+                .map(Value::BigInt),
+            StoreValueKind::Bytes => value
+                .as_ref()
+                .and_then(|v| v.as_blob())
+                .map(|bytes| Value::Bytes(Bytes::from(bytes.as_slice()))),
             StoreValueKind::Array => {
-                if value.is_none() {
-                    return Value::List(vec![]);
-                }
-                let inner_values = value.unwrap().as_list().cloned().unwrap_or_default();
-                let inner_values = inner_values
+                let inner_values = match value {
+                    None => return Ok(Value::List(vec![])),
+                    Some(value) => value.as_list().cloned().unwrap_or_default(),
+                };
+                let inner_kind = FieldKind {
+                    kind: field_kind.list_inner_kind.unwrap(),
+                    relation: None,
+                    list_inner_kind: None,
+                    is_nullable: false,
+                };
+                return inner_values
                     .into_iter()
                     .map(|inner_val| {
                         Scylladb::cql_value_to_store_value(
-                            FieldKind {
-                                kind: field_kind.list_inner_kind.unwrap(),
-                                relation: None,
-                                list_inner_kind: None,
-                            },
+                            field_name,
+                            inner_kind.clone(),
                             Some(inner_val),
                         )
                     })
-                    .collect::<Vec<_>>();
-                Value::List(inner_values)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(Value::List);
             }
             StoreValueKind::Null => unimplemented!(),
+        };
+
+        match converted {
+            Some(converted) => Ok(converted),
+            None => {
+                warn!(
+                    Scylladb,
+                    "Invalid stored value";
+                    field_name => field_name,
+                    kind => format!("{:?}", field_kind.kind),
+                    value => format!("{:?}", value)
+                );
+                Err(DatabaseError::InvalidValue(field_name.to_owned()))
+            }
+        }
+    }
+
+    fn row_to_entity(
+        &self,
+        entity_type: &str,
+        col_specs: &[ColumnSpec],
+        row: Row,
+    ) -> Result<RawEntity, DatabaseError> {
+        let mut entity = RawEntity::new();
+        for (idx, column) in row.columns.into_iter().enumerate() {
+            let field_name = col_specs[idx].name.clone();
+            let field_kind = self.schemas.get_field(entity_type, &field_name);
+            let value = match column {
+                Some(CqlValue::Blob(data))
+                    if self.schemas.is_encrypted(entity_type, &field_name) =>
+                {
+                    self.cipher()?.decrypt(&field_name, &data)?
+                }
+                column => Scylladb::cql_value_to_store_value(&field_name, field_kind, column)?,
+            };
+            entity.insert(field_name, value);
         }
+        Ok(entity)
+    }
+
+    fn cipher(&self) -> Result<&FieldCipher, DatabaseError> {
+        self.cipher
+            .as_ref()
+            .ok_or(DatabaseError::MissingEncryptionKey)
     }
 
     fn handle_entity_query_result(
@@ -165,21 +625,13 @@ impl Scylladb {
         entity_type: &str,
         entity_query_result: QueryResult,
         include_deleted: bool,
-    ) -> Vec<RawEntity> {
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
         let col_specs = entity_query_result.col_specs.clone();
         let rows = entity_query_result.rows().expect("Not a record-query");
         let mut result = vec![];
 
         for row in rows {
-            let mut entity = RawEntity::new();
-            for (idx, column) in row.columns.iter().enumerate() {
-                let col_spec = col_specs[idx].clone();
-                let field_name = col_spec.name.clone();
-                let field_kind = self.schemas.get_field(entity_type, &field_name);
-                let value = Scylladb::cql_value_to_store_value(field_kind, column.clone());
-                entity.insert(field_name, value);
-            }
-
+            let entity = self.row_to_entity(entity_type, &col_specs, row)?;
             let is_deleted = entity
                 .get("__is_deleted__")
                 .cloned()
@@ -192,7 +644,7 @@ impl Scylladb {
             result.push(entity)
         }
 
-        result
+        Ok(result)
     }
 
     async fn insert_entity(
@@ -202,11 +654,13 @@ impl Scylladb {
         data: RawEntity,
         is_deleted: bool,
     ) -> Result<(), DatabaseError> {
-        assert!(data.contains_key("id"));
+        assert!(data.contains_key(self.schemas.get_primary_key(entity_type)));
         let mut data_raw = data.clone();
         data_raw.insert("__is_deleted__".to_string(), Value::Bool(is_deleted));
-        let (query, values) = self.generate_insert_query(entity_type, data_raw, block_ptr);
-        self.session.query(query, values).await?;
+        let (query, values) = self.generate_insert_query(entity_type, data_raw, block_ptr)?;
+        self.query(query, values)
+            .await
+            .map_err(|error| Scylladb::table_not_found(entity_type, error))?;
 
         Ok(())
     }
@@ -217,10 +671,12 @@ impl Scylladb {
         block_filter: &BlockPtrFilter,
     ) -> Result<HashSet<String>, DatabaseError> {
         let query = format!(
-            r#"SELECT id FROM {}."{}" WHERE {}"#,
-            self.keyspace, entity_type, block_filter
+            r#"SELECT {} FROM {} WHERE {}"#,
+            self.primary_key(entity_type),
+            self.entity_table(entity_type),
+            block_filter
         );
-        let rows = self.session.query(query, ()).await?.rows().unwrap();
+        let rows = self.read(query, ()).await?.rows().unwrap();
         let ids = rows
             .into_iter()
             .map(|r| {
@@ -241,11 +697,13 @@ impl Scylladb {
     async fn drop_tables(&self) -> Result<(), DatabaseError> {
         let entities = self.schemas.get_entity_names();
         for table_name in entities {
-            let query = format!(r#"DROP TABLE IF EXISTS {}."{}""#, self.keyspace, table_name);
-            self.session.query(query, ()).await?;
+            let query = format!(r#"DROP TABLE IF EXISTS {}"#, self.entity_table(&table_name));
+            self.query(query, ()).await?;
         }
-        let query = format!(r#"DROP TABLE IF EXISTS {}.block_ptr"#, self.keyspace);
-        self.session.query(query, ()).await?;
+        let query = format!(r#"DROP TABLE IF EXISTS {}"#, self.block_ptr_table());
+        self.query(query, ()).await?;
+        let query = format!(r#"DROP TABLE IF EXISTS {}"#, self.checkpoint_table());
+        self.query(query, ()).await?;
         Ok(())
     }
 
@@ -270,12 +728,46 @@ impl Scylladb {
             .await
     }
 
+    /// Split entities into batches of at most `chunk_size` statements.
+    /// With `per_entity_type`, each batch only contains a single entity type
+    /// so Scylla does not have to coordinate a batch across multiple tables
+    fn split_into_batches(
+        values: Vec<(EntityType, RawEntity)>,
+        chunk_size: usize,
+        per_entity_type: bool,
+    ) -> Vec<Vec<(EntityType, RawEntity)>> {
+        if !per_entity_type {
+            return values
+                .chunks(chunk_size)
+                .map(|chunk| chunk.to_vec())
+                .collect();
+        }
+
+        let mut grouped_values = BTreeMap::<EntityType, Vec<(EntityType, RawEntity)>>::new();
+        for (entity_type, data) in values {
+            grouped_values
+                .entry(entity_type.clone())
+                .or_default()
+                .push((entity_type, data));
+        }
+
+        grouped_values
+            .into_values()
+            .flat_map(|group| {
+                group
+                    .chunks(chunk_size)
+                    .map(|chunk| chunk.to_vec())
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
     fn generate_insert_query(
         &self,
         entity_type: &str,
         data: RawEntity,
         block_ptr: BlockPtr,
-    ) -> (String, Vec<CqlValue>) {
+    ) -> Result<(String, Vec<CqlValue>), DatabaseError> {
         let schema = self.schemas.get_schema(entity_type);
         let mut fields: Vec<String> = vec![
             "\"__block_ptr__\"".to_string(),
@@ -289,7 +781,8 @@ impl Scylladb {
         for (field_name, field_kind) in schema.iter() {
             let value = match data.get(field_name) {
                 None => {
-                    //handle case when field is missing but has in schema
+                    //handle case when field is missing but has in schema,
+                    //written as NULL if the field is nullable
                     debug!(
                         Scylladb,
                         "Missing field";
@@ -297,9 +790,18 @@ impl Scylladb {
                         field_name => field_name,
                         data => format!("{:?}", data)
                     );
-                    let default_value =
-                        Scylladb::cql_value_to_store_value(field_kind.clone(), None);
-                    CqlValue::from(default_value)
+                    match self.schemas.is_encrypted(entity_type, field_name) {
+                        true => CqlValue::Empty,
+                        false => CqlValue::from(Scylladb::cql_value_to_store_value(
+                            field_name,
+                            field_kind.clone(),
+                            None,
+                        )?),
+                    }
+                }
+                Some(Value::Null) => CqlValue::Empty,
+                Some(val) if self.schemas.is_encrypted(entity_type, field_name) => {
+                    CqlValue::Blob(self.cipher()?.encrypt(field_name, val)?)
                 }
                 Some(val) => CqlValue::from(val.clone()),
             };
@@ -313,11 +815,13 @@ impl Scylladb {
         let joint_column_values = column_values.join(",");
 
         let query = format!(
-            r#"INSERT INTO {}."{}" ({}) VALUES ({})"#,
-            self.keyspace, entity_type, joint_column_names, joint_column_values
+            r#"INSERT INTO {} ({}) VALUES ({})"#,
+            self.entity_table(entity_type),
+            joint_column_names,
+            joint_column_values
         );
 
-        (query, values_params)
+        Ok((query, values_params))
     }
 }
 
@@ -329,7 +833,10 @@ impl ExternDBTrait for Scylladb {
             let schema = self.schemas.get_schema(&entity_type);
             let mut column_definitions: Vec<String> = vec![];
             for (colum_name, store_kind) in schema.iter() {
-                let column_type = Scylladb::store_kind_to_db_type(store_kind.clone());
+                let column_type = match self.schemas.is_encrypted(&entity_type, colum_name) {
+                    true => "blob".to_string(),
+                    false => Scylladb::store_kind_to_db_type(store_kind.clone()),
+                };
                 let definition = format!("\"{colum_name}\" {column_type}");
                 column_definitions.push(definition);
             }
@@ -340,16 +847,36 @@ impl ExternDBTrait for Scylladb {
             column_definitions.push("__is_deleted__ boolean".to_string());
 
             // Define primary-key
-            column_definitions.push("PRIMARY KEY (id, __block_ptr__)".to_string());
+            column_definitions.push(format!(
+                "PRIMARY KEY ({}, __block_ptr__)",
+                self.primary_key(&entity_type)
+            ));
 
             let joint_column_definition = column_definitions.join(",\n");
             let query = format!(
-                r#"CREATE TABLE IF NOT EXISTS {}."{}" (
+                r#"CREATE TABLE IF NOT EXISTS {} (
             {joint_column_definition}
-            ) WITH compression = {{'sstable_compression': 'LZ4Compressor'}} AND CLUSTERING ORDER BY (__block_ptr__ DESC)"#,
-                self.keyspace, entity_type
+            ) WITH {}"#,
+                self.entity_table(&entity_type),
+                Scylladb::entity_table_options(self.compaction.as_ref())
             );
-            self.session.query(query, &[]).await?;
+            self.query(query, &[]).await?;
+
+            if self.time_range_queries && self.schemas.has_block_timestamp_field(&entity_type) {
+                let query = format!(
+                    r#"CREATE INDEX IF NOT EXISTS ON {} (__block_timestamp__)"#,
+                    self.entity_table(&entity_type)
+                );
+                self.query(query, &[]).await?;
+            }
+
+            for field in self.schemas.get_indexed_fields(&entity_type) {
+                let query = format!(
+                    r#"CREATE INDEX IF NOT EXISTS ON {} ("{field}")"#,
+                    self.entity_table(&entity_type)
+                );
+                self.query(query, &[]).await?;
+            }
         }
 
         Ok(())
@@ -362,7 +889,7 @@ impl ExternDBTrait for Scylladb {
     async fn create_block_ptr_table(&self) -> Result<(), DatabaseError> {
         let query = format!(
             r#"
-            CREATE TABLE IF NOT EXISTS {}.block_ptr (
+            CREATE TABLE IF NOT EXISTS {} (
                 sgd text,
                 block_number bigint,
                 block_hash text,
@@ -370,9 +897,55 @@ impl ExternDBTrait for Scylladb {
                 PRIMARY KEY (sgd, block_number)
             ) WITH compression = {{'sstable_compression': 'LZ4Compressor'}} AND CLUSTERING ORDER BY (block_number DESC)
             "#,
-            self.keyspace
+            self.block_ptr_table()
+        );
+        self.query(query, ()).await?;
+        Ok(())
+    }
+
+    async fn create_datasource_table(&self) -> Result<(), DatabaseError> {
+        let query = format!(
+            r#"
+            CREATE TABLE IF NOT EXISTS {} (
+                key text PRIMARY KEY,
+                created_at_block bigint,
+                datasource text
+            )"#,
+            self.datasource_table()
+        );
+        self.query(query, ()).await?;
+        Ok(())
+    }
+
+    /// A single row, overwritten by every checkpoint
+    async fn create_checkpoint_table(&self) -> Result<(), DatabaseError> {
+        let query = format!(
+            r#"
+            CREATE TABLE IF NOT EXISTS {} (
+                sgd text PRIMARY KEY,
+                block_number bigint,
+                block_hash text,
+                parent_hash text,
+                cursor text
+            )"#,
+            self.checkpoint_table()
+        );
+        self.query(query, ()).await?;
+        Ok(())
+    }
+
+    /// A single row, overwritten by every reprocess
+    async fn create_reprocess_table(&self) -> Result<(), DatabaseError> {
+        let query = format!(
+            r#"
+            CREATE TABLE IF NOT EXISTS {} (
+                sgd text PRIMARY KEY,
+                from_block bigint,
+                to_block bigint
+            )"#,
+            self.reprocess_table()
         );
-        self.session.query(query, ()).await?;
+        self.query(query, ()).await?;
         Ok(())
     }
 
@@ -383,19 +956,23 @@ impl ExternDBTrait for Scylladb {
     ) -> Result<Option<RawEntity>, DatabaseError> {
         let query = format!(
             r#"
-            SELECT * from {}."{}"
-            WHERE id = ?
+            SELECT * from {}
+            WHERE {} = ?
             ORDER BY __block_ptr__ DESC
             LIMIT 1
             "#,
-            self.keyspace, entity_type
+            self.entity_table(entity_type),
+            self.primary_key(entity_type)
         );
 
-        let entity_query_result = self.session.query(query, (entity_id,)).await;
+        let entity_query_result = self
+            .read(query, (entity_id,))
+            .await
+            .map_err(|error| Scylladb::table_not_found(entity_type, error));
         match entity_query_result {
             Ok(result) => {
                 let entity = self
-                    .handle_entity_query_result(entity_type, result, false)
+                    .handle_entity_query_result(entity_type, result, false)?
                     .first()
                     .cloned();
                 Ok(entity)
@@ -407,7 +984,7 @@ impl ExternDBTrait for Scylladb {
                     entity_id => entity_id,
                     error => format!("{:?}", err)
                 );
-                Err(err.into())
+                Err(err)
             }
         }
     }
@@ -427,16 +1004,16 @@ impl ExternDBTrait for Scylladb {
         block_ptr: BlockPtr,
         values: Vec<(String, RawEntity)>,
     ) -> Result<(), DatabaseError> {
-        let mut inserts = vec![];
-        let chunk_size = 100;
-        let chunks = values.chunks(chunk_size);
+        let mut batches = vec![];
+        let statement_count = values.len();
+        let chunks =
+            Scylladb::split_into_batches(values, self.batch_size, self.batch_per_entity_type);
 
         for chunk in chunks {
             let mut batch_queries = Batch::default();
             let mut batch_values = vec![];
-            let session = self.session.clone();
 
-            for (entity_type, data) in chunk.iter().cloned() {
+            for (entity_type, data) in chunk.into_iter() {
                 if data.get("__is_deleted__").is_none() {
                     error!(ExternDB,
                            "Missing is_deleted field";
@@ -449,36 +1026,46 @@ impl ExternDBTrait for Scylladb {
                 }
 
                 let (query, values) =
-                    self.generate_insert_query(&entity_type, data, block_ptr.clone());
+                    self.generate_insert_query(&entity_type, data, block_ptr.clone())?;
                 batch_queries.append_statement(query.as_str());
                 batch_values.push(values);
             }
 
-            let st = session.prepare_batch(&batch_queries).await?;
-            let insert = tokio::spawn(async move {
+            let st = self.session.get().prepare_batch(&batch_queries).await?;
+            batches.push((st, batch_values));
+        }
+
+        // At most `max_inflight_batches` are sent at once, so catching up does not flood the cluster
+        let inserts = batches.into_iter().map(|(st, batch_values)| {
+            let session = self.session.clone();
+            async move {
                 Retry::spawn(ExponentialBackoff::from_millis(100), || {
-                    session.batch(&st, batch_values.clone())
+                    session.with_reconnect(|current| {
+                        let st = st.clone();
+                        let values = batch_values.clone();
+                        async move { current.batch(&st, values).await }
+                    })
                 })
                 .await
-            });
-
-            inserts.push(insert);
-        }
-
-        let result = try_join_all(inserts).await.unwrap();
+            }
+        });
+        let result = futures_util::stream::iter(inserts)
+            .buffer_unordered(self.max_inflight_batches)
+            .collect::<Vec<_>>()
+            .await;
         info!(
             Scylladb,
             "Commit result";
-            statements => format!("{:?} statements", result.len() * chunk_size),
+            statements => format!("{:?} statements", statement_count),
             batch => format!("{:?} batches", result.len()),
-            ok_batch => format!("{:?}", result.iter().filter(|r| r.is_ok()).collect::<Vec<_>>().len()),
+            ok_batch => format!("{:?}", result.iter().filter(|r| r.is_ok()).count()),
             fail_batch => format!("{:?}", result.iter().filter(|r| r.is_err()).collect::<Vec<_>>())
         );
 
         Ok(())
     }
 
-    async fn revert_from_block(&self, from_block: u64) -> Result<(), DatabaseError> {
+    async fn revert_from_block(&self, from_block: u64) -> Result<usize, DatabaseError> {
         let entity_names = self.schemas.get_entity_names();
         let mut batch_queries: Batch = Batch::default();
         let mut batch_values = vec![];
@@ -490,145 +1077,478 @@ impl ExternDBTrait for Scylladb {
             for id in ids {
                 let query = format!(
                     r#"
-                    DELETE FROM {}."{}" WHERE id = ? AND {}"#,
-                    self.keyspace, entity_type, block_ptr_filter
+                    DELETE FROM {} WHERE {} = ? AND {}"#,
+                    self.entity_table(&entity_type),
+                    self.primary_key(&entity_type),
+                    block_ptr_filter
                 );
                 batch_queries.append_statement(query.as_str());
                 batch_values.push((id,));
             }
         }
-        let st_batch = self.session.prepare_batch(&batch_queries).await?;
-        self.session.batch(&st_batch, batch_values).await?;
-        Ok(())
+        let count = batch_values.len();
+        self.batch(&batch_queries, batch_values).await?;
+        Ok(count)
     }
 
     async fn save_block_ptr(&self, block_ptr: BlockPtr) -> Result<(), DatabaseError> {
         let partition_key = "dfr";
         let query = format!(
             r#"
-            INSERT INTO {}.block_ptr (sgd, block_number, block_hash, parent_hash) VALUES ('{partition_key}', ?, ?, ?)"#,
-            self.keyspace
+            INSERT INTO {} (sgd, block_number, block_hash, parent_hash) VALUES ('{partition_key}', ?, ?, ?)"#,
+            self.block_ptr_table()
         );
-        self.session
-            .query(
-                query,
-                (
-                    block_ptr.number as i64,
-                    block_ptr.hash,
-                    block_ptr.parent_hash,
-                ),
-            )
-            .await?;
+        self.query(
+            query,
+            (
+                block_ptr.number as i64,
+                block_ptr.hash,
+                block_ptr.parent_hash,
+            ),
+        )
+        .await?;
         Ok(())
     }
 
-    async fn load_entities(
-        &self,
-        entity_type: &str,
-        ids: Vec<String>,
-    ) -> Result<Vec<RawEntity>, DatabaseError> {
-        let ids = format!(
-            "({})",
-            ids.into_iter()
-                .map(|e| format!("'{}'", e))
-                .collect::<Vec<_>>()
-                .join(",")
-        );
+    async fn save_checkpoint(&self, checkpoint: Checkpoint) -> Result<(), DatabaseError> {
         let query = format!(
             r#"
-            SELECT * from {}."{}"
-            WHERE id IN {}"#,
-            self.keyspace, entity_type, ids
+            INSERT INTO {} (sgd, block_number, block_hash, parent_hash, cursor) VALUES ('dfr', ?, ?, ?, ?)"#,
+            self.checkpoint_table()
         );
-        let entity_query_result = self.session.query(query, ()).await?;
-        Ok(self.handle_entity_query_result(entity_type, entity_query_result, false))
+        let block_ptr = checkpoint.block_ptr;
+        self.query(
+            query,
+            (
+                block_ptr.number as i64,
+                block_ptr.hash,
+                block_ptr.parent_hash,
+                checkpoint.cursor,
+            ),
+        )
+        .await?;
+        Ok(())
     }
 
-    async fn load_recent_block_ptrs(
-        &self,
-        number_of_blocks: u16,
-    ) -> Result<Vec<BlockPtr>, DatabaseError> {
+    async fn load_checkpoint(&self) -> Result<Option<Checkpoint>, DatabaseError> {
         let query = format!(
-            "SELECT JSON block_number as number, block_hash as hash, parent_hash FROM {}.block_ptr LIMIT {};",
-            self.keyspace, number_of_blocks
+            r#"
+            SELECT block_number, block_hash, parent_hash, cursor FROM {} WHERE sgd = 'dfr'"#,
+            self.checkpoint_table()
         );
-        let result = self.session.query(query, &[]).await?;
-
-        if let Ok(mut rows) = result.rows() {
-            let block_ptrs = rows
-                .iter_mut()
-                .rev()
-                .filter_map(|r| {
-                    let json = r
-                        .columns
-                        .first()
-                        .cloned()
-                        .unwrap()
-                        .unwrap()
-                        .as_text()
-                        .cloned()
-                        .unwrap();
-                    serde_json::from_str::<BlockPtr>(&json).ok()
-                })
-                .collect::<Vec<_>>();
+        let row = match self
+            .read(query, &[])
+            .await?
+            .rows()
+            .unwrap_or_default()
+            .pop()
+        {
+            Some(row) => row,
+            None => return Ok(None),
+        };
 
-            return Ok(block_ptrs);
+        let mut columns = row.columns.into_iter();
+        let number = columns.next().flatten().and_then(|v| v.as_bigint());
+        let hash = columns.next().flatten().and_then(|v| v.into_string());
+        let parent_hash = columns.next().flatten().and_then(|v| v.into_string());
+        let cursor = columns.next().flatten().and_then(|v| v.into_string());
+        match (number, hash, parent_hash, cursor) {
+            (Some(number), Some(hash), Some(parent_hash), Some(cursor)) => Ok(Some(Checkpoint {
+                block_ptr: BlockPtr {
+                    number: number as u64,
+                    hash,
+                    parent_hash,
+                },
+                cursor,
+            })),
+            _ => Err(DatabaseError::MissingField("checkpoint".to_string())),
         }
-
-        Ok(vec![])
     }
 
-    async fn get_earliest_block_ptr(&self) -> Result<Option<BlockPtr>, DatabaseError> {
-        let min_block_number = self
-            .session
-            .query(
-                format!("SELECT min(block_number) FROM {}.block_ptr", self.keyspace),
-                &[],
-            )
+    async fn save_reprocessed_range(&self, range: ReprocessedRange) -> Result<(), DatabaseError> {
+        let query = format!(
+            "INSERT INTO {} (sgd, from_block, to_block) VALUES ('dfr', ?, ?)",
+            self.reprocess_table()
+        );
+        self.query(query, (range.from_block as i64, range.to_block as i64))
             .await?;
-        let row = min_block_number.first_row().unwrap();
-        let column = row.columns.get(0).cloned().unwrap();
+        Ok(())
+    }
 
-        if column.is_none() {
-            return Ok(None);
+    async fn load_reprocessed_range(&self) -> Result<Option<ReprocessedRange>, DatabaseError> {
+        let query = format!(
+            "SELECT from_block, to_block FROM {} WHERE sgd = 'dfr'",
+            self.reprocess_table()
+        );
+        let row = match self
+            .read(query, &[])
+            .await?
+            .rows()
+            .unwrap_or_default()
+            .pop()
+        {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+
+        let mut columns = row.columns.into_iter();
+        let from_block = columns.next().flatten().and_then(|v| v.as_bigint());
+        let to_block = columns.next().flatten().and_then(|v| v.as_bigint());
+        match (from_block, to_block) {
+            (Some(from_block), Some(to_block)) => Ok(Some(ReprocessedRange {
+                from_block: from_block as u64,
+                to_block: to_block as u64,
+            })),
+            _ => Err(DatabaseError::MissingField("reprocess".to_string())),
         }
+    }
 
-        let block_number = column.unwrap().as_bigint().unwrap() as u64;
+    async fn save_datasources(&self, datasources: Vec<Datasource>) -> Result<(), DatabaseError> {
         let query = format!(
-            r#"
-SELECT JSON block_number as number, block_hash as hash, parent_hash
-FROM {}.block_ptr
-WHERE sgd = ? AND block_number = {}"#,
-            self.keyspace, block_number
+            "INSERT INTO {} (key, created_at_block, datasource) VALUES (?, ?, ?) IF NOT EXISTS",
+            self.datasource_table()
         );
-        let result = self.session.query(query, vec!["dfr".to_string()]).await?;
-        let row = result.first_row().unwrap();
-        let data = row.columns.get(0).cloned().unwrap();
-        let text = data.unwrap().into_string().unwrap();
-        return Ok(serde_json::from_str(&text).ok());
+        for ds in datasources {
+            let created_at_block = ds.source.startBlock.unwrap_or_default();
+            let key = format!(
+                "{}:{}:{}",
+                ds.name,
+                ds.source.address.clone().unwrap_or_default(),
+                created_at_block
+            );
+            let datasource =
+                serde_json::to_string(&ds).map_err(|e| DatabaseError::Plain(e.to_string()))?;
+            self.query(query.clone(), (key, created_at_block as i64, datasource))
+                .await?;
+        }
+        Ok(())
     }
 
-    async fn remove_snapshots(
-        &self,
-        entities: Vec<(EntityType, EntityID)>,
-        to_block: u64,
-    ) -> Result<usize, DatabaseError> {
-        let mut batch_queries: Batch = Batch::default();
-        let mut batch_values = vec![];
-        let block_ptr_filter = BlockPtrFilter::Lt(to_block);
+    async fn load_datasources(&self) -> Result<Option<Vec<Datasource>>, DatabaseError> {
+        let query = format!(
+            "SELECT created_at_block, datasource FROM {}",
+            self.datasource_table()
+        );
+        let rows = self.read(query, &[]).await?.rows().unwrap_or_default();
+
+        let mut result = vec![];
+        for row in rows {
+            let mut columns = row.columns.into_iter();
+            let created_at_block = columns.next().flatten().and_then(|v| v.as_bigint());
+            let datasource = columns.next().flatten().and_then(|v| v.into_string());
+            let datasource = match datasource {
+                Some(datasource) => datasource,
+                None => return Err(DatabaseError::MissingField("datasource".to_string())),
+            };
+            let datasource: Datasource = serde_json::from_str(&datasource)
+                .map_err(|e| DatabaseError::Plain(e.to_string()))?;
+            result.push((created_at_block.unwrap_or_default(), datasource));
+        }
+
+        if result.is_empty() {
+            return Ok(None);
+        }
+
+        // Rows are not ordered across partitions, datasources are restored in creation order
+        result.sort_by_key(|(created_at_block, _)| *created_at_block);
+        Ok(Some(result.into_iter().map(|(_, ds)| ds).collect()))
+    }
+
+    async fn load_entities(
+        &self,
+        entity_type: &str,
+        ids: Vec<String>,
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
+        let mut seen = HashSet::new();
+        let ids = ids
+            .into_iter()
+            .filter(|id| seen.insert(id.clone()))
+            .map(CqlValue::Text)
+            .collect::<Vec<_>>();
+        let query = format!(
+            r#"SELECT * FROM {} WHERE {} IN ? PER PARTITION LIMIT 1"#,
+            self.entity_table(entity_type),
+            self.primary_key(entity_type)
+        );
+
+        // Long id lists are split, the cluster rejects or crawls through a huge `IN` query
+        let loads = ids
+            .chunks(self.load_chunk_size)
+            .map(|chunk| self.read(query.clone(), (CqlValue::List(chunk.to_vec()),)));
+        let results = futures_util::stream::iter(loads)
+            .buffer_unordered(self.max_inflight_batches)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut entities = vec![];
+        for result in results {
+            let result = result.map_err(|error| Scylladb::table_not_found(entity_type, error))?;
+            entities.extend(self.handle_entity_query_result(entity_type, result, false)?);
+        }
+        Ok(entities)
+    }
+
+    async fn load_entity_history(
+        &self,
+        entity_type: &str,
+        entity_id: &str,
+    ) -> Result<Vec<(u64, RawEntity)>, DatabaseError> {
+        let query = format!(
+            r#"
+            SELECT * from {}
+            WHERE {} = ?
+            ORDER BY __block_ptr__ ASC
+            "#,
+            self.entity_table(entity_type),
+            self.primary_key(entity_type)
+        );
+        let entity_query_result = self
+            .read(query, (entity_id,))
+            .await
+            .map_err(|error| Scylladb::table_not_found(entity_type, error))?;
+        let history = self
+            .handle_entity_query_result(entity_type, entity_query_result, true)?
+            .into_iter()
+            .map(|entity| match entity.get("__block_ptr__") {
+                Some(Value::Int8(block_number)) => Ok((*block_number as u64, entity)),
+                _ => Err(DatabaseError::MissingField("__block_ptr__".to_string())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(history)
+    }
+
+    async fn load_deleted_entities(
+        &self,
+        entity_type: &str,
+    ) -> Result<Vec<(u64, RawEntity)>, DatabaseError> {
+        let query = format!(
+            r#"
+            SELECT * from {}
+            WHERE __is_deleted__ = true
+            ALLOW FILTERING
+            "#,
+            self.entity_table(entity_type)
+        );
+        let entity_query_result = self
+            .read(query, ())
+            .await
+            .map_err(|error| Scylladb::table_not_found(entity_type, error))?;
+        self.handle_entity_query_result(entity_type, entity_query_result, true)?
+            .into_iter()
+            .map(|entity| match entity.get("__block_ptr__") {
+                Some(Value::Int8(block_number)) => Ok((*block_number as u64, entity)),
+                _ => Err(DatabaseError::MissingField("__block_ptr__".to_string())),
+            })
+            .collect()
+    }
+
+    async fn load_entities_by_time_range(
+        &self,
+        entity_type: &str,
+        from_timestamp: u64,
+        to_timestamp: u64,
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
+        // Filters the whole table through a secondary index, see `time_range_queries`
+        if !self.time_range_queries {
+            return Err(DatabaseError::TimeRangeQueriesDisabled);
+        }
+        let query = format!(
+            r#"
+            SELECT * from {}
+            WHERE __block_timestamp__ >= ? AND __block_timestamp__ <= ?
+            ALLOW FILTERING
+            "#,
+            self.entity_table(entity_type)
+        );
+        let entity_query_result = self
+            .read(query, (from_timestamp as i64, to_timestamp as i64))
+            .await?;
+        self.handle_entity_query_result(entity_type, entity_query_result, false)
+    }
+
+    async fn load_entities_by_field(
+        &self,
+        entity_type: &str,
+        field: &str,
+        value: Value,
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
+        // The index matches every snapshot that ever had the value,
+        // so it is only used to find the candidate entity ids
+        let ids = self
+            .load_entity_ids_by_field(entity_type, field, value.clone())
+            .await?
+            .into_iter()
+            .map(CqlValue::Text)
+            .collect::<Vec<_>>();
+
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let query = format!(
+            r#"SELECT * FROM {} WHERE {} IN ? PER PARTITION LIMIT 1"#,
+            self.entity_table(entity_type),
+            self.primary_key(entity_type)
+        );
+        let result = self.read(query, (CqlValue::List(ids),)).await?;
+        let entities = self
+            .handle_entity_query_result(entity_type, result, false)?
+            .into_iter()
+            .filter(|entity| entity.get(field) == Some(&value))
+            .collect();
+        Ok(entities)
+    }
+
+    async fn load_entity_ids_by_field(
+        &self,
+        entity_type: &str,
+        field: &str,
+        value: Value,
+    ) -> Result<Vec<EntityID>, DatabaseError> {
+        if !self.schemas.is_indexed(entity_type, field) {
+            return Err(DatabaseError::FieldNotIndexed(
+                entity_type.to_owned(),
+                field.to_owned(),
+            ));
+        }
+
+        let query = format!(
+            r#"SELECT {} FROM {} WHERE "{}" = ?"#,
+            self.primary_key(entity_type),
+            self.entity_table(entity_type),
+            field
+        );
+        let result = self
+            .read(query, (CqlValue::from(value),))
+            .await
+            .map_err(|error| Scylladb::table_not_found(entity_type, error))?;
+        let mut ids: Vec<EntityID> = vec![];
+        for row in result.rows().unwrap_or_default() {
+            let id = row.columns.into_iter().next().flatten();
+            if let Some(id) = id.and_then(|id| id.into_string()) {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    async fn load_recent_block_ptrs(
+        &self,
+        number_of_blocks: u16,
+    ) -> Result<Vec<BlockPtr>, DatabaseError> {
+        let query = format!(
+            "SELECT JSON block_number as number, block_hash as hash, parent_hash FROM {} LIMIT {};",
+            self.block_ptr_table(),
+            number_of_blocks
+        );
+        let result = self.read(query, &[]).await?;
+
+        if let Ok(mut rows) = result.rows() {
+            let block_ptrs = rows
+                .iter_mut()
+                .rev()
+                .filter_map(|r| {
+                    let json = r
+                        .columns
+                        .first()
+                        .cloned()
+                        .unwrap()
+                        .unwrap()
+                        .as_text()
+                        .cloned()
+                        .unwrap();
+                    serde_json::from_str::<BlockPtr>(&json).ok()
+                })
+                .collect::<Vec<_>>();
+
+            return Ok(block_ptrs);
+        }
+
+        Ok(vec![])
+    }
+
+    async fn get_earliest_block_ptr(&self) -> Result<Option<BlockPtr>, DatabaseError> {
+        let min_block_number = self
+            .read(
+                format!("SELECT min(block_number) FROM {}", self.block_ptr_table()),
+                &[],
+            )
+            .await?;
+        let row = min_block_number.first_row().unwrap();
+        let column = row.columns.get(0).cloned().unwrap();
+
+        if column.is_none() {
+            return Ok(None);
+        }
+
+        let block_number = column.unwrap().as_bigint().unwrap() as u64;
+        let query = format!(
+            r#"
+SELECT JSON block_number as number, block_hash as hash, parent_hash
+FROM {}
+WHERE sgd = ? AND block_number = {}"#,
+            self.block_ptr_table(),
+            block_number
+        );
+        let result = self.read(query, vec!["dfr".to_string()]).await?;
+        let row = result.first_row().unwrap();
+        let data = row.columns.get(0).cloned().unwrap();
+        let text = data.unwrap().into_string().unwrap();
+        return Ok(serde_json::from_str(&text).ok());
+    }
+
+    async fn find_block_ptr_gaps(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<u64>, DatabaseError> {
+        let query = format!(
+            "SELECT block_number FROM {} WHERE sgd = ? AND block_number >= ? AND block_number <= ?",
+            self.block_ptr_table()
+        );
+        let rows = self
+            .read(
+                query,
+                ("dfr".to_string(), from_block as i64, to_block as i64),
+            )
+            .await?
+            .rows()
+            .unwrap_or_default();
+        let stored = rows.into_iter().filter_map(|row| {
+            row.columns
+                .first()
+                .cloned()
+                .flatten()
+                .and_then(|v| v.as_bigint())
+                .map(|number| number as u64)
+        });
+        Ok(super::missing_block_numbers(from_block, to_block, stored))
+    }
+
+    async fn remove_snapshots(
+        &self,
+        entities: Vec<(EntityType, EntityID)>,
+        to_block: u64,
+    ) -> Result<usize, DatabaseError> {
+        let mut batch_queries: Batch = Batch::default();
+        let mut batch_values = vec![];
+        let block_ptr_filter = BlockPtrFilter::Lt(to_block);
         let mut count = 0;
         for (entity_name, entity_id) in entities {
             let query = format!(
-                "DELETE FROM {}.\"{}\" WHERE id = ? AND {}",
-                self.keyspace, entity_name, block_ptr_filter
+                "DELETE FROM {} WHERE {} = ? AND {}",
+                self.entity_table(&entity_name),
+                self.primary_key(&entity_name),
+                block_ptr_filter
             );
             batch_queries.append_statement(query.as_str());
             batch_values.push((entity_id,));
             count += 1;
         }
 
-        let st_batch = self.session.prepare_batch(&batch_queries).await?;
-        self.session.batch(&st_batch, batch_values).await?;
+        self.batch(&batch_queries, batch_values).await?;
         Ok(count)
     }
 
@@ -646,21 +1566,1010 @@ WHERE sgd = ? AND block_number = {}"#,
             for id in ids {
                 let query = format!(
                     r#"
-                    DELETE FROM {}."{}" WHERE id = ? AND {}"#,
-                    self.keyspace, entity_type, block_ptr_filter
+                    DELETE FROM {} WHERE {} = ? AND {}"#,
+                    self.entity_table(&entity_type),
+                    self.primary_key(&entity_type),
+                    block_ptr_filter
                 );
                 batch_queries.append_statement(query.as_str());
                 batch_values.push((id,));
             }
         }
         let query = format!(
-            "DELETE FROM {}.block_ptr WHERE sgd = ? AND block_number < {to_block}",
-            self.keyspace
+            "DELETE FROM {} WHERE sgd = ? AND block_number < {to_block}",
+            self.block_ptr_table()
         );
         batch_queries.append_statement(query.as_str());
         batch_values.push(("dfr".to_string(),));
-        let st_batch = self.session.prepare_batch(&batch_queries).await?;
-        self.session.batch(&st_batch, batch_values).await?;
+        self.batch(&batch_queries, batch_values).await?;
         Ok(count as u64)
     }
+
+    async fn export_snapshot<W: Write + Send>(
+        &self,
+        writer: &mut W,
+    ) -> Result<usize, DatabaseError> {
+        let mut count = 0;
+        for entity_type in self.schemas.get_entity_names() {
+            let query = format!("SELECT * FROM {}", self.entity_table(&entity_type));
+            let mut rows = self
+                .read_session
+                .with_reconnect(|session| {
+                    let query = query.clone();
+                    async move { session.query_iter(query, ()).await }
+                })
+                .await?;
+            let col_specs = rows.get_column_specs().to_vec();
+            let primary_key = self.schemas.get_primary_key(&entity_type);
+            let mut last_id = None;
+
+            while let Some(row) = rows.next().await {
+                let entity = self.row_to_entity(&entity_type, &col_specs, row?)?;
+
+                // Snapshots of an entity are clustered by `__block_ptr__` DESC,
+                // so only the first row of every id is its latest snapshot
+                if last_id.as_ref() == entity.get(primary_key) {
+                    continue;
+                }
+                last_id = entity.get(primary_key).cloned();
+
+                if entity.get("__is_deleted__") == Some(&Value::Bool(true)) {
+                    continue;
+                }
+
+                let record = SnapshotRecord {
+                    entity_type: entity_type.clone(),
+                    entity,
+                };
+                record.write(writer)?;
+                count += 1;
+            }
+        }
+
+        info!(Scylladb, "entities snapshot exported"; count => count);
+        Ok(count)
+    }
+
+    async fn import_snapshot<R: BufRead + Send>(&self, reader: R) -> Result<usize, DatabaseError> {
+        let mut count = 0;
+        for record in SnapshotRecord::read_lines(reader) {
+            let record = record?;
+            let block_ptr = record.block_ptr();
+            self.insert_entity(block_ptr, &record.entity_type, record.entity, false)
+                .await?;
+            count += 1;
+        }
+
+        info!(Scylladb, "entities snapshot imported"; count => count);
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::Schema;
+    use crate::entity;
+    use crate::schema;
+    use std::env;
+
+    fn test_config() -> ScyllaConfig {
+        ScyllaConfig {
+            uri: env::var("SCYLLA_URI").unwrap_or("localhost:9042".to_string()),
+            keyspace: env::var("SCYLLA_KEYSPACE").unwrap_or("ks".to_string()),
+            batch_per_entity_type: None,
+            db_query_timeout: None,
+            prefix_tables: None,
+            read_uri: None,
+            read_consistency: None,
+            write_consistency: None,
+            bootstrap_schema: None,
+            encryption_key: None,
+            compaction: None,
+            local_datacenter: None,
+            batch_size: None,
+            max_inflight_batches: None,
+            load_chunk_size: None,
+            time_range_queries: None,
+        }
+    }
+
+    async fn setup(batch_per_entity_type: bool) -> Result<Scylladb, DatabaseError> {
+        env_logger::try_init().unwrap_or_default();
+        let cfg = ScyllaConfig {
+            batch_per_entity_type: Some(batch_per_entity_type),
+            ..test_config()
+        };
+        setup_with_config(&cfg, "test").await
+    }
+
+    async fn setup_with_config(
+        cfg: &ScyllaConfig,
+        subgraph_name: &str,
+    ) -> Result<Scylladb, DatabaseError> {
+        let mut schema = Schemas::default();
+
+        let token_schema: Schema = schema!(
+            id => StoreValueKind::String,
+            name => StoreValueKind::String,
+            total_supply => StoreValueKind::BigInt
+        );
+        schema.add_schema("Token", token_schema);
+
+        let pool_schema: Schema = schema!(
+            id => StoreValueKind::String,
+            fee => StoreValueKind::Int8
+        );
+        schema.add_schema("Pool", pool_schema);
+
+        let swap_schema: Schema = schema!(
+            id => StoreValueKind::String,
+            timestamp => StoreValueKind::Timestamp
+        );
+        schema.add_schema("Swap", swap_schema);
+
+        let mut position_schema: Schema = schema!(
+            id => StoreValueKind::String,
+            owner => StoreValueKind::String,
+            ticks => StoreValueKind::Array
+        );
+        position_schema.get_mut("ticks").unwrap().list_inner_kind = Some(StoreValueKind::Int8);
+        schema.add_schema("Position", position_schema);
+        schema.set_indexed("Position", "owner");
+
+        let db = Scylladb::new(cfg, schema, subgraph_name).await?;
+        db.drop_tables().await?;
+        db.create_entity_tables().await?;
+        db.create_block_ptr_table().await?;
+        db.create_checkpoint_table().await?;
+        Ok(db)
+    }
+
+    fn mixed_entities(count: usize) -> Vec<(EntityType, RawEntity)> {
+        let mut values = vec![];
+        for i in 0..count {
+            let token: RawEntity = entity! {
+                id => Value::String(format!("token_{i}")),
+                name => Value::String(format!("Token #{i}")),
+                total_supply => Value::BigInt(BigInt::from(i as u64)),
+                __is_deleted__ => Value::Bool(false)
+            };
+            values.push(("Token".to_string(), token));
+
+            let pool: RawEntity = entity! {
+                id => Value::String(format!("pool_{i}")),
+                fee => Value::Int8(i as i64),
+                __is_deleted__ => Value::Bool(false)
+            };
+            values.push(("Pool".to_string(), pool));
+        }
+        values
+    }
+
+    #[tokio::test]
+    async fn test_query_timeout() {
+        env_logger::try_init().unwrap_or_default();
+        // NOTE: a zero timeout elapses before any response can arrive
+        let cfg = ScyllaConfig {
+            db_query_timeout: Some(0),
+            ..test_config()
+        };
+
+        let result = Scylladb::new(&cfg, Schemas::default(), "test").await;
+        assert!(
+            matches!(result, Err(DatabaseError::Timeout(msg)) if msg.contains("CREATE KEYSPACE"))
+        );
+    }
+
+    #[test]
+    fn test_split_into_batches() {
+        env_logger::try_init().unwrap_or_default();
+
+        let batches = Scylladb::split_into_batches(mixed_entities(10), 100, false);
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].len(), 20);
+
+        let batches = Scylladb::split_into_batches(mixed_entities(10), 100, true);
+        assert_eq!(batches.len(), 2);
+        for batch in batches.iter() {
+            assert_eq!(batch.len(), 10);
+            let entity_type = batch[0].0.clone();
+            assert!(batch.iter().all(|(t, _)| *t == entity_type));
+        }
+
+        let batches = Scylladb::split_into_batches(mixed_entities(150), 100, true);
+        assert_eq!(batches.len(), 4);
+        assert_eq!(batches.iter().map(|b| b.len()).sum::<usize>(), 300);
+        for batch in batches.iter() {
+            let entity_type = batch[0].0.clone();
+            assert!(batch.iter().all(|(t, _)| *t == entity_type));
+        }
+    }
+
+    #[test]
+    fn test_int8_list_mapping() {
+        let field_kind = FieldKind {
+            kind: StoreValueKind::Array,
+            relation: None,
+            list_inner_kind: Some(StoreValueKind::Int8),
+            is_nullable: false,
+        };
+        assert_eq!(
+            Scylladb::store_kind_to_db_type(field_kind.clone()),
+            "list<bigint>"
+        );
+
+        let ticks = Value::List(vec![
+            Value::Int8(i64::MIN),
+            Value::Int8(-887272),
+            Value::Int8(0),
+            Value::Int8(887272),
+            Value::Int8(i64::MAX),
+        ]);
+        let cql_value = CqlValue::from(ticks.clone());
+        assert_eq!(
+            Scylladb::cql_value_to_store_value("ticks", field_kind, Some(cql_value)).unwrap(),
+            ticks
+        );
+    }
+
+    #[test]
+    fn test_canonical_big_decimal() {
+        let field_kind = FieldKind {
+            kind: StoreValueKind::BigDecimal,
+            relation: None,
+            list_inner_kind: None,
+            is_nullable: false,
+        };
+        let stored = |text: &str| {
+            let value = Value::BigDecimal(BigDecimal::from_str(text).unwrap());
+            let cql_value = CqlValue::from(value);
+            let loaded = Scylladb::cql_value_to_store_value(
+                "price",
+                field_kind.clone(),
+                Some(cql_value.clone()),
+            )
+            .unwrap();
+            (cql_value, loaded)
+        };
+
+        for (text, canonical) in [
+            ("1.10", "1.1"),
+            ("1.1", "1.1"),
+            ("1.1e3", "1100"),
+            ("-0.00012000", "-0.00012"),
+            ("12E-10", "0.0000000012"),
+            ("1E+20", "100000000000000000000"),
+            ("0.000", "0"),
+        ] {
+            let (cql_value, loaded) = stored(text);
+            assert_eq!(cql_value, CqlValue::Text(canonical.to_string()));
+            assert_eq!(
+                loaded,
+                Value::BigDecimal(BigDecimal::from_str(canonical).unwrap())
+            );
+        }
+        assert_eq!(stored("1.10"), stored("1.1"));
+    }
+
+    #[test]
+    fn test_nullable_field_mapping() {
+        let mut field_kind = FieldKind {
+            kind: StoreValueKind::BigInt,
+            relation: None,
+            list_inner_kind: None,
+            is_nullable: true,
+        };
+        let value = Scylladb::cql_value_to_store_value("amount", field_kind.clone(), None).unwrap();
+        assert_eq!(value, Value::Null);
+        assert_eq!(CqlValue::from(value), CqlValue::Empty);
+
+        field_kind.kind = StoreValueKind::Array;
+        field_kind.list_inner_kind = Some(StoreValueKind::Int8);
+        assert_eq!(
+            Scylladb::cql_value_to_store_value("amount", field_kind, None).unwrap(),
+            Value::List(vec![])
+        );
+    }
+
+    #[test]
+    fn test_corrupt_stored_value() {
+        env_logger::try_init().unwrap_or_default();
+        let field_kind = FieldKind {
+            kind: StoreValueKind::BigInt,
+            relation: None,
+            list_inner_kind: None,
+            is_nullable: false,
+        };
+        for garbage in ["0x", "", "12.5"] {
+            let value = Some(CqlValue::Text(garbage.to_string()));
+            assert!(matches!(
+                Scylladb::cql_value_to_store_value("amount", field_kind.clone(), value),
+                Err(DatabaseError::InvalidValue(field)) if field == "amount"
+            ));
+        }
+
+        // A value of another type than the field's, or a missing non-nullable value
+        let value = Some(CqlValue::Int(1));
+        assert!(Scylladb::cql_value_to_store_value("amount", field_kind.clone(), value).is_err());
+        assert!(Scylladb::cql_value_to_store_value("amount", field_kind, None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_int8_list_round_trip() {
+        let db = setup(false).await.unwrap();
+        let ticks = Value::List(vec![
+            Value::Int8(-887272),
+            Value::Int8(-1),
+            Value::Int8(0),
+            Value::Int8(60),
+            Value::Int8(i64::MAX),
+        ]);
+        let position: RawEntity = entity! {
+            id => Value::String("position".to_string()),
+            ticks => ticks.clone()
+        };
+        db.create_entity(BlockPtr::default(), "Position", position)
+            .await
+            .unwrap();
+
+        let loaded = db
+            .load_entity("Position", "position")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.get("ticks").cloned().unwrap(), ticks);
+    }
+
+    #[tokio::test]
+    async fn test_timestamp_round_trip() {
+        let field_kind = FieldKind {
+            kind: StoreValueKind::Timestamp,
+            relation: None,
+            list_inner_kind: None,
+            is_nullable: false,
+        };
+        assert_eq!(Scylladb::store_kind_to_db_type(field_kind), "bigint");
+
+        let db = setup(false).await.unwrap();
+        let timestamp = Value::Int8(1_700_000_000_123_456);
+        let swap: RawEntity = entity! {
+            id => Value::String("swap".to_string()),
+            timestamp => timestamp.clone()
+        };
+        db.create_entity(BlockPtr::default(), "Swap", swap)
+            .await
+            .unwrap();
+
+        let loaded = db.load_entity("Swap", "swap").await.unwrap().unwrap();
+        assert_eq!(loaded.get("timestamp").cloned().unwrap(), timestamp);
+    }
+
+    #[tokio::test]
+    async fn test_time_range_queries() {
+        env_logger::try_init().unwrap_or_default();
+        let schemas = || {
+            let mut schemas = Schemas::default();
+            schemas.add_schema(
+                "Pool",
+                schema!(id => StoreValueKind::String, fee => StoreValueKind::Int8),
+            );
+            schemas.add_block_timestamp_field();
+            schemas
+        };
+
+        let db = Scylladb::new(&test_config(), schemas(), "time-range")
+            .await
+            .unwrap();
+        assert!(matches!(
+            db.load_entities_by_time_range("Pool", 0, u64::MAX).await,
+            Err(DatabaseError::TimeRangeQueriesDisabled)
+        ));
+
+        let cfg = ScyllaConfig {
+            time_range_queries: Some(true),
+            ..test_config()
+        };
+        let db = Scylladb::new(&cfg, schemas(), "time-range").await.unwrap();
+        db.drop_tables().await.unwrap();
+        db.create_entity_tables().await.unwrap();
+        for number in 1..=5u64 {
+            let pool: RawEntity = entity! {
+                id => Value::String(format!("pool-{number}")),
+                fee => Value::Int8(number as i64),
+                __block_timestamp__ => Value::Int8(1_700_000_000 + number as i64 * 12),
+                __is_deleted__ => Value::Bool(false)
+            };
+            db.create_entity(
+                BlockPtr {
+                    number,
+                    ..Default::default()
+                },
+                "Pool",
+                pool,
+            )
+            .await
+            .unwrap();
+        }
+
+        let mut ids = db
+            .load_entities_by_time_range("Pool", 1_700_000_024, 1_700_000_048)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| e.get("id").cloned().unwrap())
+            .collect::<Vec<_>>();
+        ids.sort_by_key(|id| format!("{id:?}"));
+        assert_eq!(
+            ids,
+            ["pool-2", "pool-3", "pool-4"].map(|id| Value::String(id.to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_entities_by_field() {
+        let db = setup(false).await.unwrap();
+
+        for (block_number, id, owner) in [
+            (1, "position_0", "alice"),
+            (1, "position_1", "bob"),
+            (1, "position_2", "alice"),
+            (2, "position_2", "bob"),
+        ] {
+            let block_ptr = BlockPtr {
+                number: block_number,
+                hash: format!("n={block_number}"),
+                parent_hash: format!("n={}", block_number - 1),
+            };
+            let position: RawEntity = entity! {
+                id => Value::String(id.to_string()),
+                owner => Value::String(owner.to_string()),
+                ticks => Value::List(vec![])
+            };
+            db.create_entity(block_ptr, "Position", position)
+                .await
+                .unwrap();
+        }
+
+        let load_ids = |owner: &str| {
+            let owner = Value::String(owner.to_string());
+            let db = &db;
+            async move {
+                let mut ids = db
+                    .load_entities_by_field("Position", "owner", owner)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|e| e.get("id").cloned().unwrap())
+                    .collect::<Vec<_>>();
+                ids.sort_by_key(|id| format!("{id:?}"));
+                ids
+            }
+        };
+
+        // position_2 changed owner, only its latest snapshot counts
+        assert_eq!(
+            load_ids("alice").await,
+            vec![Value::String("position_0".to_string())]
+        );
+        assert_eq!(
+            load_ids("bob").await,
+            vec![
+                Value::String("position_1".to_string()),
+                Value::String("position_2".to_string())
+            ]
+        );
+        assert!(load_ids("carol").await.is_empty());
+
+        assert!(matches!(
+            db.load_entities_by_field("Position", "ticks", Value::List(vec![]))
+                .await,
+            Err(DatabaseError::FieldNotIndexed(..))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_batch_insert_per_entity_type() {
+        let db = setup(true).await.unwrap();
+        let block_ptr = BlockPtr {
+            number: 1,
+            hash: "n=1".to_string(),
+            parent_hash: "n=0".to_string(),
+        };
+
+        db.batch_insert_entities(block_ptr, mixed_entities(10))
+            .await
+            .unwrap();
+
+        let ids = (0..10).map(|i| format!("token_{i}")).collect::<Vec<_>>();
+        let tokens = db.load_entities("Token", ids).await.unwrap();
+        assert_eq!(tokens.len(), 10);
+
+        let pool = db.load_entity("Pool", "pool_7").await.unwrap().unwrap();
+        assert_eq!(pool.get("fee").cloned().unwrap(), Value::Int8(7));
+        assert_eq!(pool.get("__block_ptr__").cloned().unwrap(), Value::Int8(1));
+
+        let token = db.load_entity("Token", "token_3").await.unwrap().unwrap();
+        assert_eq!(
+            token.get("name").cloned().unwrap(),
+            Value::String("Token #3".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bounded_batch_insert() {
+        env_logger::try_init().unwrap_or_default();
+        let cfg = ScyllaConfig {
+            batch_size: Some(7),
+            max_inflight_batches: Some(2),
+            ..test_config()
+        };
+        let db = setup_with_config(&cfg, "test").await.unwrap();
+        assert_eq!(db.batch_size, 7);
+        assert_eq!(db.max_inflight_batches, 2);
+
+        let block_ptr = BlockPtr {
+            number: 1,
+            hash: "n=1".to_string(),
+            parent_hash: "n=0".to_string(),
+        };
+        // 100 statements, sent as 15 batches at most 2 at a time
+        db.batch_insert_entities(block_ptr, mixed_entities(50))
+            .await
+            .unwrap();
+
+        let ids = (0..50).map(|i| format!("pool_{i}")).collect::<Vec<_>>();
+        let pools = db.load_entities("Pool", ids).await.unwrap();
+        assert_eq!(pools.len(), 50);
+    }
+
+    #[tokio::test]
+    async fn test_load_entities_chunked() {
+        let db = setup(false).await.unwrap();
+        assert_eq!(db.load_chunk_size, Scylladb::DEFAULT_LOAD_CHUNK_SIZE);
+
+        for number in [1, 2] {
+            let block_ptr = BlockPtr {
+                number,
+                hash: format!("n={number}"),
+                parent_hash: format!("n={}", number - 1),
+            };
+            db.batch_insert_entities(block_ptr, mixed_entities(500))
+                .await
+                .unwrap();
+        }
+
+        // Every pool has 2 snapshots & is asked for twice, each one comes back once
+        let ids = (0..500)
+            .chain(0..500)
+            .map(|i| format!("pool_{i}"))
+            .collect::<Vec<_>>();
+        let pools = db.load_entities("Pool", ids).await.unwrap();
+        assert_eq!(pools.len(), 500);
+        let ids = pools
+            .iter()
+            .filter_map(|pool| match pool.get("id") {
+                Some(Value::String(id)) => Some(id.to_owned()),
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        assert_eq!(ids.len(), 500);
+    }
+
+    #[tokio::test]
+    async fn test_load_entity_history() {
+        let db = setup(false).await.unwrap();
+
+        for block_number in [3, 1, 2] {
+            let block_ptr = BlockPtr {
+                number: block_number,
+                hash: format!("n={block_number}"),
+                parent_hash: format!("n={}", block_number - 1),
+            };
+            let token: RawEntity = entity! {
+                id => Value::String("token".to_string()),
+                name => Value::String(format!("Token@{block_number}")),
+                total_supply => Value::BigInt(BigInt::from(block_number))
+            };
+            db.create_entity(block_ptr, "Token", token).await.unwrap();
+        }
+
+        let history = db.load_entity_history("Token", "token").await.unwrap();
+        assert_eq!(history.len(), 3);
+        for (idx, (block_number, entity)) in history.into_iter().enumerate() {
+            let expected_block = idx as u64 + 1;
+            assert_eq!(block_number, expected_block);
+            assert_eq!(
+                entity.get("name").cloned().unwrap(),
+                Value::String(format!("Token@{expected_block}"))
+            );
+        }
+
+        let history = db.load_entity_history("Token", "no-token").await.unwrap();
+        assert!(history.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint() {
+        let db = setup(false).await.unwrap();
+        assert!(db.load_checkpoint().await.unwrap().is_none());
+
+        for number in [1, 2] {
+            let checkpoint = Checkpoint {
+                block_ptr: BlockPtr {
+                    number,
+                    hash: format!("n={number}"),
+                    parent_hash: format!("n={}", number - 1),
+                },
+                cursor: format!("cursor-{number}"),
+            };
+            db.save_checkpoint(checkpoint.clone()).await.unwrap();
+            assert_eq!(db.load_checkpoint().await.unwrap(), Some(checkpoint));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reprocessed_range() {
+        let db = setup(false).await.unwrap();
+        assert!(db.load_reprocessed_range().await.unwrap().is_none());
+
+        for (from_block, to_block) in [(1, 5), (3, 4)] {
+            let range = ReprocessedRange {
+                from_block,
+                to_block,
+            };
+            db.save_reprocessed_range(range.clone()).await.unwrap();
+            assert_eq!(db.load_reprocessed_range().await.unwrap(), Some(range));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_find_block_ptr_gaps() {
+        let db = setup(false).await.unwrap();
+
+        for block_number in [1, 2, 4, 5] {
+            let block_ptr = BlockPtr {
+                number: block_number,
+                hash: format!("n={block_number}"),
+                parent_hash: format!("n={}", block_number - 1),
+            };
+            db.save_block_ptr(block_ptr).await.unwrap();
+        }
+
+        assert_eq!(db.find_block_ptr_gaps(1, 5).await.unwrap(), vec![3]);
+        assert_eq!(
+            db.find_block_ptr_gaps(4, 5).await.unwrap(),
+            Vec::<u64>::new()
+        );
+        assert_eq!(db.find_block_ptr_gaps(3, 7).await.unwrap(), vec![3, 6, 7]);
+    }
+
+    #[tokio::test]
+    async fn test_prefixed_tables_are_isolated() {
+        env_logger::try_init().unwrap_or_default();
+        let cfg = ScyllaConfig {
+            prefix_tables: Some(true),
+            ..test_config()
+        };
+
+        assert_eq!(
+            Scylladb::table_prefix("uniswap/v3-pools"),
+            "uniswap_v3_pools_"
+        );
+
+        let db_a = setup_with_config(&cfg, "subgraph-a").await.unwrap();
+        let db_b = setup_with_config(&cfg, "subgraph-b").await.unwrap();
+
+        for (db, name) in [(&db_a, "A"), (&db_b, "B")] {
+            let token: RawEntity = entity! {
+                id => Value::String("token".to_string()),
+                name => Value::String(name.to_string()),
+                total_supply => Value::BigInt(BigInt::from(1))
+            };
+            db.create_entity(BlockPtr::default(), "Token", token)
+                .await
+                .unwrap();
+        }
+
+        let token = db_a.load_entity("Token", "token").await.unwrap().unwrap();
+        assert_eq!(
+            token.get("name").cloned().unwrap(),
+            Value::String("A".to_string())
+        );
+
+        assert_eq!(db_b.revert_from_block(0).await.unwrap(), 1);
+        assert!(db_b.load_entity("Token", "token").await.unwrap().is_none());
+        assert!(db_a.load_entity("Token", "token").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_separate_read_write_sessions() {
+        env_logger::try_init().unwrap_or_default();
+        let mut cfg = test_config();
+
+        let db = setup_with_config(&cfg, "test").await.unwrap();
+        assert!(Arc::ptr_eq(&db.session, &db.read_session));
+
+        cfg.read_uri = Some(cfg.uri.clone());
+        cfg.read_consistency = Some(ScyllaConsistency::One);
+        cfg.write_consistency = Some(ScyllaConsistency::All);
+        let db = setup_with_config(&cfg, "test").await.unwrap();
+        assert!(!Arc::ptr_eq(&db.session, &db.read_session));
+
+        let count_reads = || db.read_session.get().get_metrics().get_queries_num();
+        let count_writes = || db.session.get().get_metrics().get_queries_num();
+        let (reads, writes) = (count_reads(), count_writes());
+
+        let token: RawEntity = entity! {
+            id => Value::String("token".to_string()),
+            name => Value::String("Token".to_string()),
+            total_supply => Value::BigInt(BigInt::from(1))
+        };
+        db.create_entity(BlockPtr::default(), "Token", token)
+            .await
+            .unwrap();
+        assert_eq!(count_writes(), writes + 1);
+        assert_eq!(count_reads(), reads);
+
+        let token = db.load_entity("Token", "token").await.unwrap();
+        assert!(token.is_some());
+        assert_eq!(count_reads(), reads + 1);
+        assert_eq!(count_writes(), writes + 1);
+    }
+
+    #[tokio::test]
+    async fn test_export_import_snapshot() {
+        let db = setup(false).await.unwrap();
+
+        for block_number in 1..=2u64 {
+            let block_ptr = BlockPtr {
+                number: block_number,
+                ..Default::default()
+            };
+            let token: RawEntity = entity! {
+                id => Value::String("token".to_string()),
+                name => Value::String(format!("Token@{block_number}")),
+                total_supply => Value::BigInt(BigInt::from(block_number))
+            };
+            db.create_entity(block_ptr.clone(), "Token", token)
+                .await
+                .unwrap();
+
+            let pool: RawEntity = entity! {
+                id => Value::String(format!("pool_{block_number}")),
+                fee => Value::Int8(block_number as i64)
+            };
+            db.create_entity(block_ptr, "Pool", pool).await.unwrap();
+        }
+        db.soft_delete_entity(
+            BlockPtr {
+                number: 3,
+                ..Default::default()
+            },
+            "Pool",
+            "pool_1",
+        )
+        .await
+        .unwrap();
+
+        let mut archive = vec![];
+        let count = db.export_snapshot(&mut archive).await.unwrap();
+        assert_eq!(count, 2);
+
+        let cfg = ScyllaConfig {
+            prefix_tables: Some(true),
+            ..test_config()
+        };
+        let fresh_db = setup_with_config(&cfg, "imported").await.unwrap();
+        let count = fresh_db.import_snapshot(archive.as_slice()).await.unwrap();
+        assert_eq!(count, 2);
+
+        for (entity_type, entity_id) in [("Token", "token"), ("Pool", "pool_2")] {
+            let expected = db.load_entity(entity_type, entity_id).await.unwrap();
+            let imported = fresh_db.load_entity(entity_type, entity_id).await.unwrap();
+            assert!(expected.is_some());
+            assert_eq!(expected, imported);
+        }
+        assert!(fresh_db
+            .load_entity("Pool", "pool_1")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_disabled_missing_tables() {
+        env_logger::try_init().unwrap_or_default();
+        // Makes sure the keyspace itself exists
+        let db = setup(false).await.unwrap();
+
+        let mut schema = Schemas::default();
+        schema.add_schema("Token", schema!(id => StoreValueKind::String));
+        let cfg = ScyllaConfig {
+            keyspace: db.keyspace.clone(),
+            prefix_tables: Some(true),
+            bootstrap_schema: Some(false),
+            ..test_config()
+        };
+
+        let result = Scylladb::new(&cfg, schema, "not-provisioned").await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::MissingTables { tables, .. })
+                if tables == vec![
+                    "not_provisioned_Token",
+                    "not_provisioned_block_ptr",
+                    "not_provisioned_datasources",
+                    "not_provisioned_checkpoint",
+                    "not_provisioned_reprocess",
+                ]
+        ));
+
+        // Tables created by a regular startup are found
+        let cfg = ScyllaConfig {
+            prefix_tables: None,
+            ..cfg
+        };
+        let mut schema = Schemas::default();
+        schema.add_schema("Token", schema!(id => StoreValueKind::String));
+        assert!(Scylladb::new(&cfg, schema, "test").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_field() {
+        env_logger::try_init().unwrap_or_default();
+        let cfg = ScyllaConfig {
+            prefix_tables: Some(true),
+            ..test_config()
+        };
+        let schemas = || {
+            let mut schema = Schemas::default();
+            schema.add_schema(
+                "Account",
+                schema!(id => StoreValueKind::String, email => StoreValueKind::String),
+            );
+            schema.set_encrypted("Account", "email");
+            schema
+        };
+
+        let result = Scylladb::new(&cfg, schemas(), "encrypted").await;
+        assert!(matches!(result, Err(DatabaseError::MissingEncryptionKey)));
+
+        let cfg = ScyllaConfig {
+            encryption_key: Some(
+                "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f".to_string(),
+            ),
+            ..cfg
+        };
+        let db = Scylladb::new(&cfg, schemas(), "encrypted").await.unwrap();
+        db.drop_tables().await.unwrap();
+        db.create_entity_tables().await.unwrap();
+
+        let email = "alice@example.com";
+        let account = entity! {
+            id => Value::String("account_0".to_string()),
+            email => Value::String(email.to_string())
+        };
+        db.create_entity(
+            BlockPtr {
+                number: 1,
+                hash: "hash_1".to_string(),
+                parent_hash: "hash_0".to_string(),
+            },
+            "Account",
+            account,
+        )
+        .await
+        .unwrap();
+
+        let query = format!("SELECT email FROM {}", db.entity_table("Account"));
+        let row = db.read(query, ()).await.unwrap().rows().unwrap().remove(0);
+        let stored = row.columns[0].clone().unwrap().into_blob().unwrap();
+        assert!(!stored
+            .windows(email.len())
+            .any(|window| window == email.as_bytes()));
+
+        let loaded = db
+            .load_entity("Account", "account_0")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.get("email"), Some(&Value::String(email.to_string())));
+    }
+
+    #[test]
+    fn test_entity_table_compaction() {
+        env_logger::try_init().unwrap_or_default();
+        let options = Scylladb::entity_table_options(None);
+        assert!(options.ends_with("CLUSTERING ORDER BY (__block_ptr__ DESC)"));
+        assert!(!options.contains("compaction"));
+
+        let compaction: ScyllaCompaction = serde_json::from_str(
+            r#"{"class": "time_window", "window_unit": "hours", "window_size": 6}"#,
+        )
+        .unwrap();
+        let options = Scylladb::entity_table_options(Some(&compaction));
+        assert!(options.ends_with(
+            "compaction = {'class': 'TimeWindowCompactionStrategy', 'compaction_window_unit': 'HOURS', 'compaction_window_size': 6}"
+        ));
+
+        let compaction: ScyllaCompaction = serde_json::from_str(r#"{"class": "leveled"}"#).unwrap();
+        let options = Scylladb::entity_table_options(Some(&compaction));
+        assert!(options.contains("compaction = {'class': 'LeveledCompactionStrategy'}"));
+    }
+
+    #[test]
+    fn test_local_datacenter_profile() {
+        env_logger::try_init().unwrap_or_default();
+        let profile = SessionOptions::default().execution_profile();
+        let policy = format!("{:?}", profile.get_load_balancing_policy());
+        assert!(!policy.contains("dc2"));
+        assert!(profile.get_request_timeout().is_some());
+
+        let options = SessionOptions {
+            timeout: Some(5000),
+            local_datacenter: Some("dc2".to_string()),
+            ..Default::default()
+        };
+        let profile = options.execution_profile();
+        let policy = format!("{:?}", profile.get_load_balancing_policy());
+        assert!(policy.contains("\"dc2\""));
+        assert_eq!(
+            profile.get_request_timeout(),
+            Some(Duration::from_millis(5000))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_table_not_found() {
+        env_logger::try_init().unwrap_or_default();
+        let db = setup(false).await.unwrap();
+
+        let result = db.load_entity("Unknown", "id_0").await;
+        assert!(matches!(
+            result,
+            Err(DatabaseError::TableNotFound { entity_type }) if entity_type == "Unknown"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_on_connection_error() {
+        let db = setup(false).await.unwrap();
+        let session = db.session.clone();
+        let initial = session.get();
+        let attempts = AtomicUsize::new(0);
+
+        // First attempt fails as if the node had restarted, the retry runs on a new session
+        let result = session
+            .with_reconnect(|current| {
+                let attempt = attempts.fetch_add(1, Ordering::Relaxed);
+                async move {
+                    if attempt == 0 {
+                        let error = std::io::Error::from(std::io::ErrorKind::ConnectionReset);
+                        return Err(QueryError::IoError(Arc::new(error)));
+                    }
+                    current.query("SELECT now() FROM system.local", ()).await
+                }
+            })
+            .await;
+
+        assert!(result.is_ok());
+        assert_eq!(attempts.load(Ordering::Relaxed), 2);
+        assert_eq!(session.reconnects.load(Ordering::Relaxed), 1);
+        assert!(!Arc::ptr_eq(&initial, &session.get()));
+
+        // Other errors are returned as-is, without rebuilding the session
+        let result = session
+            .with_reconnect(|current| async move {
+                current
+                    .query("SELECT * FROM no_such_keyspace.no_such_table", ())
+                    .await
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(session.reconnects.load(Ordering::Relaxed), 1);
+
+        // The database keeps working on top of the rebuilt session
+        db.save_block_ptr(BlockPtr::default()).await.unwrap();
+    }
 }
@@ -53,6 +53,7 @@ impl MemoryDb {
     pub fn create_entity(
         &mut self,
         entity_type: &str,
+        primary_key: &str,
         data: RawEntity,
     ) -> Result<(), DatabaseError> {
         let store = &mut self.0;
@@ -61,24 +62,25 @@ impl MemoryDb {
         }
 
         let table = store.get_mut(entity_type).unwrap();
-        if let Value::String(entity_id) = data.get("id").ok_or(DatabaseError::MissingID)? {
-            // Check if this id exists or not
-            if table.get(entity_id).is_none() {
-                table.insert(entity_id.to_owned(), vec![]);
-            };
-
-            // Push new record
-            let snapshots = table.get_mut(entity_id).unwrap();
+        if let Value::String(entity_id) = data.get(primary_key).ok_or(DatabaseError::MissingID)? {
             let mut new_data = data.clone();
             new_data.insert("__is_deleted__".to_string(), Value::Bool(false));
-            snapshots.push(new_data);
+
+            // Only the final version of an entity is ever extracted,
+            // so writing it again overwrites the previous version instead of accumulating
+            let snapshots = table.entry(entity_id.to_owned()).or_default();
+            match snapshots.last_mut() {
+                Some(last) => *last = new_data,
+                None => snapshots.push(new_data),
+            }
             Ok(())
         } else {
             error!(MemoryDb, "id is invalid";
                 entity_type => entity_type,
+                primary_key => primary_key,
                 rawEntity => format!("{:?}", data)
             );
-            Err(DatabaseError::InvalidValue("id".to_string()))
+            Err(DatabaseError::InvalidValue(primary_key.to_string()))
         }
     }
 
@@ -133,6 +135,10 @@ impl MemoryDb {
         result
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.0.values().all(|table| table.is_empty())
+    }
+
     pub fn clear(&mut self) {
         self.0 = HashMap::new();
     }
@@ -150,7 +156,7 @@ mod tests {
         data.insert("id".to_string(), Value::String("1".to_string()));
         data.insert("name".to_string(), Value::String("test".to_string()));
 
-        let result = db.create_entity("test", data);
+        let result = db.create_entity("test", "id", data);
         assert!(result.is_ok());
         let latest = db.load_entity_latest("test", "1");
         assert!(latest.is_ok());
@@ -173,7 +179,7 @@ mod tests {
         data.insert("id".to_string(), Value::String("1".to_string()));
         data.insert("name".to_string(), Value::String("test".to_string()));
 
-        db.create_entity("test", data).unwrap();
+        db.create_entity("test", "id", data).unwrap();
         let latest = db.load_entity_latest("test", "1");
         assert!(latest.is_ok());
 
@@ -201,34 +207,51 @@ mod tests {
         let mut data = HashMap::new();
         data.insert("id".to_string(), Value::String("1".to_string()));
         data.insert("name".to_string(), Value::String("test".to_string()));
-        db.create_entity("test", data).unwrap();
+        db.create_entity("test", "id", data).unwrap();
         let mut data = HashMap::new();
         data.insert("id".to_string(), Value::String("1".to_string()));
         data.insert("name".to_string(), Value::String("test111".to_string()));
-        db.create_entity("test", data).unwrap();
+        db.create_entity("test", "id", data).unwrap();
 
         let mut data = HashMap::new();
         data.insert("id".to_string(), Value::String("2".to_string()));
         data.insert("name".to_string(), Value::String("test22".to_string()));
-        db.create_entity("test2", data).unwrap();
+        db.create_entity("test2", "id", data).unwrap();
 
         let mut data = HashMap::new();
         data.insert("id".to_string(), Value::String("2".to_string()));
         data.insert("name".to_string(), Value::String("test222".to_string()));
-        db.create_entity("test2", data).unwrap();
+        db.create_entity("test2", "id", data).unwrap();
 
         let mut data = HashMap::new();
         data.insert("id".to_string(), Value::String("3".to_string()));
         data.insert("name".to_string(), Value::String("test".to_string()));
-        db.create_entity("test2", data).unwrap();
+        db.create_entity("test2", "id", data).unwrap();
 
         let mut data = HashMap::new();
         data.insert("id".to_string(), Value::String("3".to_string()));
         data.insert("name".to_string(), Value::String("test333".to_string()));
-        db.create_entity("test2", data).unwrap();
+        db.create_entity("test2", "id", data).unwrap();
 
         let extract_data = db.extract_data().unwrap();
         log::info!("extract_data: {:?}", extract_data);
         assert_eq!(extract_data.len(), 3);
     }
+
+    #[test]
+    fn test_memory_04_repeated_writes() {
+        env_logger::try_init().unwrap_or_default();
+        let mut db = MemoryDb::default();
+        for i in 0..100 {
+            let mut data = HashMap::new();
+            data.insert("id".to_string(), Value::String("1".to_string()));
+            data.insert("count".to_string(), Value::Int(i));
+            db.create_entity("test", "id", data).unwrap();
+        }
+
+        assert_eq!(db.0.get("test").unwrap().get("1").unwrap().len(), 1);
+        let extract_data = db.extract_data().unwrap();
+        assert_eq!(extract_data.len(), 1);
+        assert_eq!(extract_data[0].1.get("count").unwrap(), &Value::Int(99));
+    }
 }
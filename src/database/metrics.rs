@@ -10,9 +10,12 @@ pub struct DatabaseMetrics {
     pub database_cache_miss: IntCounter,
     pub extern_db_write: IntCounter,
     pub extern_db_load: IntCounter,
+    pub load_related_truncated: IntCounter,
+    pub abnormal_entity_count: IntCounter,
     pub extern_db_get_duration: Histogram,
     pub extern_db_set_duration: Histogram,
     pub handle_store_request_duration: HistogramVec,
+    pub migrate_duration: Histogram,
 }
 
 impl DatabaseMetrics {
@@ -39,6 +42,24 @@ impl DatabaseMetrics {
             .register(Box::new(extern_db_load.clone()))
             .unwrap_or_default();
 
+        let load_related_truncated = IntCounter::new(
+            "load_related_truncated",
+            "load-related results truncated to max_related_entities",
+        )
+        .unwrap();
+        registry
+            .register(Box::new(load_related_truncated.clone()))
+            .unwrap_or_default();
+
+        let abnormal_entity_count = IntCounter::new(
+            "abnormal_entity_count",
+            "blocks writing more entities than max_entities_per_block",
+        )
+        .unwrap();
+        registry
+            .register(Box::new(abnormal_entity_count.clone()))
+            .unwrap_or_default();
+
         let duration_opts =
             HistogramOpts::new("extern_db_get_duration", "duration of extern db get entity");
         let extern_db_get_duration = Histogram::with_opts(duration_opts).unwrap();
@@ -61,15 +82,30 @@ impl DatabaseMetrics {
         );
         let handle_store_request_duration =
             HistogramVec::new(opts, &["request_type", "entity"]).unwrap();
+        registry
+            .register(Box::new(handle_store_request_duration.clone()))
+            .unwrap_or_default();
+
+        let opts = HistogramOpts::new(
+            "migrate_duration",
+            "duration of migrating a block's entities from memory to the database",
+        );
+        let migrate_duration = Histogram::with_opts(opts).unwrap();
+        registry
+            .register(Box::new(migrate_duration.clone()))
+            .unwrap_or_default();
 
         Self {
             database_cache_hit,
             database_cache_miss,
             extern_db_write,
             extern_db_load,
+            load_related_truncated,
+            abnormal_entity_count,
             extern_db_get_duration,
             extern_db_set_duration,
             handle_store_request_duration,
+            migrate_duration,
         }
     }
 }
@@ -1,77 +1,370 @@
+mod change_feed;
 mod extern_db;
 mod memory_db;
 mod metrics;
 mod utils;
 
 use crate::common::BlockPtr;
+use crate::common::Checkpoint;
+use crate::common::Datasource;
 use crate::common::EntityID;
 use crate::common::EntityType;
 use crate::common::FieldName;
 use crate::common::RawEntity;
+use crate::common::ReprocessedRange;
 use crate::common::Schemas;
 use crate::common::StoreOperationMessage;
 use crate::common::StoreRequestResult;
-use crate::config::DatabaseConfig;
+use crate::config::Config;
+use crate::config::DuplicateCreatePolicy;
+use crate::config::EntityIdCase;
+use crate::config::SchemaValidationPolicy;
+use crate::critical;
+use crate::debug;
 use crate::errors::DatabaseError;
 use crate::info;
+use crate::runtime::asc::native_types::store::StoreValueKind;
 use crate::runtime::asc::native_types::store::Value;
 use crate::warn;
+pub use change_feed::forward_changes;
+use change_feed::ChangeFeed;
+use change_feed::ChangeOp;
+use change_feed::EntityChange;
 use extern_db::ExternDB;
 use extern_db::ExternDBTrait;
 use memory_db::MemoryDb;
 use metrics::DatabaseMetrics;
 use prometheus::Registry;
 use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::rc::Rc;
 use std::time::Instant;
 
+/// Number of related entities loaded from the database at once
+pub const LOAD_RELATED_PAGE_SIZE: usize = 100;
+
+/// Called with the block pointer of every committed block. It runs synchronously
+/// on the commit path, right after the block pointer is saved, so a slow hook stalls indexing
+pub type CommitHook = Box<dyn Fn(&BlockPtr) + Send>;
+
+/// Entities of a committed block that is not yet `commit_depth` deep, held back from the database
+struct ProvisionalBlock {
+    block_ptr: BlockPtr,
+    entities: HashMap<(EntityType, EntityID), RawEntity>,
+    changes: Vec<EntityChange>,
+}
+
 pub struct Database {
     pub mem: MemoryDb,
     pub db: ExternDB,
     pub earliest_block: u64,
     metrics: DatabaseMetrics,
     schema: Schemas,
+    fast_forward_to_block: Option<u64>,
+    /// Set once the entities' final state is written at the cutover
+    fast_forward_done: bool,
+    entities_allowlist: Option<Vec<EntityType>>,
+    entities_denylist: Option<Vec<EntityType>>,
+    with_block_timestamp: bool,
+    block_timestamp: u64,
+    with_checksum: bool,
+    entity_id_case: HashMap<EntityType, EntityIdCase>,
+    schema_validation: SchemaValidationPolicy,
+    commit_hook: Option<CommitHook>,
+    max_related_entities: Option<usize>,
+    /// Blocks writing more entities than this are reported
+    max_entities_per_block: Option<usize>,
+    /// Entity writes of the block being processed
+    block_writes: usize,
+    /// Number of entity types written concurrently when committing a block
+    migration_concurrency: usize,
+    /// Rank of every entity type when a block's writes follow schema relations
+    write_order: Option<HashMap<EntityType, usize>>,
+    duplicate_create: DuplicateCreatePolicy,
+    /// Blocks are only written to the database once they are this many blocks behind the head
+    commit_depth: u64,
+    provisional: VecDeque<ProvisionalBlock>,
+    /// Blocks written since the last snapshot clean-up, with the entities they wrote
+    persisted: Vec<(u64, Vec<(EntityType, EntityID)>)>,
+    /// Deleted entities are removed from the database once their deletion is this many blocks old
+    hard_delete_after: Option<u64>,
+    /// Written blocks with the entities they deleted, not yet removed from the database
+    soft_deleted: VecDeque<(u64, Vec<(EntityType, EntityID)>)>,
+    change_feed: Option<ChangeFeed>,
+    /// Entities created, updated or deleted since the last commit
+    written_in_block: HashSet<(EntityType, EntityID)>,
+    /// Immutable entities created by the block being processed
+    created_in_block: HashSet<(EntityType, EntityID)>,
+    /// While replaying: the stashed in-memory state & the recorded write operations
+    replay: Option<(MemoryDb, Vec<StoreOperationMessage>)>,
+    /// Entities cached in memory that were loaded from the committed store
+    loaded_from_db: HashSet<(EntityType, EntityID)>,
+    /// Source cursors of the blocks not yet written, by block number
+    cursors: BTreeMap<u64, String>,
 }
 
 impl Database {
     pub async fn new(
-        config: &DatabaseConfig,
-        schema: Schemas,
+        config: &Config,
+        mut schema: Schemas,
         registry: &Registry,
     ) -> Result<Self, DatabaseError> {
+        let with_block_timestamp = config.block_timestamp_column.unwrap_or(false);
+        if with_block_timestamp {
+            schema.add_block_timestamp_field();
+        }
+        let with_checksum = config.entity_checksum.unwrap_or(false);
+        if with_checksum {
+            schema.add_checksum_field();
+        }
         let mem = MemoryDb::default();
-        let db = ExternDB::new(config, schema.clone()).await?;
+        let db = ExternDB::new(&config.database, schema.clone(), &config.subgraph_name).await?;
         let earliest_block = db
             .get_earliest_block_ptr()
             .await?
             .map(|b| b.number)
             .unwrap_or(0);
+        // A cutover committed by a previous run is not done again
+        let fast_forward_done = match config.fast_forward_to_block {
+            Some(cutover) => db
+                .load_recent_block_ptrs(1)
+                .await?
+                .first()
+                .is_some_and(|block_ptr| block_ptr.number >= cutover),
+            None => false,
+        };
+        let write_order = config
+            .ordered_writes
+            .unwrap_or(false)
+            .then(|| schema.write_order());
         let metrics = DatabaseMetrics::new(registry);
-        Ok(Database {
+        let mut database = Database {
             mem,
             db,
             metrics,
             schema,
             earliest_block,
-        })
+            fast_forward_to_block: config.fast_forward_to_block,
+            fast_forward_done,
+            entities_allowlist: config.entities_allowlist.clone(),
+            entities_denylist: config.entities_denylist.clone(),
+            with_block_timestamp,
+            block_timestamp: 0,
+            with_checksum,
+            entity_id_case: config.entity_id_case.clone().unwrap_or_default(),
+            schema_validation: config.schema_validation.clone().unwrap_or_default(),
+            commit_hook: None,
+            max_related_entities: config.max_related_entities,
+            max_entities_per_block: config.max_entities_per_block,
+            block_writes: 0,
+            migration_concurrency: config.migration_concurrency.unwrap_or(4),
+            write_order,
+            duplicate_create: config.duplicate_create.clone().unwrap_or_default(),
+            commit_depth: config.commit_depth.unwrap_or(0),
+            provisional: VecDeque::new(),
+            persisted: vec![],
+            hard_delete_after: config
+                .hard_delete
+                .unwrap_or(false)
+                .then_some(config.reorg_threshold as u64),
+            soft_deleted: VecDeque::new(),
+            change_feed: config.change_feed.as_ref().map(ChangeFeed::new),
+            written_in_block: HashSet::new(),
+            created_in_block: HashSet::new(),
+            replay: None,
+            loaded_from_db: HashSet::new(),
+            cursors: BTreeMap::new(),
+        };
+        if database.hard_delete_after.is_some() {
+            database.load_soft_deleted().await?;
+        }
+        Ok(database)
+    }
+
+    fn is_entity_type_allowed(&self, entity_type: &str) -> bool {
+        let allowed = match &self.entities_allowlist {
+            Some(list) => list.iter().any(|t| t == entity_type),
+            None => true,
+        };
+        let denied = self
+            .entities_denylist
+            .as_ref()
+            .is_some_and(|list| list.iter().any(|t| t == entity_type));
+        allowed && !denied
+    }
+
+    fn validate_entity(&self, entity_type: &str, data: &RawEntity) -> Result<(), DatabaseError> {
+        match self.schema_validation {
+            SchemaValidationPolicy::Off => Ok(()),
+            SchemaValidationPolicy::Warn => {
+                if let Err(error) = self.schema.validate_entity(entity_type, data) {
+                    warn!(Database, "entity does not match its schema"; entity_type => entity_type, error => error);
+                }
+                Ok(())
+            }
+            SchemaValidationPolicy::Strict => Ok(self.schema.validate_entity(entity_type, data)?),
+        }
+    }
+
+    /// Mappings may store a BigInt into an `Int` field, narrow it to i32
+    /// instead of letting it wrap around
+    fn narrow_int_fields(
+        &self,
+        entity_type: &str,
+        data: &mut RawEntity,
+    ) -> Result<(), DatabaseError> {
+        for field in self.schema.get_int_fields(entity_type) {
+            if let Some(Value::BigInt(value)) = data.get(&field) {
+                let int = i32::try_from(value).map_err(|_| {
+                    DatabaseError::ValueOverflow(
+                        entity_type.to_owned(),
+                        field.to_owned(),
+                        value.to_string(),
+                    )
+                })?;
+                data.insert(field, Value::Int(int));
+            }
+        }
+        Ok(())
+    }
+
+    fn verify_checksum(&self, entity_type: &str, data: &RawEntity) -> Result<(), DatabaseError> {
+        if !self.with_checksum {
+            return Ok(());
+        }
+
+        let checksum = utils::entity_checksum(data);
+        if data.get("__checksum__") == Some(&Value::Int8(checksum)) {
+            return Ok(());
+        }
+
+        let entity_id = match data.get(self.schema.get_primary_key(entity_type)) {
+            Some(Value::String(id)) => id.to_owned(),
+            _ => String::default(),
+        };
+        critical!(
+            Database,
+            "entity checksum mismatch, data might be corrupted";
+            entity_type => entity_type,
+            entity_id => entity_id,
+            expected => checksum,
+            stored => format!("{:?}", data.get("__checksum__"))
+        );
+        Err(DatabaseError::CorruptEntity(
+            entity_type.to_owned(),
+            entity_id,
+        ))
+    }
+
+    fn normalize_id(&self, entity_type: &str, entity_id: EntityID) -> EntityID {
+        match self.entity_id_case.get(entity_type) {
+            Some(EntityIdCase::Lower) => entity_id.to_lowercase(),
+            Some(EntityIdCase::Upper) => entity_id.to_uppercase(),
+            None => entity_id,
+        }
+    }
+
+    fn normalize_entity(&self, entity_type: &str, mut data: RawEntity) -> RawEntity {
+        let primary_key = self.schema.get_primary_key(entity_type);
+        if let Some(Value::String(entity_id)) = data.remove(primary_key) {
+            let entity_id = self.normalize_id(entity_type, entity_id);
+            data.insert(primary_key.to_owned(), Value::String(entity_id));
+        }
+        data
+    }
+
+    /// Apply the configured id case to every entity id of the request
+    fn normalize_request(&self, message: StoreOperationMessage) -> StoreOperationMessage {
+        if self.entity_id_case.is_empty() {
+            return message;
+        }
+
+        match message {
+            StoreOperationMessage::Create((entity_type, data)) => {
+                let data = self.normalize_entity(&entity_type, data);
+                StoreOperationMessage::Create((entity_type, data))
+            }
+            StoreOperationMessage::Update((entity_type, entity_id, data)) => {
+                let entity_id = self.normalize_id(&entity_type, entity_id);
+                let data = self.normalize_entity(&entity_type, data);
+                StoreOperationMessage::Update((entity_type, entity_id, data))
+            }
+            StoreOperationMessage::Merge((entity_type, entity_id, data)) => {
+                let entity_id = self.normalize_id(&entity_type, entity_id);
+                let data = self.normalize_entity(&entity_type, data);
+                StoreOperationMessage::Merge((entity_type, entity_id, data))
+            }
+            StoreOperationMessage::Load((entity_type, entity_id)) => {
+                let entity_id = self.normalize_id(&entity_type, entity_id);
+                StoreOperationMessage::Load((entity_type, entity_id))
+            }
+            StoreOperationMessage::Delete((entity_type, entity_id)) => {
+                let entity_id = self.normalize_id(&entity_type, entity_id);
+                StoreOperationMessage::Delete((entity_type, entity_id))
+            }
+            StoreOperationMessage::LoadRelated((entity_type, entity_id, field_name)) => {
+                let entity_id = self.normalize_id(&entity_type, entity_id);
+                StoreOperationMessage::LoadRelated((entity_type, entity_id, field_name))
+            }
+            StoreOperationMessage::LoadInBlock((entity_type, entity_id)) => {
+                let entity_id = self.normalize_id(&entity_type, entity_id);
+                StoreOperationMessage::LoadInBlock((entity_type, entity_id))
+            }
+            StoreOperationMessage::LoadWithFreshness((entity_type, entity_id)) => {
+                let entity_id = self.normalize_id(&entity_type, entity_id);
+                StoreOperationMessage::LoadWithFreshness((entity_type, entity_id))
+            }
+        }
     }
 
     async fn handle_store_request(
         &mut self,
         message: StoreOperationMessage,
     ) -> Result<StoreRequestResult, DatabaseError> {
+        let message = self.normalize_request(message);
         let _timer = self
             .metrics
             .handle_store_request_duration
             .with_label_values(&[&message.operation_type(), &message.entity_type()])
             .start_timer();
+
+        let is_write = matches!(
+            message,
+            StoreOperationMessage::Create(_)
+                | StoreOperationMessage::Update(_)
+                | StoreOperationMessage::Merge(_)
+                | StoreOperationMessage::Delete(_)
+        );
+        if is_write {
+            self.block_writes += 1;
+            if let Some((_, operations)) = self.replay.as_mut() {
+                operations.push(message.clone());
+            }
+        }
+
         match message {
             StoreOperationMessage::Create(data) => self.handle_create(data).await,
             StoreOperationMessage::Load(data) => self.handle_load(data).await,
             StoreOperationMessage::Update(data) => self.handle_update(data).await,
+            StoreOperationMessage::Merge(data) => self.handle_merge(data).await,
             StoreOperationMessage::Delete(data) => self.handle_delete(data).await,
-            StoreOperationMessage::LoadRelated(data) => self.handle_load_related(data).await,
+            StoreOperationMessage::LoadRelated(data) => {
+                let mut related_entities = vec![];
+                self.handle_load_related(data, |page| {
+                    related_entities.extend(page);
+                    Ok(())
+                })
+                .await?;
+                Ok(StoreRequestResult::LoadRelated(related_entities))
+            }
             StoreOperationMessage::LoadInBlock(data) => self.handle_load_in_block(data),
+            StoreOperationMessage::LoadWithFreshness(data) => {
+                self.handle_load_with_freshness(data).await
+            }
         }
     }
 
@@ -79,14 +372,66 @@ impl Database {
         &mut self,
         data: (EntityType, RawEntity),
     ) -> Result<StoreRequestResult, DatabaseError> {
-        let (entity_type, data) = data;
-        let entity_id = data.get("id").cloned().expect("Missing ID in RawEntity");
-        self.mem.create_entity(&entity_type, data)?;
+        let (entity_type, mut data) = data;
+        self.narrow_int_fields(&entity_type, &mut data)?;
+        self.validate_entity(&entity_type, &data)?;
+        let primary_key = self.schema.get_primary_key(&entity_type).to_owned();
+        let entity_id = data
+            .get(&primary_key)
+            .cloned()
+            .ok_or(DatabaseError::MissingID)?;
+
+        // Immutable entities are written once: no load-before-write against the database,
+        // only a write on top of an already cached snapshot is rejected. Within the block
+        // that created them they can still be overwritten, unless duplicates are strict
+        if self.schema.is_immutable(&entity_type) {
+            if let Value::String(entity_id) = &entity_id {
+                let key = (entity_type.clone(), entity_id.to_owned());
+                if self
+                    .mem
+                    .load_entity_latest(&entity_type, entity_id)?
+                    .is_some()
+                {
+                    if !self.created_in_block.contains(&key) {
+                        return Err(DatabaseError::ImmutableEntityUpdate(
+                            entity_type,
+                            entity_id.to_owned(),
+                        ));
+                    }
+                    if self.duplicate_create == DuplicateCreatePolicy::Strict {
+                        return Err(DatabaseError::DuplicateEntityCreate(
+                            entity_type,
+                            entity_id.to_owned(),
+                        ));
+                    }
+                }
+                self.created_in_block.insert(key);
+            }
+        }
+
+        if self.with_block_timestamp {
+            data.insert(
+                "__block_timestamp__".to_string(),
+                Value::Int8(self.block_timestamp as i64),
+            );
+        }
+
+        if self.is_entity_type_allowed(&entity_type) {
+            self.mem.create_entity(&entity_type, &primary_key, data)?;
+            if let Value::String(entity_id) = &entity_id {
+                self.written_in_block
+                    .insert((entity_type.clone(), entity_id.to_owned()));
+            }
+        } else {
+            debug!(Database, "entity type not allowed, skip storing"; entity_type => entity_type);
+        }
 
         if let Value::String(entity_id) = entity_id {
             Ok(StoreRequestResult::Create(entity_id))
         } else {
-            Err(DatabaseError::InvalidValue("id is not string".to_string()))
+            Err(DatabaseError::InvalidValue(format!(
+                "{primary_key} is not string"
+            )))
         }
     }
 
@@ -100,6 +445,16 @@ impl Database {
 
         if entity.is_none() {
             self.metrics.database_cache_miss.inc();
+            if let Some(entity) = self.load_provisional(&entity_type, &entity_id) {
+                if let Some(data) = &entity {
+                    let primary_key = self.schema.get_primary_key(&entity_type);
+                    self.mem
+                        .create_entity(&entity_type, primary_key, data.clone())?;
+                    self.loaded_from_db.insert((entity_type, entity_id));
+                }
+                return Ok(StoreRequestResult::Load(entity));
+            }
+
             self.metrics.extern_db_load.inc();
             let timer = self.metrics.extern_db_get_duration.start_timer();
             let entity = self.db.load_entity(&entity_type, &entity_id).await?;
@@ -109,7 +464,11 @@ impl Database {
             }
 
             let data = entity.unwrap();
-            self.mem.create_entity(&entity_type, data.clone())?;
+            self.verify_checksum(&entity_type, &data)?;
+            let primary_key = self.schema.get_primary_key(&entity_type);
+            self.mem
+                .create_entity(&entity_type, primary_key, data.clone())?;
+            self.loaded_from_db.insert((entity_type, entity_id));
             return Ok(StoreRequestResult::Load(Some(data)));
         }
 
@@ -118,104 +477,684 @@ impl Database {
         Ok(StoreRequestResult::Load(Some(data)))
     }
 
+    async fn handle_load_with_freshness(
+        &mut self,
+        data: (EntityType, EntityID),
+    ) -> Result<StoreRequestResult, DatabaseError> {
+        let key = data.clone();
+        match self.handle_load(data).await? {
+            StoreRequestResult::Load(entity) => {
+                let created_in_block = !self.loaded_from_db.contains(&key);
+                Ok(StoreRequestResult::LoadWithFreshness(
+                    entity.map(|e| (e, created_in_block)),
+                ))
+            }
+            _ => Err(DatabaseError::Invalid),
+        }
+    }
+
     fn handle_load_in_block(
         &self,
         data: (EntityType, EntityID),
     ) -> Result<StoreRequestResult, DatabaseError> {
         let (entity_type, entity_id) = data;
         let entity = self.mem.load_entity_latest(&entity_type, &entity_id)?;
-        Ok(StoreRequestResult::Load(entity))
+        Ok(StoreRequestResult::LoadInBlock(entity))
     }
 
     async fn handle_update(
         &mut self,
         data: (EntityType, EntityID, RawEntity),
     ) -> Result<StoreRequestResult, DatabaseError> {
-        let (entity_type, _entity_id, data) = data;
+        let (entity_type, entity_id, mut data) = data;
+        // `store.set` replaces the whole entity, use `store.merge` to keep the other fields
+        let primary_key = self.schema.get_primary_key(&entity_type);
+        if !data.contains_key(primary_key) {
+            // WARN: v0.0.5 Entity has `id` stripped off (why???)
+            data.insert(primary_key.to_owned(), Value::String(entity_id));
+        }
         self.handle_create((entity_type, data)).await?;
         Ok(StoreRequestResult::Update)
     }
 
+    async fn handle_merge(
+        &mut self,
+        data: (EntityType, EntityID, RawEntity),
+    ) -> Result<StoreRequestResult, DatabaseError> {
+        let (entity_type, entity_id, partial) = data;
+        // Fields left out keep the value of the stored entity if any,
+        // bookkeeping fields (block-ptr, checksum...) are always written anew
+        let mut data = match self
+            .handle_load((entity_type.clone(), entity_id.clone()))
+            .await?
+        {
+            StoreRequestResult::Load(Some(existing)) => existing
+                .into_iter()
+                .filter(|(field_name, _)| !field_name.starts_with("__"))
+                .collect(),
+            StoreRequestResult::Load(None) => RawEntity::new(),
+            _ => return Err(DatabaseError::Invalid),
+        };
+        data.extend(partial);
+        self.handle_update((entity_type, entity_id, data)).await
+    }
+
     async fn handle_delete(
         &mut self,
         data: (EntityType, EntityID),
     ) -> Result<StoreRequestResult, DatabaseError> {
         let (entity_type, entity_id) = data;
+        // The entity may only be stored, it is loaded to be deleted from the cache
+        let stored = self
+            .handle_load((entity_type.clone(), entity_id.clone()))
+            .await?;
+        if !matches!(stored, StoreRequestResult::Load(Some(_))) {
+            return Ok(StoreRequestResult::Delete(0));
+        }
         self.mem.soft_delete(&entity_type, &entity_id)?;
-        Ok(StoreRequestResult::Delete)
+        self.written_in_block.insert((entity_type, entity_id));
+        Ok(StoreRequestResult::Delete(1))
     }
 
-    async fn handle_load_related(
+    /// Hand the related entities to `on_page` as they are loaded, at most
+    /// `LOAD_RELATED_PAGE_SIZE` of them at a time
+    async fn handle_load_related<F>(
         &mut self,
         data: (EntityType, EntityID, FieldName),
-    ) -> Result<StoreRequestResult, DatabaseError> {
+        mut on_page: F,
+    ) -> Result<(), DatabaseError>
+    where
+        F: FnMut(Vec<RawEntity>) -> Result<(), DatabaseError>,
+    {
         let (entity_type, entity_id, field_name) = data;
-        let entity = self.mem.load_entity_latest(&entity_type, &entity_id)?;
+        if let Some((relation_table, relation_field)) =
+            self.schema.get_derived_field(&entity_type, &field_name)
+        {
+            let mut related_entities = self
+                .load_derived_entities(&relation_table, &relation_field, &entity_id)
+                .await?;
+            self.cap_related(&entity_type, &entity_id, &field_name, &mut related_entities);
+            return on_page(related_entities);
+        }
 
-        //In memory always have the latest version of the entity by action load before.
-        //We don't need to check the db
-
-        let entity = entity.unwrap();
-        let field_related_ids = entity.get(&field_name).cloned().unwrap();
-        let ids = match field_related_ids {
-            Value::String(id) => vec![id],
-            Value::List(list) => {
-                let mut ids = vec![];
-                list.iter().for_each(|value| {
-                    if let Value::String(entity_id) = value {
-                        ids.push(entity_id.clone())
-                    }
-                });
-                ids
+        let mut ids = self
+            .related_ids(&entity_type, &entity_id, &field_name)
+            .await?;
+        self.cap_related(&entity_type, &entity_id, &field_name, &mut ids);
+        self.stream_related_entities(&entity_type, &field_name, ids, on_page)
+            .await
+    }
+
+    /// Truncate the result of a `store.loadRelated` to `max_related_entities`
+    fn cap_related<T>(
+        &self,
+        entity_type: &str,
+        entity_id: &str,
+        field_name: &str,
+        related: &mut Vec<T>,
+    ) {
+        if let Some(cap) = self.max_related_entities {
+            if related.len() > cap {
+                warn!(
+                    Database,
+                    "too many related entities, result truncated";
+                    entity_type => entity_type,
+                    entity_id => entity_id,
+                    field_name => field_name,
+                    total => related.len(),
+                    cap => cap
+                );
+                self.metrics.load_related_truncated.inc();
+                related.truncate(cap);
             }
+        }
+    }
+
+    /// Load the related entities at `offset..offset + limit` of the relation list,
+    /// not subject to `max_related_entities`
+    async fn handle_load_related_page(
+        &mut self,
+        data: (EntityType, EntityID, FieldName),
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
+        let (entity_type, entity_id, field_name) = data;
+        let entity_id = self.normalize_id(&entity_type, entity_id);
+        if let Some((relation_table, relation_field)) =
+            self.schema.get_derived_field(&entity_type, &field_name)
+        {
+            return self
+                .load_derived_page(&relation_table, &relation_field, &entity_id, offset, limit)
+                .await;
+        }
+
+        let ids = self
+            .related_ids(&entity_type, &entity_id, &field_name)
+            .await?
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .collect();
+        let mut related_entities = vec![];
+        self.stream_related_entities(&entity_type, &field_name, ids, |page| {
+            related_entities.extend(page);
+            Ok(())
+        })
+        .await?;
+        Ok(related_entities)
+    }
+
+    /// Ids listed by the relation field of an entity, which is loaded like a `store.get`
+    /// as it may not be cached anymore, eg committed in an earlier batch
+    async fn related_ids(
+        &mut self,
+        entity_type: &str,
+        entity_id: &str,
+        field_name: &str,
+    ) -> Result<Vec<EntityID>, DatabaseError> {
+        let entity = match self
+            .handle_load((entity_type.to_owned(), entity_id.to_owned()))
+            .await?
+        {
+            StoreRequestResult::Load(entity) => entity,
+            _ => None,
+        };
+        let ids = match entity.and_then(|entity| entity.get(field_name).cloned()) {
+            Some(Value::String(id)) => vec![id],
+            Some(Value::List(list)) => list
+                .into_iter()
+                .filter_map(|value| match value {
+                    Value::String(entity_id) => Some(entity_id),
+                    _ => None,
+                })
+                .collect(),
             _ => vec![],
         };
+        Ok(ids)
+    }
 
-        if let Some((relation_table, _field_name)) =
-            self.schema.get_relation_field(&entity_type, &field_name)
-        {
+    /// Entities of `relation_table` whose `relation_field` references `entity_id`, ordered by id.
+    /// Committed ones are looked up by the field, which the schema indexes for every `@derivedFrom`,
+    /// and are superseded by the versions written since
+    async fn load_derived_entities(
+        &mut self,
+        relation_table: &str,
+        relation_field: &str,
+        entity_id: &str,
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
+        let primary_key = self.schema.get_primary_key(relation_table).to_owned();
+        let timer = self.metrics.extern_db_get_duration.start_timer();
+        let committed = self
+            .db
+            .load_entities_by_field(
+                relation_table,
+                relation_field,
+                Value::String(entity_id.to_owned()),
+            )
+            .await?;
+        timer.stop_and_record();
+
+        let mut committed: HashMap<EntityID, RawEntity> = committed
+            .into_iter()
+            .filter_map(|entity| match entity.get(&primary_key) {
+                Some(Value::String(id)) => Some((id.to_owned(), entity)),
+                _ => None,
+            })
+            .collect();
+        let in_mem: HashSet<EntityID> = self
+            .mem
+            .get_latest_entity_ids()
+            .into_iter()
+            .filter(|(entity_type, _)| entity_type == relation_table)
+            .map(|(_, id)| id)
+            .collect();
+        let mut ids: BTreeSet<EntityID> = committed.keys().cloned().collect();
+        ids.extend(in_mem.iter().cloned());
+        ids.extend(
+            self.provisional
+                .iter()
+                .flat_map(|block| block.entities.keys())
+                .filter(|(entity_type, _)| entity_type == relation_table)
+                .map(|(_, id)| id.to_owned()),
+        );
+
+        let mut related_entities = vec![];
+        for id in ids {
+            let entity = if in_mem.contains(&id) {
+                self.mem.load_entity_latest(relation_table, &id)?
+            } else {
+                let entity = match self.load_provisional(relation_table, &id) {
+                    Some(entity) => entity,
+                    None => match committed.remove(&id) {
+                        Some(data) => {
+                            self.verify_checksum(relation_table, &data)?;
+                            Some(data)
+                        }
+                        None => None,
+                    },
+                };
+                if let Some(data) = &entity {
+                    self.mem
+                        .create_entity(relation_table, &primary_key, data.clone())?;
+                    self.loaded_from_db
+                        .insert((relation_table.to_owned(), id.clone()));
+                }
+                entity
+            };
+
+            if entity
+                .as_ref()
+                .is_some_and(|entity| utils::references(entity, relation_field, entity_id))
+            {
+                related_entities.extend(entity);
+            }
+        }
+        Ok(related_entities)
+    }
+
+    /// Like `load_derived_entities` from `offset` on, with at most `limit` entities.
+    /// Only the candidate ids are looked up at once, the entities are loaded from the database
+    /// a page at a time & not cached, until the requested ones are found
+    async fn load_derived_page(
+        &mut self,
+        relation_table: &str,
+        relation_field: &str,
+        entity_id: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
+        let primary_key = self.schema.get_primary_key(relation_table).to_owned();
+        let timer = self.metrics.extern_db_get_duration.start_timer();
+        let committed = self
+            .db
+            .load_entity_ids_by_field(
+                relation_table,
+                relation_field,
+                Value::String(entity_id.to_owned()),
+            )
+            .await?;
+        timer.stop_and_record();
+
+        let in_mem: HashSet<EntityID> = self
+            .mem
+            .get_latest_entity_ids()
+            .into_iter()
+            .filter(|(entity_type, _)| entity_type == relation_table)
+            .map(|(_, id)| id)
+            .collect();
+        let mut ids: BTreeSet<EntityID> = committed.into_iter().collect();
+        ids.extend(in_mem.iter().cloned());
+        ids.extend(
+            self.provisional
+                .iter()
+                .flat_map(|block| block.entities.keys())
+                .filter(|(entity_type, _)| entity_type == relation_table)
+                .map(|(_, id)| id.to_owned()),
+        );
+        let ids = ids.into_iter().collect::<Vec<_>>();
+
+        let mut skipped = 0;
+        let mut related_entities = vec![];
+        for page in ids.chunks(LOAD_RELATED_PAGE_SIZE) {
+            let mut entities = BTreeMap::new();
+            let mut missing_ids = vec![];
+            for id in page {
+                let entity = match in_mem.contains(id) {
+                    true => Some(self.mem.load_entity_latest(relation_table, id)?),
+                    false => self.load_provisional(relation_table, id),
+                };
+                match entity {
+                    Some(entity) => entities.extend(entity.map(|data| (id.to_owned(), data))),
+                    None => missing_ids.push(id.to_owned()),
+                }
+            }
+
+            if !missing_ids.is_empty() {
+                let timer = self.metrics.extern_db_get_duration.start_timer();
+                let loaded = self.db.load_entities(relation_table, missing_ids).await?;
+                timer.stop_and_record();
+                for data in loaded {
+                    self.verify_checksum(relation_table, &data)?;
+                    if let Some(Value::String(id)) = data.get(&primary_key) {
+                        entities.insert(id.to_owned(), data);
+                    }
+                }
+            }
+
+            for data in entities.into_values() {
+                if !utils::references(&data, relation_field, entity_id) {
+                    continue;
+                }
+                if skipped < offset {
+                    skipped += 1;
+                    continue;
+                }
+                if related_entities.len() == limit {
+                    return Ok(related_entities);
+                }
+                related_entities.push(data);
+            }
+        }
+        Ok(related_entities)
+    }
+
+    /// Entities loaded from the database are handed out page by page and not cached,
+    /// so that a large relation never has more than a page of entities in memory
+    async fn stream_related_entities<F>(
+        &mut self,
+        entity_type: &str,
+        field_name: &str,
+        ids: Vec<EntityID>,
+        mut on_page: F,
+    ) -> Result<(), DatabaseError>
+    where
+        F: FnMut(Vec<RawEntity>) -> Result<(), DatabaseError>,
+    {
+        let relation_table = match self.schema.get_relation_field(entity_type, field_name) {
+            Some((relation_table, _field_name)) => relation_table,
+            None => return Ok(()),
+        };
+
+        for page in ids.chunks(LOAD_RELATED_PAGE_SIZE) {
             let mut related_entities = vec![];
             let mut missing_ids = vec![];
-            for id in ids {
-                let entity = self.mem.load_entity_latest(&relation_table, &id)?;
-                if entity.is_some() {
-                    related_entities.push(entity.unwrap());
-                } else {
-                    missing_ids.push(id);
+            for id in page {
+                let id = self.normalize_id(&relation_table, id.to_owned());
+                match self.mem.load_entity_latest(&relation_table, &id)? {
+                    Some(entity) => related_entities.push(entity),
+                    None => match self.load_provisional(&relation_table, &id) {
+                        Some(entity) => related_entities.extend(entity),
+                        None => missing_ids.push(id),
+                    },
                 }
             }
+
             if !missing_ids.is_empty() {
                 let timer = self.metrics.extern_db_get_duration.start_timer();
                 let entities = self.db.load_entities(&relation_table, missing_ids).await?;
                 timer.stop_and_record();
-
                 for entity in entities {
-                    related_entities.push(entity.clone());
-                    self.mem.create_entity(&relation_table, entity)?;
+                    self.verify_checksum(&relation_table, &entity)?;
+                    related_entities.push(entity);
                 }
             }
-            Ok(StoreRequestResult::LoadRelated(related_entities))
-        } else {
-            Ok(StoreRequestResult::LoadRelated(vec![]))
+
+            on_page(related_entities)?;
         }
+        Ok(())
     }
 
     async fn migrate_from_mem_to_db(&mut self, block_ptr: BlockPtr) -> Result<(), DatabaseError> {
-        let values = self.mem.extract_data()?;
-        self.metrics.extern_db_write.inc();
-        let timer = self.metrics.extern_db_set_duration.start_timer();
-        self.db
-            .batch_insert_entities(block_ptr.clone(), values)
+        let _timer = self.metrics.migrate_duration.start_timer();
+        // No entity was written: only the block-ptr needs to be recorded
+        let values = match self.mem.is_empty() {
+            true => vec![],
+            false => self.mem.extract_data()?,
+        };
+        let changes = self.entity_changes(block_ptr.number, &values);
+        self.created_in_block.clear();
+        // Once committed, written entities are part of the committed store
+        let written = std::mem::take(&mut self.written_in_block);
+        self.loaded_from_db.extend(written);
+
+        if self.commit_depth == 0 {
+            return self.persist_block(block_ptr, values, changes).await;
+        }
+
+        let mut entities = HashMap::new();
+        for (entity_type, data) in values {
+            let entity_id = match data.get(self.schema.get_primary_key(&entity_type)) {
+                Some(Value::String(entity_id)) => entity_id.to_owned(),
+                _ => continue,
+            };
+            entities.insert((entity_type, entity_id), data);
+        }
+        let head = block_ptr.number;
+        self.provisional.push_back(ProvisionalBlock {
+            block_ptr,
+            entities,
+            changes,
+        });
+
+        while self
+            .provisional
+            .front()
+            .is_some_and(|block| block.block_ptr.number + self.commit_depth <= head)
+        {
+            let block = self.provisional.pop_front().unwrap();
+            self.persist_provisional_block(block).await?;
+        }
+        Ok(())
+    }
+
+    async fn persist_provisional_block(
+        &mut self,
+        block: ProvisionalBlock,
+    ) -> Result<(), DatabaseError> {
+        let ids = block
+            .entities
+            .keys()
+            .filter(|(entity_type, _)| !self.schema.is_immutable(entity_type))
+            .cloned()
+            .collect();
+        self.persisted.push((block.block_ptr.number, ids));
+        let values = block
+            .entities
+            .into_iter()
+            .map(|((entity_type, _), data)| (entity_type, data))
+            .collect();
+        self.persist_block(block.block_ptr, values, block.changes)
+            .await
+    }
+
+    async fn persist_block(
+        &mut self,
+        block_ptr: BlockPtr,
+        mut values: Vec<(EntityType, RawEntity)>,
+        changes: Vec<EntityChange>,
+    ) -> Result<(), DatabaseError> {
+        if self.hard_delete_after.is_some() {
+            let deleted = self.deleted_entity_ids(&values);
+            if !deleted.is_empty() {
+                self.soft_deleted.push_back((block_ptr.number, deleted));
+            }
+        }
+        if !values.is_empty() {
+            if self.with_checksum {
+                for (_, data) in values.iter_mut() {
+                    let checksum = utils::entity_checksum(data);
+                    data.insert("__checksum__".to_string(), Value::Int8(checksum));
+                }
+            }
+            self.metrics.extern_db_write.inc();
+            let timer = self.metrics.extern_db_set_duration.start_timer();
+            let db = &self.db;
+            utils::insert_per_entity_type(
+                values,
+                self.migration_concurrency,
+                self.write_order.as_ref(),
+                |batch| db.batch_insert_entities(block_ptr.clone(), batch),
+            )
             .await?;
-        timer.stop_and_record();
+            timer.stop_and_record();
+        }
         self.metrics.extern_db_write.inc();
         self.db.save_block_ptr(block_ptr.clone()).await?;
+        let newer = self.cursors.split_off(&(block_ptr.number + 1));
+        if let Some(cursor) = std::mem::replace(&mut self.cursors, newer).remove(&block_ptr.number)
+        {
+            self.db
+                .save_checkpoint(Checkpoint {
+                    block_ptr: block_ptr.clone(),
+                    cursor,
+                })
+                .await?;
+        }
+        if let Some(hook) = &self.commit_hook {
+            hook(&block_ptr);
+        }
+        if let Some(feed) = self.change_feed.as_mut() {
+            for change in changes {
+                feed.send(change).await?;
+            }
+        }
+        self.hard_delete_expired(block_ptr.number).await?;
+        Ok(())
+    }
+
+    fn deleted_entity_ids(
+        &self,
+        values: &[(EntityType, RawEntity)],
+    ) -> Vec<(EntityType, EntityID)> {
+        values
+            .iter()
+            .filter(|(_, data)| data.get("__is_deleted__") == Some(&Value::Bool(true)))
+            .filter_map(|(entity_type, data)| {
+                match data.get(self.schema.get_primary_key(entity_type)) {
+                    Some(Value::String(id)) => Some((entity_type.to_owned(), id.to_owned())),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Deleted snapshots still stored are the deletes not removed for good yet,
+    /// reloading them keeps the hard deletes pending across restarts
+    async fn load_soft_deleted(&mut self) -> Result<(), DatabaseError> {
+        let mut deleted = BTreeMap::<u64, Vec<(EntityType, EntityID)>>::new();
+        for entity_type in self.schema.get_entity_names() {
+            let primary_key = self.schema.get_primary_key(&entity_type);
+            for (block_number, entity) in self.db.load_deleted_entities(&entity_type).await? {
+                if let Some(Value::String(id)) = entity.get(primary_key) {
+                    deleted
+                        .entry(block_number)
+                        .or_default()
+                        .push((entity_type.clone(), id.clone()));
+                }
+            }
+        }
+
+        info!(Database, "soft-deleted entities loaded"; number_of_blocks => deleted.len());
+        self.soft_deleted = deleted.into_iter().collect();
         Ok(())
     }
 
-    async fn revert_from_block(&mut self, block_number: u64) -> Result<(), DatabaseError> {
+    /// Remove the entities deleted beyond the reorg threshold, up to & including their
+    /// deleted snapshot. Snapshots of an entity created again later are kept
+    async fn hard_delete_expired(&mut self, head: u64) -> Result<usize, DatabaseError> {
+        let threshold = match self.hard_delete_after {
+            Some(threshold) => threshold,
+            None => return Ok(0),
+        };
+
+        let mut count = 0;
+        while self
+            .soft_deleted
+            .front()
+            .is_some_and(|(block_number, _)| block_number + threshold <= head)
+        {
+            let (block_number, entities) = self.soft_deleted.pop_front().unwrap();
+            count += self.db.remove_snapshots(entities, block_number + 1).await?;
+        }
+
+        if count > 0 {
+            info!(Database, "deleted entities removed"; number_of_entity => count, head => head);
+        }
+        Ok(count)
+    }
+
+    /// Changes of the entities written since the last commit, ordered by entity type & id,
+    /// referenced entity types first when writes are ordered
+    fn entity_changes(&self, block: u64, values: &[(EntityType, RawEntity)]) -> Vec<EntityChange> {
+        if self.change_feed.is_none() {
+            return vec![];
+        }
+
+        let mut changes = values
+            .iter()
+            .filter_map(|(entity_type, data)| {
+                let id = match data.get(self.schema.get_primary_key(entity_type)) {
+                    Some(Value::String(id)) => id.to_owned(),
+                    _ => return None,
+                };
+                let key = (entity_type.to_owned(), id);
+                if !self.written_in_block.contains(&key) {
+                    return None;
+                }
+                let op = if data.get("__is_deleted__") == Some(&Value::Bool(true)) {
+                    ChangeOp::Delete
+                } else if self.loaded_from_db.contains(&key) {
+                    ChangeOp::Update
+                } else {
+                    ChangeOp::Create
+                };
+                let (entity_type, id) = key;
+                Some(EntityChange {
+                    block,
+                    entity_type,
+                    id,
+                    op,
+                    entity: data.clone(),
+                })
+            })
+            .collect::<Vec<_>>();
+        let rank = |change: &EntityChange| {
+            self.write_order
+                .as_ref()
+                .and_then(|order| order.get(&change.entity_type).copied())
+                .unwrap_or_default()
+        };
+        changes.sort_by(|a, b| {
+            (rank(a), &a.entity_type, &a.id).cmp(&(rank(b), &b.entity_type, &b.id))
+        });
+        changes
+    }
+
+    /// Latest state of an entity among the blocks held back from the database,
+    /// `Some(None)` if it was deleted there
+    fn load_provisional(&self, entity_type: &str, entity_id: &str) -> Option<Option<RawEntity>> {
+        let key = (entity_type.to_owned(), entity_id.to_owned());
+        let data = self
+            .provisional
+            .iter()
+            .rev()
+            .find_map(|block| block.entities.get(&key))?;
+        match data.get("__is_deleted__") {
+            Some(Value::Bool(true)) => Some(None),
+            _ => Some(Some(data.clone())),
+        }
+    }
+
+    /// Entities cached in memory that may have outdated snapshots,
+    /// immutable entities only ever have one
+    fn versioned_entity_ids(&self) -> Vec<(EntityType, EntityID)> {
+        self.mem
+            .get_latest_entity_ids()
+            .into_iter()
+            .filter(|(entity_type, _)| !self.schema.is_immutable(entity_type))
+            .collect()
+    }
+
+    /// Returns the number of entities reverted, those of provisional blocks included
+    async fn revert_from_block(&mut self, block_number: u64) -> Result<usize, DatabaseError> {
         self.mem.clear();
-        self.db.revert_from_block(block_number).await
+        self.loaded_from_db.clear();
+        self.created_in_block.clear();
+        self.written_in_block.clear();
+        let reverted_provisional = self
+            .provisional
+            .iter()
+            .filter(|block| block.block_ptr.number >= block_number)
+            .flat_map(|block| block.entities.keys())
+            .collect::<HashSet<_>>()
+            .len();
+        self.provisional
+            .retain(|block| block.block_ptr.number < block_number);
+        self.soft_deleted
+            .retain(|(deleted_at, _)| *deleted_at < block_number);
+        self.cursors.split_off(&block_number);
+        let reverted = self.db.revert_from_block(block_number).await?;
+        Ok(reverted_provisional + reverted)
     }
 }
 
@@ -232,7 +1171,7 @@ unsafe impl Send for DatabaseAgent {}
 
 impl DatabaseAgent {
     pub async fn new(
-        config: &DatabaseConfig,
+        config: &Config,
         schema: Schemas,
         registry: &Registry,
     ) -> Result<Self, DatabaseError> {
@@ -240,30 +1179,192 @@ impl DatabaseAgent {
         Ok(Self::from(db))
     }
 
-    pub fn wasm_send_store_request(
-        &self,
-        message: StoreOperationMessage,
-    ) -> Result<StoreRequestResult, DatabaseError> {
-        let mut db = self.0.borrow_mut();
-        tokio::task::block_in_place(move || {
-            tokio::runtime::Handle::current().block_on(async move {
-                let result = db.handle_store_request(message).await?;
-                Ok::<StoreRequestResult, DatabaseError>(result)
-            })
-        })
+    /// While fast-forwarding, nothing is committed and entities are accumulated in memory
+    pub fn is_fast_forwarding(&self, block_number: u64) -> bool {
+        let db = self.0.borrow();
+        !db.fast_forward_done
+            && db
+                .fast_forward_to_block
+                .is_some_and(|cutover| block_number <= cutover)
     }
 
-    pub async fn get_recent_block_pointers(
-        &self,
-        number_of_blocks: u16,
-    ) -> Result<Vec<BlockPtr>, DatabaseError> {
+    /// The cutover is the first block at or past `fast_forward_to_block`,
+    /// so that it still happens if the source has no such block
+    fn is_fast_forward_cutover(&self, block_number: u64) -> bool {
         let db = self.0.borrow();
-        db.db.load_recent_block_ptrs(number_of_blocks).await
+        !db.fast_forward_done
+            && db
+                .fast_forward_to_block
+                .is_some_and(|cutover| block_number >= cutover)
     }
 
-    pub async fn commit_data(&self, block_ptr: BlockPtr) -> Result<(), DatabaseError> {
-        let time = Instant::now();
-        let block_number = block_ptr.number;
+    /// Write the entities' final state once, at the fast-forward cutover
+    pub async fn fast_forward_cutover(&self, block_ptr: BlockPtr) -> Result<(), DatabaseError> {
+        if !self.is_fast_forward_cutover(block_ptr.number) {
+            return Ok(());
+        }
+        info!(Database, "fast-forward cutover reached, writing entities' final state"; block => block_ptr);
+        self.commit_data(block_ptr.clone()).await?;
+        self.remove_outdated_snapshots(block_ptr.number).await?;
+        self.flush_cache().await?;
+        self.0.borrow_mut().fast_forward_done = true;
+        Ok(())
+    }
+
+    /// Swap in a scratch memory-db so that following store operations
+    /// are recorded but never reach the current state
+    pub fn start_replay(&self) {
+        let mut db = self.0.borrow_mut();
+        let stashed = std::mem::take(&mut db.mem);
+        db.replay = Some((stashed, vec![]));
+        db.created_in_block.clear();
+        db.written_in_block.clear();
+    }
+
+    /// Restore the stashed memory-db, returning the write operations recorded during replay
+    pub fn finish_replay(&self) -> Vec<StoreOperationMessage> {
+        let mut db = self.0.borrow_mut();
+        match db.replay.take() {
+            Some((stashed, operations)) => {
+                db.mem = stashed;
+                db.created_in_block.clear();
+                db.written_in_block.clear();
+                operations
+            }
+            None => vec![],
+        }
+    }
+
+    pub fn set_block_timestamp(&self, timestamp: u64) {
+        self.0.borrow_mut().block_timestamp = timestamp;
+    }
+
+    /// Report the block if it wrote more entities than `max_entities_per_block`,
+    /// to be called once every handler of the block has run. Returns the entity count warned about
+    pub fn finish_block(&self, block_number: u64) -> Option<usize> {
+        let mut db = self.0.borrow_mut();
+        // Immutable entities created by this block can't be written again by the next ones
+        db.created_in_block.clear();
+        let count = std::mem::take(&mut db.block_writes);
+        if let Some(cap) = db.max_entities_per_block {
+            if count > cap {
+                warn!(
+                    Database,
+                    "block writes an abnormal number of entities";
+                    block_number => block_number,
+                    entity_count => count,
+                    cap => cap
+                );
+                db.metrics.abnormal_entity_count.inc();
+                return Some(count);
+            }
+        }
+        None
+    }
+
+    // Extension point for integrations, nothing registers a hook by default
+    #[allow(dead_code)]
+    pub fn set_commit_hook(&self, hook: CommitHook) {
+        self.0.borrow_mut().commit_hook = Some(hook);
+    }
+
+    /// Kind of every field of `entity_type` as declared by the schema, eg `Timestamp` for a value held as `Int8`
+    pub fn get_field_kinds(&self, entity_type: &str) -> HashMap<FieldName, StoreValueKind> {
+        self.0.borrow().schema.get_field_kinds(entity_type)
+    }
+
+    /// Entity type the relation field `field_name` of `entity_type` points at
+    pub fn get_related_entity_type(&self, entity_type: &str, field_name: &str) -> Option<String> {
+        self.0
+            .borrow()
+            .schema
+            .get_relation_field(entity_type, field_name)
+            .map(|(related_type, _)| related_type)
+    }
+
+    /// Related entities of a `store.loadRelated`, handed to `on_page` one page at a time
+    pub fn wasm_load_related<F>(
+        &self,
+        message: StoreOperationMessage,
+        on_page: F,
+    ) -> Result<(), DatabaseError>
+    where
+        F: FnMut(Vec<RawEntity>) -> Result<(), DatabaseError>,
+    {
+        let mut db = self.0.borrow_mut();
+        let message = db.normalize_request(message);
+        let _timer = db
+            .metrics
+            .handle_store_request_duration
+            .with_label_values(&[&message.operation_type(), &message.entity_type()])
+            .start_timer();
+        let data = match message {
+            StoreOperationMessage::LoadRelated(data) => data,
+            _ => return Err(DatabaseError::WasmSendInvalidRequest),
+        };
+        tokio::task::block_in_place(move || {
+            tokio::runtime::Handle::current().block_on(db.handle_load_related(data, on_page))
+        })
+    }
+
+    /// Page through a relation list that may be larger than `max_related_entities`
+    pub async fn load_related_page(
+        &self,
+        data: (EntityType, EntityID, FieldName),
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<RawEntity>, DatabaseError> {
+        let mut db = self.0.borrow_mut();
+        db.handle_load_related_page(data, offset, limit).await
+    }
+
+    pub fn wasm_send_store_request(
+        &self,
+        message: StoreOperationMessage,
+    ) -> Result<StoreRequestResult, DatabaseError> {
+        let mut db = self.0.borrow_mut();
+        tokio::task::block_in_place(move || {
+            tokio::runtime::Handle::current().block_on(async move {
+                let result = db.handle_store_request(message).await?;
+                Ok::<StoreRequestResult, DatabaseError>(result)
+            })
+        })
+    }
+
+    pub async fn get_recent_block_pointers(
+        &self,
+        number_of_blocks: u16,
+    ) -> Result<Vec<BlockPtr>, DatabaseError> {
+        let db = self.0.borrow();
+        db.db.load_recent_block_ptrs(number_of_blocks).await
+    }
+
+    pub async fn save_datasources(
+        &self,
+        datasources: Vec<Datasource>,
+    ) -> Result<(), DatabaseError> {
+        let db = self.0.borrow();
+        db.db.save_datasources(datasources).await
+    }
+
+    pub async fn load_datasources(&self) -> Result<Vec<Datasource>, DatabaseError> {
+        let db = self.0.borrow();
+        Ok(db.db.load_datasources().await?.unwrap_or_default())
+    }
+
+    /// The source cursor of a block, saved in a checkpoint once the block is written
+    pub fn set_cursor(&self, block_number: u64, cursor: String) {
+        self.0.borrow_mut().cursors.insert(block_number, cursor);
+    }
+
+    pub async fn load_checkpoint(&self) -> Result<Option<Checkpoint>, DatabaseError> {
+        let db = self.0.borrow();
+        db.db.load_checkpoint().await
+    }
+
+    pub async fn commit_data(&self, block_ptr: BlockPtr) -> Result<(), DatabaseError> {
+        let time = Instant::now();
+        let block_number = block_ptr.number;
         let mut db = self.0.borrow_mut();
         db.migrate_from_mem_to_db(block_ptr).await?;
         info!(
@@ -275,24 +1376,107 @@ impl DatabaseAgent {
         Ok(())
     }
 
+    /// Receive every entity change once its block is written, if the change feed is enabled
+    pub fn subscribe_changes(&self) -> Option<kanal::AsyncReceiver<EntityChange>> {
+        let db = self.0.borrow();
+        db.change_feed.as_ref().map(|feed| feed.subscribe())
+    }
+
+    /// Write every block still held back by `commit_depth`, eg once a finite source is exhausted
+    pub async fn commit_provisional(&self) -> Result<(), DatabaseError> {
+        let mut db = self.0.borrow_mut();
+        while let Some(block) = db.provisional.pop_front() {
+            db.persist_provisional_block(block).await?;
+        }
+        Ok(())
+    }
+
+    /// Write what is still held in memory once the block-stream has ended: the entities accumulated
+    /// while fast-forwarding, and the blocks held back by `commit_depth` as no newer block is coming
+    /// to confirm them. When shutting down those are left out, and indexed again on restart
+    pub async fn commit_final(
+        &self,
+        last_block: Option<BlockPtr>,
+        shutting_down: bool,
+    ) -> Result<(), DatabaseError> {
+        if let Some(last_block) = last_block {
+            if self.is_fast_forwarding(last_block.number) {
+                info!(Database, "block-stream ended while fast-forwarding, writing entities' final state"; block => last_block);
+                self.commit_data(last_block.clone()).await?;
+                self.remove_outdated_snapshots(last_block.number).await?;
+                self.flush_cache().await?;
+            }
+        }
+
+        if !shutting_down {
+            self.commit_provisional().await?;
+        }
+
+        Ok(())
+    }
+
     pub async fn flush_cache(&self) -> Result<(), DatabaseError> {
         let mut db = self.0.borrow_mut();
         db.mem.clear();
+        db.loaded_from_db.clear();
         info!(Database, "flushed entity cache");
         Ok(())
     }
 
-    pub async fn revert_from_block(&self, block_number: u64) -> Result<(), DatabaseError> {
+    pub async fn revert_from_block(&self, block_number: u64) -> Result<usize, DatabaseError> {
         warn!(Database, "Reverting data (probably due to reorg)"; revert_from_block_number => block_number);
         let mut db = self.0.borrow_mut();
-        db.revert_from_block(block_number).await?;
-        warn!(Database, "Database reverted OK"; revert_from_block_number => block_number);
-        Ok(())
+        let reverted = db.revert_from_block(block_number).await?;
+        warn!(Database, "Database reverted OK"; revert_from_block_number => block_number, number_of_entity => reverted);
+        Ok(reverted)
+    }
+
+    /// Revert everything from `from_block` so the range, and the blocks after it, are indexed again.
+    /// A range is reverted once, `None` is returned if it already was, eg the runtime restarting
+    /// with the same range still configured
+    pub async fn reprocess(
+        &self,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Option<usize>, DatabaseError> {
+        if to_block < from_block {
+            return Err(DatabaseError::InvalidBlockRange(from_block, to_block));
+        }
+
+        let range = ReprocessedRange {
+            from_block,
+            to_block,
+        };
+        if self.0.borrow().db.load_reprocessed_range().await? == Some(range.clone()) {
+            info!(Database, "block range already reprocessed"; from_block => from_block, to_block => to_block);
+            return Ok(None);
+        }
+
+        let earliest_block = self.0.borrow().earliest_block;
+        if from_block < earliest_block {
+            // Snapshots before the earliest block are cleaned up, reverting would lose state
+            return Err(DatabaseError::PrunedBlock(from_block, earliest_block));
+        }
+
+        warn!(Database, "reprocessing block range"; from_block => from_block, to_block => to_block);
+        let reverted = self.revert_from_block(from_block).await?;
+        self.0.borrow().db.save_reprocessed_range(range).await?;
+        Ok(Some(reverted))
     }
 
     pub async fn remove_outdated_snapshots(&self, at_block: u64) -> Result<usize, DatabaseError> {
-        let db = self.0.borrow();
-        let entities = db.mem.get_latest_entity_ids();
+        let mut db = self.0.borrow_mut();
+        if db.commit_depth > 0 {
+            // Only blocks already written can be cleaned up, each at its own block number
+            let mut count = 0;
+            for (block_number, entities) in std::mem::take(&mut db.persisted) {
+                count += db.db.remove_snapshots(entities, block_number).await?;
+            }
+            info!(Database, "entities' snapshot removed"; number_of_entity => count);
+            return Ok(count);
+        }
+
+        let entities = db.versioned_entity_ids();
         let count = db.db.remove_snapshots(entities, at_block).await?;
         info!(Database, "entities' snapshot removed"; number_of_entity => count);
         Ok(count)
@@ -327,7 +1511,1557 @@ impl DatabaseAgent {
             metrics,
             schema: Schemas::default(),
             earliest_block: 0,
+            fast_forward_to_block: None,
+            fast_forward_done: false,
+            entities_allowlist: None,
+            entities_denylist: None,
+            with_block_timestamp: false,
+            block_timestamp: 0,
+            with_checksum: false,
+            entity_id_case: HashMap::new(),
+            schema_validation: SchemaValidationPolicy::Off,
+            commit_hook: None,
+            max_related_entities: None,
+            max_entities_per_block: None,
+            block_writes: 0,
+            migration_concurrency: 4,
+            write_order: None,
+            duplicate_create: DuplicateCreatePolicy::Overwrite,
+            commit_depth: 0,
+            provisional: VecDeque::new(),
+            persisted: vec![],
+            hard_delete_after: None,
+            soft_deleted: VecDeque::new(),
+            change_feed: None,
+            written_in_block: HashSet::new(),
+            created_in_block: HashSet::new(),
+            replay: None,
+            loaded_from_db: HashSet::new(),
+            cursors: BTreeMap::new(),
         };
         DatabaseAgent::from(database)
     }
+
+    #[cfg(test)]
+    pub fn set_commit_depth(&self, commit_depth: u64) {
+        self.0.borrow_mut().commit_depth = commit_depth;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ChangeFeedConfig;
+    use crate::config::OverflowPolicy;
+    use crate::runtime::bignumber::bigint::BigInt;
+    use prometheus::default_registry;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_fast_forward_single_snapshot() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        agent.0.borrow_mut().fast_forward_to_block = Some(10);
+        agent.0.borrow_mut().change_feed = Some(ChangeFeed::new(&ChangeFeedConfig {
+            capacity: 100,
+            policy: None,
+            output: None,
+        }));
+        let changes = agent.subscribe_changes().unwrap();
+        let received = || {
+            let mut received = vec![];
+            while let Ok(Some(change)) = changes.try_recv() {
+                received.push((change.block, change.entity["block"].clone()));
+            }
+            received
+        };
+
+        // The source has no block 10, the cutover happens at the next one
+        for number in (1..=12u64).filter(|number| *number != 10) {
+            let mut data = RawEntity::new();
+            data.insert("id".to_string(), Value::String("token".to_string()));
+            data.insert("block".to_string(), Value::Int8(number as i64));
+            let request = StoreOperationMessage::update("Token".into(), "token".into(), data);
+            agent.wasm_send_store_request(request).unwrap();
+            agent.finish_block(number);
+
+            let block_ptr = BlockPtr {
+                number,
+                ..Default::default()
+            };
+            agent.fast_forward_cutover(block_ptr.clone()).await.unwrap();
+            if number < 11 {
+                assert!(agent.is_fast_forwarding(number));
+                assert!(received().is_empty());
+                continue;
+            }
+            assert!(!agent.is_fast_forwarding(number));
+            if number == 11 {
+                // A single snapshot holding the final state of the fast-forwarded blocks
+                assert_eq!(received(), vec![(11, Value::Int8(11))]);
+            }
+            agent.commit_data(block_ptr).await.unwrap();
+            agent.flush_cache().await.unwrap();
+        }
+
+        assert_eq!(received(), vec![(12, Value::Int8(12))]);
+        assert!(agent.0.borrow().fast_forward_done);
+        // Later blocks are no cutover anymore
+        agent
+            .fast_forward_cutover(BlockPtr {
+                number: 13,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert!(received().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_entities_denylist() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        agent.0.borrow_mut().entities_denylist = Some(vec!["Pool".to_string()]);
+
+        for entity_type in ["Token", "Pool"] {
+            let mut data = RawEntity::new();
+            data.insert("id".to_string(), Value::String("id_0".to_string()));
+            let request = StoreOperationMessage::Create((entity_type.to_string(), data));
+            let result = agent.wasm_send_store_request(request).unwrap();
+            assert!(matches!(result, StoreRequestResult::Create(id) if id == "id_0"));
+        }
+
+        let db = agent.0.borrow();
+        assert!(db
+            .mem
+            .load_entity_latest("Token", "id_0")
+            .unwrap()
+            .is_some());
+        assert!(db.mem.load_entity_latest("Pool", "id_0").unwrap().is_none());
+        drop(db);
+
+        agent.0.borrow_mut().entities_denylist = None;
+        agent.0.borrow_mut().entities_allowlist = Some(vec!["Pool".to_string()]);
+        let mut data = RawEntity::new();
+        data.insert("id".to_string(), Value::String("id_1".to_string()));
+        let request = StoreOperationMessage::Create(("Token".to_string(), data));
+        agent.wasm_send_store_request(request).unwrap();
+        assert!(agent
+            .0
+            .borrow()
+            .mem
+            .load_entity_latest("Token", "id_1")
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_replay_does_not_persist() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+
+        let mut data = RawEntity::new();
+        data.insert("id".to_string(), Value::String("token".to_string()));
+        data.insert("supply".to_string(), Value::Int8(1));
+        let request = StoreOperationMessage::Create(("Token".to_string(), data));
+        agent.wasm_send_store_request(request).unwrap();
+
+        agent.start_replay();
+
+        let request = StoreOperationMessage::Load(("Token".to_string(), "token".to_string()));
+        agent.wasm_send_store_request(request).unwrap();
+
+        let mut data = RawEntity::new();
+        data.insert("id".to_string(), Value::String("token".to_string()));
+        data.insert("supply".to_string(), Value::Int8(2));
+        let request =
+            StoreOperationMessage::Update(("Token".to_string(), "token".to_string(), data));
+        agent.wasm_send_store_request(request).unwrap();
+
+        let operations = agent.finish_replay();
+        assert_eq!(operations.len(), 1);
+        assert!(matches!(
+            &operations[0],
+            StoreOperationMessage::Update((entity_type, entity_id, data))
+                if entity_type == "Token"
+                    && entity_id == "token"
+                    && data.get("supply") == Some(&Value::Int8(2))
+        ));
+
+        let db = agent.0.borrow();
+        let values = db.mem.extract_data().unwrap();
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0].1.get("supply").cloned(), Some(Value::Int8(1)));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_load_with_freshness() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+
+        let mut data = RawEntity::new();
+        data.insert("id".to_string(), Value::String("fresh".to_string()));
+        let request = StoreOperationMessage::Create(("Token".to_string(), data));
+        agent.wasm_send_store_request(request).unwrap();
+
+        // Simulate an entity that `handle_load` previously fetched from the committed store
+        let mut data = RawEntity::new();
+        data.insert("id".to_string(), Value::String("committed".to_string()));
+        let mut db = agent.0.borrow_mut();
+        db.mem.create_entity("Token", "id", data).unwrap();
+        db.loaded_from_db
+            .insert(("Token".to_string(), "committed".to_string()));
+        drop(db);
+
+        for (entity_id, expected) in [("fresh", true), ("committed", false)] {
+            let request =
+                StoreOperationMessage::LoadWithFreshness(("Token".to_string(), entity_id.into()));
+            let result = agent.wasm_send_store_request(request).unwrap();
+            assert!(matches!(
+                result,
+                StoreRequestResult::LoadWithFreshness(Some((_, created_in_block)))
+                    if created_in_block == expected
+            ));
+        }
+
+        let request =
+            StoreOperationMessage::LoadWithFreshness(("Token".to_string(), "missing".into()));
+        let result = agent.wasm_send_store_request(request).unwrap();
+        assert!(matches!(
+            result,
+            StoreRequestResult::LoadWithFreshness(None)
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_load_in_block() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+
+        for entity_id in ["kept", "removed"] {
+            let mut data = RawEntity::new();
+            data.insert("id".to_string(), Value::String(entity_id.to_string()));
+            let request = StoreOperationMessage::Create(("Token".to_string(), data));
+            agent.wasm_send_store_request(request).unwrap();
+        }
+        let request = StoreOperationMessage::Delete(("Token".to_string(), "removed".into()));
+        assert!(matches!(
+            agent.wasm_send_store_request(request).unwrap(),
+            StoreRequestResult::Delete(1)
+        ));
+        // Nothing is deleted twice, nor deleted if it never existed
+        for entity_id in ["removed", "missing"] {
+            let request = StoreOperationMessage::Delete(("Token".to_string(), entity_id.into()));
+            assert!(matches!(
+                agent.wasm_send_store_request(request).unwrap(),
+                StoreRequestResult::Delete(0)
+            ));
+        }
+
+        let request = StoreOperationMessage::LoadInBlock(("Token".to_string(), "kept".into()));
+        let result = agent.wasm_send_store_request(request).unwrap();
+        assert!(matches!(
+            result,
+            StoreRequestResult::LoadInBlock(Some(entity))
+                if entity.get("id") == Some(&Value::String("kept".to_string()))
+        ));
+
+        for entity_id in ["removed", "missing"] {
+            let request =
+                StoreOperationMessage::LoadInBlock(("Token".to_string(), entity_id.into()));
+            let result = agent.wasm_send_store_request(request).unwrap();
+            assert!(matches!(result, StoreRequestResult::LoadInBlock(None)));
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_commit_empty_block() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+
+        agent
+            .commit_data(BlockPtr {
+                number: 1,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let db = agent.0.borrow();
+        assert_eq!(db.metrics.extern_db_set_duration.get_sample_count(), 0);
+        assert_eq!(db.metrics.extern_db_write.get(), 1);
+        drop(db);
+
+        let mut data = RawEntity::new();
+        data.insert("id".to_string(), Value::String("token".to_string()));
+        let request = StoreOperationMessage::Create(("Token".to_string(), data));
+        agent.wasm_send_store_request(request).unwrap();
+        agent
+            .commit_data(BlockPtr {
+                number: 2,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let db = agent.0.borrow();
+        assert_eq!(db.metrics.extern_db_set_duration.get_sample_count(), 1);
+        assert_eq!(db.metrics.extern_db_write.get(), 3);
+    }
+
+    #[test]
+    fn test_checksum_mismatch_detected() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        agent.0.borrow_mut().with_checksum = true;
+
+        let mut data = RawEntity::new();
+        data.insert("id".to_string(), Value::String("token".to_string()));
+        data.insert("supply".to_string(), Value::Int8(100));
+        data.insert("holders".to_string(), Value::List(vec![]));
+        data.insert("__is_deleted__".to_string(), Value::Bool(false));
+        let checksum = utils::entity_checksum(&data);
+        data.insert("__checksum__".to_string(), Value::Int8(checksum));
+
+        // Private & empty fields do not contribute to the checksum
+        let mut loaded = data.clone();
+        loaded.remove("holders");
+        loaded.insert("__block_ptr__".to_string(), Value::Int8(1));
+        let db = agent.0.borrow();
+        assert!(db.verify_checksum("Token", &loaded).is_ok());
+
+        let mut tampered = data.clone();
+        tampered.insert("supply".to_string(), Value::Int8(1));
+        assert!(matches!(
+            db.verify_checksum("Token", &tampered),
+            Err(DatabaseError::CorruptEntity(entity_type, entity_id))
+                if entity_type == "Token" && entity_id == "token"
+        ));
+
+        let mut missing = data;
+        missing.remove("__checksum__");
+        assert!(db.verify_checksum("Token", &missing).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_custom_primary_key() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        agent
+            .0
+            .borrow_mut()
+            .schema
+            .set_primary_key("Snapshot", "pk");
+
+        let mut data = RawEntity::new();
+        data.insert("pk".to_string(), Value::String("snapshot_1".to_string()));
+        data.insert("value".to_string(), Value::Int8(1));
+        let request = StoreOperationMessage::Create(("Snapshot".to_string(), data));
+        let result = agent.wasm_send_store_request(request).unwrap();
+        assert!(matches!(result, StoreRequestResult::Create(id) if id == "snapshot_1"));
+
+        // The primary key is filled from the entity id when missing from the update
+        let mut data = RawEntity::new();
+        data.insert("value".to_string(), Value::Int8(2));
+        let request =
+            StoreOperationMessage::Update(("Snapshot".to_string(), "snapshot_2".into(), data));
+        agent.wasm_send_store_request(request).unwrap();
+
+        for (entity_id, value) in [("snapshot_1", 1), ("snapshot_2", 2)] {
+            let request = StoreOperationMessage::Load(("Snapshot".to_string(), entity_id.into()));
+            let result = agent.wasm_send_store_request(request).unwrap();
+            assert!(matches!(
+                result,
+                StoreRequestResult::Load(Some(entity))
+                    if entity.get("pk") == Some(&Value::String(entity_id.to_string()))
+                        && entity.get("value") == Some(&Value::Int8(value))
+                        && !entity.contains_key("id")
+            ));
+        }
+
+        let mut data = RawEntity::new();
+        data.insert("id".to_string(), Value::String("snapshot_3".to_string()));
+        let request = StoreOperationMessage::Create(("Snapshot".to_string(), data));
+        assert!(matches!(
+            agent.wasm_send_store_request(request),
+            Err(DatabaseError::MissingID)
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_update_replaces_entity() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+
+        let load = |entity_id: &str| {
+            let request = StoreOperationMessage::load("Token".into(), entity_id.into());
+            match agent.wasm_send_store_request(request).unwrap() {
+                StoreRequestResult::Load(Some(entity)) => entity,
+                _ => panic!("unexpected store result"),
+            }
+        };
+
+        let mut data = RawEntity::new();
+        data.insert("id".to_string(), Value::String("token".to_string()));
+        data.insert("symbol".to_string(), Value::String("TKN".to_string()));
+        data.insert("decimals".to_string(), Value::Int(18));
+        let request = StoreOperationMessage::Create(("Token".to_string(), data));
+        agent.wasm_send_store_request(request).unwrap();
+
+        // `store.set` replaces the whole entity, like graph-node does
+        let mut data = RawEntity::new();
+        data.insert("decimals".to_string(), Value::Int(6));
+        let request = StoreOperationMessage::update("Token".into(), "token".into(), data);
+        assert!(matches!(
+            agent.wasm_send_store_request(request).unwrap(),
+            StoreRequestResult::Update
+        ));
+        let entity = load("token");
+        assert_eq!(entity.get("id"), Some(&Value::String("token".to_string())));
+        assert_eq!(entity.get("symbol"), None);
+        assert_eq!(entity.get("decimals"), Some(&Value::Int(6)));
+
+        // Mappings load the entity before setting a field, which keeps the other ones
+        let mut data = load("token");
+        data.insert("symbol".to_string(), Value::String("TKN".to_string()));
+        let request = StoreOperationMessage::update("Token".into(), "token".into(), data);
+        agent.wasm_send_store_request(request).unwrap();
+
+        // The stored entity is also loaded once committed & flushed from the cache
+        agent.0.borrow_mut().commit_depth = 10;
+        let block_ptr = BlockPtr {
+            number: 1,
+            ..Default::default()
+        };
+        agent.commit_data(block_ptr).await.unwrap();
+        agent.flush_cache().await.unwrap();
+        let mut data = load("token");
+        data.insert("updatedAt".to_string(), Value::Int8(300));
+        let request = StoreOperationMessage::update("Token".into(), "token".into(), data);
+        agent.wasm_send_store_request(request).unwrap();
+        let entity = load("token");
+        assert_eq!(
+            entity.get("symbol"),
+            Some(&Value::String("TKN".to_string()))
+        );
+        assert_eq!(entity.get("decimals"), Some(&Value::Int(6)));
+        assert_eq!(entity.get("updatedAt"), Some(&Value::Int8(300)));
+
+        // Without a stored entity, the update creates it as-is
+        let mut data = RawEntity::new();
+        data.insert("decimals".to_string(), Value::Int(8));
+        let request = StoreOperationMessage::update("Token".into(), "other".into(), data);
+        agent.wasm_send_store_request(request).unwrap();
+        assert_eq!(load("other").len(), 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_merge_keeps_other_fields() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+
+        let load = || {
+            let request = StoreOperationMessage::load("Token".into(), "token".into());
+            match agent.wasm_send_store_request(request).unwrap() {
+                StoreRequestResult::Load(Some(entity)) => entity,
+                _ => panic!("unexpected store result"),
+            }
+        };
+
+        let mut data = RawEntity::new();
+        data.insert("id".to_string(), Value::String("token".to_string()));
+        data.insert("symbol".to_string(), Value::String("TKN".to_string()));
+        data.insert("decimals".to_string(), Value::Int(18));
+        let request = StoreOperationMessage::Create(("Token".to_string(), data));
+        agent.wasm_send_store_request(request).unwrap();
+
+        // `store.merge` only writes the given fields
+        let mut partial = RawEntity::new();
+        partial.insert("decimals".to_string(), Value::Int(6));
+        let request = StoreOperationMessage::merge("Token".into(), "token".into(), partial);
+        assert!(matches!(
+            agent.wasm_send_store_request(request).unwrap(),
+            StoreRequestResult::Update
+        ));
+        let entity = load();
+        assert_eq!(entity.get("id"), Some(&Value::String("token".to_string())));
+        assert_eq!(
+            entity.get("symbol"),
+            Some(&Value::String("TKN".to_string()))
+        );
+        assert_eq!(entity.get("decimals"), Some(&Value::Int(6)));
+
+        // The stored entity is also merged into once committed & flushed from the cache
+        agent.0.borrow_mut().commit_depth = 10;
+        let block_ptr = BlockPtr {
+            number: 1,
+            ..Default::default()
+        };
+        agent.commit_data(block_ptr).await.unwrap();
+        agent.flush_cache().await.unwrap();
+        let mut partial = RawEntity::new();
+        partial.insert("updatedAt".to_string(), Value::Int8(300));
+        let request = StoreOperationMessage::merge("Token".into(), "token".into(), partial);
+        agent.wasm_send_store_request(request).unwrap();
+        let entity = load();
+        assert_eq!(
+            entity.get("symbol"),
+            Some(&Value::String("TKN".to_string()))
+        );
+        assert_eq!(entity.get("decimals"), Some(&Value::Int(6)));
+        assert_eq!(entity.get("updatedAt"), Some(&Value::Int8(300)));
+
+        // Merging onto a missing entity creates it from the given fields
+        let mut partial = RawEntity::new();
+        partial.insert("decimals".to_string(), Value::Int(8));
+        let request = StoreOperationMessage::merge("Token".into(), "other".into(), partial);
+        agent.wasm_send_store_request(request).unwrap();
+        let request = StoreOperationMessage::load("Token".into(), "other".into());
+        assert!(matches!(
+            agent.wasm_send_store_request(request).unwrap(),
+            StoreRequestResult::Load(Some(entity)) if entity.len() == 3
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_immutable_entity() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        agent.0.borrow_mut().schema.set_immutable("Transfer");
+
+        for entity_type in ["Transfer", "Token"] {
+            let mut data = RawEntity::new();
+            data.insert("id".to_string(), Value::String("id_0".to_string()));
+            data.insert("amount".to_string(), Value::Int8(1));
+            let request =
+                StoreOperationMessage::Update((entity_type.to_string(), "id_0".into(), data));
+            agent.wasm_send_store_request(request).unwrap();
+        }
+
+        // Inserted once, and left out of the snapshot clean-up
+        let db = agent.0.borrow();
+        assert_eq!(db.mem.extract_data().unwrap().len(), 2);
+        assert_eq!(
+            db.versioned_entity_ids(),
+            vec![("Token".to_string(), "id_0".to_string())]
+        );
+        drop(db);
+        agent
+            .commit_data(BlockPtr {
+                number: 1,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let mut data = RawEntity::new();
+        data.insert("id".to_string(), Value::String("id_0".to_string()));
+        data.insert("amount".to_string(), Value::Int8(2));
+        let request = StoreOperationMessage::Update(("Transfer".to_string(), "id_0".into(), data));
+        assert!(matches!(
+            agent.wasm_send_store_request(request),
+            Err(DatabaseError::ImmutableEntityUpdate(entity_type, entity_id))
+                if entity_type == "Transfer" && entity_id == "id_0"
+        ));
+
+        let request = StoreOperationMessage::Load(("Transfer".to_string(), "id_0".into()));
+        assert!(matches!(
+            agent.wasm_send_store_request(request).unwrap(),
+            StoreRequestResult::Load(Some(entity)) if entity.get("amount") == Some(&Value::Int8(1))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_duplicate_create_in_block() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        agent.0.borrow_mut().schema.set_immutable("Transfer");
+
+        let create = |entity_type: &str, entity_id: &str, amount: i64| {
+            let mut data = RawEntity::new();
+            data.insert("id".to_string(), Value::String(entity_id.to_string()));
+            data.insert("amount".to_string(), Value::Int8(amount));
+            agent.wasm_send_store_request(StoreOperationMessage::Create((
+                entity_type.to_string(),
+                data,
+            )))
+        };
+
+        // Last write wins by default, immutable or not
+        for entity_type in ["Transfer", "Token"] {
+            create(entity_type, "id_0", 1).unwrap();
+            create(entity_type, "id_0", 2).unwrap();
+        }
+
+        agent.0.borrow_mut().duplicate_create = DuplicateCreatePolicy::Strict;
+        create("Token", "id_1", 1).unwrap();
+        create("Token", "id_1", 2).unwrap();
+        create("Transfer", "id_1", 1).unwrap();
+        assert!(matches!(
+            create("Transfer", "id_1", 2),
+            Err(DatabaseError::DuplicateEntityCreate(entity_type, entity_id))
+                if entity_type == "Transfer" && entity_id == "id_1"
+        ));
+
+        let request = StoreOperationMessage::Load(("Transfer".to_string(), "id_1".into()));
+        assert!(matches!(
+            agent.wasm_send_store_request(request).unwrap(),
+            StoreRequestResult::Load(Some(entity)) if entity.get("amount") == Some(&Value::Int8(1))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_immutable_create_across_blocks() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        agent.0.borrow_mut().schema.set_immutable("Transfer");
+
+        let create = |entity_id: &str, amount: i64| {
+            let mut data = RawEntity::new();
+            data.insert("id".to_string(), Value::String(entity_id.to_string()));
+            data.insert("amount".to_string(), Value::Int8(amount));
+            agent.wasm_send_store_request(StoreOperationMessage::Create((
+                "Transfer".to_string(),
+                data,
+            )))
+        };
+
+        // Both blocks are part of the same batch: nothing is committed in between
+        create("id_0", 1).unwrap();
+        create("id_0", 2).unwrap();
+        agent.finish_block(1);
+
+        assert!(matches!(
+            create("id_0", 3),
+            Err(DatabaseError::ImmutableEntityUpdate(entity_type, entity_id))
+                if entity_type == "Transfer" && entity_id == "id_0"
+        ));
+        create("id_1", 1).unwrap();
+        agent.finish_block(2);
+
+        let request = StoreOperationMessage::Load(("Transfer".to_string(), "id_0".into()));
+        assert!(matches!(
+            agent.wasm_send_store_request(request).unwrap(),
+            StoreRequestResult::Load(Some(entity)) if entity.get("amount") == Some(&Value::Int8(2))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_entity_id_case() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        agent
+            .0
+            .borrow_mut()
+            .entity_id_case
+            .insert("Token".to_string(), EntityIdCase::Lower);
+
+        let checksummed = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48";
+        let lowercase = checksummed.to_lowercase();
+
+        for entity_type in ["Token", "Pool"] {
+            let mut data = RawEntity::new();
+            data.insert("id".to_string(), Value::String(checksummed.to_string()));
+            let request =
+                StoreOperationMessage::Update((entity_type.to_string(), checksummed.into(), data));
+            agent.wasm_send_store_request(request).unwrap();
+        }
+
+        let request = StoreOperationMessage::Load(("Token".to_string(), lowercase.clone()));
+        assert!(matches!(
+            agent.wasm_send_store_request(request).unwrap(),
+            StoreRequestResult::Load(Some(entity))
+                if entity.get("id") == Some(&Value::String(lowercase.clone()))
+        ));
+
+        let request = StoreOperationMessage::Load(("Token".to_string(), checksummed.into()));
+        assert!(matches!(
+            agent.wasm_send_store_request(request).unwrap(),
+            StoreRequestResult::Load(Some(_))
+        ));
+
+        // Entity types without normalization keep ids as-is
+        let request = StoreOperationMessage::Load(("Pool".to_string(), lowercase.clone()));
+        assert!(matches!(
+            agent.wasm_send_store_request(request).unwrap(),
+            StoreRequestResult::Load(None)
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_reprocess() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        agent.0.borrow_mut().earliest_block = 5;
+
+        let mut data = RawEntity::new();
+        data.insert("id".to_string(), Value::String("token".to_string()));
+        let request = StoreOperationMessage::Create(("Token".to_string(), data));
+        agent.wasm_send_store_request(request).unwrap();
+
+        assert!(matches!(
+            agent.reprocess(3, 10).await,
+            Err(DatabaseError::PrunedBlock(3, 5))
+        ));
+        assert!(matches!(
+            agent.reprocess(10, 6).await,
+            Err(DatabaseError::InvalidBlockRange(10, 6))
+        ));
+        assert!(!agent.0.borrow().mem.is_empty());
+
+        agent.reprocess(6, 10).await.unwrap();
+        assert!(agent.0.borrow().mem.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_reprocess_refeed() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        agent.0.borrow_mut().commit_depth = 10;
+
+        // Stand-in for a handler counting the blocks it went through
+        let index_block = |number: u64| {
+            let request = StoreOperationMessage::load("Counter".into(), "counter".into());
+            let count = match agent.wasm_send_store_request(request).unwrap() {
+                StoreRequestResult::Load(Some(entity)) => match entity.get("count") {
+                    Some(Value::Int8(count)) => *count,
+                    _ => panic!("unexpected counter"),
+                },
+                _ => 0,
+            };
+            let mut data = RawEntity::new();
+            data.insert("count".to_string(), Value::Int8(count + 1));
+            data.insert("lastBlock".to_string(), Value::Int8(number as i64));
+            let request = StoreOperationMessage::update("Counter".into(), "counter".into(), data);
+            agent.wasm_send_store_request(request).unwrap();
+            agent.finish_block(number);
+        };
+        let counter = || {
+            let request = StoreOperationMessage::load("Counter".into(), "counter".into());
+            match agent.wasm_send_store_request(request).unwrap() {
+                StoreRequestResult::Load(Some(entity)) => {
+                    (entity["count"].clone(), entity["lastBlock"].clone())
+                }
+                _ => panic!("unexpected store result"),
+            }
+        };
+        let block_ptr = |number: u64| BlockPtr {
+            number,
+            ..Default::default()
+        };
+
+        for number in 1..=5 {
+            index_block(number);
+            agent.commit_data(block_ptr(number)).await.unwrap();
+            agent.flush_cache().await.unwrap();
+        }
+        assert_eq!(counter(), (Value::Int8(5), Value::Int8(5)));
+
+        // Blocks 3 & 4 are reprocessed, block 5 being reverted too is fed again after them
+        agent.reprocess(3, 4).await.unwrap();
+        assert_eq!(counter(), (Value::Int8(2), Value::Int8(2)));
+        agent.flush_cache().await.unwrap();
+        for number in 3..=5 {
+            index_block(number);
+            agent.commit_data(block_ptr(number)).await.unwrap();
+            agent.flush_cache().await.unwrap();
+            assert_eq!(
+                counter(),
+                (Value::Int8(number as i64), Value::Int8(number as i64))
+            );
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_schema_validation() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        {
+            let mut db = agent.0.borrow_mut();
+            db.schema =
+                Schemas::new_from_graphql_schema("type Token @entity { id: ID! decimals: Int! }");
+            db.schema_validation = SchemaValidationPolicy::Strict;
+        }
+
+        let mut data = RawEntity::new();
+        data.insert("id".to_string(), Value::String("token_0".to_string()));
+        data.insert("decimals".to_string(), Value::String("18".to_string()));
+        let request = StoreOperationMessage::Create(("Token".to_string(), data.clone()));
+        assert!(matches!(
+            agent.wasm_send_store_request(request),
+            Err(DatabaseError::Schema(_))
+        ));
+
+        // Only logged when not strict
+        agent.0.borrow_mut().schema_validation = SchemaValidationPolicy::Warn;
+        let request = StoreOperationMessage::Create(("Token".to_string(), data));
+        assert!(agent.wasm_send_store_request(request).is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_int_field_overflow() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        agent.0.borrow_mut().schema =
+            Schemas::new_from_graphql_schema("type Token @entity { id: ID! decimals: Int! }");
+
+        let mut data = RawEntity::new();
+        data.insert("id".to_string(), Value::String("token".to_string()));
+        data.insert(
+            "decimals".to_string(),
+            Value::BigInt(BigInt::from(1u64 << 40)),
+        );
+        let request = StoreOperationMessage::Create(("Token".to_string(), data.clone()));
+        assert!(matches!(
+            agent.wasm_send_store_request(request),
+            Err(DatabaseError::ValueOverflow(entity_type, field, _))
+                if entity_type == "Token" && field == "decimals"
+        ));
+
+        // BigInts fitting i32 are stored as Int
+        data.insert("decimals".to_string(), Value::BigInt(BigInt::from(-18)));
+        let request = StoreOperationMessage::Create(("Token".to_string(), data));
+        agent.wasm_send_store_request(request).unwrap();
+
+        let request = StoreOperationMessage::Load(("Token".to_string(), "token".to_string()));
+        let entity = match agent.wasm_send_store_request(request).unwrap() {
+            StoreRequestResult::Load(Some(entity)) => entity,
+            _ => panic!("unexpected store result"),
+        };
+        assert_eq!(entity.get("decimals"), Some(&Value::Int(-18)));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_timestamp_field() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        agent.0.borrow_mut().schema =
+            Schemas::new_from_graphql_schema("type Swap @entity { id: ID! timestamp: Timestamp! }");
+        agent.0.borrow_mut().schema_validation = SchemaValidationPolicy::Strict;
+
+        // Timestamps are microseconds since the epoch
+        let timestamp = Value::Int8(1_700_000_000_123_456);
+        let mut data = RawEntity::new();
+        data.insert("id".to_string(), Value::String("swap".to_string()));
+        data.insert("timestamp".to_string(), timestamp.clone());
+        let request = StoreOperationMessage::Create(("Swap".to_string(), data.clone()));
+        agent.wasm_send_store_request(request).unwrap();
+
+        let request = StoreOperationMessage::Load(("Swap".to_string(), "swap".to_string()));
+        let entity = match agent.wasm_send_store_request(request).unwrap() {
+            StoreRequestResult::Load(Some(entity)) => entity,
+            _ => panic!("unexpected store result"),
+        };
+        assert_eq!(entity.get("timestamp"), Some(&timestamp));
+
+        data.insert("timestamp".to_string(), Value::Int(1_700_000_000));
+        let request = StoreOperationMessage::Create(("Swap".to_string(), data));
+        assert!(matches!(
+            agent.wasm_send_store_request(request),
+            Err(DatabaseError::Schema(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_abnormal_entity_count() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        agent.0.borrow_mut().max_entities_per_block = Some(2);
+
+        let create_tokens = |ids: std::ops::Range<u64>| {
+            for i in ids {
+                let mut data = RawEntity::new();
+                data.insert("id".to_string(), Value::String(format!("token_{i}")));
+                let request = StoreOperationMessage::Create(("Token".to_string(), data));
+                agent.wasm_send_store_request(request).unwrap();
+            }
+        };
+
+        create_tokens(0..2);
+        assert_eq!(agent.finish_block(1), None);
+        assert_eq!(agent.0.borrow().metrics.abnormal_entity_count.get(), 0);
+
+        // Counted per block, even though nothing was committed in between
+        create_tokens(2..4);
+        assert_eq!(agent.finish_block(2), None);
+        assert_eq!(agent.0.borrow().metrics.abnormal_entity_count.get(), 0);
+
+        // The warning carries the block's own count, not the total since the start
+        create_tokens(4..7);
+        assert_eq!(agent.finish_block(3), Some(3));
+        assert_eq!(agent.0.borrow().metrics.abnormal_entity_count.get(), 1);
+
+        assert_eq!(agent.finish_block(4), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_load_related_capped() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        {
+            let mut db = agent.0.borrow_mut();
+            db.schema = Schemas::new_from_graphql_schema(
+                "type Pool @entity { id: ID! tokens: [Token!]! } type Token @entity { id: ID! }",
+            );
+            db.max_related_entities = Some(3);
+        }
+
+        let mut token_ids = vec![];
+        for i in 0..5 {
+            let mut data = RawEntity::new();
+            data.insert("id".to_string(), Value::String(format!("token_{i}")));
+            let request = StoreOperationMessage::Create(("Token".to_string(), data));
+            agent.wasm_send_store_request(request).unwrap();
+            token_ids.push(Value::String(format!("token_{i}")));
+        }
+
+        let mut data = RawEntity::new();
+        data.insert("id".to_string(), Value::String("pool".to_string()));
+        data.insert("tokens".to_string(), Value::List(token_ids));
+        let request = StoreOperationMessage::Create(("Pool".to_string(), data));
+        agent.wasm_send_store_request(request).unwrap();
+
+        let request = StoreOperationMessage::LoadRelated((
+            "Pool".to_string(),
+            "pool".to_string(),
+            "tokens".to_string(),
+        ));
+        let related = match agent.wasm_send_store_request(request).unwrap() {
+            StoreRequestResult::LoadRelated(related) => related,
+            _ => panic!("unexpected store result"),
+        };
+        assert_eq!(related.len(), 3);
+        assert_eq!(agent.0.borrow().metrics.load_related_truncated.get(), 1);
+
+        // Paging walks the whole relation regardless of the cap
+        let mut paged = vec![];
+        for offset in (0..6).step_by(2) {
+            let data = ("Pool".to_string(), "pool".to_string(), "tokens".to_string());
+            paged.extend(agent.load_related_page(data, offset, 2).await.unwrap());
+        }
+        assert_eq!(paged.len(), 5);
+        assert_eq!(
+            paged.last().unwrap().get("id"),
+            Some(&Value::String("token_4".to_string()))
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_load_related_in_pages() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        {
+            let mut db = agent.0.borrow_mut();
+            db.schema = Schemas::new_from_graphql_schema(
+                "type Pool @entity { id: ID! tokens: [Token!]! } type Token @entity { id: ID! }",
+            );
+            db.commit_depth = 10;
+        }
+
+        // Tokens of an earlier block are no longer cached in memory
+        let mut token_ids = vec![];
+        for i in 0..550 {
+            let mut data = RawEntity::new();
+            data.insert("id".to_string(), Value::String(format!("token_{i}")));
+            let request = StoreOperationMessage::Create(("Token".to_string(), data));
+            agent.wasm_send_store_request(request).unwrap();
+            token_ids.push(Value::String(format!("token_{i}")));
+        }
+        let block_ptr = BlockPtr {
+            number: 1,
+            ..Default::default()
+        };
+        agent.commit_data(block_ptr).await.unwrap();
+        agent.flush_cache().await.unwrap();
+
+        let mut data = RawEntity::new();
+        data.insert("id".to_string(), Value::String("pool".to_string()));
+        data.insert("tokens".to_string(), Value::List(token_ids));
+        let request = StoreOperationMessage::Create(("Pool".to_string(), data));
+        agent.wasm_send_store_request(request).unwrap();
+
+        let mut pages = vec![];
+        let request = StoreOperationMessage::LoadRelated((
+            "Pool".to_string(),
+            "pool".to_string(),
+            "tokens".to_string(),
+        ));
+        agent
+            .wasm_load_related(request, |page| {
+                pages.push(page.len());
+                Ok(())
+            })
+            .unwrap();
+
+        // No more than a page of entities is ever held, and none of them is cached
+        assert_eq!(pages, vec![100, 100, 100, 100, 100, 50]);
+        let db = agent.0.borrow();
+        assert!(db
+            .mem
+            .load_entity_latest("Token", "token_0")
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_load_related_of_stored_entity() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        {
+            let mut db = agent.0.borrow_mut();
+            db.schema = Schemas::new_from_graphql_schema(
+                "type Pool @entity { id: ID! tokens: [Token!]! } type Token @entity { id: ID! }",
+            );
+            db.commit_depth = 10;
+        }
+
+        for id in ["token_0", "token_1"] {
+            let mut data = RawEntity::new();
+            data.insert("id".to_string(), Value::String(id.to_string()));
+            let request = StoreOperationMessage::Create(("Token".to_string(), data));
+            agent.wasm_send_store_request(request).unwrap();
+        }
+        let mut data = RawEntity::new();
+        data.insert("id".to_string(), Value::String("pool".to_string()));
+        data.insert(
+            "tokens".to_string(),
+            Value::List(vec![
+                Value::String("token_0".to_string()),
+                Value::String("token_1".to_string()),
+            ]),
+        );
+        let request = StoreOperationMessage::Create(("Pool".to_string(), data));
+        agent.wasm_send_store_request(request).unwrap();
+
+        // The pool itself is no longer cached, its relation is still followed
+        let block_ptr = BlockPtr {
+            number: 1,
+            ..Default::default()
+        };
+        agent.commit_data(block_ptr).await.unwrap();
+        agent.flush_cache().await.unwrap();
+
+        let mut related = vec![];
+        let request = StoreOperationMessage::LoadRelated((
+            "Pool".to_string(),
+            "pool".to_string(),
+            "tokens".to_string(),
+        ));
+        agent
+            .wasm_load_related(request, |page| {
+                related.extend(page);
+                Ok(())
+            })
+            .unwrap();
+        let mut ids = related
+            .iter()
+            .map(|entity| entity.get("id").cloned().unwrap())
+            .collect::<Vec<_>>();
+        ids.sort_by_key(|id| format!("{id:?}"));
+        assert_eq!(
+            ids,
+            vec![
+                Value::String("token_0".to_string()),
+                Value::String("token_1".to_string())
+            ]
+        );
+
+        let page = agent
+            .load_related_page(
+                ("Pool".to_string(), "pool".to_string(), "tokens".to_string()),
+                1,
+                10,
+            )
+            .await
+            .unwrap();
+        assert_eq!(page.len(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_load_derived_related() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        agent.0.borrow_mut().schema = Schemas::new_from_graphql_schema(
+            r#"
+            type Pool @entity { id: ID! positions: [Position!]! @derivedFrom(field: "pool") }
+            type Position @entity { id: ID! pool: Pool! }
+            "#,
+        );
+
+        let create_position = |id: &str, pool: &str| {
+            let mut data = RawEntity::new();
+            data.insert("id".to_string(), Value::String(id.to_string()));
+            data.insert("pool".to_string(), Value::String(pool.to_string()));
+            let request = StoreOperationMessage::Create(("Position".to_string(), data));
+            agent.wasm_send_store_request(request).unwrap();
+        };
+        let load_positions = |pool: &str| {
+            let request = StoreOperationMessage::LoadRelated((
+                "Pool".to_string(),
+                pool.to_string(),
+                "positions".to_string(),
+            ));
+            match agent.wasm_send_store_request(request).unwrap() {
+                StoreRequestResult::LoadRelated(related) => related
+                    .into_iter()
+                    .map(|entity| entity.get("id").cloned().unwrap())
+                    .collect::<Vec<_>>(),
+                _ => panic!("unexpected store result"),
+            }
+        };
+
+        create_position("position_2", "pool_a");
+        create_position("position_1", "pool_b");
+        create_position("position_0", "pool_a");
+        assert_eq!(
+            load_positions("pool_a"),
+            vec![
+                Value::String("position_0".to_string()),
+                Value::String("position_2".to_string())
+            ]
+        );
+
+        // Pages follow the same order
+        let data = (
+            "Pool".to_string(),
+            "pool_a".to_string(),
+            "positions".to_string(),
+        );
+        let page = agent.load_related_page(data, 1, 1).await.unwrap();
+        assert_eq!(
+            page.iter()
+                .map(|entity| entity.get("id"))
+                .collect::<Vec<_>>(),
+            vec![Some(&Value::String("position_2".to_string()))]
+        );
+
+        // Positions moved to another pool or removed are no longer related
+        create_position("position_2", "pool_b");
+        let request = StoreOperationMessage::Delete(("Position".to_string(), "position_1".into()));
+        agent.wasm_send_store_request(request).unwrap();
+        assert_eq!(
+            load_positions("pool_a"),
+            vec![Value::String("position_0".to_string())]
+        );
+        assert_eq!(
+            load_positions("pool_b"),
+            vec![Value::String("position_2".to_string())]
+        );
+        assert!(load_positions("pool_c").is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_migrate_per_entity_type() {
+        env_logger::try_init().unwrap_or_default();
+        let entity_types = ["Token", "Pool", "Position", "Swap", "Tick"];
+        let mut values = vec![];
+        for entity_type in entity_types {
+            for i in 0..3 {
+                let mut data = RawEntity::new();
+                data.insert(
+                    "id".to_string(),
+                    Value::String(format!("{entity_type}_{i}")),
+                );
+                values.push((entity_type.to_string(), data));
+            }
+        }
+
+        let persisted = Mutex::new(HashMap::<EntityType, usize>::new());
+        let in_flight = Mutex::new((0, 0));
+        let batches = utils::insert_per_entity_type(values, 2, None, |batch| {
+            let persisted = &persisted;
+            let in_flight = &in_flight;
+            async move {
+                {
+                    let mut in_flight = in_flight.lock().unwrap();
+                    in_flight.0 += 1;
+                    in_flight.1 = in_flight.1.max(in_flight.0);
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+                // Every batch only holds a single entity type
+                let entity_type = batch[0].0.clone();
+                assert!(batch.iter().all(|(t, _)| *t == entity_type));
+                *persisted.lock().unwrap().entry(entity_type).or_default() += batch.len();
+                in_flight.lock().unwrap().0 -= 1;
+                Ok(())
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(batches, 5);
+        assert_eq!(in_flight.lock().unwrap().1, 2);
+        let persisted = persisted.into_inner().unwrap();
+        assert_eq!(persisted.len(), 5);
+        assert!(entity_types.iter().all(|t| persisted[*t] == 3));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_commit_depth() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        agent.0.borrow_mut().commit_depth = 3;
+        let persisted = Arc::new(Mutex::new(vec![]));
+        let hook_persisted = persisted.clone();
+        agent.set_commit_hook(Box::new(move |block_ptr| {
+            hook_persisted.lock().unwrap().push(block_ptr.number);
+        }));
+
+        for number in 1..=6u64 {
+            let mut data = RawEntity::new();
+            data.insert("id".to_string(), Value::String("token".to_string()));
+            data.insert("block".to_string(), Value::Int8(number as i64));
+            let request = StoreOperationMessage::Create(("Token".to_string(), data));
+            agent.wasm_send_store_request(request).unwrap();
+            agent.set_cursor(number, format!("cursor_{number}"));
+            agent
+                .commit_data(BlockPtr {
+                    number,
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            agent.flush_cache().await.unwrap();
+        }
+
+        // Head is #6: only blocks up to #3 are written, the rest stay provisional
+        assert_eq!(*persisted.lock().unwrap(), vec![1, 2, 3]);
+        assert_eq!(agent.0.borrow().provisional.len(), 3);
+        // Cursors are only checkpointed once their block is written
+        let cursors = agent.0.borrow().cursors.keys().copied().collect::<Vec<_>>();
+        assert_eq!(cursors, vec![4, 5, 6]);
+
+        // Provisional state is still visible to handlers
+        let request = StoreOperationMessage::Load(("Token".to_string(), "token".into()));
+        assert!(matches!(
+            agent.wasm_send_store_request(request).unwrap(),
+            StoreRequestResult::Load(Some(entity)) if entity.get("block") == Some(&Value::Int8(6))
+        ));
+
+        agent.revert_from_block(6).await.unwrap();
+        agent.commit_provisional().await.unwrap();
+        assert_eq!(*persisted.lock().unwrap(), vec![1, 2, 3, 4, 5]);
+        assert!(agent.0.borrow().cursors.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_revert_reorged_blocks() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        // Nothing reaches the (absent) extern-db, every block stays revertable in memory
+        agent.0.borrow_mut().commit_depth = 10;
+
+        let write_block = |number: u64, hash: &str| {
+            let mut data = RawEntity::new();
+            data.insert("id".to_string(), Value::String(format!("token_{number}")));
+            data.insert("hash".to_string(), Value::String(hash.to_string()));
+            let request = StoreOperationMessage::Create(("Token".to_string(), data));
+            agent.wasm_send_store_request(request).unwrap();
+
+            let mut data = RawEntity::new();
+            data.insert("block".to_string(), Value::Int8(number as i64));
+            let request = StoreOperationMessage::update("Pool".into(), "pool".into(), data);
+            agent.wasm_send_store_request(request).unwrap();
+        };
+        let load = |entity_type: &str, entity_id: &str| {
+            let request = StoreOperationMessage::load(entity_type.into(), entity_id.into());
+            match agent.wasm_send_store_request(request).unwrap() {
+                StoreRequestResult::Load(entity) => entity,
+                _ => panic!("unexpected store result"),
+            }
+        };
+
+        for number in 1..=6u64 {
+            write_block(number, "canonical");
+            agent
+                .commit_data(BlockPtr {
+                    number,
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+        }
+
+        // Blocks #4, #5 & #6 are reorged out, reverting their tokens & the pool
+        assert_eq!(agent.revert_from_block(4).await.unwrap(), 4);
+        assert!(agent.0.borrow().mem.is_empty());
+        for number in 1..=3 {
+            assert!(load("Token", &format!("token_{number}")).is_some());
+        }
+        for number in 4..=6 {
+            assert!(load("Token", &format!("token_{number}")).is_none());
+        }
+        let pool = load("Pool", "pool").unwrap();
+        assert_eq!(pool.get("block"), Some(&Value::Int8(3)));
+
+        // The fork-blocks are then processed on top of block #3
+        write_block(4, "fork");
+        agent
+            .commit_data(BlockPtr {
+                number: 4,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        agent.flush_cache().await.unwrap();
+        let token = load("Token", "token_4").unwrap();
+        assert_eq!(token.get("hash"), Some(&Value::String("fork".to_string())));
+        assert!(load("Token", "token_5").is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_change_feed() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        let config = ChangeFeedConfig {
+            capacity: 10,
+            policy: None,
+            output: None,
+        };
+        agent.0.borrow_mut().change_feed = Some(ChangeFeed::new(&config));
+        let changes = agent.subscribe_changes().unwrap();
+        let received = || {
+            let mut received = vec![];
+            while let Ok(Some(change)) = changes.try_recv() {
+                received.push((change.block, change.entity_type, change.id, change.op));
+            }
+            received
+        };
+        let create = |entity_type: &str, entity_id: &str| {
+            let mut data = RawEntity::new();
+            data.insert("id".to_string(), Value::String(entity_id.to_string()));
+            let request = StoreOperationMessage::Create((entity_type.to_string(), data));
+            agent.wasm_send_store_request(request).unwrap();
+        };
+        let commit = |number| {
+            agent.commit_data(BlockPtr {
+                number,
+                ..Default::default()
+            })
+        };
+
+        create("Token", "b");
+        create("Token", "a");
+        create("Pool", "p");
+        commit(1).await.unwrap();
+        assert_eq!(
+            received(),
+            vec![
+                (1, "Pool".to_string(), "p".to_string(), ChangeOp::Create),
+                (1, "Token".to_string(), "a".to_string(), ChangeOp::Create),
+                (1, "Token".to_string(), "b".to_string(), ChangeOp::Create),
+            ]
+        );
+
+        // Entities only kept in cache are not reported again
+        create("Token", "a");
+        let request = StoreOperationMessage::Delete(("Token".to_string(), "b".to_string()));
+        agent.wasm_send_store_request(request).unwrap();
+        commit(2).await.unwrap();
+        assert_eq!(
+            received(),
+            vec![
+                (2, "Token".to_string(), "a".to_string(), ChangeOp::Update),
+                (2, "Token".to_string(), "b".to_string(), ChangeOp::Delete),
+            ]
+        );
+
+        // A full feed drops its oldest changes instead of stalling commits
+        let config = ChangeFeedConfig {
+            capacity: 2,
+            policy: Some(OverflowPolicy::DropOldest),
+            output: None,
+        };
+        agent.0.borrow_mut().change_feed = Some(ChangeFeed::new(&config));
+        let changes = agent.subscribe_changes().unwrap();
+        for id in ["x", "y", "z"] {
+            create("Token", id);
+        }
+        commit(3).await.unwrap();
+        let mut ids = vec![];
+        while let Ok(Some(change)) = changes.try_recv() {
+            ids.push(change.id);
+        }
+        assert_eq!(ids, vec!["y", "z"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_ordered_writes() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        {
+            let mut db = agent.0.borrow_mut();
+            db.schema = Schemas::new_from_graphql_schema(
+                "type Wallet @entity { id: ID! accounts: [Account!]! @derivedFrom(field: \"wallet\") }
+                 type Account @entity { id: ID! wallet: Wallet! }",
+            );
+            db.write_order = Some(db.schema.write_order());
+            db.change_feed = Some(ChangeFeed::new(&ChangeFeedConfig {
+                capacity: 10,
+                policy: None,
+                output: None,
+            }));
+        }
+        let changes = agent.subscribe_changes().unwrap();
+
+        let mut account = RawEntity::new();
+        account.insert("id".to_string(), Value::String("account".to_string()));
+        account.insert("wallet".to_string(), Value::String("wallet".to_string()));
+        let request = StoreOperationMessage::Create(("Account".to_string(), account));
+        agent.wasm_send_store_request(request).unwrap();
+        let mut wallet = RawEntity::new();
+        wallet.insert("id".to_string(), Value::String("wallet".to_string()));
+        let request = StoreOperationMessage::Create(("Wallet".to_string(), wallet));
+        agent.wasm_send_store_request(request).unwrap();
+
+        agent
+            .commit_data(BlockPtr {
+                number: 1,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        // The parent is reported before its child, though the child sorts first by name
+        let mut received = vec![];
+        while let Ok(Some(change)) = changes.try_recv() {
+            received.push(change.entity_type);
+        }
+        assert_eq!(received, vec!["Wallet", "Account"]);
+
+        // Batches are written one at a time, parents first
+        let written = Mutex::new(vec![]);
+        let values = vec![
+            ("Account".to_string(), RawEntity::new()),
+            ("Wallet".to_string(), RawEntity::new()),
+        ];
+        let write_order = agent.0.borrow().write_order.clone();
+        utils::insert_per_entity_type(values, 4, write_order.as_ref(), |batch| {
+            written.lock().unwrap().push(batch[0].0.clone());
+            async { Ok(()) }
+        })
+        .await
+        .unwrap();
+        assert_eq!(written.into_inner().unwrap(), vec!["Wallet", "Account"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_hard_delete() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        {
+            let mut db = agent.0.borrow_mut();
+            db.schema = Schemas::new_from_graphql_schema("type Token @entity { id: ID! }");
+            db.hard_delete_after = Some(5);
+        }
+        let agent = &agent;
+        let create_and_delete = || {
+            let mut token = RawEntity::new();
+            token.insert("id".to_string(), Value::String("token".to_string()));
+            let request = StoreOperationMessage::Create(("Token".to_string(), token));
+            agent.wasm_send_store_request(request).unwrap();
+            let request = StoreOperationMessage::Delete(("Token".to_string(), "token".to_string()));
+            agent.wasm_send_store_request(request).unwrap();
+        };
+        let commit = move |number: u64| async move {
+            agent
+                .commit_data(BlockPtr {
+                    number,
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            agent.flush_cache().await.unwrap();
+        };
+
+        create_and_delete();
+        commit(10).await;
+
+        // Within the reorg window the delete is soft, so it can still be reverted
+        commit(14).await;
+        assert_eq!(
+            agent.0.borrow().soft_deleted,
+            VecDeque::from([(10, vec![("Token".to_string(), "token".to_string())])])
+        );
+
+        // Once beyond the reorg window, the entity is removed for good
+        commit(15).await;
+        assert!(agent.0.borrow().soft_deleted.is_empty());
+
+        // Reverted deletes are never hard-deleted
+        create_and_delete();
+        commit(16).await;
+        assert_eq!(agent.0.borrow().soft_deleted.len(), 1);
+        agent.revert_from_block(16).await.unwrap();
+        assert!(agent.0.borrow().soft_deleted.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_change_feed_overflow() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+
+        for policy in [
+            OverflowPolicy::DropOldest,
+            OverflowPolicy::DisableOnOverflow,
+        ] {
+            let config = ChangeFeedConfig {
+                capacity: 2,
+                policy: Some(policy.clone()),
+                output: None,
+            };
+            agent.0.borrow_mut().change_feed = Some(ChangeFeed::new(&config));
+            let changes = agent.subscribe_changes().unwrap();
+
+            // Nothing consumes the feed, committing must not wait on it
+            for number in 1..=4u64 {
+                let mut data = RawEntity::new();
+                data.insert("id".to_string(), Value::String(format!("token_{number}")));
+                let request = StoreOperationMessage::Create(("Token".to_string(), data));
+                agent.wasm_send_store_request(request).unwrap();
+                let commit = agent.commit_data(BlockPtr {
+                    number,
+                    ..Default::default()
+                });
+                tokio::time::timeout(std::time::Duration::from_secs(1), commit)
+                    .await
+                    .expect("commit blocked on the change feed")
+                    .unwrap();
+            }
+
+            let mut blocks = vec![];
+            while let Ok(Some(change)) = changes.try_recv() {
+                blocks.push(change.block);
+            }
+            match policy {
+                OverflowPolicy::DropOldest => assert_eq!(blocks, vec![3, 4]),
+                _ => assert_eq!(blocks, vec![1, 2]),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_commit_hook() {
+        env_logger::try_init().unwrap_or_default();
+        let agent = DatabaseAgent::empty(default_registry());
+        let committed = Arc::new(Mutex::new(vec![]));
+        let hook_committed = committed.clone();
+        agent.set_commit_hook(Box::new(move |block_ptr| {
+            hook_committed.lock().unwrap().push(block_ptr.number);
+        }));
+
+        for number in 1..=3 {
+            agent
+                .commit_data(BlockPtr {
+                    number,
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(*committed.lock().unwrap(), vec![1, 2, 3]);
+    }
 }
@@ -1,3 +1,14 @@
+use crate::common::EntityType;
+use crate::common::RawEntity;
+use crate::errors::DatabaseError;
+use crate::runtime::asc::native_types::store::Value;
+use futures_util::StreamExt;
+use futures_util::TryStreamExt;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::future::Future;
+use tiny_keccak::Hasher;
+
 #[macro_export]
 macro_rules! schema {
     ($($k:ident => $v:expr),* $(,)?) => {{
@@ -7,6 +18,7 @@ macro_rules! schema {
             kind: $v,
             relation: None,
             list_inner_kind: None,
+            is_nullable: true,
         }),)*]))
     }};
 }
@@ -18,3 +30,69 @@ macro_rules! entity {
         Iterator::collect(IntoIterator::into_iter([$((stringify!($k).to_string(), $v),)*]))
     }};
 }
+
+/// Checksum of an entity's user fields, private `__*__` fields and
+/// absent-like values (null, empty list) are ignored as backends may
+/// not round-trip them identically
+pub fn entity_checksum(data: &RawEntity) -> i64 {
+    let fields = data
+        .iter()
+        .filter(|(field, _)| !field.starts_with("__"))
+        .filter(|(_, value)| !matches!(value, Value::Null) && **value != Value::List(vec![]))
+        .collect::<BTreeMap<_, _>>();
+    let bytes = serde_json::to_vec(&fields).expect("entity is always serializable");
+
+    let mut hasher = tiny_keccak::Keccak::v256();
+    let mut output = [0u8; 32];
+    hasher.update(&bytes);
+    hasher.finalize(&mut output);
+    i64::from_le_bytes(output[..8].try_into().unwrap())
+}
+
+/// Whether the `field` of an entity references `entity_id`, alone or in a list
+pub fn references(data: &RawEntity, field: &str, entity_id: &str) -> bool {
+    match data.get(field) {
+        Some(Value::String(id)) => id == entity_id,
+        Some(Value::List(list)) => list
+            .iter()
+            .any(|value| matches!(value, Value::String(id) if id == entity_id)),
+        _ => false,
+    }
+}
+
+/// Group entities by type and hand each type's batch to `insert`,
+/// running up to `concurrency` inserts at once. With a `write_order`, batches
+/// are inserted one after the other following it. Returns the number of batches
+pub async fn insert_per_entity_type<F, Fut>(
+    values: Vec<(EntityType, RawEntity)>,
+    concurrency: usize,
+    write_order: Option<&HashMap<EntityType, usize>>,
+    insert: F,
+) -> Result<usize, DatabaseError>
+where
+    F: Fn(Vec<(EntityType, RawEntity)>) -> Fut,
+    Fut: Future<Output = Result<(), DatabaseError>>,
+{
+    let mut grouped = BTreeMap::<(usize, EntityType), Vec<(EntityType, RawEntity)>>::new();
+    for (entity_type, data) in values {
+        let rank = write_order
+            .and_then(|order| order.get(&entity_type).copied())
+            .unwrap_or_default();
+        grouped
+            .entry((rank, entity_type.clone()))
+            .or_default()
+            .push((entity_type, data));
+    }
+
+    let concurrency = match write_order {
+        Some(_) => 1,
+        None => concurrency.max(1),
+    };
+    let batches = grouped.len();
+    futures_util::stream::iter(grouped.into_values())
+        .map(insert)
+        .buffer_unordered(concurrency)
+        .try_collect::<Vec<_>>()
+        .await?;
+    Ok(batches)
+}
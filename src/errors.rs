@@ -1,3 +1,4 @@
+use crate::runtime::asc::native_types::store::StoreValueKind;
 use deltalake::datafusion::error::DataFusionError;
 use deltalake::DeltaTableError;
 use kanal::SendError;
@@ -58,6 +59,10 @@ pub enum AscError {
     SizeNotMatch,
     #[error("Maximum Recursion Depth reached!")]
     MaxRecursion,
+    #[error("Out of bounds read: offset={offset}, len={len}, memory size={size}")]
+    OutOfBounds { offset: u64, len: u64, size: u64 },
+    #[error("Guest object length {0} exceeds the limit of {1} bytes")]
+    LengthLimitExceeded(u32, u32),
     #[error(transparent)]
     BigNumberOutOfRange(#[from] BigNumberErr),
     #[error(transparent)]
@@ -94,6 +99,14 @@ pub enum ManifestLoaderError {
     SchemaParsingError,
     #[error("Create datasource failed")]
     CreateDatasourceFail,
+    #[error("Subgraph is an ipfs manifest but no ipfs gateway is configured")]
+    IpfsNotConfigured,
+    #[error("IPFS client failed: {0}")]
+    IpfsClient(String),
+    #[error("IPFS gateway timed out fetching {0}")]
+    IpfsTimeout(String),
+    #[error("CID not resolvable from the IPFS gateway: {0}")]
+    UnresolvableCid(String),
 }
 
 #[derive(Debug, Error)]
@@ -108,6 +121,29 @@ pub enum SubgraphError {
     InvalidHandlerName(String),
     #[error("Create source failed: `{0}`")]
     CreateSourceFail(String),
+    #[error("Handlers missing from wasm exports: {0}")]
+    MissingHandlers(String),
+    #[error("Handler `{0}` aborted: gas limit of {1} exceeded")]
+    GasLimitExceeded(String, u64),
+}
+
+#[derive(Debug, Error)]
+pub enum SchemaError {
+    #[error("No such entity `{0}` in schema")]
+    UnknownEntity(String),
+    #[error("Entity `{0}` is missing required field `{1}`")]
+    MissingField(String, String),
+    #[error("Entity `{0}` has no field named `{1}`")]
+    UnknownField(String, String),
+    #[error("Field `{entity_type}.{field}` expects {expected:?}, got {found:?}")]
+    InvalidFieldType {
+        entity_type: String,
+        field: String,
+        expected: StoreValueKind,
+        found: StoreValueKind,
+    },
+    #[error("Schema catalog `{0}` unusable: {1}")]
+    Catalog(String, String),
 }
 
 #[derive(Debug, Error)]
@@ -134,6 +170,39 @@ pub enum DatabaseError {
     MissingBlockPtr,
     #[error("Wasm-Host sent an invalid request")]
     WasmSendInvalidRequest,
+    #[error("Checksum mismatch for entity `{0}` with id=`{1}`")]
+    CorruptEntity(String, String),
+    #[error("Entity `{0}` is immutable, id=`{1}` cannot be updated")]
+    ImmutableEntityUpdate(String, String),
+    #[error("Entity `{0}` is immutable, id=`{1}` was already created in this block")]
+    DuplicateEntityCreate(String, String),
+    #[error("Invalid block range: #{0} to #{1}")]
+    InvalidBlockRange(u64, u64),
+    #[error("Block #{0} is in the pruned history, earliest block kept is #{1}")]
+    PrunedBlock(u64, u64),
+    #[error(transparent)]
+    Schema(#[from] SchemaError),
+    #[error("Field `{0}.{1}` is not indexed, flag it with `@index` in the schema")]
+    FieldNotIndexed(String, String),
+    #[error("No table for entity `{entity_type}`, the schema might not match the database")]
+    TableNotFound { entity_type: String },
+    #[error("Tables missing from keyspace `{keyspace}`: {}", .tables.join(", "))]
+    MissingTables {
+        keyspace: String,
+        tables: Vec<String>,
+    },
+    #[error("Value of `{0}.{1}` does not fit in an `Int` field: {2}")]
+    ValueOverflow(String, String, String),
+    #[error("Fields flagged `@encrypted` require an encryption key")]
+    MissingEncryptionKey,
+    #[error("Invalid encryption key, expected 32 hex-encoded bytes")]
+    InvalidEncryptionKey,
+    #[error("Decrypting field `{0}` failed, the encryption key might be wrong")]
+    DecryptionFailed(String),
+    #[error("Fields flagged `@encrypted` are not supported by the {0} backend")]
+    EncryptionNotSupported(String),
+    #[error("Invalid multi-backend database config: {0}")]
+    InvalidMultiBackend(String),
 
     #[cfg(feature = "scylla")]
     #[error("Init failed")]
@@ -141,15 +210,40 @@ pub enum DatabaseError {
 
     #[cfg(feature = "scylla")]
     #[error("Query Error: `{0}`")]
-    ScyllaQuery(#[from] ScyllaError::QueryError),
+    ScyllaQuery(ScyllaError::QueryError),
+
+    #[cfg(feature = "scylla")]
+    #[error("Query timed out: `{0}`")]
+    Timeout(String),
+
+    #[cfg(feature = "scylla")]
+    #[error("Time-range queries are disabled, set `time_range_queries` to enable them")]
+    TimeRangeQueriesDisabled,
 
     #[cfg(feature = "mongo")]
     #[error("Init failed")]
     MongoDBInit(#[from] MongoError::Error),
+
+    #[cfg(feature = "postgres")]
+    #[error("Postgres error: {0}")]
+    Postgres(#[from] tokio_postgres::Error),
+}
+
+#[cfg(feature = "scylla")]
+impl From<ScyllaError::QueryError> for DatabaseError {
+    fn from(error: ScyllaError::QueryError) -> Self {
+        match error {
+            ScyllaError::QueryError::RequestTimeout(msg) => DatabaseError::Timeout(msg),
+            error => DatabaseError::ScyllaQuery(error),
+        }
+    }
 }
 
 #[derive(Debug, Error)]
-pub enum FilterError {}
+pub enum FilterError {
+    #[error("Invalid event topic0 `{0}`")]
+    InvalidTopic(String),
+}
 
 #[derive(Debug, Error)]
 pub enum SerializerError {
@@ -179,6 +273,20 @@ pub enum SourceError {
     DeltaSerializationError,
     #[error("No blocks found from Delta")]
     DeltaEmptyData,
+    #[error("Read source file failed: {0}")]
+    ReadFileFail(String),
+    #[error("Unsupported source content-type: {0}")]
+    UnsupportedContentType(String),
+    #[error("Decoding a block payload of {0} bytes failed: {1}")]
+    DecodeError(usize, String),
+    #[error("Firehose connection failed: {0}")]
+    FirehoseConnectFail(String),
+    #[error("Firehose stream failed: {0}")]
+    FirehoseStream(#[from] tonic::Status),
+    #[error("Chained source does not continue from block #{0}, got block #{1}")]
+    ChainedSourceBroken(u64, u64),
+    #[error("Merged sources disagree on the hash or parent-hash of block #{0}")]
+    MergedSourceMismatch(u64),
 }
 
 #[derive(Debug, Error)]
@@ -203,6 +311,8 @@ pub enum RPCError {
     Revert(String),
     #[error("Get latest-block failed")]
     GetLatestBlockFail,
+    #[error("Get block {0} failed")]
+    GetBlockFail(u64),
 }
 
 #[derive(Debug, Error)]
@@ -213,4 +323,12 @@ pub enum MainError {
     Subgraph(#[from] SubgraphError),
     #[error("filter error: `{0}`")]
     Filter(#[from] FilterError),
+    #[error("rpc error: `{0}`")]
+    Rpc(#[from] RPCError),
+    #[error("reorg-threshold {0} exceeds the allowed maximum of {1}")]
+    ReorgThresholdTooLarge(u16, u16),
+    #[error("stored block pointers diverged from the source deeper than reorg-threshold")]
+    HeadDiverged,
+    #[error("reorg at block #{0} is deeper than max-reorg-depth, halted")]
+    ReorgTooDeep(u64),
 }
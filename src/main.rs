@@ -9,15 +9,20 @@ mod metrics;
 mod rpc_client;
 mod runtime;
 
+use common::Schemas;
 use components::*;
 use config::Config;
+use database::forward_changes;
 use database::DatabaseAgent;
+use database::LOAD_RELATED_PAGE_SIZE;
 use errors::MainError;
-use metrics::default_registry;
 use metrics::run_metric_server;
+use metrics::subgraph_registry;
 use rpc_client::RpcAgent;
 use std::fmt::Debug;
 use std::fs;
+use tokio::signal::unix::signal;
+use tokio::signal::unix::SignalKind;
 
 fn welcome() {
     // TODO: include file in build script
@@ -32,6 +37,15 @@ fn handle_task_result<E: Debug>(r: Result<(), E>, task_name: &str) {
     info!(main, format!("{task_name} has finished"); result => format!("{:?}", r));
 }
 
+/// Resolve once the process is asked to stop, by SIGTERM or SIGINT
+async fn shutdown_signal() {
+    let mut sigterm = signal(SignalKind::terminate()).expect("Failed to listen to SIGTERM");
+    tokio::select!(
+        _ = sigterm.recv() => (),
+        _ = tokio::signal::ctrl_c() => (),
+    );
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::try_init().unwrap_or_default();
@@ -40,39 +54,172 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let config = Config::load();
     info!(main, "Config loaded!");
 
-    let registry = default_registry();
+    let registry = subgraph_registry(&config.subgraph_name);
 
-    let manifest = ManifestAgent::new(&config.subgraph_dir).await?;
+    let manifest = ManifestAgent::new(&config.subgraph_dir, config.ipfs.as_ref()).await?;
     info!(main, "Manifest loaded!");
 
-    let valve = Valve::new(&config.valve, registry);
+    if let Some(catalog) = &config.schema_catalog {
+        let catalog = std::path::Path::new(catalog);
+        let schemas = manifest.schemas();
+        if let Some(saved) = Schemas::load_catalog(catalog)? {
+            let drifted = schemas.drifted_entities(&saved);
+            if !drifted.is_empty() {
+                warn!(
+                    main,
+                    "schema changed since the last run, stored entities may not match it";
+                    entity_types => drifted.join(", ")
+                );
+            }
+        }
+        schemas.save_catalog(catalog)?;
+        info!(main, "Schema catalog saved!");
+    }
+
+    let valve = Valve::new(&config.valve, &registry);
     let source_valve = valve.clone();
 
-    let db = DatabaseAgent::new(&config.database, manifest.schemas(), registry).await?;
+    let db = DatabaseAgent::new(&config, manifest.schemas(), &registry).await?;
     info!(main, "Database ready!");
 
-    let mut inspector = Inspector::new(
-        db.get_recent_block_pointers(config.reorg_threshold).await?,
-        manifest.min_start_block(),
+    if let Some(relation) = &config.inspect_relation {
+        let mut offset = 0;
+        loop {
+            let data = (
+                relation.entity_type.clone(),
+                relation.entity_id.clone(),
+                relation.field_name.clone(),
+            );
+            let page = db
+                .load_related_page(data, offset, LOAD_RELATED_PAGE_SIZE)
+                .await?;
+            for entity in page.iter() {
+                info!(main, "related entity"; offset => offset, entity => format!("{:?}", entity));
+                offset += 1;
+            }
+            if page.len() < LOAD_RELATED_PAGE_SIZE {
+                break;
+            }
+        }
+        info!(main, "relation inspected, exiting"; total => offset);
+        return Ok(());
+    }
+
+    let created_datasources = db.load_datasources().await?;
+    info!(main, "Datasources created from templates restored!"; count => created_datasources.len());
+    manifest.restore_datasources(created_datasources);
+
+    if let Some(changes) = db.subscribe_changes() {
+        let output = config
+            .change_feed
+            .as_ref()
+            .and_then(|feed| feed.output.clone());
+        tokio::spawn(async move {
+            if let Err(e) = forward_changes(changes, output).await {
+                error!(main, "change feed stopped"; error => e);
+            }
+        });
+        info!(main, "Change-feed ready!");
+    }
+
+    check_reorg_threshold(
         config.reorg_threshold,
-    );
+        config
+            .max_reorg_threshold
+            .unwrap_or(DEFAULT_MAX_REORG_THRESHOLD),
+        &config.reorg_threshold_policy.clone().unwrap_or_default(),
+    )?;
+
+    let mut rpc = RpcAgent::new(&config, manifest.abis(), &registry).await?;
+    info!(main, "Rpc-Client ready!");
+
+    // Only a range not reprocessed yet is reverted, restarts carry on from the stored head
+    let reprocess = match config.reprocess.clone() {
+        Some(reprocess) => db
+            .reprocess(reprocess.from_block, reprocess.to_block)
+            .await?
+            .map(|_| reprocess),
+        None => None,
+    };
+    let mut inspector = match &reprocess {
+        Some(reprocess) => Inspector::new(vec![], reprocess.from_block, config.reorg_threshold),
+        None => Inspector::new(
+            db.get_recent_block_pointers(config.reorg_threshold).await?,
+            manifest.min_start_block(),
+            config.reorg_threshold,
+        ),
+    };
+
+    if config.verify_head.unwrap_or(false) {
+        recover_diverged_head(&mut inspector, &db, |number| rpc.get_block_ptr(number)).await?;
+        info!(main, "Stored head verified against the source!");
+    }
+
+    inspector.set_source_start_blocks(manifest.source_start_blocks());
+    inspector.set_max_reorg_depth(config.max_reorg_depth);
     info!(main, "BlockInspector ready!"; next_start_block => inspector.get_expected_block_number());
 
-    let block_source =
-        BlockSource::new(&config, inspector.get_expected_block_number(), registry).await?;
+    let start_block = config
+        .replay_block
+        .unwrap_or(inspector.get_expected_block_number());
+    let mut reorder_buffer = config
+        .reorder_window
+        .map(|window| ReorderBuffer::new(window, start_block));
+    if let Some(datasource_sources) = &config.datasource_sources {
+        let datasource_names = manifest.datasource_names();
+        for name in datasource_sources.keys() {
+            if !datasource_names.contains(name) {
+                warn!(main, "source configured for an unknown datasource"; datasource => name);
+            }
+        }
+    }
+    // A checkpoint is only trusted at the stored head, a reverted or replayed head
+    // means the stream restarts from the block number instead. Merged sources
+    // have no single cursor to resume from
+    let resume_cursor = match db.load_checkpoint().await? {
+        Some(checkpoint)
+            if config.replay_block.is_none()
+                && reprocess.is_none()
+                && config.datasource_sources.is_none()
+                && inspector.get_head() == Some(checkpoint.block_ptr.clone()) =>
+        {
+            info!(main, "resuming source from checkpoint"; block => checkpoint.block_ptr);
+            Some(checkpoint.cursor)
+        }
+        _ => None,
+    };
+    let block_source = BlockSource::new(
+        &config,
+        &manifest.datasource_addresses(),
+        start_block,
+        resume_cursor,
+        &registry,
+    )
+    .await?;
     info!(main, "BlockSource ready!");
 
-    let filter = DataFilter::new(
+    let mut filter = DataFilter::new(
         config.chain.clone(),
         manifest.datasource_and_templates().into(),
-        manifest.abis(),
+        &manifest,
     )?;
+    if let Some(event_allowlist) = &config.event_allowlist {
+        let datasource_names = manifest
+            .datasource_and_templates()
+            .inner()
+            .iter()
+            .map(|ds| ds.name())
+            .collect::<Vec<_>>();
+        for name in event_allowlist.keys() {
+            if !datasource_names.contains(name) {
+                warn!(main, "event allowlist configured for an unknown datasource"; datasource => name);
+            }
+        }
+        filter.set_event_allowlist(event_allowlist)?;
+    }
     info!(main, "DataFilter ready!");
 
-    let mut rpc = RpcAgent::new(&config, manifest.abis(), registry).await?;
-    info!(main, "Rpc-Client ready!");
-
-    let mut subgraph = Subgraph::new(&db, &rpc, &manifest, registry);
+    let mut subgraph = Subgraph::new(&config, &db, &rpc, &manifest, &registry);
     info!(main, "Subgraph ready!");
 
     let (sender, recv) = kanal::bounded_async(1);
@@ -81,8 +228,31 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     subgraph.create_sources()?;
 
+    // Spawned right away so a signal arriving mid-batch is not missed
+    let mut shutdown = tokio::spawn(shutdown_signal());
+
+    let mut reprocess_to_block = reprocess.map(|reprocess| reprocess.to_block);
+
     let main_flow = async move {
-        while let Ok(blocks) = recv.recv().await {
+        let mut last_processed = None;
+        let mut shutting_down = false;
+
+        loop {
+            // The signal is only listened to between batches, so the batch in progress
+            // is always processed and committed before stopping
+            let blocks = tokio::select!(
+                biased;
+                _ = &mut shutdown => {
+                    warn!(main, "shutdown signal received, stopping after the last committed batch");
+                    shutting_down = true;
+                    break;
+                }
+                received = recv.recv() => match received {
+                    Ok(blocks) => blocks,
+                    Err(_) => break,
+                },
+            );
+
             info!(
                 main,
                 "block batch recevied and about to be processed 🚀";
@@ -92,6 +262,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             let time = std::time::Instant::now();
             let blocks = filter.filter_multi(blocks)?;
             let count_blocks = blocks.len();
+            let blocks = match reorder_buffer.as_mut() {
+                Some(buffer) => blocks
+                    .into_iter()
+                    .flat_map(|b| buffer.push(b.get_block_ptr().number, b))
+                    .collect::<Vec<_>>(),
+                None => blocks,
+            };
+
+            if blocks.is_empty() {
+                // Every block of this batch is held until its predecessor arrives
+                valve.release_inflight(count_blocks);
+                continue;
+            }
+
             let last_block = blocks.last().map(|b| b.get_block_ptr()).unwrap();
 
             info!(
@@ -101,12 +285,35 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 count_blocks => count_blocks
             );
 
+            if let Some(replay_block) = config.replay_block {
+                let block = blocks
+                    .into_iter()
+                    .find(|b| b.get_block_ptr().number == replay_block);
+
+                if let Some(block) = block {
+                    let block_ptr = block.get_block_ptr();
+                    subgraph.set_active_sources(&inspector.get_active_sources(block_ptr.number));
+                    rpc.set_block_ptr(&block_ptr);
+                    manifest.set_block_ptr(&block_ptr);
+                    db.set_block_timestamp(block.get_block_timestamp());
+
+                    for operation in subgraph.replay_block(block)? {
+                        info!(main, "replayed store operation"; operation => format!("{:?}", operation));
+                    }
+                } else {
+                    warn!(main, "block to replay not found from source"; replay_block => replay_block);
+                }
+
+                return Ok(());
+            }
+
             let time = std::time::Instant::now();
 
             for block in blocks {
                 let block_ptr = block.get_block_ptr();
                 rpc.set_block_ptr(&block_ptr);
                 manifest.set_block_ptr(&block_ptr);
+                db.set_block_timestamp(block.get_block_timestamp());
 
                 match inspector.check_block(block_ptr.clone()) {
                     BlockInspectionResult::UnexpectedBlock
@@ -120,30 +327,55 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     BlockInspectionResult::ForkBlock => {
                         db.revert_from_block(block_ptr.number).await?;
                     }
+                    BlockInspectionResult::ReorgTooDeep => {
+                        return Err(MainError::ReorgTooDeep(block_ptr.number));
+                    }
                     BlockInspectionResult::OkToProceed => (),
                 };
 
+                subgraph.set_active_sources(&inspector.get_active_sources(block_ptr.number));
+
                 if subgraph.should_process(&block) {
                     subgraph.process(block)?;
                     rpc.clear_block_level_cache();
+                    db.finish_block(block_ptr.number);
                 }
 
+                db.fast_forward_cutover(block_ptr.clone()).await?;
+
                 valve.set_finished(block_ptr.number);
             }
 
+            let created_datasources = manifest.take_created_datasources();
+            if !created_datasources.is_empty() {
+                db.save_datasources(created_datasources).await?;
+            }
+
             let elapsed = time.elapsed();
 
-            db.commit_data(last_block.clone()).await?;
-            db.remove_outdated_snapshots(last_block.number).await?;
-            db.flush_cache().await?;
+            if let Some(cursor) = valve.take_cursor(last_block.number) {
+                db.set_cursor(last_block.number, cursor);
+            }
 
-            if let Some(history_size) = config.block_data_retention {
-                if last_block.number > history_size {
-                    db.clean_data_history(last_block.number - history_size)
-                        .await?;
+            if !db.is_fast_forwarding(last_block.number) {
+                db.commit_data(last_block.clone()).await?;
+                db.remove_outdated_snapshots(last_block.number).await?;
+                db.flush_cache().await?;
+
+                if let Some(history_size) = config.block_data_retention {
+                    if last_block.number > history_size {
+                        db.clean_data_history(last_block.number - history_size)
+                            .await?;
+                    }
                 }
             }
 
+            valve.release_inflight(count_blocks);
+
+            let reprocess_done =
+                reprocess_to_block.is_some_and(|to_block| last_block.number >= to_block);
+            last_processed = Some(last_block);
+
             info!(
                 main,
                 "BLOCK BATCH PROCESSED DONE  🎉🎉🎉🎉";
@@ -151,16 +383,51 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 number_of_blocks => count_blocks,
                 avg_speed => format!("~{:?} blocks/sec", { count_blocks as u64 / elapsed.as_secs() })
             );
+
+            if reprocess_done {
+                // The blocks after the range were reverted too, they are indexed again from here
+                info!(main, "block range reprocessed, indexing on"; to_block => reprocess_to_block.unwrap());
+                reprocess_to_block = None;
+            }
+        }
+
+        if !shutting_down {
+            warn!(main, "No more messages returned from block-stream");
         }
 
-        warn!(main, "No more messages returned from block-stream");
+        if let Some(last_block) = &last_processed {
+            if let Some(cursor) = valve.take_cursor(last_block.number) {
+                db.set_cursor(last_block.number, cursor);
+            }
+        }
+
+        db.commit_final(last_processed, shutting_down).await?;
+
         Ok::<(), MainError>(())
     };
 
+    // The main flow keeps draining the stream after the source is exhausted,
+    // so the last batch is always flushed before exiting. Once the main flow
+    // stops on its own, eg on shutdown, the source has no one to send blocks to
+    let run_to_end = async {
+        tokio::pin!(main_flow);
+        tokio::select!(
+            source_result = query_blocks => {
+                handle_task_result(source_result, "block-source");
+                handle_task_result(main_flow.await, "Main flow stopped");
+            }
+            flow_result = &mut main_flow => {
+                handle_task_result(flow_result, "Main flow stopped");
+            }
+        );
+    };
+
     tokio::select!(
-        r = query_blocks => handle_task_result(r, "block-source"),
-        r = main_flow => handle_task_result(r, "Main flow stopped"),
-        _ = tokio::spawn(run_metric_server(config.metric_port.unwrap_or(8081))) => ()
+        _ = run_to_end => (),
+        _ = tokio::spawn(run_metric_server(
+            config.metric_port.unwrap_or(8081),
+            registry.clone()
+        )) => ()
     );
 
     Ok(())
@@ -1,25 +1,54 @@
-pub use prometheus::default_registry;
+use prometheus::Registry;
 use prometheus::TextEncoder;
+use std::collections::HashMap;
 use warp::Filter;
 use warp::Rejection;
 use warp::Reply;
 
-async fn metrics_handler() -> Result<impl Reply, Rejection> {
+/// Every metric registered here is prefixed with `subgraph_` and labelled with the subgraph's id,
+/// so several runtimes can be scraped into the same prometheus
+pub fn subgraph_registry(subgraph_id: &str) -> Registry {
+    let labels = HashMap::from([("subgraph_id".to_string(), subgraph_id.to_string())]);
+    Registry::new_custom(Some("subgraph".to_string()), Some(labels))
+        .expect("Failed to create metrics registry")
+}
+
+async fn metrics_handler(registry: Registry) -> Result<impl Reply, Rejection> {
     let encoder = TextEncoder::new();
     let mut buffer = String::from("");
 
     encoder
-        .encode_utf8(&prometheus::gather(), &mut buffer)
+        .encode_utf8(&registry.gather(), &mut buffer)
         .expect("Failed to encode metrics");
 
-    let response = buffer.clone();
-    buffer.clear();
-
-    Ok(response)
+    Ok(buffer)
 }
 
-pub async fn run_metric_server(port: u16) {
+pub async fn run_metric_server(port: u16, registry: Registry) {
     crate::info!(Prometheus, format!("Start metrics server at port: {port}"));
-    let metrics_route = warp::path!("metrics").and_then(metrics_handler);
+    let metrics_route = warp::path!("metrics")
+        .and(warp::any().map(move || registry.clone()))
+        .and_then(metrics_handler);
     warp::serve(metrics_route).run(([0, 0, 0, 0], port)).await;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::IntCounter;
+
+    #[tokio::test]
+    async fn test_subgraph_registry() {
+        let registry = subgraph_registry("uniswap-v3");
+        let counter = IntCounter::new("block_processed_counter", "count processed blocks").unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+        counter.inc();
+
+        let response = metrics_handler(registry).await.unwrap().into_response();
+        let body = warp::hyper::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(r#"subgraph_block_processed_counter{subgraph_id="uniswap-v3"} 1"#));
+    }
+}
@@ -254,6 +254,19 @@ impl RPCTrait for EthereumRPC {
             .unwrap()
     }
 
+    async fn get_block_ptr(&mut self, block_number: u64) -> Result<BlockPtr, RPCError> {
+        self.client
+            .eth()
+            .block(BlockId::Number(BlockNumber::Number(block_number.into())))
+            .await
+            .map_err(|e| {
+                error!(EthereumRPC, "get block failed"; error => e, block_number => block_number);
+                RPCError::GetBlockFail(block_number)
+            })?
+            .map(BlockPtr::from)
+            .ok_or(RPCError::GetBlockFail(block_number))
+    }
+
     fn cache_get(&self, call: &CallRequest) -> Option<CallResponse> {
         self.cache.0.get(call).cloned()
     }
@@ -19,6 +19,7 @@ pub use types::*;
 pub trait RPCTrait {
     async fn handle_request(&mut self, call: CallRequestContext) -> Result<CallResponse, RPCError>;
     async fn get_latest_block(&mut self) -> Result<BlockPtr, RPCError>;
+    async fn get_block_ptr(&mut self, block_number: u64) -> Result<BlockPtr, RPCError>;
     fn cache_get(&self, call: &CallRequest) -> Option<CallResponse>;
     fn cache_set(&mut self, call: &CallRequest, result: &CallResponse);
 }
@@ -48,6 +49,13 @@ impl RPCTrait for RPCChain {
         }
     }
 
+    async fn get_block_ptr(&mut self, block_number: u64) -> Result<BlockPtr, RPCError> {
+        match self {
+            RPCChain::Ethereum(client) => client.get_block_ptr(block_number).await,
+            RPCChain::None => Err(RPCError::GetBlockFail(block_number)),
+        }
+    }
+
     fn cache_get(&self, call: &CallRequest) -> Option<CallResponse> {
         match self {
             RPCChain::Ethereum(client) => client.cache_get(call),
@@ -119,6 +127,10 @@ impl RpcClient {
         Ok(result)
     }
 
+    pub async fn get_block_ptr(&mut self, block_number: u64) -> Result<BlockPtr, RPCError> {
+        self.rpc_client.get_block_ptr(block_number).await
+    }
+
     pub fn set_block_ptr(&mut self, block_ptr: &BlockPtr) {
         self.block_ptr = block_ptr.clone();
     }
@@ -146,6 +158,14 @@ impl RpcAgent {
         })
     }
 
+    /// The block the source chain currently has at this number
+    pub fn get_block_ptr(&mut self, block_number: u64) -> Result<BlockPtr, RPCError> {
+        let mut rpc = self.0.borrow_mut();
+        tokio::task::block_in_place(move || {
+            tokio::runtime::Handle::current().block_on(rpc.get_block_ptr(block_number))
+        })
+    }
+
     pub fn set_block_ptr(&mut self, block_ptr: &BlockPtr) {
         let mut rpc = self.0.borrow_mut();
         rpc.set_block_ptr(block_ptr);
@@ -162,6 +182,19 @@ pub mod tests {
     use super::*;
     use std::fs::File;
 
+    impl RpcAgent {
+        /// Agent without a chain client, for host functions that never call the rpc
+        pub fn new_mock(registry: &Registry) -> Self {
+            let client = RpcClient {
+                rpc_client: RPCChain::None,
+                block_ptr: BlockPtr::default(),
+                cache_by_block: HashMap::new(),
+                metrics: RpcMetrics::new(registry),
+            };
+            RpcAgent(Rc::new(RefCell::new(client)))
+        }
+    }
+
     pub async fn create_rpc_client_test(version: &str) -> RpcAgent {
         let rpc = "https://eth.merkle.io";
         let abi_file = File::open(format!(
@@ -9,6 +9,7 @@ use std::mem::MaybeUninit;
 pub const SIZE_OF_RT_SIZE: u32 = 4;
 pub const HEADER_SIZE: usize = 20;
 pub const MAX_RECURSION_DEPTH: usize = 128;
+pub const DEFAULT_MAX_ASC_LENGTH: u32 = 256 * 1024 * 1024;
 
 pub trait AscIndexId {
     /// Constant string with the name of the type in AssemblyScript.
@@ -32,6 +33,12 @@ pub trait AscHeap {
 
     fn read_u32(&self, offset: u32) -> Result<u32, AscError>;
 
+    /// Size in bytes of the guest memory backing this heap.
+    fn memory_size(&self) -> u64;
+
+    /// Largest object, in bytes, that may be read from guest memory at once.
+    fn max_asc_length(&self) -> u32;
+
     fn api_version(&self) -> Version;
 
     fn asc_type_id(&mut self, type_id_index: IndexForAscTypeId) -> Result<u32, AscError>;
@@ -141,6 +148,21 @@ impl<C: AscType> AscPtr<C> {
             C::from_asc_bytes(buffer, &heap.api_version())
         };
 
+        // Lengths come from guest memory, so never trust them to size our buffer
+        let max_length = heap.max_asc_length();
+        if len > max_length {
+            return Err(AscError::LengthLimitExceeded(len, max_length));
+        }
+
+        let size = heap.memory_size();
+        if self.0 as u64 + len as u64 > size {
+            return Err(AscError::OutOfBounds {
+                offset: self.0 as u64,
+                len: len as u64,
+                size,
+            });
+        }
+
         let len = len as usize;
 
         if len <= 32 {
@@ -442,10 +464,11 @@ pub enum IndexForAscTypeId {
     ArrayLog = 1003,
     ArrayTypedMapStringStoreValue = 1004,
     ArrayEthereumTransaction = 1005,
+    LoadedEntity = 1006,
     // Continue to add more Ethereum type IDs here.
     // e.g.:
-    // NextEthereumType = 1004,
-    // AnotherEthereumType = 1005,
+    // NextEthereumType = 1007,
+    // AnotherEthereumType = 1008,
     // ...
     // LastEthereumType = 1499,
 
@@ -650,3 +673,174 @@ impl ToAscObj<bool> for bool {
 pub trait FromAscObj<C: AscType>: Sized {
     fn from_asc_obj<H: AscHeap + ?Sized>(obj: C, heap: &H, depth: usize) -> Result<Self, AscError>;
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::runtime::asc::native_types::array::Array;
+    use crate::runtime::asc::native_types::string::AscString;
+    use crate::runtime::asc::native_types::Uint8Array;
+
+    /// Flat byte-vector heap standing in for a wasm memory
+    pub struct MockHeap {
+        memory: Vec<u8>,
+        max_length: u32,
+    }
+
+    impl MockHeap {
+        pub fn new(size: usize) -> Self {
+            Self {
+                memory: vec![0; size],
+                max_length: DEFAULT_MAX_ASC_LENGTH,
+            }
+        }
+
+        fn write_u32(&mut self, offset: u32, value: u32) {
+            let offset = offset as usize;
+            self.memory[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+
+        /// Lay out an object header whose `rt_size` claims `len` bytes of content at `ptr`
+        fn write_object(&mut self, ptr: u32, len: u32, content: &[u32]) {
+            self.write_u32(ptr - SIZE_OF_RT_SIZE, len);
+            for (i, value) in content.iter().enumerate() {
+                self.write_u32(ptr + 4 * i as u32, *value);
+            }
+        }
+    }
+
+    impl AscHeap for MockHeap {
+        fn raw_new(&mut self, bytes: &[u8]) -> Result<u32, AscError> {
+            let ptr = self.memory.len() as u32;
+            self.memory.extend_from_slice(bytes);
+            Ok(ptr)
+        }
+
+        fn read<'a>(
+            &self,
+            offset: u32,
+            buffer: &'a mut [MaybeUninit<u8>],
+        ) -> Result<&'a mut [u8], AscError> {
+            let start = offset as usize;
+            let source = &self.memory[start..start + buffer.len()];
+            for (dst, byte) in buffer.iter_mut().zip(source) {
+                dst.write(*byte);
+            }
+            // Safety: every byte of `buffer` has just been initialized
+            Ok(unsafe { &mut *(buffer as *mut [MaybeUninit<u8>] as *mut [u8]) })
+        }
+
+        fn read_u32(&self, offset: u32) -> Result<u32, AscError> {
+            let offset = offset as usize;
+            let bytes = self.memory[offset..offset + 4].try_into().unwrap();
+            Ok(u32::from_le_bytes(bytes))
+        }
+
+        fn memory_size(&self) -> u64 {
+            self.memory.len() as u64
+        }
+
+        fn max_asc_length(&self) -> u32 {
+            self.max_length
+        }
+
+        fn api_version(&self) -> Version {
+            Version::new(0, 0, 5)
+        }
+
+        fn asc_type_id(&mut self, _type_id_index: IndexForAscTypeId) -> Result<u32, AscError> {
+            Ok(0)
+        }
+    }
+
+    #[test]
+    fn test_asc_get_string_out_of_bounds() {
+        let mut heap = MockHeap::new(128);
+        heap.write_object(32, 0xFFFF_FFF0, &[]);
+
+        let result = asc_get::<String, AscString, _>(&heap, AscPtr::new(32), 0);
+        assert!(matches!(
+            result,
+            Err(AscError::OutOfBounds {
+                offset: 32,
+                len: 0xFFFF_FFF0,
+                size: 128
+            })
+        ));
+
+        // A length that fits the memory is still read as usual
+        heap.write_object(32, 4, &[0x0062_0061]);
+        let result = asc_get::<String, AscString, _>(&heap, AscPtr::new(32), 0).unwrap();
+        assert_eq!(result, "ab");
+    }
+
+    #[test]
+    fn test_asc_get_uint8_array_out_of_bounds() {
+        let mut heap = MockHeap::new(128);
+        // TypedArray { buffer, data_start, byte_length } pointing at an oversized buffer
+        heap.write_object(32, 12, &[64, 64, 16]);
+        heap.write_object(64, u32::MAX, &[]);
+
+        let result = asc_get::<Vec<u8>, Uint8Array, _>(&heap, AscPtr::new(32), 0);
+        assert!(matches!(
+            result,
+            Err(AscError::OutOfBounds { offset: 64, .. })
+        ));
+
+        // A data_start past the end of the backing buffer is rejected too
+        heap.write_object(64, 4, &[0x0403_0201]);
+        heap.write_object(32, 12, &[64, 96, 4]);
+        let result = asc_get::<Vec<u8>, Uint8Array, _>(&heap, AscPtr::new(32), 0);
+        assert!(matches!(
+            result,
+            Err(AscError::OutOfBounds { offset: 32, .. })
+        ));
+
+        heap.write_object(32, 12, &[64, 64, 4]);
+        let result = asc_get::<Vec<u8>, Uint8Array, _>(&heap, AscPtr::new(32), 0).unwrap();
+        assert_eq!(result, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_asc_get_array_out_of_bounds() {
+        let mut heap = MockHeap::new(128);
+        // Array { buffer, buffer_data_start, buffer_data_length, length }
+        heap.write_object(32, 16, &[64, 64, 8, 2]);
+        heap.write_object(64, 0x8000_0000, &[]);
+
+        let result = asc_get::<Vec<String>, Array<AscPtr<AscString>>, _>(&heap, AscPtr::new(32), 0);
+        assert!(matches!(
+            result,
+            Err(AscError::OutOfBounds {
+                offset: 64,
+                len: 0x8000_0000,
+                size: 128
+            })
+        ));
+
+        // The array itself claiming more than the memory holds
+        heap.write_object(124, u32::MAX, &[]);
+        let result =
+            asc_get::<Vec<String>, Array<AscPtr<AscString>>, _>(&heap, AscPtr::new(124), 0);
+        assert!(matches!(result, Err(AscError::OutOfBounds { .. })));
+    }
+
+    #[test]
+    fn test_asc_get_length_limit() {
+        let mut heap = MockHeap::new(256);
+        heap.max_length = 64;
+        // The backing buffer fits the memory but declares more than the cap
+        heap.write_object(32, 16, &[64, 64, 8, 2]);
+        heap.write_object(64, 100, &[]);
+
+        let result = asc_get::<Vec<String>, Array<AscPtr<AscString>>, _>(&heap, AscPtr::new(32), 0);
+        assert!(matches!(
+            result,
+            Err(AscError::LengthLimitExceeded(100, 64))
+        ));
+
+        heap.write_object(64, 64, &[]);
+        let result = asc_get::<Vec<String>, Array<AscPtr<AscString>>, _>(&heap, AscPtr::new(32), 0);
+        assert!(!matches!(result, Err(AscError::LengthLimitExceeded(..))));
+    }
+}
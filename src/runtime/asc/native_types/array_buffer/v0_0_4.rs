@@ -40,7 +40,7 @@ impl ArrayBuffer {
 
     /// Read `length` elements of type `T` starting at `byte_offset`.
     ///
-    /// Panics if that tries to read beyond the length of `self.content`.
+    /// Fails with `OutOfBounds` if `byte_offset` is beyond the length of `self.content`.
     pub fn get<T: AscType>(
         &self,
         byte_offset: u32,
@@ -50,7 +50,13 @@ impl ArrayBuffer {
         let length = length as usize;
         let byte_offset = byte_offset as usize;
 
-        self.content[byte_offset..]
+        self.content
+            .get(byte_offset..)
+            .ok_or(AscError::OutOfBounds {
+                offset: byte_offset as u64,
+                len: length as u64 * size_of::<T>() as u64,
+                size: self.content.len() as u64,
+            })?
             .chunks(size_of::<T>())
             .take(length)
             .map(|asc_obj| T::from_asc_bytes(asc_obj, &api_version))
@@ -114,6 +120,8 @@ impl AscType for ArrayBuffer {
         let byte_length = ptr.read_u32(heap)?;
         let byte_length_size = size_of::<u32>() as u32;
         let padding_size = size_of::<u32>() as u32;
-        Ok(byte_length_size + padding_size + byte_length)
+        byte_length
+            .checked_add(byte_length_size + padding_size)
+            .ok_or(AscError::Overflow(byte_length))
     }
 }
@@ -32,7 +32,7 @@ impl ArrayBuffer {
 
     /// Read `length` elements of type `T` starting at `byte_offset`.
     ///
-    /// Panics if that tries to read beyond the length of `self.content`.
+    /// Fails with `OutOfBounds` if `byte_offset` is beyond the length of `self.content`.
     pub fn get<T: AscType>(
         &self,
         byte_offset: u32,
@@ -42,7 +42,13 @@ impl ArrayBuffer {
         let length = length as usize;
         let byte_offset = byte_offset as usize;
 
-        self.content[byte_offset..]
+        self.content
+            .get(byte_offset..)
+            .ok_or(AscError::OutOfBounds {
+                offset: byte_offset as u64,
+                len: length as u64 * size_of::<T>() as u64,
+                size: self.content.len() as u64,
+            })?
             .chunks(size_of::<T>())
             .take(length)
             .map(|asc_obj| T::from_asc_bytes(asc_obj, &api_version))
@@ -108,7 +108,7 @@ pub enum Value {
 }
 
 #[repr(u32)]
-#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
 pub enum StoreValueKind {
     String,
     Int,
@@ -120,6 +120,8 @@ pub enum StoreValueKind {
     Bytes,
     BigInt,
     Int8,
+    /// Microseconds since the epoch, held as `Value::Int8`
+    Timestamp,
 }
 
 impl_asc_type_enum!(
@@ -132,9 +134,15 @@ impl_asc_type_enum!(
     Null => 5,
     Bytes => 6,
     BigInt => 7,
-    Int8 => 8
+    Int8 => 8,
+    Timestamp => 9
 );
 
+/// A field of a stored entity along with the kind of its schema field,
+/// which differs from the value's own for timestamps held as `Value::Int8`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FieldValue(pub StoreValueKind, pub Value);
+
 impl StoreValueKind {
     pub fn get_kind(value: &Value) -> StoreValueKind {
         match value {
@@ -164,6 +172,7 @@ impl FromStr for StoreValueKind {
             "Bool" => Self::Bool,
             "Int" => Self::Int,
             "Int8" => Self::Int8,
+            "Timestamp" => Self::Timestamp,
             "Array" => Self::Array,
             _ => unimplemented!(),
         };
@@ -51,6 +51,24 @@ impl AscIndexId for Array<AscPtr<AscEntity>> {
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::ArrayTypedMapStringStoreValue;
 }
 
+/// An entity returned by `store.get_with_freshness`,
+/// `created_in_block` is true if the entity has not been committed yet
+#[repr(C)]
+pub struct AscLoadedEntity {
+    pub entity: AscPtr<AscEntity>,
+    pub created_in_block: bool,
+}
+
+impl_asc_type_struct!(
+    AscLoadedEntity;
+    entity => AscPtr<AscEntity>,
+    created_in_block => bool
+);
+
+impl AscIndexId for AscLoadedEntity {
+    const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::LoadedEntity;
+}
+
 impl AscIndexId for AscTypedMap<AscString, AscEnum<JsonValueKind>> {
     const INDEX_ASC_TYPE_ID: IndexForAscTypeId = IndexForAscTypeId::TypedMapStringJsonValue;
 }
@@ -15,14 +15,18 @@ use super::native_types::json::JsonValueKind;
 use super::native_types::r#enum::AscEnum;
 use super::native_types::r#enum::AscEnumArray;
 use super::native_types::r#enum::EnumPayload;
+use super::native_types::store::FieldValue;
 use super::native_types::store::StoreValueKind;
 use super::native_types::store::Value;
 use super::native_types::string::AscString;
 use super::native_types::typed_array::TypedArray;
 use super::native_types::typed_array::Uint8Array;
 use super::native_types::typed_map::AscEntity;
+use super::native_types::typed_map::AscLoadedEntity;
 use super::native_types::typed_map::AscTypedMap;
 use super::native_types::typed_map::AscTypedMapEntry;
+use super::native_types::AscResult;
+use super::native_types::AscWrapped;
 use crate::errors::AscError;
 use crate::runtime::bignumber::bigint::BigInt;
 use std::collections::HashMap;
@@ -185,7 +189,7 @@ impl FromAscObj<AscEnum<StoreValueKind>> for Value {
                 Value::String(asc_get(heap, ptr, depth)?)
             }
             StoreValueKind::Int => Value::Int(i32::from(payload)),
-            StoreValueKind::Int8 => Value::Int8(i64::from(payload)),
+            StoreValueKind::Int8 | StoreValueKind::Timestamp => Value::Int8(i64::from(payload)),
             StoreValueKind::BigDecimal => {
                 let ptr: AscPtr<AscBigDecimal> = AscPtr::from(payload);
                 Value::BigDecimal(asc_get(heap, ptr, depth)?)
@@ -241,6 +245,20 @@ impl ToAscObj<AscEnum<StoreValueKind>> for Value {
     }
 }
 
+/// Timestamps reach the mappings as such rather than as the `Int8` holding them
+impl ToAscObj<AscEnum<StoreValueKind>> for FieldValue {
+    fn to_asc_obj<H: AscHeap + ?Sized>(
+        &self,
+        heap: &mut H,
+    ) -> Result<AscEnum<StoreValueKind>, AscError> {
+        let mut asc_enum = self.1.to_asc_obj(heap)?;
+        if let (StoreValueKind::Timestamp, Value::Int8(_)) = (self.0, &self.1) {
+            asc_enum.kind = StoreValueKind::Timestamp;
+        }
+        Ok(asc_enum)
+    }
+}
+
 impl ToAscObj<AscEnum<JsonValueKind>> for serde_json::Value {
     fn to_asc_obj<H: AscHeap + ?Sized>(
         &self,
@@ -265,6 +283,28 @@ impl ToAscObj<AscEnum<JsonValueKind>> for serde_json::Value {
     }
 }
 
+/// Either the wrapped value or the wrapped error is set, the other one is null
+impl ToAscObj<AscResult<AscPtr<AscEnum<JsonValueKind>>, bool>> for Result<serde_json::Value, bool> {
+    fn to_asc_obj<H: AscHeap + ?Sized>(
+        &self,
+        heap: &mut H,
+    ) -> Result<AscResult<AscPtr<AscEnum<JsonValueKind>>, bool>, AscError> {
+        Ok(match self {
+            Ok(value) => {
+                let inner = asc_new(heap, value)?;
+                AscResult {
+                    value: asc_new(heap, &AscWrapped { inner })?,
+                    error: AscPtr::null(),
+                }
+            }
+            Err(error) => AscResult {
+                value: AscPtr::null(),
+                error: asc_new(heap, &AscWrapped { inner: *error })?,
+            },
+        })
+    }
+}
+
 impl ToAscObj<AscJson> for serde_json::Map<String, serde_json::Value> {
     fn to_asc_obj<H: AscHeap + ?Sized>(&self, heap: &mut H) -> Result<AscJson, AscError> {
         Ok(AscTypedMap {
@@ -289,6 +329,23 @@ impl ToAscObj<AscEntity> for Vec<(&str, &Value)> {
     }
 }
 
+impl ToAscObj<AscEntity> for Vec<(String, FieldValue)> {
+    fn to_asc_obj<H: AscHeap + ?Sized>(&self, heap: &mut H) -> Result<AscEntity, AscError> {
+        Ok(AscTypedMap {
+            entries: asc_new(heap, self.as_slice())?,
+        })
+    }
+}
+
+impl ToAscObj<AscLoadedEntity> for (Vec<(String, FieldValue)>, bool) {
+    fn to_asc_obj<H: AscHeap + ?Sized>(&self, heap: &mut H) -> Result<AscLoadedEntity, AscError> {
+        Ok(AscLoadedEntity {
+            entity: asc_new(heap, &self.0)?,
+            created_in_block: self.1,
+        })
+    }
+}
+
 impl ToAscObj<Array<AscPtr<AscEntity>>> for Vec<Vec<(String, Value)>> {
     fn to_asc_obj<H: AscHeap + ?Sized>(
         &self,
@@ -299,3 +356,50 @@ impl ToAscObj<Array<AscPtr<AscEntity>>> for Vec<Vec<(String, Value)>> {
         Array::new(&content, heap)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::asc::base::tests::MockHeap;
+
+    #[test]
+    fn test_timestamp_field_round_trip() {
+        let mut heap = MockHeap::new(0);
+        let timestamp = 1_700_000_000_000_000;
+
+        // Timestamps are held as `Int8` but reach the mapping tagged as such
+        let field = FieldValue(StoreValueKind::Timestamp, Value::Int8(timestamp));
+        let ptr: AscPtr<AscEnum<StoreValueKind>> = asc_new(&mut heap, &field).unwrap();
+        assert_eq!(ptr.read_ptr(&heap).unwrap().kind, StoreValueKind::Timestamp);
+        let value: Value = asc_get(&heap, ptr, 0).unwrap();
+        assert_eq!(value, Value::Int8(timestamp));
+
+        // Plain `Int8` fields & unset timestamps keep the kind of their value
+        let field = FieldValue(StoreValueKind::Int8, Value::Int8(timestamp));
+        let ptr: AscPtr<AscEnum<StoreValueKind>> = asc_new(&mut heap, &field).unwrap();
+        assert_eq!(ptr.read_ptr(&heap).unwrap().kind, StoreValueKind::Int8);
+        let field = FieldValue(StoreValueKind::Timestamp, Value::Null);
+        let ptr: AscPtr<AscEnum<StoreValueKind>> = asc_new(&mut heap, &field).unwrap();
+        assert_eq!(ptr.read_ptr(&heap).unwrap().kind, StoreValueKind::Null);
+
+        let entity = vec![
+            (
+                "id".to_string(),
+                FieldValue(StoreValueKind::String, Value::String("a".to_string())),
+            ),
+            (
+                "createdAt".to_string(),
+                FieldValue(StoreValueKind::Timestamp, Value::Int8(timestamp)),
+            ),
+        ];
+        let ptr: AscPtr<AscEntity> = asc_new(&mut heap, &entity).unwrap();
+        let entity: HashMap<String, Value> = asc_get(&heap, ptr, 0).unwrap();
+        assert_eq!(
+            entity,
+            HashMap::from([
+                ("id".to_string(), Value::String("a".to_string())),
+                ("createdAt".to_string(), Value::Int8(timestamp)),
+            ])
+        );
+    }
+}
@@ -38,7 +38,7 @@ impl BigDecimal {
     }
 
     pub fn parse_bytes(bytes: &[u8]) -> Option<Self> {
-        bigdecimal::BigDecimal::parse_bytes(bytes, 10).map(Self)
+        bigdecimal::BigDecimal::parse_bytes(bytes, 10).map(Self::from)
     }
 
     pub fn zero() -> BigDecimal {
@@ -75,6 +75,32 @@ impl BigDecimal {
 
         BigDecimal(bigdecimal::BigDecimal::new(int_val, scale))
     }
+
+    /// Plain notation of the normalized value, without exponent nor trailing zeros,
+    /// so that equal values are always written to the database the same way
+    pub fn to_canonical_string(&self) -> String {
+        let (int_val, scale) = self.normalized().as_bigint_and_exponent();
+        let sign = match int_val.sign() {
+            num_bigint::Sign::Minus => "-",
+            _ => "",
+        };
+        let digits = int_val.magnitude().to_str_radix(10);
+
+        if scale <= 0 {
+            return format!(
+                "{sign}{digits}{}",
+                "0".repeat(scale.unsigned_abs() as usize)
+            );
+        }
+
+        let scale = scale as usize;
+        if digits.len() > scale {
+            let (integer, fraction) = digits.split_at(digits.len() - scale);
+            format!("{sign}{integer}.{fraction}")
+        } else {
+            format!("{sign}0.{}{digits}", "0".repeat(scale - digits.len()))
+        }
+    }
 }
 
 impl Display for BigDecimal {
@@ -110,6 +110,13 @@ impl<'a> TryFrom<&'a BigInt> for u64 {
     }
 }
 
+impl<'a> TryFrom<&'a BigInt> for i32 {
+    type Error = BigIntOutOfRangeError;
+    fn try_from(value: &'a BigInt) -> Result<i32, BigIntOutOfRangeError> {
+        i32::try_from(&value.0).map_err(|_| BigIntOutOfRangeError::Overflow)
+    }
+}
+
 impl TryFrom<BigInt> for u64 {
     type Error = BigIntOutOfRangeError;
     fn try_from(value: BigInt) -> Result<u64, BigIntOutOfRangeError> {
@@ -195,6 +202,28 @@ impl BigInt {
         BigInt::new(self.inner().pow(exponent))
     }
 
+    /// Greatest common divisor, never negative
+    pub fn gcd(self, other: BigInt) -> BigInt {
+        use num_traits::Signed;
+        use num_traits::Zero;
+
+        let (mut x, mut y) = (self.inner().abs(), other.inner().abs());
+        while !y.is_zero() {
+            let rem = &x % &y;
+            x = y;
+            y = rem;
+        }
+        BigInt::unchecked_new(x)
+    }
+
+    /// Floor of the square root, `None` for negative numbers
+    pub fn sqrt(&self) -> Option<BigInt> {
+        match self.sign() {
+            BigIntSign::Minus => None,
+            _ => Some(BigInt::unchecked_new(self.0.sqrt())),
+        }
+    }
+
     pub fn from_hex(hex: String) -> Result<BigInt, BigNumberErr> {
         let big_int = num_bigint::BigInt::from_str_radix(&hex, 16)?;
         BigInt::new(big_int)
@@ -3,6 +3,7 @@ use crate::runtime::asc::base::AscHeap;
 use crate::runtime::asc::base::AscPtr;
 use crate::runtime::asc::base::IndexForAscTypeId;
 use crate::runtime::wasm_host::Env;
+use crate::runtime::wasm_host::GasCounter;
 use semver::Version;
 use std::mem::MaybeUninit;
 use std::sync::Arc;
@@ -93,6 +94,19 @@ impl AscHeap for FunctionEnvMut<'_, Env> {
         Ok(u32::from_le_bytes(bytes))
     }
 
+    fn memory_size(&self) -> u64 {
+        let memory = &self
+            .data()
+            .memory
+            .clone()
+            .expect("(FunctionEnvMut::memory_size) Memory must be initialized beforehand");
+        memory.view(&self.as_store_ref()).data_size()
+    }
+
+    fn max_asc_length(&self) -> u32 {
+        self.data().max_asc_length
+    }
+
     fn api_version(&self) -> Version {
         self.data().api_version.clone()
     }
@@ -123,6 +137,8 @@ pub struct AscHost {
     pub id_of_type: Option<TypedFunction<u32, u32>>,
     pub memory_allocate: Option<TypedFunction<i32, i32>>,
     pub arena_start_ptr: ArenaStartPtr,
+    pub max_asc_length: u32,
+    pub gas: GasCounter,
 }
 
 impl AscHost {
@@ -179,6 +195,14 @@ impl AscHeap for AscHost {
         Ok(u32::from_le_bytes(bytes))
     }
 
+    fn memory_size(&self) -> u64 {
+        self.memory.view(&self.store.as_store_ref()).data_size()
+    }
+
+    fn max_asc_length(&self) -> u32 {
+        self.max_asc_length
+    }
+
     fn api_version(&self) -> Version {
         self.api_version.clone()
     }
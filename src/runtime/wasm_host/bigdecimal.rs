@@ -14,6 +14,7 @@ pub fn big_decimal_plus(
     big_decimal_x_ptr: AscPtr<AscBigDecimal>,
     big_decimal_y_ptr: AscPtr<AscBigDecimal>,
 ) -> Result<AscPtr<AscBigDecimal>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let x: BigDecimal = asc_get(&fenv, big_decimal_x_ptr, 0)?;
     let y: BigDecimal = asc_get(&fenv, big_decimal_y_ptr, 0)?;
     let result = x + y;
@@ -26,6 +27,7 @@ pub fn big_decimal_minus(
     big_decimal_x_ptr: AscPtr<AscBigDecimal>,
     big_decimal_y_ptr: AscPtr<AscBigDecimal>,
 ) -> Result<AscPtr<AscBigDecimal>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let x: BigDecimal = asc_get(&fenv, big_decimal_x_ptr, 0)?;
     let y: BigDecimal = asc_get(&fenv, big_decimal_y_ptr, 0)?;
     let result = x - y;
@@ -38,6 +40,7 @@ pub fn big_decimal_times(
     big_decimal_x_ptr: AscPtr<AscBigDecimal>,
     big_decimal_y_ptr: AscPtr<AscBigDecimal>,
 ) -> Result<AscPtr<AscBigDecimal>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let x: BigDecimal = asc_get(&fenv, big_decimal_x_ptr, 0)?;
     let y: BigDecimal = asc_get(&fenv, big_decimal_y_ptr, 0)?;
     let result = x * y;
@@ -50,12 +53,11 @@ pub fn big_decimal_divided_by(
     big_decimal_x_ptr: AscPtr<AscBigDecimal>,
     big_decimal_y_ptr: AscPtr<AscBigDecimal>,
 ) -> Result<AscPtr<AscBigDecimal>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let x: BigDecimal = asc_get(&fenv, big_decimal_x_ptr, 0)?;
     let y: BigDecimal = asc_get(&fenv, big_decimal_y_ptr, 0)?;
     if y == BigDecimal::from(0) {
-        return Err(RuntimeError::new(
-            "Divide by zero | big_decimal_divided_by error!",
-        ));
+        return Err(RuntimeError::new(format!("divide by zero: {x} / 0")));
     }
 
     let result = x / y;
@@ -67,6 +69,7 @@ pub fn big_decimal_from_string(
     mut fenv: FunctionEnvMut<Env>,
     big_decimal_x_ptr: AscPtr<AscString>,
 ) -> Result<AscPtr<AscBigDecimal>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let string: String = asc_get(&fenv, big_decimal_x_ptr, 0)?;
 
     let result = BigDecimal::from_str(&string)
@@ -80,6 +83,7 @@ pub fn big_decimal_to_string(
     mut fenv: FunctionEnvMut<Env>,
     big_decimal_x_ptr: AscPtr<AscBigDecimal>,
 ) -> Result<AscPtr<AscString>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let big_decimal: BigDecimal = asc_get(&fenv, big_decimal_x_ptr, 0)?;
     let result = big_decimal.to_string();
     let asc_pt = asc_new(&mut fenv, &result)?;
@@ -91,6 +95,7 @@ pub fn big_decimal_equals(
     big_decimal_x_ptr: AscPtr<AscBigDecimal>,
     big_decimal_y_ptr: AscPtr<AscBigDecimal>,
 ) -> Result<i32, RuntimeError> {
+    fenv.data().gas.consume()?;
     let x: BigDecimal = asc_get(&fenv, big_decimal_x_ptr, 0)?;
     let y: BigDecimal = asc_get(&fenv, big_decimal_y_ptr, 0)?;
     let result = x == y;
@@ -17,6 +17,7 @@ pub fn big_int_plus(
     bigint_x_ptr: AscPtr<AscBigInt>,
     bigint_y_ptr: AscPtr<AscBigInt>,
 ) -> Result<AscPtr<AscBigInt>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
     let y: BigInt = asc_get(&fenv, bigint_y_ptr, 0)?;
     let result = x + y;
@@ -29,6 +30,7 @@ pub fn big_int_minus(
     bigint_x_ptr: AscPtr<AscBigInt>,
     bigint_y_ptr: AscPtr<AscBigInt>,
 ) -> Result<AscPtr<AscBigInt>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
     let y: BigInt = asc_get(&fenv, bigint_y_ptr, 0)?;
     let result = x - y;
@@ -41,6 +43,7 @@ pub fn big_int_times(
     bigint_x_ptr: AscPtr<AscBigInt>,
     bigint_y_ptr: AscPtr<AscBigInt>,
 ) -> Result<AscPtr<AscBigInt>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
     let y: BigInt = asc_get(&fenv, bigint_y_ptr, 0)?;
     let result = x * y;
@@ -53,10 +56,11 @@ pub fn big_int_divided_by(
     bigint_x_ptr: AscPtr<AscBigInt>,
     bigint_y_ptr: AscPtr<AscBigInt>,
 ) -> Result<AscPtr<AscBigInt>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
     let y: BigInt = asc_get(&fenv, bigint_y_ptr, 0)?;
     if y == BigInt::from(0) {
-        return Err(RuntimeError::new("Divide by zero error!"));
+        return Err(RuntimeError::new(format!("divide by zero: {x} / 0")));
     }
     let result = x / y;
     let asc_pt = asc_new(&mut fenv, &result)?;
@@ -68,6 +72,7 @@ pub fn big_int_bit_or(
     bigint_x_ptr: AscPtr<AscBigInt>,
     bigint_y_ptr: AscPtr<AscBigInt>,
 ) -> Result<AscPtr<AscBigInt>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
     let y: BigInt = asc_get(&fenv, bigint_y_ptr, 0)?;
     if y == 0.into() {
@@ -83,6 +88,7 @@ pub fn big_int_bit_and(
     bigint_x_ptr: AscPtr<AscBigInt>,
     bigint_y_ptr: AscPtr<AscBigInt>,
 ) -> Result<AscPtr<AscBigInt>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
     let y: BigInt = asc_get(&fenv, bigint_y_ptr, 0)?;
     if y == 0.into() {
@@ -98,10 +104,11 @@ pub fn big_int_divided_by_decimal(
     bigint_x_ptr: AscPtr<AscBigInt>,
     bigint_y_ptr: AscPtr<AscBigDecimal>,
 ) -> Result<AscPtr<AscBigDecimal>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let x: BigDecimal = BigDecimal::new(asc_get(&fenv, bigint_x_ptr, 0)?, 0);
     let y: BigDecimal = asc_get(&fenv, bigint_y_ptr, 0)?;
     if y == 0.into() {
-        return Err(RuntimeError::new("Divide by zero error!"));
+        return Err(RuntimeError::new(format!("divide by zero: {x} / 0")));
     }
     let result = x / y;
     let asc_pt = asc_new(&mut fenv, &result)?;
@@ -113,10 +120,11 @@ pub fn big_int_mod(
     bigint_x_ptr: AscPtr<AscBigInt>,
     bigint_y_ptr: AscPtr<AscBigInt>,
 ) -> Result<AscPtr<AscBigInt>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
     let y: BigInt = asc_get(&fenv, bigint_y_ptr, 0)?;
     if y == 0.into() {
-        return Err(RuntimeError::new("Divide by zero error!"));
+        return Err(RuntimeError::new(format!("divide by zero: {x} % 0")));
     }
     // NOTE: 20 %-9 = 2 => Đéo hiểu tại sao = 2
     let result = x.rem(y);
@@ -129,6 +137,7 @@ pub fn big_int_pow(
     bigint_x_ptr: AscPtr<AscBigInt>,
     exp: i32,
 ) -> Result<AscPtr<AscBigInt>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
     let result = x.pow(exp as u32)?;
     let asc_pt = asc_new(&mut fenv, &result)?;
@@ -140,6 +149,7 @@ pub fn big_int_left_shift(
     bigint_x_ptr: AscPtr<AscBigInt>,
     exp: i32,
 ) -> Result<AscPtr<AscBigInt>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
     let bits = u8::try_from(exp)
         .map_err(|_| RuntimeError::new("Exponent must be a positive integer less than 256"))?;
@@ -153,6 +163,7 @@ pub fn big_int_right_shift(
     bigint_x_ptr: AscPtr<AscBigInt>,
     exp: i32,
 ) -> Result<AscPtr<AscBigInt>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
     let bits = u8::try_from(exp)
         .map_err(|_| RuntimeError::new("Exponent must be a positive integer less than 256"))?;
@@ -161,27 +172,126 @@ pub fn big_int_right_shift(
     Ok(asc_pt)
 }
 
+pub fn big_int_gcd(
+    mut fenv: FunctionEnvMut<Env>,
+    bigint_x_ptr: AscPtr<AscBigInt>,
+    bigint_y_ptr: AscPtr<AscBigInt>,
+) -> Result<AscPtr<AscBigInt>, RuntimeError> {
+    fenv.data().gas.consume()?;
+    let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
+    let y: BigInt = asc_get(&fenv, bigint_y_ptr, 0)?;
+    let result = x.gcd(y);
+    let asc_pt = asc_new(&mut fenv, &result)?;
+    Ok(asc_pt)
+}
+
+pub fn big_int_sqrt(
+    mut fenv: FunctionEnvMut<Env>,
+    bigint_x_ptr: AscPtr<AscBigInt>,
+) -> Result<AscPtr<AscBigInt>, RuntimeError> {
+    fenv.data().gas.consume()?;
+    let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
+    let result = x
+        .sqrt()
+        .ok_or_else(|| RuntimeError::new(format!("Square root of negative BigInt {x}")))?;
+    let asc_pt = asc_new(&mut fenv, &result)?;
+    Ok(asc_pt)
+}
+
+/// -1, 0 or 1 as `x` is less than, equal to or greater than `y`
+pub fn big_int_compare(
+    fenv: FunctionEnvMut<Env>,
+    bigint_x_ptr: AscPtr<AscBigInt>,
+    bigint_y_ptr: AscPtr<AscBigInt>,
+) -> Result<i32, RuntimeError> {
+    fenv.data().gas.consume()?;
+    let x: BigInt = asc_get(&fenv, bigint_x_ptr, 0)?;
+    let y: BigInt = asc_get(&fenv, bigint_y_ptr, 0)?;
+    Ok(x.cmp(&y) as i32)
+}
+
 pub fn big_int_from_string(
     mut fenv: FunctionEnvMut<Env>,
     string_ptr: AscPtr<AscString>,
 ) -> Result<AscPtr<AscBigInt>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let x: String = asc_get(&fenv, string_ptr, 0)?;
-    let result = BigInt::from_str(&x)?;
+    let result = parse_big_int(&x)?;
     let asc_pt = asc_new(&mut fenv, &result)?;
     Ok(asc_pt)
 }
 
+/// Malformed strings fail the handler with the offending input, rather than a bare parser error
+pub fn parse_big_int(value: &str) -> Result<BigInt, RuntimeError> {
+    BigInt::from_str(value)
+        .map_err(|e| RuntimeError::new(format!("Invalid BigInt string {value:?}: {e}")))
+}
+
 #[cfg(test)]
 mod tests {
+    use super::super::bigdecimal::big_decimal_divided_by;
     use super::super::test::*;
     use crate::host_fn_test;
     use crate::runtime::asc::base::asc_get;
+    use crate::runtime::asc::base::asc_new;
     use crate::runtime::asc::base::AscPtr;
     use crate::runtime::asc::bignumber::AscBigDecimal;
     use crate::runtime::asc::bignumber::AscBigInt;
     use crate::runtime::bignumber::bigdecimal::BigDecimal;
     use crate::runtime::bignumber::bigint::BigInt;
 
+    #[test]
+    fn test_parse_big_int() {
+        assert_eq!(
+            super::parse_big_int("-12345").unwrap(),
+            BigInt::from(-12345)
+        );
+
+        for garbage in ["0x", "", "12.5"] {
+            let error = super::parse_big_int(garbage).unwrap_err();
+            assert!(error.message().contains(&format!("{garbage:?}")));
+        }
+    }
+
+    #[test]
+    fn test_big_int_gcd_sqrt() {
+        assert_eq!(BigInt::from(144).sqrt(), Some(BigInt::from(12)));
+        assert_eq!(BigInt::from(145).sqrt(), Some(BigInt::from(12)));
+        assert_eq!(BigInt::from(0).sqrt(), Some(BigInt::from(0)));
+        assert_eq!(BigInt::from(-4).sqrt(), None);
+
+        assert_eq!(BigInt::from(12).gcd(BigInt::from(18)), BigInt::from(6));
+        assert_eq!(BigInt::from(-12).gcd(BigInt::from(18)), BigInt::from(6));
+        assert_eq!(BigInt::from(7).gcd(BigInt::from(0)), BigInt::from(7));
+        assert_eq!(BigInt::from(12).cmp(&BigInt::from(18)) as i32, -1);
+    }
+
+    /// Dividing by zero traps the mapping, the error is returned to the caller of the handler
+    #[test]
+    fn test_divide_by_zero() {
+        env_logger::try_init().unwrap_or_default();
+        let (mut store, env) = mock_env(prometheus::default_registry(), None);
+
+        let mut fenv = env.clone().into_mut(&mut store);
+        let x = asc_new(&mut fenv, &BigInt::from(10)).unwrap();
+        let zero = asc_new(&mut fenv, &BigInt::from(0)).unwrap();
+        let decimal_x = asc_new(&mut fenv, &BigDecimal::from(10)).unwrap();
+        let decimal_zero = asc_new(&mut fenv, &BigDecimal::from(0)).unwrap();
+
+        let errors = [
+            super::big_int_divided_by(env.clone().into_mut(&mut store), x, zero).unwrap_err(),
+            super::big_int_mod(env.clone().into_mut(&mut store), x, zero).unwrap_err(),
+            super::big_int_divided_by_decimal(env.clone().into_mut(&mut store), x, decimal_zero)
+                .unwrap_err(),
+            big_decimal_divided_by(env.clone().into_mut(&mut store), decimal_x, decimal_zero)
+                .unwrap_err(),
+        ];
+
+        for error in errors {
+            assert!(error.message().contains("divide by zero"), "{error}");
+        }
+    }
+
     host_fn_test!("TestTypes", test_big_int_plus, host, ptr {
         let asc_ptr = AscPtr::<AscBigInt>::new(ptr);
         let bigint_result: BigInt = asc_get(&host, asc_ptr, 0).unwrap();
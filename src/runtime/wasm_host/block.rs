@@ -0,0 +1,50 @@
+use super::Env;
+use crate::runtime::asc::base::asc_new;
+use crate::runtime::asc::base::AscPtr;
+use crate::runtime::asc::native_types::string::AscString;
+use wasmer::FunctionEnvMut;
+use wasmer::RuntimeError;
+
+/// Number of the block being processed, also available outside of event handlers
+pub fn block_number(fenv: FunctionEnvMut<Env>) -> Result<u64, RuntimeError> {
+    Ok(fenv.data().manifest.block_ptr().number)
+}
+
+pub fn block_hash(mut fenv: FunctionEnvMut<Env>) -> Result<AscPtr<AscString>, RuntimeError> {
+    let hash = fenv.data().manifest.block_ptr().hash;
+    let hash_ptr = asc_new(&mut fenv, &hash)?;
+    Ok(hash_ptr)
+}
+
+#[cfg(test)]
+mod test {
+    use super::block_hash;
+    use super::block_number;
+    use crate::common::BlockPtr;
+    use crate::runtime::asc::base::asc_get;
+    use crate::runtime::wasm_host::test::*;
+    use prometheus::default_registry;
+
+    #[test]
+    fn test_current_block() {
+        env_logger::try_init().unwrap_or_default();
+        let (mut store, env) = mock_env(default_registry(), None);
+
+        // The host reads the block the manifest was last set to, as done for every block
+        let hash = "0xfe52a399d93c48b67bb147432aff55873576997d9d05de2c97087027609ae440";
+        env.as_ref(&store).manifest.set_block_ptr(&BlockPtr {
+            number: 153453,
+            hash: hash.to_string(),
+            parent_hash: String::default(),
+        });
+
+        let fenv = env.clone().into_mut(&mut store);
+        assert_eq!(block_number(fenv).unwrap(), 153453);
+
+        let fenv = env.clone().into_mut(&mut store);
+        let hash_ptr = block_hash(fenv).unwrap();
+        let fenv = env.clone().into_mut(&mut store);
+        let returned_hash: String = asc_get(&fenv, hash_ptr, 0).unwrap();
+        assert_eq!(returned_hash, hash);
+    }
+}
@@ -2,6 +2,7 @@ use crate::runtime::asc::base::asc_get;
 use crate::runtime::asc::base::asc_new;
 use crate::runtime::asc::base::AscPtr;
 use crate::runtime::asc::native_types::array::Array;
+use crate::runtime::asc::native_types::store::Value;
 use crate::runtime::asc::native_types::string::AscString;
 use crate::runtime::asc::native_types::typed_map::AscEntity;
 use crate::runtime::asc::native_types::Uint8Array;
@@ -19,7 +20,7 @@ pub fn datasource_create(
     let env = fenv.data_mut();
     env.manifest
         .create_datasource(&source_name, source_params)
-        .unwrap();
+        .map_err(|e| RuntimeError::new(e.to_string()))?;
     Ok(())
 }
 
@@ -35,12 +36,7 @@ pub fn datasource_create_context(
 pub fn datasource_address(
     mut fenv: FunctionEnvMut<Env>,
 ) -> Result<AscPtr<Uint8Array>, RuntimeError> {
-    let address = fenv
-        .data()
-        .address
-        .as_ref()
-        .map(|a| a.as_bytes().to_vec())
-        .unwrap_or(vec![]);
+    let address = address_bytes(fenv.data().address.as_deref())?;
     let address_ptr = asc_new(&mut fenv, address.as_slice())?;
     Ok(address_ptr)
 }
@@ -53,6 +49,46 @@ pub fn datasource_network(
     Ok(network_ptr)
 }
 
-pub fn datasource_context(_fenv: FunctionEnvMut<Env>) -> Result<AscPtr<AscEntity>, RuntimeError> {
-    todo!()
+/// Datasources are not created with a context yet, so mappings always see an empty one
+pub fn datasource_context(
+    mut fenv: FunctionEnvMut<Env>,
+) -> Result<AscPtr<AscEntity>, RuntimeError> {
+    let context: Vec<(String, Value)> = vec![];
+    let context_ptr = asc_new(&mut fenv, &context)?;
+    Ok(context_ptr)
+}
+
+/// The 20 raw bytes of the datasource's hex address, empty for templates without one
+fn address_bytes(address: Option<&str>) -> Result<Vec<u8>, RuntimeError> {
+    match address {
+        None => Ok(vec![]),
+        Some(address) => {
+            let bytes = hex::decode(address.trim_start_matches("0x")).map_err(|e| {
+                RuntimeError::new(format!("Invalid datasource address {address:?}: {e}"))
+            })?;
+            if bytes.len() != 20 {
+                return Err(RuntimeError::new(format!(
+                    "Invalid datasource address {address:?}: expected 20 bytes"
+                )));
+            }
+            Ok(bytes)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_address_bytes() {
+        let bytes = address_bytes(Some("0x1f9840a85d5af5bf1d1762f925bdaddc4201f984")).unwrap();
+        assert_eq!(bytes.len(), 20);
+        assert_eq!(bytes[0], 0x1f);
+        assert_eq!(bytes[19], 0x84);
+
+        assert!(address_bytes(None).unwrap().is_empty());
+        assert!(address_bytes(Some("0x1f98")).is_err());
+        assert!(address_bytes(Some("not-an-address")).is_err());
+    }
 }
@@ -0,0 +1,76 @@
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use wasmer::RuntimeError;
+
+/// Host operations (store, bignumber & conversion calls) done by the handler being run,
+/// shared between the host & its functions. Every operation costs one unit of gas
+#[derive(Clone, Default)]
+pub struct GasCounter {
+    used: Arc<AtomicU64>,
+    limit: Option<u64>,
+}
+
+impl GasCounter {
+    pub fn new(limit: Option<u64>) -> Self {
+        Self {
+            used: Arc::new(AtomicU64::new(0)),
+            limit,
+        }
+    }
+
+    /// Called by host functions, fails once the handler has used up its gas
+    /// so that the trap aborts it
+    pub fn consume(&self) -> Result<(), RuntimeError> {
+        let used = self.used.fetch_add(1, Ordering::Relaxed) + 1;
+        match self.limit {
+            Some(limit) if used > limit => Err(RuntimeError::new(format!(
+                "handler gas limit exceeded: used {used}, limit {limit}"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    pub fn exceeded(&self) -> bool {
+        self.limit
+            .is_some_and(|limit| self.used.load(Ordering::Relaxed) > limit)
+    }
+
+    /// Every handler starts with a full tank
+    pub fn reset(&self) {
+        self.used.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_gas_counter() {
+        let gas = GasCounter::new(Some(3));
+        let host_gas = gas.clone();
+        for _ in 0..3 {
+            host_gas.consume().unwrap();
+        }
+        assert!(!gas.exceeded());
+
+        let error = host_gas.consume().unwrap_err();
+        assert!(error.message().contains("gas limit exceeded"));
+        assert!(gas.exceeded());
+
+        gas.reset();
+        assert!(!gas.exceeded());
+        host_gas.consume().unwrap();
+
+        let unlimited = GasCounter::new(None);
+        for _ in 0..1000 {
+            unlimited.consume().unwrap();
+        }
+        assert!(!unlimited.exceeded());
+    }
+}
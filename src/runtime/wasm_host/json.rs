@@ -1,20 +1,114 @@
+use super::bigint::parse_big_int;
 use super::Env;
 use crate::runtime::asc::base::asc_get;
 use crate::runtime::asc::base::asc_new;
 use crate::runtime::asc::base::AscPtr;
 use crate::runtime::asc::bignumber::AscBigInt;
+use crate::runtime::asc::native_types::json::JsonValueKind;
+use crate::runtime::asc::native_types::r#enum::AscEnum;
 use crate::runtime::asc::native_types::string::AscString;
-use crate::runtime::bignumber::bigint::BigInt;
+use crate::runtime::asc::native_types::AscResult;
+use crate::runtime::asc::native_types::Uint8Array;
 use std::str::FromStr;
 use wasmer::FunctionEnvMut;
 use wasmer::RuntimeError;
 
+pub fn json_from_bytes(
+    mut fenv: FunctionEnvMut<Env>,
+    bytes_ptr: AscPtr<Uint8Array>,
+) -> Result<AscPtr<AscEnum<JsonValueKind>>, RuntimeError> {
+    let bytes: Vec<u8> = asc_get(&fenv, bytes_ptr, 0)?;
+    let value: serde_json::Value = serde_json::from_slice(&bytes)
+        .map_err(|e| RuntimeError::new(format!("Invalid JSON bytes: {e}")))?;
+    let asc_value = asc_new(&mut fenv, &value)?;
+    Ok(asc_value)
+}
+
+/// Malformed bytes are returned as an error result for the mapping to handle, instead of trapping
+pub fn json_try_from_bytes(
+    mut fenv: FunctionEnvMut<Env>,
+    bytes_ptr: AscPtr<Uint8Array>,
+) -> Result<AscPtr<AscResult<AscPtr<AscEnum<JsonValueKind>>, bool>>, RuntimeError> {
+    let bytes: Vec<u8> = asc_get(&fenv, bytes_ptr, 0)?;
+    let result = try_parse_json(&bytes);
+    let asc_result = asc_new(&mut fenv, &result)?;
+    Ok(asc_result)
+}
+
+pub fn json_to_i64(
+    fenv: FunctionEnvMut<Env>,
+    json_value_ptr: AscPtr<AscString>,
+) -> Result<i64, RuntimeError> {
+    let value: String = asc_get(&fenv, json_value_ptr, 0)?;
+    parse_number(&value)
+}
+
+pub fn json_to_u64(
+    fenv: FunctionEnvMut<Env>,
+    json_value_ptr: AscPtr<AscString>,
+) -> Result<u64, RuntimeError> {
+    let value: String = asc_get(&fenv, json_value_ptr, 0)?;
+    parse_number(&value)
+}
+
+pub fn json_to_f64(
+    fenv: FunctionEnvMut<Env>,
+    json_value_ptr: AscPtr<AscString>,
+) -> Result<f64, RuntimeError> {
+    let value: String = asc_get(&fenv, json_value_ptr, 0)?;
+    parse_number(&value)
+}
+
 pub fn json_to_bigint(
     mut fenv: FunctionEnvMut<Env>,
     json_value_ptr: AscPtr<AscString>,
 ) -> Result<AscPtr<AscBigInt>, RuntimeError> {
     let value: String = asc_get(&fenv, json_value_ptr, 0)?;
-    let value = BigInt::from_str(&value)?;
+    let value = parse_big_int(&value)?;
     let asc_bigint = asc_new(&mut fenv, &value)?;
     Ok(asc_bigint)
 }
+
+fn try_parse_json(bytes: &[u8]) -> Result<serde_json::Value, bool> {
+    serde_json::from_slice(bytes).map_err(|_| true)
+}
+
+/// JSON numbers are handed to the mapping as their string representation
+fn parse_number<T: FromStr>(value: &str) -> Result<T, RuntimeError> {
+    T::from_str(value).map_err(|_| {
+        RuntimeError::new(format!(
+            "JSON number {value:?} does not fit into {}",
+            std::any::type_name::<T>()
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_parse_json() {
+        let value = try_parse_json(br#"{"name": "token", "decimals": 18}"#).unwrap();
+        assert_eq!(value["decimals"], serde_json::json!(18));
+
+        let garbage: [&[u8]; 4] = [b"", b"{", b"{'name': 1}", b"\xff"];
+        for garbage in garbage {
+            assert_eq!(try_parse_json(garbage), Err(true));
+        }
+    }
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number::<i64>("-42").unwrap(), -42);
+        assert_eq!(
+            parse_number::<u64>("18446744073709551615").unwrap(),
+            u64::MAX
+        );
+        assert_eq!(parse_number::<f64>("1.5e3").unwrap(), 1500.0);
+
+        assert!(parse_number::<u64>("-1").is_err());
+        assert!(parse_number::<i64>("1.5").is_err());
+        assert!(parse_number::<i64>("18446744073709551615").is_err());
+    }
+}
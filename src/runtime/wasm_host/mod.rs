@@ -1,8 +1,10 @@
 mod asc;
 mod bigdecimal;
 mod bigint;
+mod block;
 mod chain;
 mod datasource;
+mod gas;
 mod global;
 mod json;
 mod macros;
@@ -20,6 +22,7 @@ use crate::errors::WasmHostError;
 use crate::rpc_client::RpcAgent;
 use asc::ArenaStartPtr;
 pub use asc::AscHost;
+pub use gas::GasCounter;
 use semver::Version;
 use wasmer::imports;
 use wasmer::Function;
@@ -43,6 +46,8 @@ pub struct Env {
     pub db: DatabaseAgent,
     pub rpc: RpcAgent,
     pub manifest: ManifestAgent,
+    pub max_asc_length: u32,
+    pub gas: GasCounter,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -55,9 +60,12 @@ fn create_wasm_host(
     address: Option<String>,
     network: String,
     db: DatabaseAgent,
+    max_asc_length: u32,
+    gas_limit: Option<u64>,
 ) -> Result<AscHost, WasmHostError> {
     let mut store = Store::default();
     let module = Module::new(&store, wasm_bytes)?;
+    let gas = GasCounter::new(gas_limit);
 
     let env = FunctionEnv::new(
         &mut store,
@@ -73,6 +81,8 @@ fn create_wasm_host(
             manifest,
             address,
             network,
+            max_asc_length,
+            gas: gas.clone(),
         },
     );
 
@@ -102,6 +112,9 @@ fn create_wasm_host(
             "bigInt.bitAnd" => Function::new_typed_with_env(&mut store, &env, bigint::big_int_bit_and),
             "bigInt.leftShift" => Function::new_typed_with_env(&mut store, &env, bigint::big_int_left_shift),
             "bigInt.rightShift" => Function::new_typed_with_env(&mut store, &env, bigint::big_int_right_shift),
+            "bigInt.gcd" => Function::new_typed_with_env(&mut store, &env, bigint::big_int_gcd),
+            "bigInt.sqrt" => Function::new_typed_with_env(&mut store, &env, bigint::big_int_sqrt),
+            "bigInt.compare" => Function::new_typed_with_env(&mut store, &env, bigint::big_int_compare),
             //Big Decimal
             "bigDecimal.fromString" => Function::new_typed_with_env(&mut store, &env, bigdecimal::big_decimal_from_string),
             "bigDecimal.toString" => Function::new_typed_with_env(&mut store, &env, bigdecimal::big_decimal_to_string),
@@ -112,6 +125,11 @@ fn create_wasm_host(
             "bigDecimal.equals" => Function::new_typed_with_env(&mut store, &env, bigdecimal::big_decimal_equals),
         },
         "json" => {
+            "json.fromBytes" => Function::new_typed_with_env(&mut store, &env, json::json_from_bytes),
+            "json.try_fromBytes" => Function::new_typed_with_env(&mut store, &env, json::json_try_from_bytes),
+            "json.toI64" => Function::new_typed_with_env(&mut store, &env, json::json_to_i64),
+            "json.toU64" => Function::new_typed_with_env(&mut store, &env, json::json_to_u64),
+            "json.toF64" => Function::new_typed_with_env(&mut store, &env, json::json_to_f64),
             "json.toBigInt" =>Function::new_typed_with_env(&mut store, &env, json::json_to_bigint),
         },
         "ethereum" => {
@@ -121,6 +139,10 @@ fn create_wasm_host(
             "ethereum.call" =>  Function::new_typed_with_env(&mut store, &env, chain::ethereum::ethereum_call),
             "crypto.keccak256" => Function::new_typed_with_env(&mut store, &env, chain::ethereum::crypto_keccak_256),
         },
+        "block" => {
+            "block.number" => Function::new_typed_with_env(&mut store, &env, block::block_number),
+            "block.hash" => Function::new_typed_with_env(&mut store, &env, block::block_hash),
+        },
         "datasource" => {
             // Datasource
             "dataSource.create" => Function::new_typed_with_env(&mut store, &env, datasource::datasource_create),
@@ -131,10 +153,12 @@ fn create_wasm_host(
         },
         "index" => { //index for subgraph version <= 4
             "store.set" => Function::new_typed_with_env(&mut store, &env, store::store_set),
+            "store.merge" => Function::new_typed_with_env(&mut store, &env, store::store_merge),
             "store.get" => Function::new_typed_with_env(&mut store, &env, store::store_get),
             "store.remove" => Function::new_typed_with_env(&mut store, &env, store::store_remove),
             "store.loadRelated" => Function::new_typed_with_env(&mut store, &env, store::store_load_related),
             "store.get_in_block" => Function::new_typed_with_env(&mut store, &env, store::store_get_in_block),
+            "store.get_with_freshness" => Function::new_typed_with_env(&mut store, &env, store::store_get_with_freshness),
             //Convert
             "typeConversion.bytesToString" => Function::new_typed_with_env(&mut store, &env, types_conversion::bytes_to_string),
             "typeConversion.bytesToHex" => Function::new_typed_with_env(&mut store, &env, types_conversion::bytes_to_hex),
@@ -150,6 +174,9 @@ fn create_wasm_host(
             "dataSource.address" => Function::new_typed_with_env(&mut store, &env, datasource::datasource_address),
             "dataSource.network" => Function::new_typed_with_env(&mut store, &env, datasource::datasource_network),
             "dataSource.context" => Function::new_typed_with_env(&mut store, &env, datasource::datasource_context),
+            // Block
+            "block.number" => Function::new_typed_with_env(&mut store, &env, block::block_number),
+            "block.hash" => Function::new_typed_with_env(&mut store, &env, block::block_hash),
             // BigInt
             "bigInt.plus" => Function::new_typed_with_env(&mut store, &env, bigint::big_int_plus),
             "bigInt.minus" => Function::new_typed_with_env(&mut store, &env, bigint::big_int_minus),
@@ -164,6 +191,9 @@ fn create_wasm_host(
             "bigInt.bitAnd" => Function::new_typed_with_env(&mut store, &env, bigint::big_int_bit_and),
             "bigInt.leftShift" => Function::new_typed_with_env(&mut store, &env, bigint::big_int_left_shift),
             "bigInt.rightShift" => Function::new_typed_with_env(&mut store, &env, bigint::big_int_right_shift),
+            "bigInt.gcd" => Function::new_typed_with_env(&mut store, &env, bigint::big_int_gcd),
+            "bigInt.sqrt" => Function::new_typed_with_env(&mut store, &env, bigint::big_int_sqrt),
+            "bigInt.compare" => Function::new_typed_with_env(&mut store, &env, bigint::big_int_compare),
             //Big Decimal
             "bigDecimal.fromString" => Function::new_typed_with_env(&mut store, &env, bigdecimal::big_decimal_from_string),
             "bigDecimal.toString" => Function::new_typed_with_env(&mut store, &env, bigdecimal::big_decimal_to_string),
@@ -237,14 +267,32 @@ fn create_wasm_host(
         memory_allocate,
         id_of_type,
         arena_start_ptr,
+        max_asc_length,
+        gas,
     })
 }
 
-impl TryFrom<(DatasourceBundle, DatabaseAgent, RpcAgent, ManifestAgent)> for AscHost {
+impl
+    TryFrom<(
+        DatasourceBundle,
+        DatabaseAgent,
+        RpcAgent,
+        ManifestAgent,
+        u32,
+        Option<u64>,
+    )> for AscHost
+{
     type Error = WasmHostError;
 
     fn try_from(
-        (ds, db, rpc, manifest): (DatasourceBundle, DatabaseAgent, RpcAgent, ManifestAgent),
+        (ds, db, rpc, manifest, max_asc_length, gas_limit): (
+            DatasourceBundle,
+            DatabaseAgent,
+            RpcAgent,
+            ManifestAgent,
+            u32,
+            Option<u64>,
+        ),
     ) -> Result<Self, Self::Error> {
         create_wasm_host(
             ds.api_version(),
@@ -255,6 +303,8 @@ impl TryFrom<(DatasourceBundle, DatabaseAgent, RpcAgent, ManifestAgent)> for Asc
             ds.address(),
             ds.network(),
             db,
+            max_asc_length,
+            gas_limit,
         )
     }
 }
@@ -262,8 +312,10 @@ impl TryFrom<(DatasourceBundle, DatabaseAgent, RpcAgent, ManifestAgent)> for Asc
 #[cfg(test)]
 pub mod test {
     use super::*;
+    use crate::runtime::asc::base::DEFAULT_MAX_ASC_LENGTH;
     use prometheus::Registry;
     use std::path::PathBuf;
+    use wasmer::MemoryType;
 
     pub fn mock_wasm_host(
         api_version: Version,
@@ -290,10 +342,38 @@ pub mod test {
             None,
             "Test".to_string(),
             db,
+            DEFAULT_MAX_ASC_LENGTH,
+            None,
         )
         .unwrap()
     }
 
+    /// Env without a wasm instance so host functions can be called directly,
+    /// asc objects are allocated from the start of a single page of memory
+    pub fn mock_env(registry: &Registry, gas_limit: Option<u64>) -> (Store, FunctionEnv<Env>) {
+        let mut store = Store::default();
+        let memory = Memory::new(&mut store, MemoryType::new(1, None, false)).unwrap();
+        let env = FunctionEnv::new(
+            &mut store,
+            Env {
+                memory: Some(memory),
+                memory_allocate: None,
+                id_of_type: None,
+                api_version: Version::new(0, 0, 4),
+                arena_start_ptr: Arc::new(Mutex::new(8)),
+                host_name: "test".to_string(),
+                network: "Test".to_string(),
+                address: None,
+                db: DatabaseAgent::empty(registry),
+                rpc: RpcAgent::new_mock(registry),
+                manifest: ManifestAgent::default(),
+                max_asc_length: DEFAULT_MAX_ASC_LENGTH,
+                gas: GasCounter::new(gas_limit),
+            },
+        );
+        (store, env)
+    }
+
     pub fn get_subgraph_testing_resource(version: &str, host_name: &str) -> (Version, String) {
         let version = Version::parse(version).expect("Bad api-version");
         let mut project_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -1,14 +1,21 @@
 use super::Env;
+use crate::common::EntityKey;
+use crate::common::EntityTypeName;
+use crate::common::FieldName;
 use crate::common::RawEntity;
 use crate::common::StoreOperationMessage;
 use crate::common::StoreRequestResult;
+use crate::errors::DatabaseError;
 use crate::runtime::asc::base::asc_get;
 use crate::runtime::asc::base::asc_new;
 use crate::runtime::asc::base::AscPtr;
 use crate::runtime::asc::native_types::array::Array;
+use crate::runtime::asc::native_types::store::FieldValue;
+use crate::runtime::asc::native_types::store::StoreValueKind;
 use crate::runtime::asc::native_types::store::Value;
 use crate::runtime::asc::native_types::string::AscString;
 use crate::runtime::asc::native_types::typed_map::AscEntity;
+use crate::runtime::asc::native_types::typed_map::AscLoadedEntity;
 use std::collections::HashMap;
 use wasmer::FunctionEnvMut;
 use wasmer::RuntimeError;
@@ -19,19 +26,45 @@ pub fn store_set(
     entity_id_ptr: AscPtr<AscString>,
     data_ptr: AscPtr<AscEntity>,
 ) -> Result<(), RuntimeError> {
+    fenv.data().gas.consume()?;
     let env = fenv.data();
     let db = env.db.clone();
     let entity_id: String = asc_get(&fenv, entity_id_ptr, 0)?;
-    let mut data: HashMap<String, Value> = asc_get(&fenv, data_ptr, 0)?;
+    let data: HashMap<String, Value> = asc_get(&fenv, data_ptr, 0)?;
     let entity_type: String = asc_get(&fenv, entity_type_ptr, 0)?;
 
-    if !data.contains_key("id") {
-        // WARN: v0.0.5 Entity has `id` stripped off (why???)
-        // If entity is not yet created, forcing ID might not be a good idea
-        data.insert("id".to_string(), Value::String(entity_id.clone()));
-    }
+    // NOTE: `Update` upserts, replacing the whole entity if any,
+    // and the entity's primary key is filled from `entity_id` by the database if missing
+    let request = StoreOperationMessage::update(
+        EntityTypeName::from(entity_type),
+        EntityKey::from(entity_id),
+        data,
+    );
+    let _result = db
+        .wasm_send_store_request(request)
+        .map_err(|e| RuntimeError::new(e.to_string()))?;
+
+    Ok(())
+}
+
+/// Only the given fields are written, the other ones keep the value of the stored entity
+pub fn store_merge(
+    fenv: FunctionEnvMut<Env>,
+    entity_type_ptr: AscPtr<AscString>,
+    entity_id_ptr: AscPtr<AscString>,
+    data_ptr: AscPtr<AscEntity>,
+) -> Result<(), RuntimeError> {
+    fenv.data().gas.consume()?;
+    let db = fenv.data().db.clone();
+    let entity_id: String = asc_get(&fenv, entity_id_ptr, 0)?;
+    let data: HashMap<String, Value> = asc_get(&fenv, data_ptr, 0)?;
+    let entity_type: String = asc_get(&fenv, entity_type_ptr, 0)?;
 
-    let request = StoreOperationMessage::Update((entity_type, entity_id, data));
+    let request = StoreOperationMessage::merge(
+        EntityTypeName::from(entity_type),
+        EntityKey::from(entity_id),
+        data,
+    );
     let _result = db
         .wasm_send_store_request(request)
         .map_err(|e| RuntimeError::new(e.to_string()))?;
@@ -44,11 +77,15 @@ pub fn store_get(
     entity_type_ptr: AscPtr<AscString>,
     entity_id_ptr: AscPtr<AscString>,
 ) -> Result<AscPtr<AscEntity>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let entity_type: String = asc_get(&fenv, entity_type_ptr, 0)?;
     let entity_id: String = asc_get(&fenv, entity_id_ptr, 0)?;
     let env = fenv.data();
     let db = env.db.clone();
-    let request = StoreOperationMessage::Load((entity_type, entity_id));
+    let request = StoreOperationMessage::load(
+        EntityTypeName::from(entity_type.clone()),
+        EntityKey::from(entity_id),
+    );
     let result = db
         .wasm_send_store_request(request)
         .map_err(|e| RuntimeError::new(e.to_string()))?;
@@ -56,7 +93,10 @@ pub fn store_get(
     match result {
         StoreRequestResult::Load(data) => {
             if let Some(data) = data {
-                let asc_result = asc_new(&mut fenv, &data.into_iter().collect::<Vec<_>>())?;
+                let asc_result = asc_new(
+                    &mut fenv,
+                    &field_values(&db.get_field_kinds(&entity_type), data),
+                )?;
                 Ok(asc_result)
             } else {
                 Ok(AscPtr::null())
@@ -74,12 +114,14 @@ pub fn store_remove(
     entity_type_ptr: AscPtr<AscString>,
     entity_id_ptr: AscPtr<AscString>,
 ) -> Result<(), RuntimeError> {
+    fenv.data().gas.consume()?;
     let env = fenv.data();
     let db = env.db.clone();
     let entity_id: String = asc_get(&fenv, entity_id_ptr, 0)?;
     let entity_type: String = asc_get(&fenv, entity_type_ptr, 0)?;
 
-    let request = StoreOperationMessage::Delete((entity_type, entity_id));
+    let request =
+        StoreOperationMessage::delete(EntityTypeName::from(entity_type), EntityKey::from(entity_id));
     let _result = db
         .wasm_send_store_request(request)
         .map_err(|e| RuntimeError::new(e.to_string()))?;
@@ -92,10 +134,14 @@ pub fn store_get_in_block(
     entity_type_ptr: AscPtr<AscString>,
     entity_id_ptr: AscPtr<AscString>,
 ) -> Result<AscPtr<AscEntity>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let entity_id: String = asc_get(&fenv, entity_id_ptr, 0)?;
     let entity_type: String = asc_get(&fenv, entity_type_ptr, 0)?;
     let db = fenv.data().db.clone();
-    let request = StoreOperationMessage::LoadInBlock((entity_type, entity_id));
+    let request = StoreOperationMessage::load_in_block(
+        EntityTypeName::from(entity_type.clone()),
+        EntityKey::from(entity_id),
+    );
     let result = db
         .wasm_send_store_request(request)
         .map_err(|e| RuntimeError::new(e.to_string()))?;
@@ -104,13 +150,56 @@ pub fn store_get_in_block(
         StoreRequestResult::LoadInBlock(raw_entity) => {
             if let Some(entity) = raw_entity {
                 let entity = remove_private_field(vec![entity]).pop().unwrap();
-                let asc_result = asc_new(&mut fenv, &entity.into_iter().collect::<Vec<_>>())?;
+                let asc_result = asc_new(
+                    &mut fenv,
+                    &field_values(&db.get_field_kinds(&entity_type), entity),
+                )?;
                 Ok(asc_result)
             } else {
                 Ok(AscPtr::null())
             }
         }
-        _ => unimplemented!(),
+        other => Err(RuntimeError::new(format!(
+            "Load entity in block failed, recevied response: {:?}",
+            other
+        ))),
+    }
+}
+
+pub fn store_get_with_freshness(
+    mut fenv: FunctionEnvMut<Env>,
+    entity_type_ptr: AscPtr<AscString>,
+    entity_id_ptr: AscPtr<AscString>,
+) -> Result<AscPtr<AscLoadedEntity>, RuntimeError> {
+    fenv.data().gas.consume()?;
+    let entity_type: String = asc_get(&fenv, entity_type_ptr, 0)?;
+    let entity_id: String = asc_get(&fenv, entity_id_ptr, 0)?;
+    let db = fenv.data().db.clone();
+    let request = StoreOperationMessage::load_with_freshness(
+        EntityTypeName::from(entity_type.clone()),
+        EntityKey::from(entity_id),
+    );
+    let result = db
+        .wasm_send_store_request(request)
+        .map_err(|e| RuntimeError::new(e.to_string()))?;
+
+    match result {
+        StoreRequestResult::LoadWithFreshness(Some((entity, created_in_block))) => {
+            let entity = remove_private_field(vec![entity]).pop().unwrap();
+            let asc_result = asc_new(
+                &mut fenv,
+                &(
+                    field_values(&db.get_field_kinds(&entity_type), entity),
+                    created_in_block,
+                ),
+            )?;
+            Ok(asc_result)
+        }
+        StoreRequestResult::LoadWithFreshness(None) => Ok(AscPtr::null()),
+        other => Err(RuntimeError::new(format!(
+            "Load entity failed, recevied response: {:?}",
+            other
+        ))),
     }
 }
 
@@ -120,30 +209,55 @@ pub fn store_load_related(
     entity_id_ptr: AscPtr<AscString>,
     field_ptr: AscPtr<AscString>,
 ) -> Result<AscPtr<Array<AscPtr<AscEntity>>>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let env = fenv.data();
     let db = env.db.clone();
     let entity_id: String = asc_get(&fenv, entity_id_ptr, 0)?;
     let entity_type: String = asc_get(&fenv, entity_type_ptr, 0)?;
     let field_name: String = asc_get(&fenv, field_ptr, 0)?;
+    let field_kinds = db
+        .get_related_entity_type(&entity_type, &field_name)
+        .map(|related_type| db.get_field_kinds(&related_type))
+        .unwrap_or_default();
 
-    let request = StoreOperationMessage::LoadRelated((entity_type, entity_id, field_name));
-    let result = db
-        .wasm_send_store_request(request)
-        .map_err(|e| RuntimeError::new(e.to_string()))?;
-    match result {
-        StoreRequestResult::LoadRelated(entities) => {
-            let entities = remove_private_field(entities);
-            let vec_entities: Vec<Vec<(String, Value)>> = entities
-                .into_iter()
-                .map(|e| e.into_iter().collect::<Vec<_>>())
-                .collect();
+    let request = StoreOperationMessage::load_related(
+        EntityTypeName::from(entity_type),
+        EntityKey::from(entity_id),
+        field_name,
+    );
+    // Entities are written to the guest memory page by page as they are loaded
+    let mut entities = vec![];
+    db.wasm_load_related(request, |page| {
+        for entity in remove_private_field(page) {
+            let entity = field_values(&field_kinds, entity);
+            let entity_ptr: AscPtr<AscEntity> =
+                asc_new(&mut fenv, &entity).map_err(|e| DatabaseError::Plain(e.to_string()))?;
+            entities.push(entity_ptr);
+        }
+        Ok(())
+    })
+    .map_err(|e| RuntimeError::new(e.to_string()))?;
 
-            let array_ptr = asc_new(&mut fenv, &vec_entities)?;
+    let array = Array::new(&entities, &mut fenv)?;
+    let array_ptr = AscPtr::alloc_obj(array, &mut fenv)?;
+    Ok(array_ptr)
+}
 
-            Ok(array_ptr)
-        }
-        _ => unimplemented!(),
-    }
+/// Fields along with the kind of their schema field, so that timestamps reach the mapping as such
+fn field_values(
+    field_kinds: &HashMap<FieldName, StoreValueKind>,
+    entity: RawEntity,
+) -> Vec<(String, FieldValue)> {
+    entity
+        .into_iter()
+        .map(|(field_name, value)| {
+            let kind = field_kinds
+                .get(&field_name)
+                .copied()
+                .unwrap_or_else(|| StoreValueKind::get_kind(&value));
+            (field_name, FieldValue(kind, value))
+        })
+        .collect()
 }
 
 fn remove_private_field(entities: Vec<RawEntity>) -> Vec<RawEntity> {
@@ -233,3 +347,70 @@ fn remove_private_field(entities: Vec<RawEntity>) -> Vec<RawEntity> {
 //         assert_eq!(*entity.get("totalSupply").unwrap(), Value::BigInt(BigInt::from_str("1000000000000").unwrap()));
 //     });
 // }
+
+#[cfg(test)]
+mod test {
+    use super::super::test::*;
+    use super::store_get;
+    use super::store_get_with_freshness;
+    use crate::common::RawEntity;
+    use crate::common::StoreOperationMessage;
+    use crate::host_fn_test;
+    use crate::runtime::asc::base::asc_get;
+    use crate::runtime::asc::base::asc_new;
+    use crate::runtime::asc::native_types::store::Value;
+    use prometheus::default_registry;
+    use std::collections::HashMap;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_store_get_with_freshness() {
+        env_logger::try_init().unwrap_or_default();
+        let (mut store, env) = mock_env(default_registry(), None);
+
+        let mut data = RawEntity::new();
+        data.insert("id".to_string(), Value::String("fresh".to_string()));
+        let request = StoreOperationMessage::Create(("Token".to_string(), data));
+        let db = env.as_ref(&store).db.clone();
+        db.wasm_send_store_request(request).unwrap();
+
+        let mut fenv = env.clone().into_mut(&mut store);
+        let entity_type = asc_new(&mut fenv, "Token").unwrap();
+        let entity_id = asc_new(&mut fenv, "fresh").unwrap();
+        let result = store_get_with_freshness(fenv, entity_type, entity_id).unwrap();
+
+        let fenv = env.clone().into_mut(&mut store);
+        let loaded = result.read_ptr(&fenv).unwrap();
+        assert!(loaded.created_in_block);
+        let entity: HashMap<String, Value> = asc_get(&fenv, loaded.entity, 0).unwrap();
+        assert_eq!(entity.get("id"), Some(&Value::String("fresh".to_string())));
+
+        let mut fenv = env.clone().into_mut(&mut store);
+        let entity_type = asc_new(&mut fenv, "Token").unwrap();
+        let entity_id = asc_new(&mut fenv, "missing").unwrap();
+        let result = store_get_with_freshness(fenv, entity_type, entity_id).unwrap();
+        assert!(result.is_null());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_store_gas_limit() {
+        env_logger::try_init().unwrap_or_default();
+        let (mut store, env) = mock_env(default_registry(), Some(2));
+
+        // Every store call costs one unit of gas, the third one is aborted
+        for call in 0..3 {
+            let mut fenv = env.clone().into_mut(&mut store);
+            let entity_type = asc_new(&mut fenv, "Token").unwrap();
+            let entity_id = asc_new(&mut fenv, "missing").unwrap();
+            let result = store_get(fenv, entity_type, entity_id);
+            match call {
+                2 => assert!(result.unwrap_err().message().contains("gas limit exceeded")),
+                _ => assert!(result.unwrap().is_null()),
+            }
+        }
+
+        let gas = env.as_ref(&store).gas.clone();
+        assert!(gas.exceeded());
+        gas.reset();
+        assert!(!gas.exceeded());
+    }
+}
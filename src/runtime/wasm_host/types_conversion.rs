@@ -42,6 +42,7 @@ pub fn bytes_to_string(
     mut fenv: FunctionEnvMut<Env>,
     bytes_ptr: AscPtr<Uint8Array>,
 ) -> Result<AscPtr<AscString>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let bytes: Vec<u8> = asc_get(&fenv, bytes_ptr, 0).unwrap();
     let string = convert_bytes_to_string(bytes);
     let asc_string = asc_new(&mut fenv, &string)?;
@@ -52,6 +53,7 @@ pub fn bytes_to_hex(
     mut fenv: FunctionEnvMut<Env>,
     bytes_ptr: AscPtr<Uint8Array>,
 ) -> Result<AscPtr<AscString>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let bytes: Vec<u8> = asc_get(&fenv, bytes_ptr, 0).unwrap();
     let asc_hex = asc_new(&mut fenv, &format!("0x{}", hex::encode(bytes)))?;
     Ok(asc_hex)
@@ -61,6 +63,7 @@ pub fn big_int_to_string(
     mut fenv: FunctionEnvMut<Env>,
     big_int_ptr: AscPtr<AscBigInt>,
 ) -> Result<AscPtr<AscString>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let big_int: BigInt = asc_get(&fenv, big_int_ptr, 0).unwrap();
     let big_int_string = asc_new(&mut fenv, &big_int.to_string())?;
     Ok(big_int_string)
@@ -70,6 +73,7 @@ pub fn big_int_to_hex(
     mut fenv: FunctionEnvMut<Env>,
     big_int_ptr: AscPtr<AscBigInt>,
 ) -> Result<AscPtr<AscString>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let big_int: BigInt = asc_get(&fenv, big_int_ptr, 0).unwrap();
     if big_int == 0.into() {
         let result = asc_new(&mut fenv, "0x0")?;
@@ -88,6 +92,7 @@ pub fn string_to_h160(
     mut fenv: FunctionEnvMut<Env>,
     string_ptr: AscPtr<AscString>,
 ) -> Result<AscPtr<AscH160>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let string: String = asc_get(&fenv, string_ptr, 0).unwrap();
     let h160 = convert_string_to_h160(&string)?;
     let result = asc_new(&mut fenv, &h160)?;
@@ -99,6 +104,7 @@ pub fn bytes_to_base58(
     mut fenv: FunctionEnvMut<Env>,
     bytes_ptr: AscPtr<Uint8Array>,
 ) -> Result<AscPtr<AscString>, RuntimeError> {
+    fenv.data().gas.consume()?;
     let bytes: Vec<u8> = asc_get(&fenv, bytes_ptr, 0).unwrap();
     let result = asc_new(&mut fenv, &bs58::encode(bytes).into_string())?;
     Ok(result)